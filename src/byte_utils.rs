@@ -9,6 +9,7 @@ pub trait VecUtils {
     fn get_string(&self, from: usize, to: usize) -> R<String>;
     fn get_string_null_terminated(&self, offset: usize) -> R<String>;
     fn get_i32(&self, offset: usize) -> R<i32>;
+    fn get_i16(&self, offset: usize) -> R<i16>;
     fn get_u16(&self, offset: usize) -> R<u16>;
     fn get_u32(&self, offset: usize) -> R<u32>;
     fn get_f32(&self, offset: usize) -> R<f32>;
@@ -50,6 +51,11 @@ impl VecUtils for Vec<u8> {
         Ok(i32::from_le_bytes(slice))
     }
 
+    fn get_i16(&self, offset: usize) -> R<i16> {
+        let slice: [u8; 2] = self.get_two_bytes(offset)?;
+        Ok(i16::from_le_bytes(slice))
+    }
+
     fn get_u16(&self, offset: usize) -> R<u16> {
         let slice: [u8; 2] = self.get_two_bytes(offset)?;
         Ok(u16::from_le_bytes(slice))
@@ -110,4 +116,45 @@ impl VecUtils for Vec<u8> {
         }
         Ok(acc)
     }
-}
\ No newline at end of file
+}
+
+pub trait VecWriteUtils {
+    fn push_i16(&mut self, value: i16);
+    fn push_u16(&mut self, value: u16);
+    fn push_u32(&mut self, value: u32);
+    fn push_i32(&mut self, value: i32);
+    fn push_f32(&mut self, value: f32);
+    fn push_null_terminated_string(&mut self, value: &str);
+    fn set_u32(&mut self, offset: usize, value: u32);
+}
+
+impl VecWriteUtils for Vec<u8> {
+    fn push_i16(&mut self, value: i16) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn push_u16(&mut self, value: u16) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn push_u32(&mut self, value: u32) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn push_i32(&mut self, value: i32) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn push_f32(&mut self, value: f32) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn push_null_terminated_string(&mut self, value: &str) {
+        self.extend_from_slice(value.as_bytes());
+        self.push(0);
+    }
+
+    fn set_u32(&mut self, offset: usize, value: u32) {
+        self[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+}
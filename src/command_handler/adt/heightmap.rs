@@ -0,0 +1,223 @@
+use crate::command_handler::adt::MapFolder;
+use crate::common::{err, R};
+use crate::formats::adt::heightmap::{HeightmapFormat, HeightmapMetadata, TileHeightmap, TILE_HEIGHTMAP_SIZE};
+use crate::formats::adt::{get_tile_coords, AdtFile};
+use crate::formats::png::GrayscaleImage;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// tiles share their edge row and column
+const TILE_STRIDE: u32 = TILE_HEIGHTMAP_SIZE as u32 - 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HeightmapExportResult {
+    pub image: PathBuf,
+    pub metadata_path: PathBuf,
+    pub metadata: HeightmapMetadata,
+    pub warns: Vec<HeightmapWarn>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HeightmapImportResult {
+    pub updated: Vec<PathBuf>,
+    pub unchanged: Vec<PathBuf>,
+    pub warns: Vec<HeightmapWarn>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum HeightmapWarn {
+    MissingAdt(PathBuf),
+}
+
+pub fn export_heightmap(input: &str, output: &str) -> R<HeightmapExportResult> {
+    let output = PathBuf::from(output);
+    let format = get_format_from_extension(&output)?;
+    let mut warns = Vec::new();
+
+    let mut tiles: Vec<((u32, u32), TileHeightmap)> = Vec::new();
+    let input = Path::new(input);
+    if input.is_dir() {
+        let map_folder = MapFolder::from_path(input)?;
+        for (x, y) in map_folder.get_grid_tiles()? {
+            let adt_path = map_folder.get_adt_path(x, y);
+            if !adt_path.exists() {
+                warns.push(HeightmapWarn::MissingAdt(adt_path));
+                continue;
+            }
+            tiles.push(((x, y), read_heightmap(&adt_path)?));
+        }
+    } else {
+        let coords = get_tile_coords(input).unwrap_or((0, 0));
+        tiles.push((coords, read_heightmap(input)?));
+    }
+    if tiles.is_empty() {
+        return err(format!("No ADTs to export in {}", input.display()));
+    }
+
+    let origin_x = tiles.iter().map(|((x, _), _)| *x).min().unwrap();
+    let origin_y = tiles.iter().map(|((_, y), _)| *y).min().unwrap();
+    let tiles_x = tiles.iter().map(|((x, _), _)| *x).max().unwrap() - origin_x + 1;
+    let tiles_y = tiles.iter().map(|((_, y), _)| *y).max().unwrap() - origin_y + 1;
+    let (min_height, max_height) = tiles.iter()
+        .map(|(_, heightmap)| heightmap.min_max())
+        .fold((f32::MAX, f32::MIN), |(min, max), (a, b)| (min.min(a), max.max(b)));
+
+    let metadata = HeightmapMetadata {
+        format,
+        width: tiles_x * TILE_STRIDE + 1,
+        height: tiles_y * TILE_STRIDE + 1,
+        min_height,
+        max_height,
+        origin_tile: [origin_x, origin_y],
+        tiles: tiles.iter().map(|((x, y), _)| [*x, *y]).collect(),
+    };
+
+    let mut image = GrayscaleImage::new(metadata.width, metadata.height);
+    for ((x, y), heightmap) in &tiles {
+        let (left, top) = ((x - origin_x) * TILE_STRIDE, (y - origin_y) * TILE_STRIDE);
+        for row in 0..TILE_HEIGHTMAP_SIZE {
+            for col in 0..TILE_HEIGHTMAP_SIZE {
+                let pixel = metadata.height_to_pixel(heightmap.get(col, row));
+                image.set(left + col as u32, top + row as u32, pixel);
+            }
+        }
+    }
+
+    let bytes = match format {
+        HeightmapFormat::Png => image.to_png()?,
+        HeightmapFormat::R16 => image.pixels.iter().flat_map(|px| px.to_le_bytes().to_vec()).collect(),
+    };
+    fs::write(&output, bytes).with_context(|| format!("Failed to write {}", output.display()))?;
+    let metadata_path = get_metadata_path(&output);
+    fs::write(&metadata_path, serde_json::to_string_pretty(&metadata)?)
+        .with_context(|| format!("Failed to write {}", metadata_path.display()))?;
+
+    Ok(HeightmapExportResult {
+        image: output,
+        metadata_path,
+        metadata,
+        warns,
+    })
+}
+
+pub fn import_heightmap(input: &str, image_path: &str) -> R<HeightmapImportResult> {
+    let image_path = PathBuf::from(image_path);
+    let metadata_path = get_metadata_path(&image_path);
+    let metadata: HeightmapMetadata = serde_json::from_slice(
+        &fs::read(&metadata_path)
+            .with_context(|| format!("Missing heightmap metadata {}, it is written by the export", metadata_path.display()))?,
+    )?;
+    let image = read_image(&image_path, &metadata)?;
+
+    let input = Path::new(input);
+    let mut targets = Vec::new();
+    if input.is_dir() {
+        let map_folder = MapFolder::from_path(input)?;
+        for [x, y] in &metadata.tiles {
+            targets.push(((*x, *y), map_folder.get_adt_path(*x, *y)));
+        }
+    } else {
+        let coords = match get_tile_coords(input) {
+            Some(coords) if metadata.tiles.contains(&[coords.0, coords.1]) => coords,
+            _ if metadata.tiles.len() == 1 => (metadata.tiles[0][0], metadata.tiles[0][1]),
+            _ => return err(format!("{} is not one of the tiles in {}", input.display(), metadata_path.display())),
+        };
+        targets.push((coords, input.to_path_buf()));
+    }
+
+    let mut result = HeightmapImportResult {
+        updated: Vec::new(),
+        unchanged: Vec::new(),
+        warns: Vec::new(),
+    };
+    for ((x, y), adt_path) in targets {
+        if !adt_path.exists() {
+            result.warns.push(HeightmapWarn::MissingAdt(adt_path));
+            continue;
+        }
+        let mut adt = AdtFile::from_path(&adt_path)
+            .with_context(|| format!("Failed to read {}", adt_path.display()))?;
+        let old = adt.get_heightmap()?;
+        let mut new = old.clone();
+        let left = (x - metadata.origin_tile[0]) * TILE_STRIDE;
+        let top = (y - metadata.origin_tile[1]) * TILE_STRIDE;
+        for row in 0..TILE_HEIGHTMAP_SIZE {
+            for col in 0..TILE_HEIGHTMAP_SIZE {
+                let pixel = image.get(left + col as u32, top + row as u32);
+                let index = row * TILE_HEIGHTMAP_SIZE + col;
+                // unedited pixels keep the exact height instead of the quantized one
+                if metadata.height_to_pixel(old.heights[index]) != pixel {
+                    new.heights[index] = metadata.pixel_to_height(pixel);
+                }
+            }
+        }
+
+        if new == old {
+            result.unchanged.push(adt_path);
+            continue;
+        }
+        adt.set_heightmap(&new)?;
+        adt.write_to_path(&adt_path)?;
+        result.updated.push(adt_path);
+    }
+    Ok(result)
+}
+
+fn read_heightmap(adt_path: &Path) -> R<TileHeightmap> {
+    AdtFile::from_path(adt_path)
+        .and_then(|adt| adt.get_heightmap())
+        .with_context(|| format!("Failed to read heights of {}", adt_path.display()))
+}
+
+fn read_image(path: &Path, metadata: &HeightmapMetadata) -> R<GrayscaleImage> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let image = match metadata.format {
+        HeightmapFormat::Png => GrayscaleImage::from_png(&bytes)?,
+        HeightmapFormat::R16 => {
+            if bytes.len() != (metadata.width * metadata.height * 2) as usize {
+                return err(format!(
+                    "{} is {} bytes, expected {}x{} 16 bit pixels",
+                    path.display(),
+                    bytes.len(),
+                    metadata.width,
+                    metadata.height
+                ));
+            }
+            GrayscaleImage {
+                width: metadata.width,
+                height: metadata.height,
+                pixels: bytes.chunks(2).map(|px| u16::from_le_bytes([px[0], px[1]])).collect(),
+            }
+        }
+    };
+    if image.width != metadata.width || image.height != metadata.height {
+        return err(format!(
+            "{} is {}x{} but was exported as {}x{}",
+            path.display(),
+            image.width,
+            image.height,
+            metadata.width,
+            metadata.height
+        ));
+    }
+    Ok(image)
+}
+
+fn get_format_from_extension(path: &Path) -> R<HeightmapFormat> {
+    let extension = path.extension()
+        .map(|it| it.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "png" => Ok(HeightmapFormat::Png),
+        "r16" | "raw" => Ok(HeightmapFormat::R16),
+        _ => err(format!("Unknown heightmap format for {}, use .png, .r16 or .raw", path.display())),
+    }
+}
+
+fn get_metadata_path(image_path: &Path) -> PathBuf {
+    let mut path = image_path.as_os_str().to_owned();
+    path.push(".json");
+    PathBuf::from(path)
+}
@@ -0,0 +1,170 @@
+pub mod heightmap;
+pub mod placements;
+pub mod relocate;
+pub mod replace_paths;
+pub mod set_area;
+
+use crate::common::{err, R};
+use crate::formats::adt::get_tile_coords;
+use crate::formats::wdt::WdtFile;
+use crate::{AdtHeightmapCmd, AdtToolCmd};
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+pub fn handle_adt_command(cmd: &AdtToolCmd) -> R<Box<dyn erased_serde::Serialize>> {
+    let res: Box<dyn erased_serde::Serialize> = match cmd {
+        AdtToolCmd::Heightmap { cmd } => match cmd {
+            AdtHeightmapCmd::Export(cmd) => Box::new(heightmap::export_heightmap(&cmd.input, &cmd.output)?),
+            AdtHeightmapCmd::Import(cmd) => Box::new(heightmap::import_heightmap(&cmd.input, &cmd.image)?),
+        },
+        AdtToolCmd::Move(cmd) => Box::new(relocate::move_tile(
+            &cmd.map_folder,
+            (cmd.from.0, cmd.from.1),
+            (cmd.to.0, cmd.to.1),
+        )?),
+        AdtToolCmd::CopyTiles(cmd) => {
+            let tiles: Vec<(u32, u32)> = cmd.tiles.iter().map(|it| (it.0, it.1)).collect();
+            Box::new(relocate::copy_tiles(&cmd.source, &cmd.dest, &tiles, cmd.to.map(|it| (it.0, it.1)))?)
+        }
+        AdtToolCmd::ReplacePaths(cmd) => {
+            Box::new(replace_paths::replace_paths(&cmd.input, &cmd.mapping_file, cmd.dry_run)?)
+        }
+        AdtToolCmd::SetArea(cmd) => {
+            let operation = match (cmd.area_id, cmd.remap.is_empty()) {
+                (Some(area_id), true) => set_area::SetAreaOperation::Set {
+                    area_id,
+                    rect: cmd.chunks.map(|it| it.0),
+                },
+                (None, false) if cmd.chunks.is_none() => {
+                    set_area::SetAreaOperation::Remap(cmd.remap.iter().map(|it| (it.0, it.1)).collect())
+                }
+                _ => return err("Use either --area-id, optionally with --chunks, or --remap".to_string()),
+            };
+            Box::new(set_area::set_area(&cmd.input, &operation)?)
+        }
+        AdtToolCmd::Placements(cmd) => Box::new(placements::edit_placements(
+            &cmd.map_folder,
+            &cmd.placement_file,
+            cmd.workspace.as_deref(),
+        )?),
+    };
+    Ok(res)
+}
+
+/// The ADTs of a map folder (`World/Maps/<map>`) keyed by tile coordinates, plus its WDT when there is one.
+/// ADTs named after tiles outside of the 64x64 grid are left out. The WDT is only read on demand,
+/// so commands that rebuild it don't depend on it being valid.
+pub struct MapFolder {
+    pub name: String,
+    pub path: PathBuf,
+    pub wdt_path: Option<PathBuf>,
+    pub adts: BTreeMap<(u32, u32), PathBuf>,
+}
+
+impl MapFolder {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> R<MapFolder> {
+        let path = path.as_ref().to_path_buf();
+        if !path.is_dir() {
+            return err(format!("{} is not a map folder", path.display()));
+        }
+        let name = path.file_name()
+            .and_then(|it| it.to_str())
+            .context("Map folder has no usable name")?
+            .to_string();
+
+        let wdt_path = read_dir(&path)?
+            .filter_map(|e| e.ok())
+            .find(|e| e.file_name().to_string_lossy().to_lowercase() == format!("{}.wdt", name.to_lowercase()))
+            .map(|e| e.path());
+        let mut adts = BTreeMap::new();
+        for file_path in find_adts(&path, false) {
+            let file_name = file_path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+            if !file_name.starts_with(&format!("{}_", name.to_lowercase())) {
+                continue;
+            }
+            if let Some((x, y)) = get_tile_coords(&file_path).filter(|(x, y)| *x < 64 && *y < 64) {
+                adts.insert((x, y), file_path);
+            }
+        }
+        Ok(MapFolder { name, path, wdt_path, adts })
+    }
+
+    pub fn read_wdt(&self) -> R<Option<WdtFile>> {
+        match &self.wdt_path {
+            Some(wdt_path) => Ok(Some(WdtFile::from_path(wdt_path)
+                .with_context(|| format!("Failed to read {}", wdt_path.display()))?)),
+            None => Ok(None),
+        }
+    }
+
+    /// `<map>.wdt`, as found in the folder or where it would be written.
+    pub fn get_wdt_path(&self) -> PathBuf {
+        self.wdt_path.clone().unwrap_or_else(|| self.path.join(format!("{}.wdt", self.name)))
+    }
+
+    /// Tiles flagged in the WDT MAIN grid, or every ADT found when the map has no WDT.
+    pub fn get_grid_tiles(&self) -> R<Vec<(u32, u32)>> {
+        Ok(match self.read_wdt()? {
+            Some(wdt) => wdt.main.get_tiles(),
+            None => self.adts.keys().cloned().collect(),
+        })
+    }
+
+    pub fn get_adt_path(&self, x: u32, y: u32) -> PathBuf {
+        self.adts.get(&(x, y))
+            .cloned()
+            .unwrap_or_else(|| self.path.join(format!("{}_{}_{}.adt", self.name, x, y)))
+    }
+}
+
+/// `input` itself when it is a file, otherwise the ADTs of the folder sorted by path.
+/// `recursive` also searches its subfolders, to go through every map of `World/Maps` for instance.
+pub fn find_adts<P: AsRef<Path>>(input: P, recursive: bool) -> Vec<PathBuf> {
+    let input = input.as_ref();
+    if input.is_file() {
+        return vec![input.to_path_buf()];
+    }
+    let walk = WalkDir::new(input).min_depth(1);
+    let walk = if recursive { walk } else { walk.max_depth(1) };
+    let mut adts: Vec<PathBuf> = walk
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .filter(|path| path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase() == "adt")
+            .unwrap_or(false))
+        .collect();
+    adts.sort();
+    adts
+}
+
+#[cfg(test)]
+#[test]
+fn adts_are_found_in_map_folders() {
+    let dir = crate::test_utils::TempDir::new("wow-file-tools-find-adts");
+    let (azeroth, kalimdor) = (dir.join("Azeroth"), dir.join("Kalimdor"));
+    std::fs::create_dir_all(&azeroth).unwrap();
+    std::fs::create_dir_all(&kalimdor).unwrap();
+    for path in &[
+        azeroth.join("Azeroth_32_48.ADT"),
+        azeroth.join("Azeroth_64_0.adt"),
+        azeroth.join("Kalimdor_1_1.adt"),
+        azeroth.join("Azeroth.wdt"),
+        kalimdor.join("Kalimdor_1_1.adt"),
+    ] {
+        std::fs::write(path, b"").unwrap();
+    }
+
+    assert_eq!(find_adts(dir.join(""), false), Vec::<PathBuf>::new());
+    assert_eq!(find_adts(dir.join(""), true).len(), 4);
+    assert_eq!(find_adts(&azeroth, false).len(), 3);
+    assert_eq!(find_adts(kalimdor.join("Kalimdor_1_1.adt"), false), vec![kalimdor.join("Kalimdor_1_1.adt")]);
+
+    let map_folder = MapFolder::from_path(&azeroth).unwrap();
+    assert_eq!(map_folder.adts.keys().collect::<Vec<_>>(), vec![&(32, 48)]);
+    assert_eq!(map_folder.wdt_path, Some(azeroth.join("Azeroth.wdt")));
+}
@@ -0,0 +1,456 @@
+use crate::command_handler::adt::MapFolder;
+use crate::command_handler::resolve_map_assets::join_path_ignoring_casing;
+use crate::common::{err, R};
+use crate::formats::adt::placement::{get_extents_tiles, get_placement_tile, DOODAD_SCALE_ONE};
+use crate::formats::adt::AdtFile;
+use crate::formats::chunk::{Chunk, ChunkMddfItem, ChunkModfItem, ChunkVecUtils};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The JSON placement file, removals run first, then updates, then additions.
+#[derive(Debug, Deserialize)]
+pub struct PlacementEdits {
+    #[serde(default)]
+    pub remove: Vec<u32>,
+    #[serde(default)]
+    pub update: Vec<PlacementUpdate>,
+    #[serde(default)]
+    pub add: Vec<PlacementAdd>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlacementKind {
+    Doodad,
+    Wmo,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlacementAdd {
+    pub kind: PlacementKind,
+    pub path: String,
+    pub position: [f32; 3],
+    #[serde(default)]
+    pub rotation: [f32; 3],
+    pub scale: Option<f32>,
+    // a new one is picked when omitted
+    pub unique_id: Option<u32>,
+    #[serde(default)]
+    pub flags: u16,
+    #[serde(default)]
+    pub doodad_set: u16,
+    #[serde(default)]
+    pub name_set: u16,
+    pub extents: Option<[f32; 6]>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlacementUpdate {
+    pub unique_id: u32,
+    pub position: Option<[f32; 3]>,
+    pub rotation: Option<[f32; 3]>,
+    pub scale: Option<f32>,
+    pub extents: Option<[f32; 6]>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlacementEditResult {
+    pub removed: Vec<u32>,
+    pub updated: Vec<u32>,
+    pub added: Vec<PlacedEntry>,
+    pub written: Vec<PathBuf>,
+    pub warns: Vec<PlacementWarn>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlacedEntry {
+    pub unique_id: u32,
+    pub kind: PlacementKind,
+    pub tiles: Vec<(u32, u32)>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum PlacementWarn {
+    // a unique ID placed twice on one tile, or placed differently on several tiles
+    DuplicateUniqueId(u32),
+}
+
+enum Placement {
+    Doodad(String, ChunkMddfItem),
+    Wmo(String, ChunkModfItem),
+}
+
+impl Placement {
+    fn all_of(adt: &AdtFile) -> impl Iterator<Item = Placement> + '_ {
+        let doodads = adt.mddf.0.iter()
+            .map(|it| Placement::Doodad(it.name.clone().unwrap_or_default(), it.clone()));
+        let map_objects = adt.modf.0.iter()
+            .map(|it| Placement::Wmo(it.name.clone().unwrap_or_default(), it.clone()));
+        doodads.chain(map_objects)
+    }
+
+    fn unique_id(&self) -> u32 {
+        match self {
+            Placement::Doodad(_, item) => item.unique_id,
+            Placement::Wmo(_, item) => item.unique_id,
+        }
+    }
+
+    /// Whether both are the same placement, their name indices differ between tiles.
+    fn same_as(&self, other: &Placement) -> bool {
+        match (self, other) {
+            (Placement::Doodad(path, a), Placement::Doodad(other_path, b)) => path.eq_ignore_ascii_case(other_path)
+                && a.position == b.position
+                && a.rotation == b.rotation
+                && a.scale == b.scale
+                && a.flags == b.flags,
+            (Placement::Wmo(path, a), Placement::Wmo(other_path, b)) => path.eq_ignore_ascii_case(other_path)
+                && a.position == b.position
+                && a.rotation == b.rotation
+                && a.extents == b.extents
+                && a.doodad_set == b.doodad_set
+                && a.name_set == b.name_set,
+            _ => false,
+        }
+    }
+}
+
+struct MapTiles<'a> {
+    map_folder: &'a MapFolder,
+    loaded: BTreeMap<(u32, u32), AdtFile>,
+    // unique ID -> tiles placing it
+    placed_on: HashMap<u32, BTreeSet<(u32, u32)>>,
+    dirty: BTreeSet<(u32, u32)>,
+}
+
+pub fn edit_placements(map_folder: &str, placement_file: &str, workspace: Option<&str>) -> R<PlacementEditResult> {
+    let edits: PlacementEdits = serde_json::from_slice(
+        &fs::read(placement_file).with_context(|| format!("Failed to read {}", placement_file))?,
+    ).with_context(|| format!("Invalid placement file {}", placement_file))?;
+    let map_folder = MapFolder::from_path(map_folder)?;
+    let workspace = workspace.map(Path::new);
+
+    let mut result = PlacementEditResult {
+        removed: Vec::new(),
+        updated: Vec::new(),
+        added: Vec::new(),
+        written: Vec::new(),
+        warns: Vec::new(),
+    };
+
+    // unique IDs are shared by the whole map, index them without keeping every tile in memory
+    let mut tiles = MapTiles {
+        map_folder: &map_folder,
+        loaded: BTreeMap::new(),
+        placed_on: HashMap::new(),
+        dirty: BTreeSet::new(),
+    };
+    // placements near tile edges are repeated on the neighbouring tiles, those aren't duplicates
+    let mut first_placements: HashMap<u32, Placement> = HashMap::new();
+    let mut duplicates = BTreeSet::new();
+    for (coords, adt_path) in &map_folder.adts {
+        let adt = AdtFile::from_path(adt_path)
+            .with_context(|| format!("Failed to read {}", adt_path.display()))?;
+        let mut tile_ids = BTreeSet::new();
+        for placement in Placement::all_of(&adt) {
+            let unique_id = placement.unique_id();
+            if !tile_ids.insert(unique_id) {
+                duplicates.insert(unique_id);
+            }
+            if let Some(first) = first_placements.get(&unique_id) {
+                if !first.same_as(&placement) {
+                    duplicates.insert(unique_id);
+                }
+            } else {
+                first_placements.insert(unique_id, placement);
+            }
+        }
+        for id in tile_ids {
+            tiles.placed_on.entry(id).or_default().insert(*coords);
+        }
+    }
+    result.warns.extend(duplicates.into_iter().map(PlacementWarn::DuplicateUniqueId));
+    let mut next_unique_id = tiles.placed_on.keys().max().map(|it| it + 1).unwrap_or(1);
+
+    for unique_id in &edits.remove {
+        tiles.take(*unique_id)?;
+        result.removed.push(*unique_id);
+    }
+
+    for update in &edits.update {
+        let taken = tiles.take(update.unique_id)?;
+        let (_, first) = &taken[0];
+        if taken.iter().any(|(_, it)| !it.same_as(first)) {
+            return err(format!("Unique ID {} is ambiguous, it is placed differently on several tiles", update.unique_id));
+        }
+        // a doodad repeated on neighbouring tiles stays there as long as it keeps its position
+        let mut keep_tiles: BTreeSet<(u32, u32)> = taken.iter().map(|(coords, _)| *coords).collect();
+        if update.position.is_some() {
+            keep_tiles.clear();
+        }
+        let placement = match taken.into_iter().next().unwrap().1 {
+            Placement::Doodad(path, mut item) => {
+                if update.extents.is_some() {
+                    return err(format!("Doodad {} has no extents to update", update.unique_id));
+                }
+                item.position = update.position.unwrap_or(item.position);
+                item.rotation = update.rotation.unwrap_or(item.rotation);
+                if let Some(scale) = update.scale {
+                    item.scale = to_doodad_scale(scale)?;
+                }
+                Placement::Doodad(path, item)
+            }
+            Placement::Wmo(path, mut item) => {
+                if update.scale.is_some() {
+                    return err(format!("WMO {} can't be scaled", update.unique_id));
+                }
+                let old_position = item.position;
+                item.position = update.position.unwrap_or(item.position);
+                item.rotation = update.rotation.unwrap_or(item.rotation);
+                item.extents = match (update.extents, update.rotation) {
+                    (Some(extents), _) => extents,
+                    (None, None) => {
+                        let mut extents = item.extents;
+                        for axis in 0..3 {
+                            extents[axis] += item.position[axis] - old_position[axis];
+                            extents[axis + 3] += item.position[axis] - old_position[axis];
+                        }
+                        extents
+                    }
+                    (None, Some(_)) => get_wmo_extents(workspace, &path, &item.position)
+                        .with_context(|| format!("WMO {} was rotated, give its extents or a workspace to compute them", update.unique_id))?,
+                };
+                Placement::Wmo(path, item)
+            }
+        };
+        tiles.place(placement, &keep_tiles)?;
+        result.updated.push(update.unique_id);
+    }
+
+    for add in &edits.add {
+        let unique_id = match add.unique_id {
+            Some(unique_id) if tiles.placed_on.contains_key(&unique_id) => {
+                return err(format!("Unique ID {} is already used in the map", unique_id));
+            }
+            Some(unique_id) => unique_id,
+            None => next_unique_id,
+        };
+        next_unique_id = next_unique_id.max(unique_id + 1);
+
+        let placement = match add.kind {
+            PlacementKind::Doodad => Placement::Doodad(add.path.clone(), ChunkMddfItem {
+                mmid_entry: 0,
+                name: None,
+                unique_id,
+                position: add.position,
+                rotation: add.rotation,
+                scale: to_doodad_scale(add.scale.unwrap_or(1.0))?,
+                flags: add.flags,
+            }),
+            PlacementKind::Wmo => {
+                if add.scale.is_some() {
+                    return err(format!("WMO {} can't be scaled", add.path));
+                }
+                let extents = match add.extents {
+                    Some(extents) => extents,
+                    None => get_wmo_extents(workspace, &add.path, &add.position)
+                        .with_context(|| format!("Give the extents of {} or a workspace to compute them", add.path))?,
+                };
+                Placement::Wmo(add.path.clone(), ChunkModfItem {
+                    mwid_entry: 0,
+                    name: None,
+                    unique_id,
+                    position: add.position,
+                    rotation: add.rotation,
+                    extents,
+                    flags: add.flags,
+                    doodad_set: add.doodad_set,
+                    name_set: add.name_set,
+                    scale: 0,
+                })
+            }
+        };
+        let placed_tiles = tiles.place(placement, &BTreeSet::new())?;
+        result.added.push(PlacedEntry {
+            unique_id,
+            kind: add.kind,
+            tiles: placed_tiles,
+        });
+    }
+
+    for coords in &tiles.dirty {
+        let adt_path = map_folder.get_adt_path(coords.0, coords.1);
+        tiles.loaded[coords].write_to_path(&adt_path)?;
+        result.written.push(adt_path);
+    }
+    Ok(result)
+}
+
+impl<'a> MapTiles<'a> {
+    fn get(&mut self, coords: (u32, u32)) -> R<&mut AdtFile> {
+        if !self.loaded.contains_key(&coords) {
+            let adt_path = self.map_folder.adts.get(&coords)
+                .with_context(|| format!("Tile {}_{} has no ADT in {}", coords.0, coords.1, self.map_folder.path.display()))?;
+            let adt = AdtFile::from_path(adt_path)
+                .with_context(|| format!("Failed to read {}", adt_path.display()))?;
+            self.loaded.insert(coords, adt);
+        }
+        Ok(self.loaded.get_mut(&coords).unwrap())
+    }
+
+    /// Removes every placement of the unique ID from the map, returns them with their tiles.
+    fn take(&mut self, unique_id: u32) -> R<Vec<((u32, u32), Placement)>> {
+        let placed_on = self.placed_on.remove(&unique_id)
+            .with_context(|| format!("Unique ID {} is not placed in the map", unique_id))?;
+        let mut taken = Vec::new();
+        for coords in placed_on {
+            let adt = self.get(coords)?;
+            while let Some(index) = adt.mddf.0.iter().position(|it| it.unique_id == unique_id) {
+                let item = adt.remove_doodad(index);
+                let path = item.name.clone().unwrap_or_default();
+                taken.push((coords, Placement::Doodad(path, item)));
+            }
+            while let Some(index) = adt.modf.0.iter().position(|it| it.unique_id == unique_id) {
+                let item = adt.remove_map_object(index);
+                let path = item.name.clone().unwrap_or_default();
+                taken.push((coords, Placement::Wmo(path, item)));
+            }
+            self.dirty.insert(coords);
+        }
+        if taken.is_empty() {
+            return err(format!("Unique ID {} is not placed in the map", unique_id));
+        }
+        Ok(taken)
+    }
+
+    /// Doodads go to the tile they stand on and the given neighbours of it,
+    /// WMOs to every existing tile their extents overlap.
+    fn place(&mut self, placement: Placement, neighbours: &BTreeSet<(u32, u32)>) -> R<Vec<(u32, u32)>> {
+        let (unique_id, position) = match &placement {
+            Placement::Doodad(_, item) => (item.unique_id, item.position),
+            Placement::Wmo(_, item) => (item.unique_id, item.position),
+        };
+        let home = get_placement_tile(&position)
+            .with_context(|| format!("Position {:?} of {} is outside of the map", position, unique_id))?;
+        let mut targets = vec![home];
+        match &placement {
+            Placement::Doodad(..) => {
+                for coords in neighbours {
+                    let borders = (coords.0 as i64 - home.0 as i64).abs() <= 1
+                        && (coords.1 as i64 - home.1 as i64).abs() <= 1;
+                    if *coords != home && borders && self.map_folder.adts.contains_key(coords) {
+                        targets.push(*coords);
+                    }
+                }
+            }
+            Placement::Wmo(_, item) => {
+                for coords in get_extents_tiles(&item.extents) {
+                    if coords != home && self.map_folder.adts.contains_key(&coords) {
+                        targets.push(coords);
+                    }
+                }
+            }
+        }
+
+        for coords in &targets {
+            let adt = self.get(*coords)?;
+            match &placement {
+                Placement::Doodad(path, item) => adt.add_doodad(path, item.clone())?,
+                Placement::Wmo(path, item) => adt.add_map_object(path, item.clone())?,
+            };
+            self.dirty.insert(*coords);
+        }
+        self.placed_on.insert(unique_id, targets.iter().cloned().collect());
+        Ok(targets)
+    }
+}
+
+fn to_doodad_scale(scale: f32) -> R<u16> {
+    let value = (scale * DOODAD_SCALE_ONE).round();
+    if value < 1.0 || value > u16::MAX as f32 {
+        return err(format!("Doodad scale {} is out of range", scale));
+    }
+    Ok(value as u16)
+}
+
+/// Extents from the bounding box of the WMO root, widened to its horizontal radius
+/// so they hold for any rotation around the vertical axis.
+fn get_wmo_extents(workspace: Option<&Path>, wmo_path: &str, position: &[f32; 3]) -> R<[f32; 6]> {
+    let workspace = workspace.context("No workspace given")?;
+    let root_path = join_path_ignoring_casing(workspace, wmo_path)
+        .with_context(|| format!("{} is not in the workspace", wmo_path))?;
+    let chunks = Chunk::from_path(&root_path)?;
+    let mohd = chunks.get_mohd();
+    let (a, b) = (mohd.bounding_box_corner_1, mohd.bounding_box_corner_2);
+    let corners = [(a[0], a[1]), (a[0], b[1]), (b[0], a[1]), (b[0], b[1])];
+    let radius = corners.iter()
+        .map(|(x, y)| (x * x + y * y).sqrt())
+        .fold(0.0f32, f32::max);
+    let (min_z, max_z) = (a[2].min(b[2]), a[2].max(b[2]));
+    Ok([
+        position[0] - radius,
+        position[1] + min_z,
+        position[2] - radius,
+        position[0] + radius,
+        position[1] + max_z,
+        position[2] + radius,
+    ])
+}
+
+#[cfg(test)]
+#[test]
+fn doodads_repeated_on_neighbouring_tiles_are_edited_together() {
+    use crate::formats::adt::test_utils::build_test_adt;
+    use crate::formats::adt::CHUNK_SIZE;
+
+    let dir = crate::test_utils::TempDir::new("wow-file-tools-adt-placements");
+    let map = dir.join("Test");
+    fs::create_dir_all(&map).unwrap();
+    let mut base = AdtFile::from_bytes(&build_test_adt()).unwrap();
+    base.remove_doodad(0);
+    // a doodad close to the edge of tile 0_0, the client files repeat it on tile 1_0
+    let doodad = ChunkMddfItem {
+        mmid_entry: 0,
+        name: None,
+        unique_id: 7,
+        position: [CHUNK_SIZE * 15.5, 10.0, CHUNK_SIZE * 2.5],
+        rotation: [0.0; 3],
+        scale: DOODAD_SCALE_ONE as u16,
+        flags: 0,
+    };
+    for (x, y) in &[(0, 0), (1, 0)] {
+        let mut adt = AdtFile::from_bytes(&base.to_bytes().unwrap()).unwrap();
+        adt.relocate((0, 0), (*x, *y)).unwrap();
+        adt.add_doodad("World\\Tree.m2", doodad.clone()).unwrap();
+        adt.write_to_path(map.join(format!("Test_{}_{}.adt", x, y))).unwrap();
+    }
+    let edit = |placements: &str| {
+        let placement_file = dir.join("placements.json");
+        fs::write(&placement_file, placements).unwrap();
+        edit_placements(map.to_str().unwrap(), placement_file.to_str().unwrap(), None)
+    };
+    let placed = |x: u32| AdtFile::from_path(map.join(format!("Test_{}_0.adt", x))).unwrap().mddf.0;
+
+    let result = edit(r#"{ "update": [{ "unique_id": 7, "rotation": [0, 90, 0] }] }"#).unwrap();
+    assert!(result.warns.is_empty());
+    assert_eq!(result.written.len(), 2);
+    for x in 0..2 {
+        let doodads = placed(x);
+        assert_eq!(doodads.len(), 1);
+        assert_eq!((doodads[0].unique_id, doodads[0].rotation), (7, [0.0, 90.0, 0.0]));
+    }
+
+    let mut neighbour = AdtFile::from_path(map.join("Test_1_0.adt")).unwrap();
+    neighbour.mddf.0[0].position[1] = 20.0;
+    neighbour.write_to_path(map.join("Test_1_0.adt")).unwrap();
+    assert!(matches!(edit("{}").unwrap().warns[..], [PlacementWarn::DuplicateUniqueId(7)]));
+    let ambiguous = edit(r#"{ "update": [{ "unique_id": 7, "scale": 2 }] }"#).unwrap_err();
+    assert!(format!("{:#}", ambiguous).contains("ambiguous"));
+
+    let result = edit(r#"{ "remove": [7] }"#).unwrap();
+    assert_eq!(result.removed, vec![7]);
+    assert!(placed(0).is_empty() && placed(1).is_empty());
+}
@@ -0,0 +1,225 @@
+use crate::command_handler::adt::MapFolder;
+use crate::common::{err, R};
+use crate::formats::adt::AdtFile;
+use crate::formats::wdt::WdtFile;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdtRelocateResult {
+    pub tiles: Vec<AdtRelocatedTile>,
+    // the WDT whose MAIN grid was updated, none when a moved tile's map has no WDT
+    pub wdt: Option<PathBuf>,
+    // (copied, written) unique IDs of placements whose ID was already used in the target map
+    pub reassigned_unique_ids: Vec<(u32, u32)>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdtRelocatedTile {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// Moves a tile within its map folder, the MAIN grid of the map's WDT follows when there is one.
+pub fn move_tile(map_folder: &str, from: (u32, u32), to: (u32, u32)) -> R<AdtRelocateResult> {
+    let map_folder = MapFolder::from_path(map_folder)?;
+    let source = map_folder.adts.get(&from)
+        .with_context(|| format!("No ADT for tile {}_{} in {}", from.0, from.1, map_folder.path.display()))?
+        .clone();
+    let target = map_folder.get_adt_path(to.0, to.1);
+    if target.exists() {
+        return err(format!("{} already exists", target.display()));
+    }
+    let mut wdt = map_folder.read_wdt()?;
+
+    read_relocated_adt(&source, from, to)?.write_to_path(&target)?;
+    fs::remove_file(&source).with_context(|| format!("Failed to remove {}", source.display()))?;
+    let wdt_path = match wdt.as_mut() {
+        Some(wdt) => {
+            wdt.main.set_has_adt(from.0, from.1, false)?;
+            wdt.main.set_has_adt(to.0, to.1, true)?;
+            let wdt_path = map_folder.get_wdt_path();
+            wdt.write_to_path(&wdt_path)?;
+            Some(wdt_path)
+        }
+        None => None,
+    };
+    Ok(AdtRelocateResult {
+        tiles: vec![AdtRelocatedTile { from: source, to: target }],
+        wdt: wdt_path,
+        reassigned_unique_ids: Vec::new(),
+    })
+}
+
+/// Copies tiles into another map folder, `to` is where the top left most tile lands,
+/// the other tiles keep their position relative to it.
+/// Unique IDs already used by the target map are replaced by new ones above its highest ID.
+/// The copied tiles are flagged in the target WDT, which is created when missing.
+pub fn copy_tiles(
+    source_folder: &str,
+    target_folder: &str,
+    tiles: &[(u32, u32)],
+    to: Option<(u32, u32)>,
+) -> R<AdtRelocateResult> {
+    let source = MapFolder::from_path(source_folder)?;
+    fs::create_dir_all(target_folder)
+        .with_context(|| format!("Failed to create {}", target_folder))?;
+    let target = MapFolder::from_path(target_folder)?;
+
+    let mut tiles: Vec<(u32, u32)> = if tiles.is_empty() {
+        source.adts.keys().cloned().collect()
+    } else {
+        tiles.to_vec()
+    };
+    tiles.sort_unstable();
+    tiles.dedup();
+    if tiles.is_empty() {
+        return err(format!("No ADTs to copy in {}", source.path.display()));
+    }
+    let min_x = tiles.iter().map(|(x, _)| *x).min().unwrap();
+    let min_y = tiles.iter().map(|(_, y)| *y).min().unwrap();
+    let (to_x, to_y) = to.unwrap_or((min_x, min_y));
+
+    let mut planned = Vec::with_capacity(tiles.len());
+    for (x, y) in &tiles {
+        let from_path = source.adts.get(&(*x, *y))
+            .with_context(|| format!("No ADT for tile {}_{} in {}", x, y, source.path.display()))?;
+        let new_x = (to_x + x).checked_sub(min_x).filter(|it| *it < 64);
+        let new_y = (to_y + y).checked_sub(min_y).filter(|it| *it < 64);
+        let (new_x, new_y) = match (new_x, new_y) {
+            (Some(new_x), Some(new_y)) => (new_x, new_y),
+            _ => return err(format!("Tile {}_{} would end up outside of the 64x64 map grid", x, y)),
+        };
+        let to_path = target.get_adt_path(new_x, new_y);
+        if to_path.exists() {
+            return err(format!("{} already exists", to_path.display()));
+        }
+        planned.push(((*x, *y), (new_x, new_y), from_path.clone(), to_path));
+    }
+
+    let mut wdt = match target.read_wdt()? {
+        Some(wdt) => wdt,
+        None => {
+            // the copied ADTs keep the alpha map and vertex formats of the source map
+            let mut flags = source.read_wdt()?.map(|it| it.mphd.flags).unwrap_or_default();
+            flags.uses_global_map_obj = false;
+            flags.has_maid = false;
+            let mut wdt = WdtFile::new_empty(flags);
+            for (x, y) in target.adts.keys() {
+                wdt.main.set_has_adt(*x, *y, true)?;
+            }
+            wdt
+        }
+    };
+
+    let mut adts = Vec::with_capacity(planned.len());
+    for (from, to, from_path, _) in &planned {
+        adts.push(read_relocated_adt(from_path, *from, *to)?);
+    }
+    let reassigned_unique_ids = reassign_unique_ids(&target, &mut adts)?;
+
+    let mut result = AdtRelocateResult {
+        tiles: Vec::with_capacity(planned.len()),
+        wdt: None,
+        reassigned_unique_ids,
+    };
+    for ((_, to, from_path, to_path), adt) in planned.into_iter().zip(adts) {
+        adt.write_to_path(&to_path)?;
+        wdt.main.set_has_adt(to.0, to.1, true)?;
+        result.tiles.push(AdtRelocatedTile { from: from_path, to: to_path });
+    }
+    let wdt_path = target.get_wdt_path();
+    wdt.write_to_path(&wdt_path)?;
+    result.wdt = Some(wdt_path);
+    Ok(result)
+}
+
+fn read_relocated_adt(source: &Path, from: (u32, u32), to: (u32, u32)) -> R<AdtFile> {
+    let mut adt = AdtFile::from_path(source)
+        .with_context(|| format!("Failed to read {}", source.display()))?;
+    adt.relocate(from, to)?;
+    Ok(adt)
+}
+
+/// Gives the copied placements whose unique ID is taken in the target map a new one above the
+/// highest ID of both. Placements repeated on neighbouring tiles share one ID, they keep sharing it.
+fn reassign_unique_ids(target: &MapFolder, adts: &mut [AdtFile]) -> R<Vec<(u32, u32)>> {
+    let mut used = HashSet::new();
+    for adt_path in target.adts.values() {
+        let adt = AdtFile::from_path(adt_path)
+            .with_context(|| format!("Failed to read {}", adt_path.display()))?;
+        used.extend(adt.get_unique_ids());
+    }
+    let copied_max = adts.iter().flat_map(|it| it.get_unique_ids()).max();
+    let mut next_id = used.iter().cloned().chain(copied_max).max().map_or(1, |it| it + 1);
+
+    let mut reassigned = BTreeMap::new();
+    for adt in adts.iter() {
+        for id in adt.get_unique_ids().filter(|it| used.contains(it)) {
+            if let Entry::Vacant(entry) = reassigned.entry(id) {
+                entry.insert(next_id);
+                next_id += 1;
+            }
+        }
+    }
+    for adt in adts.iter_mut() {
+        for item in adt.mddf.0.iter_mut() {
+            if let Some(id) = reassigned.get(&item.unique_id) {
+                item.unique_id = *id;
+            }
+        }
+        for item in adt.modf.0.iter_mut() {
+            if let Some(id) = reassigned.get(&item.unique_id) {
+                item.unique_id = *id;
+            }
+        }
+    }
+    Ok(reassigned.into_iter().collect())
+}
+
+#[cfg(test)]
+#[test]
+fn copied_and_moved_tiles_update_the_wdt_and_unique_ids() {
+    use crate::formats::adt::test_utils::build_test_adt;
+    use crate::formats::chunk::MphdFlags;
+
+    let dir = crate::test_utils::TempDir::new("wow-file-tools-adt-relocate");
+    let (source, target) = (dir.join("Source"), dir.join("Target"));
+    fs::create_dir_all(&source).unwrap();
+    fs::create_dir_all(&target).unwrap();
+    // every test ADT places a doodad with unique ID 42
+    fs::write(source.join("Source_0_0.adt"), build_test_adt()).unwrap();
+    fs::write(source.join("Source_1_0.adt"), build_test_adt()).unwrap();
+    fs::write(target.join("Target_5_5.adt"), build_test_adt()).unwrap();
+    let mut source_wdt = WdtFile::new_empty(MphdFlags { adt_has_big_alpha: true, has_maid: true, ..Default::default() });
+    source_wdt.main.set_has_adt(0, 0, true).unwrap();
+    source_wdt.main.set_has_adt(1, 0, true).unwrap();
+    source_wdt.write_to_path(source.join("Source.wdt")).unwrap();
+
+    let copied = copy_tiles(
+        source.to_str().unwrap(),
+        target.to_str().unwrap(),
+        &[(1, 0), (0, 0), (1, 0)],
+        Some((10, 10)),
+    ).unwrap();
+    assert_eq!(copied.tiles.len(), 2);
+    assert_eq!(copied.wdt, Some(target.join("Target.wdt")));
+    assert_eq!(copied.reassigned_unique_ids, vec![(42, 43)]);
+    for tile in &["Target_10_10.adt", "Target_11_10.adt"] {
+        let adt = AdtFile::from_path(target.join(tile)).unwrap();
+        assert_eq!(adt.get_unique_ids().collect::<Vec<u32>>(), vec![43]);
+    }
+    let wdt = WdtFile::from_path(target.join("Target.wdt")).unwrap();
+    assert_eq!(wdt.main.get_tiles(), vec![(5, 5), (10, 10), (11, 10)]);
+    assert!(wdt.mphd.flags.adt_has_big_alpha && !wdt.mphd.flags.has_maid);
+
+    let moved = move_tile(target.to_str().unwrap(), (11, 10), (12, 12)).unwrap();
+    assert_eq!(moved.wdt, Some(target.join("Target.wdt")));
+    assert!(!target.join("Target_11_10.adt").exists() && target.join("Target_12_12.adt").exists());
+    let wdt = WdtFile::from_path(target.join("Target.wdt")).unwrap();
+    assert_eq!(wdt.main.get_tiles(), vec![(5, 5), (10, 10), (12, 12)]);
+}
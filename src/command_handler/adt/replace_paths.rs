@@ -0,0 +1,151 @@
+use crate::command_handler::adt::find_adts;
+use crate::common::{err, R};
+use crate::formats::adt::paths::AdtPathReplacement;
+use crate::formats::adt::AdtFile;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplacePathsResult {
+    pub changed: Vec<ReplacePathsChangedTile>,
+    pub unchanged: usize,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplacePathsChangedTile {
+    pub path: PathBuf,
+    pub replacements: Vec<AdtPathReplacement>,
+}
+
+/// One `old -> new` line of a mapping file.
+/// `*` and `?` in the old path are wildcards, each `*` in the new path is filled
+/// with what the matching `*` of the old path captured.
+#[derive(Debug)]
+pub struct PathMapping {
+    pattern: Vec<char>,
+    replacement: String,
+}
+
+impl PathMapping {
+    pub fn apply(&self, path: &str) -> Option<String> {
+        let original: Vec<char> = path.replace('/', "\\").chars().collect();
+        let normalized: Vec<char> = normalize(path).chars().collect();
+        if original.len() != normalized.len() {
+            return None;
+        }
+        // captures keep the casing of the matched path
+        let mut captures = glob_match(&self.pattern, &normalized, 0)?
+            .into_iter()
+            .map(|(start, end)| original[start..end].iter().collect::<String>());
+        let mut result = String::with_capacity(self.replacement.len());
+        for c in self.replacement.chars() {
+            match c {
+                '*' => result.push_str(&captures.next().unwrap_or_default()),
+                _ => result.push(c),
+            }
+        }
+        Some(result)
+    }
+}
+
+pub fn replace_paths(input: &str, mapping_file: &str, dry_run: bool) -> R<ReplacePathsResult> {
+    let mappings = read_mappings(mapping_file)?;
+    let mut result = ReplacePathsResult {
+        changed: Vec::new(),
+        unchanged: 0,
+        dry_run,
+    };
+
+    for adt_path in find_adts(input, true) {
+        let mut adt = AdtFile::from_path(&adt_path)
+            .with_context(|| format!("Failed to read {}", adt_path.display()))?;
+        let replacements = adt.replace_paths(|path| mappings.iter().find_map(|it| it.apply(path)));
+        if replacements.is_empty() {
+            result.unchanged += 1;
+            continue;
+        }
+        if !dry_run {
+            adt.write_to_path(&adt_path)?;
+        }
+        result.changed.push(ReplacePathsChangedTile {
+            path: adt_path,
+            replacements,
+        });
+    }
+    Ok(result)
+}
+
+pub fn read_mappings<P: AsRef<Path>>(path: P) -> R<Vec<PathMapping>> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read mapping file {}", path.display()))?;
+    parse_mappings(&content).with_context(|| format!("Invalid mapping file {}", path.display()))
+}
+
+fn parse_mappings(content: &str) -> R<Vec<PathMapping>> {
+    let mut mappings = Vec::new();
+    for (line_number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (old, new) = match line.find("->") {
+            Some(at) => (line[..at].trim(), line[at + 2..].trim()),
+            None => return err(format!("Line {}: expected `old path -> new path`", line_number + 1)),
+        };
+        if old.is_empty() || new.is_empty() {
+            return err(format!("Line {}: both paths are required", line_number + 1));
+        }
+        if new.matches('*').count() > old.matches('*').count() {
+            return err(format!("Line {}: the new path has more `*` than the old one", line_number + 1));
+        }
+        mappings.push(PathMapping {
+            pattern: normalize(old).chars().collect(),
+            replacement: new.to_string(),
+        });
+    }
+    Ok(mappings)
+}
+
+// client paths are case insensitive and use either separator
+fn normalize(path: &str) -> String {
+    path.to_lowercase().replace('/', "\\")
+}
+
+// returns the (start, end) range every `*` captured
+fn glob_match(pattern: &[char], text: &[char], at: usize) -> Option<Vec<(usize, usize)>> {
+    let rest = &text[at..];
+    match pattern.first() {
+        None if rest.is_empty() => Some(vec![]),
+        None => None,
+        Some('*') => (at..=text.len()).find_map(|end| {
+            glob_match(&pattern[1..], text, end).map(|mut captures| {
+                captures.insert(0, (at, end));
+                captures
+            })
+        }),
+        Some('?') if !rest.is_empty() => glob_match(&pattern[1..], text, at + 1),
+        Some(c) if rest.first() == Some(c) => glob_match(&pattern[1..], text, at + 1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn replace_paths_mapping_globs() {
+    let mappings = parse_mappings(
+        "# tilesets\n\
+         Tileset/Elwynn/*.blp -> Tileset\\Custom\\*.blp\n\
+         World\\Azeroth\\Elwynn\\PassiveDoodads\\Tree?.m2 -> World\\Custom\\Tree.m2\n",
+    ).unwrap();
+    let apply = |path: &str| mappings.iter().find_map(|it| it.apply(path));
+
+    assert_eq!(apply("TILESET\\Elwynn\\ElwynnGrass01.blp"), Some("Tileset\\Custom\\ElwynnGrass01.blp".to_string()));
+    assert_eq!(apply("World\\Azeroth\\Elwynn\\PassiveDoodads\\Tree1.m2"), Some("World\\Custom\\Tree.m2".to_string()));
+    assert_eq!(apply("World\\Azeroth\\Elwynn\\PassiveDoodads\\Tree12.m2"), None);
+    assert!(parse_mappings("a.blp b.blp").is_err());
+    assert!(parse_mappings("a.blp -> *.blp").is_err());
+}
@@ -0,0 +1,57 @@
+use crate::command_handler::adt::find_adts;
+use crate::common::{err, R};
+use crate::formats::adt::mcnk::McnkRect;
+use crate::formats::adt::AdtFile;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetAreaResult {
+    pub changed: Vec<SetAreaChangedTile>,
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetAreaChangedTile {
+    pub path: PathBuf,
+    pub changed_chunks: usize,
+}
+
+pub enum SetAreaOperation {
+    Set { area_id: u32, rect: Option<McnkRect> },
+    Remap(HashMap<u32, u32>),
+}
+
+pub fn set_area(input: &str, operation: &SetAreaOperation) -> R<SetAreaResult> {
+    let input = Path::new(input);
+    if let SetAreaOperation::Set { rect: Some(_), .. } = operation {
+        if input.is_dir() {
+            return err("A chunk rectangle only applies to a single ADT, not to a map folder".to_string());
+        }
+    }
+
+    let mut result = SetAreaResult {
+        changed: Vec::new(),
+        unchanged: 0,
+    };
+    for adt_path in find_adts(input, false) {
+        let mut adt = AdtFile::from_path(&adt_path)
+            .with_context(|| format!("Failed to read {}", adt_path.display()))?;
+        let changed_chunks = match operation {
+            SetAreaOperation::Set { area_id, rect } => adt.mcnk.set_area_id(*area_id, rect.as_ref()),
+            SetAreaOperation::Remap(remap) => adt.mcnk.remap_area_ids(remap),
+        };
+        if changed_chunks == 0 {
+            result.unchanged += 1;
+            continue;
+        }
+        adt.write_to_path(&adt_path)?;
+        result.changed.push(SetAreaChangedTile {
+            path: adt_path,
+            changed_chunks,
+        });
+    }
+    Ok(result)
+}
@@ -0,0 +1,154 @@
+use crate::byte_utils::VecUtils;
+use crate::command_handler::mesh_export::{get_mesh_format, write_scene, MeshExportWarn, TextureExporter};
+use crate::common::{err, R};
+use crate::formats::m2::anim::get_anim_path;
+use crate::formats::m2::convert::{convert_m2_file_to_wrath, M2ConversionLoss};
+use crate::formats::m2::skin::SkinFile;
+use crate::formats::m2::{M2File, M2_VERSION_WRATH};
+use crate::formats::mesh::MeshFormat;
+use crate::M2ToolCmd;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub fn handle_m2_command(cmd: &M2ToolCmd) -> R<Box<dyn erased_serde::Serialize>> {
+    let res: Box<dyn erased_serde::Serialize> = match cmd {
+        M2ToolCmd::Export(cmd) => Box::new(export_m2(
+            &cmd.input,
+            &cmd.output,
+            cmd.format.map(|it| it.0),
+            cmd.workspace.as_deref(),
+            cmd.skin.unwrap_or(0),
+        )?),
+        M2ToolCmd::Convert(cmd) => Box::new(convert_m2(&cmd.input, &cmd.output, cmd.target)?),
+        M2ToolCmd::SetTexture(cmd) => Box::new(set_m2_texture(&cmd.input, cmd.index, &cmd.path, cmd.output.as_deref())?),
+    };
+    Ok(res)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct M2ExportResult {
+    pub format: MeshFormat,
+    pub files: Vec<PathBuf>,
+    pub textures: Vec<PathBuf>,
+    pub submeshes: usize,
+    pub bones: usize,
+    pub animations: usize,
+    pub warns: Vec<MeshExportWarn>,
+}
+
+/// Exports the submeshes of one skin profile. glTF also gets the skeleton and a clip per sequence,
+/// reading the keys of .anim files next to the model. Textures are converted when a workspace is given.
+pub fn export_m2(
+    input: &str,
+    output: &str,
+    format: Option<MeshFormat>,
+    workspace: Option<&str>,
+    skin_index: usize,
+) -> R<M2ExportResult> {
+    let input = Path::new(input);
+    let output = Path::new(output);
+    let format = get_mesh_format(output, format)?;
+    let mut m2 = M2File::from_path(input)?;
+    let skin_path = match m2.get_skin_paths(input).get(skin_index) {
+        Some(path) => path.clone(),
+        None => return err(format!("The model has {} skin profiles, there is no profile {}", m2.n_skin_profiles, skin_index)),
+    };
+    let skin = SkinFile::from_path(skin_path)?;
+
+    let mut warns = Vec::new();
+    if format == MeshFormat::Gltf {
+        for (path, error) in m2.load_anims(input) {
+            warns.push(if path.exists() {
+                MeshExportWarn::UnreadableAnimation { path, error }
+            } else {
+                MeshExportWarn::MissingAnimation(path)
+            });
+        }
+    }
+
+    let mut textures = TextureExporter::new(workspace, output);
+    for texture in m2.get_texture_names() {
+        textures.convert(&texture)?;
+    }
+    let mut scene = m2.to_scene(&skin, |path| textures.get_uri(path))?;
+    if format == MeshFormat::Obj {
+        scene.joints.clear();
+        scene.animations.clear();
+    }
+    let files = write_scene(&scene, output, format)?;
+
+    warns.extend(textures.warns);
+    Ok(M2ExportResult {
+        format,
+        files,
+        textures: textures.written,
+        submeshes: scene.meshes.len(),
+        bones: scene.joints.len(),
+        animations: scene.animations.iter().filter(|it| !it.channels.is_empty()).count(),
+        warns,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct M2ConvertResult {
+    pub source_version: u32,
+    pub target_version: u32,
+    pub files: Vec<PathBuf>,
+    // empty when the client gets everything back
+    pub lossy: Vec<M2ConversionLoss>,
+}
+
+pub fn convert_m2(input: &str, output: &str, target: u32) -> R<M2ConvertResult> {
+    if target != M2_VERSION_WRATH {
+        return err(format!("Models can only be converted to {} (Wrath), not {}", M2_VERSION_WRATH, target));
+    }
+    let source_version = get_source_version(Path::new(input))?;
+    let (files, lossy) = convert_m2_file_to_wrath(input, output)?;
+    Ok(M2ConvertResult { source_version, target_version: target, files, lossy })
+}
+
+// version of the MD20 header, inside the MD21 chunk of chunked models
+fn get_source_version(input: &Path) -> R<u32> {
+    let bytes = std::fs::read(input).with_context(|| format!("Failed to read {}", input.display()))?;
+    let header = if bytes.get(0..4) == Some(&b"MD21"[..]) { 8 } else { 0 };
+    bytes.get_u32(header + 4)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct M2SetTextureResult {
+    pub index: usize,
+    pub previous_type: u32,
+    pub previous_path: String,
+    pub files: Vec<PathBuf>,
+}
+
+pub fn set_m2_texture(input: &str, index: usize, path: &str, output: Option<&str>) -> R<M2SetTextureResult> {
+    let input = Path::new(input);
+    let output = output.map(Path::new).unwrap_or(input);
+    let mut m2 = M2File::from_path(input)?;
+    let previous = m2.set_texture(index, path)?;
+
+    let mut companions: Vec<(PathBuf, PathBuf)> = m2.get_skin_paths(input)
+        .into_iter()
+        .zip(m2.get_skin_paths(output))
+        .collect();
+    for sequence in m2.get_external_sequences() {
+        let sequence = &m2.sequences[sequence];
+        companions.push((get_anim_path(input, sequence), get_anim_path(output, sequence)));
+    }
+
+    if let Some(parent) = output.parent().filter(|it| !it.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    m2.write_to_path(output)?;
+    let mut files = vec![output.to_path_buf()];
+    // the skins and .anim files are unchanged, a model written elsewhere needs them under its name
+    if output != input {
+        for (from, to) in companions.into_iter().filter(|it| it.0.exists()) {
+            std::fs::copy(&from, &to).with_context(|| format!("Failed to copy {} to {}", from.display(), to.display()))?;
+            files.push(to);
+        }
+    }
+    Ok(M2SetTextureResult { index, previous_type: previous.texture_type, previous_path: previous.file_name, files })
+}
@@ -0,0 +1,127 @@
+use crate::command_handler::resolve_map_assets::join_path_ignoring_casing;
+use crate::common::{err, R};
+use crate::formats::blp::read_blp;
+use crate::formats::mesh::gltf::write_gltf;
+use crate::formats::mesh::obj::write_obj;
+use crate::formats::mesh::{MeshFormat, Scene};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum MeshExportWarn {
+    MissingTexture(String),
+    UnreadableTexture { path: PathBuf, error: String },
+    // left out of the export
+    GroupNotLoaded(PathBuf),
+    // the sequences keyed in it are exported without keys
+    MissingAnimation(PathBuf),
+    UnreadableAnimation { path: PathBuf, error: String },
+    // the doodad stays an empty node in glTF and is left out of OBJ
+    MissingDoodad(String),
+    UnreadableDoodad { path: PathBuf, error: String },
+}
+
+/// Format given on the command line, or guessed from the output extension.
+pub fn get_mesh_format(output: &Path, format: Option<MeshFormat>) -> R<MeshFormat> {
+    if let Some(format) = format {
+        return Ok(format);
+    }
+    let extension = output.extension()
+        .map(|it| it.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "obj" => Ok(MeshFormat::Obj),
+        "gltf" => Ok(MeshFormat::Gltf),
+        _ => err(format!("Can't tell the format of {}, use --format obj or gltf", output.display())),
+    }
+}
+
+/// Writes the scene and its companion file, `.mtl` for OBJ and `.bin` for glTF. Returns every written path.
+pub fn write_scene(scene: &Scene, output: &Path, format: MeshFormat) -> R<Vec<PathBuf>> {
+    let (companion_extension, main, companion) = match format {
+        MeshFormat::Obj => {
+            let mtl_path = output.with_extension("mtl");
+            let (obj, mtl) = write_obj(scene, &file_name(&mtl_path));
+            ("mtl", obj.into_bytes(), mtl.into_bytes())
+        }
+        MeshFormat::Gltf => {
+            let bin_path = output.with_extension("bin");
+            let (gltf, bin) = write_gltf(scene, &file_name(&bin_path));
+            ("bin", gltf.into_bytes(), bin)
+        }
+    };
+    if let Some(parent) = output.parent().filter(|it| !it.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let companion_path = output.with_extension(companion_extension);
+    fs::write(output, main).with_context(|| format!("Failed to write {}", output.display()))?;
+    fs::write(&companion_path, companion).with_context(|| format!("Failed to write {}", companion_path.display()))?;
+    Ok(vec![output.to_path_buf(), companion_path])
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|it| it.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// Converts BLPs found in the workspace to PNGs under `<output dir>/textures`,
+/// keyed by the client path they were referenced with.
+pub struct TextureExporter {
+    pub workspace: Option<PathBuf>,
+    pub output_dir: PathBuf,
+    pub uris: HashMap<String, String>,
+    pub written: Vec<PathBuf>,
+    pub warns: Vec<MeshExportWarn>,
+}
+
+impl TextureExporter {
+    pub fn new(workspace: Option<&str>, output: &Path) -> TextureExporter {
+        TextureExporter {
+            workspace: workspace.map(PathBuf::from),
+            output_dir: output.parent().map(Path::to_path_buf).unwrap_or_default(),
+            uris: HashMap::new(),
+            written: Vec::new(),
+            warns: Vec::new(),
+        }
+    }
+
+    pub fn convert(&mut self, texture_path: &str) -> R<()> {
+        let workspace = match &self.workspace {
+            Some(workspace) => workspace,
+            None => return Ok(()),
+        };
+        if self.uris.contains_key(texture_path) {
+            return Ok(());
+        }
+        let blp_path = match join_path_ignoring_casing(workspace, texture_path) {
+            Some(path) => path,
+            None => {
+                self.warns.push(MeshExportWarn::MissingTexture(texture_path.to_string()));
+                return Ok(());
+            }
+        };
+        let image = match read_blp(&blp_path) {
+            Ok(image) => image,
+            Err(e) => {
+                self.warns.push(MeshExportWarn::UnreadableTexture { path: blp_path, error: format!("{:#}", e) });
+                return Ok(());
+            }
+        };
+        let uri = format!("textures/{}", texture_path.replace('\\', "/"));
+        let uri = Path::new(&uri).with_extension("png").to_string_lossy().replace('\\', "/");
+        let png_path = self.output_dir.join(&uri);
+        if let Some(parent) = png_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&png_path, image.to_png()?).with_context(|| format!("Failed to write {}", png_path.display()))?;
+        self.written.push(png_path);
+        self.uris.insert(texture_path.to_string(), uri);
+        Ok(())
+    }
+
+    pub fn get_uri(&self, texture_path: &str) -> Option<String> {
+        self.uris.get(texture_path).cloned()
+    }
+}
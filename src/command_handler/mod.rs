@@ -1,5 +1,11 @@
+pub mod adt;
 pub mod view;
 pub mod resolve_map_assets;
 pub mod mpq;
 pub mod dbc_join;
-pub mod proxy;
\ No newline at end of file
+pub mod proxy;
+pub mod wdt;
+pub mod wdl;
+pub mod wmo;
+pub mod m2;
+pub mod mesh_export;
\ No newline at end of file
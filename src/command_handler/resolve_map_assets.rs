@@ -2,9 +2,10 @@ use crate::formats::adt::AdtFile;
 use crate::formats::dbc::dbc::{load_loading_screens_dbc_from_path, load_map_dbc_from_path};
 use crate::formats::dbc::map::MapDbcRow;
 use crate::formats::m2::M2File;
+use crate::formats::m2::skin::SkinFile;
 use crate::formats::mdx::MdxFile;
 use crate::formats::wdl::WdlFile;
-use crate::formats::wmo::WmoFile;
+use crate::formats::wmo::{WmoFile, WmoGroupError};
 use crate::{
     common::{err, R},
     ResolveMapAssetsCmd,
@@ -32,6 +33,12 @@ pub enum ResolveMapAssetsCmdWarn {
     FileParseFail(String),
     FailedToRemoveFile(String),
     AdtParseErr(PathBuf),
+    WmoParseErr(PathBuf, String),
+    MissingWmoGroup(PathBuf),
+    WmoGroupParseErr(PathBuf, String),
+    MissingM2Skin(PathBuf),
+    M2SkinParseErr(PathBuf, String),
+    MdxParseErr(PathBuf, String),
     MissingDbcEntry(String),
     MissingMiniMapFolder,
 }
@@ -119,7 +126,7 @@ fn resolve_map_assets(
 
             for mcnk in &adt.mcnk.items {
                 mcnk_area_id_entries.insert(ResolveMapAssetsAreaIdEntry {
-                    area_id: mcnk.area_id,
+                    area_id: mcnk.header.area_id,
                     map_id: map_id.clone(),
                     map_name: map_row.internal_name.clone(),
                 });
@@ -133,7 +140,21 @@ fn resolve_map_assets(
             add_m2_type_wow_dep(workspace_path, adt.mmdx.0, &mut results_builder, &mut warns);
 
             for wmo_path in added_wmos {
-                let wmo = WmoFile::from_path(wmo_path.str())?;
+                let wmo = match WmoFile::from_path(&wmo_path) {
+                    Ok(wmo) => wmo,
+                    Err(e) => {
+                        warns.push(ResolveMapAssetsCmdWarn::WmoParseErr(wmo_path, format!("{:#}", e)));
+                        continue;
+                    }
+                };
+                for error in &wmo.group_errors {
+                    warns.push(match error {
+                        WmoGroupError::Missing { path, .. } => ResolveMapAssetsCmdWarn::MissingWmoGroup(path.clone()),
+                        WmoGroupError::Corrupt { path, error, .. } => {
+                            ResolveMapAssetsCmdWarn::WmoGroupParseErr(path.clone(), error.clone())
+                        }
+                    });
+                }
                 add_wow_dep(
                     workspace_path,
                     wmo.root.motx.0,
@@ -343,16 +364,22 @@ fn add_wow_dep(
     added
 }
 
-fn add_m2_dependencies(m2_root: &Path, file_name: &str, results: &mut Vec<PathBuf>) {
-    let mut skin_it = 0;
-    loop {
-        let skin_file = m2_root.join(format!("{}0{}.skin", file_name, skin_it));
-        skin_it += 1;
-        if skin_file.exists() {
-            results.push(skin_file);
-        } else {
-            break;
+// skin profiles named by the header of the M2, each one is parsed to catch broken ones early
+fn add_m2_dependencies(
+    m2_path: &Path,
+    m2_file: &M2File,
+    results: &mut Vec<PathBuf>,
+    warns: &mut Vec<ResolveMapAssetsCmdWarn>,
+) {
+    for skin_path in m2_file.get_skin_paths(m2_path) {
+        if !skin_path.exists() {
+            warns.push(ResolveMapAssetsCmdWarn::MissingM2Skin(skin_path));
+            continue;
+        }
+        if let Err(e) = SkinFile::from_path(&skin_path) {
+            warns.push(ResolveMapAssetsCmdWarn::M2SkinParseErr(skin_path.clone(), format!("{:#}", e)));
         }
+        results.push(skin_path);
     }
 }
 
@@ -379,24 +406,18 @@ fn add_m2_type_wow_dep(
             if ext.eq("m2") || ext.eq("M2") {
                 // handle m2's.
                 if let Ok(m2_file) = M2File::from_path(path.clone()) {
-                    add_m2_type_wow_dep(workspace_root, m2_file.textures, results, warns);
-                    let file_stem = path
-                        .file_stem()
-                        .unwrap()
-                        .to_str()
-                        .expect("Failed to parse filename of a M2");
-                    let m2_root = path.parent().unwrap();
-                    add_m2_dependencies(m2_root, file_stem, results);
+                    add_m2_type_wow_dep(workspace_root, m2_file.get_texture_names(), results, warns);
+                    add_m2_dependencies(&path, &m2_file, results, warns);
                 } else {
                     let msg = format!("Failed to parse m2 '{}'", path.str());
                     warns.push(ResolveMapAssetsCmdWarn::FileParseFail(msg));
                 }
             } else if ext.eq("mdx") || ext.eq("MDX") {
-                if let Ok(mdx_file) = MdxFile::from_path(path.clone()) {
-                    add_m2_type_wow_dep(workspace_root, mdx_file.texs.texture_list, results, warns);
-                } else {
-                    let msg = format!("Failed to parse m2 '{}'", path.str());
-                    warns.push(ResolveMapAssetsCmdWarn::FileParseFail(msg));
+                match MdxFile::from_path(&path) {
+                    Ok(mdx_file) => {
+                        add_m2_type_wow_dep(workspace_root, mdx_file.get_texture_names(), results, warns);
+                    }
+                    Err(e) => warns.push(ResolveMapAssetsCmdWarn::MdxParseErr(path.clone(), format!("{:#}", e))),
                 }
             }
         }
@@ -468,7 +489,7 @@ fn find_and_add_loading_screen_blp(
     }
 }
 
-fn join_path_ignoring_casing(base: &Path, join: &str) -> Option<PathBuf> {
+pub(crate) fn join_path_ignoring_casing(base: &Path, join: &str) -> Option<PathBuf> {
     let parts: Vec<&str> = join.split(&['/', '\\'][..]).collect();
     let mut buf = PathBuf::new();
     buf.push(base);
@@ -1,7 +1,12 @@
 use anyhow::Context;
+use crate::command_handler::adt::MapFolder;
 use crate::formats::adt::AdtFile;
 use crate::formats::dbc::dbc::*;
+use crate::formats::dbc::db2::{load_item_db2_from_path, load_item_sparse_db2_from_path};
 use crate::formats::m2::M2File;
+use crate::formats::m2::skin::SkinFile;
+use crate::formats::mdx::MdxFile;
+use crate::formats::wdl::WdlFile;
 use crate::formats::wdt::WdtFile;
 use crate::formats::wmo::WmoFile;
 use crate::{common::R, ViewCmd};
@@ -33,6 +38,7 @@ pub fn handle_view_command(view_cmd: &ViewCmd) -> R<Box<dyn erased_serde::Serial
             "BattlemasterList.dbc" => Box::new(load_battle_master_list_from_path(file_path)?),
             "LightSkybox.dbc" => Box::new(load_light_sky_box_from_path(file_path)?),
             "Light.dbc" => Box::new(load_light_from_path(file_path)?),
+            "LiquidType.dbc" => Box::new(load_liquid_type_from_path(file_path)?),
             "LightParams.dbc" => Box::new(load_light_params_from_path(file_path)?),
             "AreaTable.dbc" => Box::new(load_area_table_from_path(file_path)?),
             "Map.dbc" => Box::new(load_map_dbc_from_path(file_path)?),
@@ -47,10 +53,37 @@ pub fn handle_view_command(view_cmd: &ViewCmd) -> R<Box<dyn erased_serde::Serial
                 return err(format!("Unsupported DBC file: `{}`", file_name))
             }
         },
+        "db2" => match file_name.deref() {
+            "Item.db2" => Box::new(load_item_db2_from_path(file_path)?),
+            "Item-sparse.db2" => Box::new(load_item_sparse_db2_from_path(file_path)?),
+            _ => {
+                return err(format!("Unsupported DB2 file: `{}`", file_name))
+            }
+        },
         "wdt" => Box::new(WdtFile::from_path(file_path)?),
+        "wdl" => Box::new(WdlFile::from_path(file_path)?),
         "wmo" => Box::new(WmoFile::from_path(file_path)?),
-        "adt" => Box::new(AdtFile::from_path(file_path)?),
-        "m2" => Box::new(M2File::from_path(file_path)?),
+        "adt" => {
+            // the WDT of the map folder tells how the alpha maps are stored
+            let big_alpha = file_path.parent()
+                .and_then(|folder| MapFolder::from_path(folder).ok())
+                .and_then(|folder| folder.read_wdt().ok().flatten())
+                .map(|wdt| wdt.mphd.flags.adt_has_big_alpha);
+            let mut adt = AdtFile::from_path_with_big_alpha(file_path, big_alpha)?;
+            if let Some(liquid_type_dbc) = &view_cmd.liquid_type_dbc {
+                adt.resolve_liquid_names(&load_liquid_type_from_path(liquid_type_dbc)?);
+            }
+            Box::new(adt)
+        }
+        "m2" => {
+            let mut m2 = M2File::from_path(&file_path)?;
+            if view_cmd.skins {
+                m2.load_skins(&file_path)?;
+            }
+            Box::new(m2)
+        }
+        "skin" => Box::new(SkinFile::from_path(file_path)?),
+        "mdx" => Box::new(MdxFile::from_path(file_path)?),
         _ => {
             return err(format!("Unsupported file extension: `{}`", extension));
         }
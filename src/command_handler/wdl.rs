@@ -0,0 +1,61 @@
+use crate::command_handler::adt::MapFolder;
+use crate::common::{err, R};
+use crate::formats::adt::AdtFile;
+use crate::formats::wdl::{WdlFile, WdlTile};
+use crate::WdlToolCmd;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+pub fn handle_wdl_command(cmd: &WdlToolCmd) -> R<Box<dyn erased_serde::Serialize>> {
+    let res: Box<dyn erased_serde::Serialize> = match cmd {
+        WdlToolCmd::Generate(cmd) => Box::new(generate_wdl(&cmd.map_folder, cmd.output.as_deref())?),
+    };
+    Ok(res)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WdlGenerateResult {
+    pub path: PathBuf,
+    pub replaced_existing: bool,
+    pub tiles: Vec<(u32, u32)>,
+}
+
+/// Builds the WDL of a map folder by sampling the heights of every ADT.
+/// The low detail WMOs (MWMO / MWID / MODF) of an existing WDL are kept.
+pub fn generate_wdl(map_folder: &str, output: Option<&str>) -> R<WdlGenerateResult> {
+    let folder = MapFolder::from_path(map_folder)?;
+    if folder.adts.is_empty() {
+        return err(format!("No <map>_<x>_<y>.adt files in {}", folder.path.display()));
+    }
+
+    let path = match output {
+        Some(output) => PathBuf::from(output),
+        None => folder.path.join(format!("{}.wdl", folder.name)),
+    };
+    let replaced_existing = path.exists();
+    let mut wdl = if replaced_existing {
+        WdlFile::from_path(&path).with_context(|| format!("Failed to read {}", path.display()))?
+    } else {
+        WdlFile::new_empty()
+    };
+
+    wdl.tiles.clear();
+    for ((x, y), adt_path) in &folder.adts {
+        let adt = AdtFile::from_path(adt_path)
+            .with_context(|| format!("Failed to read {}", adt_path.display()))?;
+        let heightmap = adt.get_heightmap()
+            .with_context(|| format!("Failed to read the heights of {}", adt_path.display()))?;
+        let holes: Vec<(u32, u32, u16)> = adt.mcnk.items.iter()
+            .map(|it| (it.header.index_x, it.header.index_y, it.header.holes))
+            .collect();
+        wdl.tiles.push(WdlTile::from_heightmap(*x, *y, &heightmap, &holes));
+    }
+
+    wdl.write_to_path(&path)?;
+    Ok(WdlGenerateResult {
+        path,
+        replaced_existing,
+        tiles: folder.adts.keys().cloned().collect(),
+    })
+}
@@ -0,0 +1,128 @@
+use crate::command_handler::adt::MapFolder;
+use crate::common::{err, R};
+use crate::formats::adt::AdtFile;
+use crate::formats::chunk::MphdFlags;
+use crate::formats::wdt::WdtFile;
+use crate::WdtToolCmd;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+pub fn handle_wdt_command(cmd: &WdtToolCmd) -> R<Box<dyn erased_serde::Serialize>> {
+    let res: Box<dyn erased_serde::Serialize> = match cmd {
+        WdtToolCmd::Generate(cmd) => Box::new(generate_wdt(&cmd.map_folder, cmd.output.as_deref(), cmd.big_alpha)?),
+    };
+    Ok(res)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WdtGenerateResult {
+    pub path: PathBuf,
+    pub replaced_existing: bool,
+    pub tiles: Vec<(u32, u32)>,
+    pub flags: MphdFlags,
+    pub big_alpha_from: BigAlphaSource,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum BigAlphaSource {
+    Argument,
+    // the WDT being replaced
+    ExistingWdt,
+    // no WDT to tell, guessed from how much space the alpha maps take
+    AlphaMapSizes,
+}
+
+/// Builds the WDT of a map folder from the `<map>_<x>_<y>.adt` files it contains.
+/// MPHD flags are set from what the ADTs use, the WDT is written as `<map>.wdt` unless `output` is given.
+/// The ADTs can't tell reliably whether they use 4096 byte alpha maps, `big_alpha` or the flag of
+/// the existing WDT win over the guess.
+pub fn generate_wdt(map_folder: &str, output: Option<&str>, big_alpha: Option<bool>) -> R<WdtGenerateResult> {
+    let folder = MapFolder::from_path(map_folder)?;
+    if folder.adts.is_empty() {
+        return err(format!("No <map>_<x>_<y>.adt files in {}", folder.path.display()));
+    }
+
+    // a WDT that doesn't parse gets replaced all the same
+    let (big_alpha, big_alpha_from) = match big_alpha {
+        Some(big_alpha) => (Some(big_alpha), BigAlphaSource::Argument),
+        None => match folder.read_wdt().ok().flatten() {
+            Some(wdt) => (Some(wdt.mphd.flags.adt_has_big_alpha), BigAlphaSource::ExistingWdt),
+            None => (None, BigAlphaSource::AlphaMapSizes),
+        },
+    };
+
+    let mut flags = MphdFlags { adt_has_big_alpha: big_alpha.unwrap_or(false), ..Default::default() };
+    for adt_path in folder.adts.values() {
+        let adt = AdtFile::from_path_with_big_alpha(adt_path, big_alpha)
+            .with_context(|| format!("Failed to read {}", adt_path.display()))?;
+        update_flags(&mut flags, &adt, big_alpha.is_none());
+    }
+
+    let mut wdt = WdtFile::new_empty(flags.clone());
+    for (x, y) in folder.adts.keys() {
+        wdt.main.set_has_adt(*x, *y, true)?;
+    }
+
+    let path = match output {
+        Some(output) => PathBuf::from(output),
+        None => folder.get_wdt_path(),
+    };
+    let replaced_existing = path.exists();
+    wdt.write_to_path(&path)?;
+    Ok(WdtGenerateResult {
+        path,
+        replaced_existing,
+        tiles: folder.adts.keys().cloned().collect(),
+        flags,
+        big_alpha_from,
+    })
+}
+
+fn update_flags(flags: &mut MphdFlags, adt: &AdtFile, guess_big_alpha: bool) {
+    let has_sub_chunk = |id: &str| adt.mcnk.items.iter()
+        .any(|mcnk| mcnk.sub_chunks.iter().any(|c| c.get_id_as_string() == id));
+
+    if guess_big_alpha {
+        flags.adt_has_big_alpha |= adt.mcnk.items.iter().any(|it| it.guess_big_alpha() == Some(true));
+    }
+    flags.adt_has_vertex_shading |= has_sub_chunk("MCCV");
+    flags.adt_has_lighting_vertices |= has_sub_chunk("MCLV");
+    flags.adt_has_height_texturing |= adt.chunks.iter().any(|c| c.get_id_as_string() == "MTXP");
+}
+
+#[cfg(test)]
+#[test]
+fn wdt_is_generated_from_the_map_folder() {
+    use crate::formats::adt::test_utils::build_test_adt;
+    use crate::formats::chunk::Chunk;
+
+    let dir = crate::test_utils::TempDir::new("wow-file-tools-wdt-generate");
+    let map = dir.join("Test");
+    std::fs::create_dir_all(&map).unwrap();
+    std::fs::write(map.join("Test_31_32.adt"), build_test_adt()).unwrap();
+    let mut adt = AdtFile::from_bytes(&build_test_adt()).unwrap();
+    adt.mcnk.items[0].sub_chunks.push(Chunk::new("MCCV", vec![0x7F; 145 * 4]));
+    adt.write_to_path(map.join("Test_32_32.adt")).unwrap();
+    // outside of the grid, and an old WDT that doesn't parse
+    std::fs::write(map.join("Test_64_0.adt"), build_test_adt()).unwrap();
+    std::fs::write(map.join("Test.wdt"), b"corrupt").unwrap();
+
+    let result = generate_wdt(map.to_str().unwrap(), None, None).unwrap();
+    assert!(result.replaced_existing);
+    assert!(matches!(result.big_alpha_from, BigAlphaSource::AlphaMapSizes));
+    assert_eq!(result.tiles, vec![(31, 32), (32, 32)]);
+    let wdt = WdtFile::from_path(map.join("Test.wdt")).unwrap();
+    assert_eq!(wdt.main.get_tiles(), vec![(31, 32), (32, 32)]);
+    assert_eq!(wdt.main.0.iter().filter(|it| it.flags != 0).count(), 2);
+    let flags = &wdt.mphd.flags;
+    assert!(flags.adt_has_vertex_shading);
+    assert!(!flags.adt_has_big_alpha && !flags.adt_has_lighting_vertices && !flags.adt_has_height_texturing);
+
+    // the flag of the WDT being replaced wins over the guess, the argument over both
+    WdtFile::new_empty(MphdFlags { adt_has_big_alpha: true, ..Default::default() }).write_to_path(map.join("Test.wdt")).unwrap();
+    let result = generate_wdt(map.to_str().unwrap(), None, None).unwrap();
+    assert!(result.flags.adt_has_big_alpha && matches!(result.big_alpha_from, BigAlphaSource::ExistingWdt));
+    let result = generate_wdt(map.to_str().unwrap(), None, Some(false)).unwrap();
+    assert!(!result.flags.adt_has_big_alpha && matches!(result.big_alpha_from, BigAlphaSource::Argument));
+}
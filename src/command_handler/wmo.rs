@@ -0,0 +1,383 @@
+use crate::command_handler::mesh_export::{get_mesh_format, write_scene, MeshExportWarn, TextureExporter};
+use crate::command_handler::resolve_map_assets::join_path_ignoring_casing;
+use crate::common::R;
+use crate::common::err;
+use crate::formats::m2::skin::SkinFile;
+use crate::formats::m2::M2File;
+use crate::formats::mesh::{MeshFormat, Scene};
+use crate::formats::wmo::collision::CollisionFormat;
+use crate::formats::wmo::root::{ChunkModdItem, WmoColor};
+use crate::formats::wmo::WmoFile;
+use crate::WmoToolCmd;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub fn handle_wmo_command(cmd: &WmoToolCmd) -> R<Box<dyn erased_serde::Serialize>> {
+    let res: Box<dyn erased_serde::Serialize> = match cmd {
+        WmoToolCmd::Export(cmd) => Box::new(export_wmo(
+            &cmd.input,
+            &cmd.output,
+            cmd.format.map(|it| it.0),
+            cmd.workspace.as_deref(),
+            cmd.doodads,
+        )?),
+        WmoToolCmd::Collision(cmd) => Box::new(export_wmo_collision(
+            &cmd.input,
+            &cmd.output,
+            cmd.format.map(|it| it.0),
+        )?),
+        WmoToolCmd::Edit(cmd) => Box::new(edit_wmo(&cmd.input, &cmd.edit_file, cmd.output.as_deref())?),
+    };
+    Ok(res)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WmoExportResult {
+    pub format: MeshFormat,
+    pub files: Vec<PathBuf>,
+    pub textures: Vec<PathBuf>,
+    pub groups: usize,
+    // placed with their meshes
+    pub doodads: usize,
+    // written as empty nodes, glTF only
+    pub doodad_placeholders: usize,
+    pub warns: Vec<MeshExportWarn>,
+}
+
+/// Exports every group of a root WMO. Textures are converted when a workspace is given.
+/// The doodads of the default set are placed with the meshes of their M2 found in the workspace,
+/// in glTF the ones that can't be loaded stay empty nodes. OBJ has no empty nodes, it needs the workspace.
+pub fn export_wmo(
+    input: &str,
+    output: &str,
+    format: Option<MeshFormat>,
+    workspace: Option<&str>,
+    include_doodads: bool,
+) -> R<WmoExportResult> {
+    let output = Path::new(output);
+    let format = get_mesh_format(output, format)?;
+    if include_doodads && workspace.is_none() && format == MeshFormat::Obj {
+        return err("OBJ can't hold doodad placeholders, give a workspace to export the doodad models".to_string());
+    }
+    let wmo = WmoFile::from_path(input)?;
+
+    let mut textures = TextureExporter::new(workspace, output);
+    for material in &wmo.root.momt.0 {
+        if let Some(texture) = &material.texture_1_name {
+            textures.convert(texture)?;
+        }
+    }
+    let mut scene = wmo.to_scene(|path| textures.get_uri(path), include_doodads);
+    let groups = scene.meshes.len();
+    let mut warns = Vec::new();
+    let doodads = match workspace {
+        Some(workspace) => place_doodad_models(&mut scene, Path::new(workspace), &mut textures, &mut warns)?,
+        None => 0,
+    };
+    if format == MeshFormat::Obj {
+        scene.placeholders.clear();
+    }
+    let files = write_scene(&scene, output, format)?;
+
+    warns.extend(textures.warns);
+    warns.extend(get_group_warns(&wmo));
+    Ok(WmoExportResult {
+        format,
+        files,
+        textures: textures.written,
+        groups,
+        doodads,
+        doodad_placeholders: scene.placeholders.len(),
+        warns,
+    })
+}
+
+/// Replaces the doodad placeholders by the meshes of their M2 (first skin profile),
+/// each model is read once. Returns how many were placed.
+fn place_doodad_models(
+    scene: &mut Scene,
+    workspace: &Path,
+    textures: &mut TextureExporter,
+    warns: &mut Vec<MeshExportWarn>,
+) -> R<usize> {
+    // lowercase client path -> model scene and its first material, None when it can't be loaded
+    let mut models: HashMap<String, Option<(Scene, usize)>> = HashMap::new();
+    let mut placed = 0;
+    for (i, placeholder) in std::mem::take(&mut scene.placeholders).into_iter().enumerate() {
+        let key = placeholder.name.to_lowercase();
+        if let Entry::Vacant(entry) = models.entry(key.clone()) {
+            let model = read_doodad_model(&placeholder.name, workspace, textures, warns)?
+                .map(|(stem, model)| {
+                    let first_material = scene.add_materials(&stem, &model.materials);
+                    (model, first_material)
+                });
+            entry.insert(model);
+        }
+        match &models[&key] {
+            Some((model, first_material)) => {
+                scene.add_instance(&format!("doodad_{:03}", i), model, *first_material, &placeholder);
+                placed += 1;
+            }
+            None => scene.placeholders.push(placeholder),
+        }
+    }
+    Ok(placed)
+}
+
+fn read_doodad_model(
+    path: &str,
+    workspace: &Path,
+    textures: &mut TextureExporter,
+    warns: &mut Vec<MeshExportWarn>,
+) -> R<Option<(String, Scene)>> {
+    // MODN often still names the .mdx the model was converted from
+    let m2_path = Path::new(path).with_extension("m2").to_string_lossy().to_string();
+    let m2_path = match join_path_ignoring_casing(workspace, &m2_path) {
+        Some(m2_path) => m2_path,
+        None => {
+            warns.push(MeshExportWarn::MissingDoodad(path.to_string()));
+            return Ok(None);
+        }
+    };
+    let read = || -> R<(M2File, SkinFile)> {
+        let m2 = M2File::from_path(&m2_path)?;
+        let skin_path = m2.get_skin_paths(&m2_path).into_iter().next()
+            .context("The model has no skin profile")?;
+        let skin = SkinFile::from_path(skin_path)?;
+        Ok((m2, skin))
+    };
+    let (m2, skin) = match read() {
+        Ok(it) => it,
+        Err(e) => {
+            warns.push(MeshExportWarn::UnreadableDoodad { path: m2_path, error: format!("{:#}", e) });
+            return Ok(None);
+        }
+    };
+    for texture in m2.get_texture_names() {
+        textures.convert(&texture)?;
+    }
+    let model = m2.to_scene(&skin, |it| textures.get_uri(it))?;
+    let stem = m2_path.file_stem().map(|it| it.to_string_lossy().to_string()).unwrap_or_default();
+    Ok(Some((stem, model)))
+}
+
+fn get_group_warns(wmo: &WmoFile) -> Vec<MeshExportWarn> {
+    wmo.group_errors.iter()
+        .map(|it| MeshExportWarn::GroupNotLoaded(it.path().to_path_buf()))
+        .collect()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WmoCollisionResult {
+    pub format: CollisionFormat,
+    pub files: Vec<PathBuf>,
+    pub groups: usize,
+    pub triangles: usize,
+    pub liquids: usize,
+    pub portals: usize,
+    pub warns: Vec<MeshExportWarn>,
+}
+
+/// Exports the collidable triangles, BSP trees, liquids and portals of a root WMO and its groups.
+pub fn export_wmo_collision(input: &str, output: &str, format: Option<CollisionFormat>) -> R<WmoCollisionResult> {
+    let output = Path::new(output);
+    let format = match format {
+        Some(format) => format,
+        None => match output.extension().map(|it| it.to_string_lossy().to_lowercase()).as_deref() {
+            Some("obj") => CollisionFormat::Obj,
+            Some("wcol") | Some("bin") => CollisionFormat::Binary,
+            _ => return err(format!("Can't tell the format of {}, use --format obj or wcol", output.display())),
+        },
+    };
+    let wmo = WmoFile::from_path(input)?;
+    let collision = wmo.to_collision();
+
+    let files = match format {
+        CollisionFormat::Obj => write_scene(&collision.to_scene(), output, MeshFormat::Obj)?,
+        CollisionFormat::Binary => {
+            if let Some(parent) = output.parent().filter(|it| !it.as_os_str().is_empty()) {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(output, collision.to_bytes()).with_context(|| format!("Failed to write {}", output.display()))?;
+            vec![output.to_path_buf()]
+        }
+    };
+
+    Ok(WmoCollisionResult {
+        format,
+        files,
+        groups: collision.groups.len(),
+        triangles: collision.groups.iter().map(|it| it.triangles.len()).sum(),
+        liquids: collision.groups.iter().filter(|it| it.liquid.is_some()).count(),
+        portals: collision.portals.len(),
+        warns: get_group_warns(&wmo),
+    })
+}
+
+/// The JSON edit file, doodad sets are removed before the new ones are added.
+#[derive(Debug, Deserialize)]
+pub struct WmoEdits {
+    #[serde(default)]
+    pub textures: Vec<WmoTextureEdit>,
+    #[serde(default)]
+    pub group_names: Vec<WmoGroupNameEdit>,
+    #[serde(default)]
+    pub remove_doodad_sets: Vec<String>,
+    #[serde(default)]
+    pub add_doodad_sets: Vec<WmoDoodadSetAdd>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WmoTextureEdit {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WmoGroupNameEdit {
+    pub group: usize,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WmoDoodadSetAdd {
+    pub name: String,
+    pub doodads: Vec<WmoDoodadAdd>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WmoDoodadAdd {
+    pub path: String,
+    pub position: [f32; 3],
+    // quaternion, xyzw
+    pub rotation: Option<[f32; 4]>,
+    pub scale: Option<f32>,
+    pub color: Option<WmoColor>,
+    #[serde(default)]
+    pub flags: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WmoEditResult {
+    // texture path -> replaced material references
+    pub textures: Vec<(String, usize)>,
+    pub renamed_groups: Vec<usize>,
+    pub removed_doodad_sets: Vec<String>,
+    pub added_doodad_sets: Vec<String>,
+    pub written: Vec<PathBuf>,
+}
+
+pub fn edit_wmo(input: &str, edit_file: &str, output: Option<&str>) -> R<WmoEditResult> {
+    let edits: WmoEdits = serde_json::from_slice(
+        &fs::read(edit_file).with_context(|| format!("Failed to read {}", edit_file))?,
+    ).with_context(|| format!("Invalid edit file {}", edit_file))?;
+    let mut wmo = WmoFile::from_path(input)?;
+
+    let mut result = WmoEditResult {
+        textures: Vec::new(),
+        renamed_groups: Vec::new(),
+        removed_doodad_sets: Vec::new(),
+        added_doodad_sets: Vec::new(),
+        written: Vec::new(),
+    };
+    for edit in &edits.textures {
+        let replaced = wmo.root.replace_texture(&edit.from, &edit.to);
+        result.textures.push((edit.from.clone(), replaced));
+    }
+    for edit in &edits.group_names {
+        wmo.set_group_name(edit.group, &edit.name)?;
+        result.renamed_groups.push(edit.group);
+    }
+    for name in &edits.remove_doodad_sets {
+        wmo.root.remove_doodad_set(name)?;
+        result.removed_doodad_sets.push(name.clone());
+    }
+    for set in &edits.add_doodad_sets {
+        let doodads = set.doodads.iter()
+            .map(|it| ChunkModdItem {
+                name_offset: 0,
+                name: Some(it.path.clone()),
+                flags: it.flags,
+                position: it.position,
+                rotation: it.rotation.unwrap_or([0.0, 0.0, 0.0, 1.0]),
+                scale: it.scale.unwrap_or(1.0),
+                color: it.color.unwrap_or(WmoColor { r: 0xFF, g: 0xFF, b: 0xFF, a: 0xFF }),
+            })
+            .collect();
+        wmo.root.add_doodad_set(&set.name, doodads)?;
+        result.added_doodad_sets.push(set.name.clone());
+    }
+
+    result.written = wmo.write_to_path(output.unwrap_or(input))?;
+    Ok(result)
+}
+
+#[cfg(test)]
+#[test]
+fn wmo_export_places_the_doodad_models() {
+    use crate::formats::m2::test_utils::{build_test_m2, build_test_skin};
+    use crate::formats::wmo::test_utils::write_test_wmo;
+
+    let dir = crate::test_utils::TempDir::new("wow-file-tools-wmo-export-doodads");
+    let input = write_test_wmo(&dir);
+    // the barrel of the default set, turned a quarter around the vertical axis and scaled twice
+    let mut wmo = WmoFile::from_path(&input).unwrap();
+    let half_sqrt = 0.5f32.sqrt();
+    wmo.root.modd.0[0].rotation = [0.0, 0.0, half_sqrt, half_sqrt];
+    wmo.root.modd.0[0].scale = 2.0;
+    wmo.write_to_path(&input).unwrap();
+    let (input, output) = (input.to_str().unwrap(), dir.join("out/test.obj"));
+    let output = output.to_str().unwrap();
+
+    assert!(export_wmo(input, output, None, None, true).is_err());
+    fs::create_dir_all(dir.join("empty")).unwrap();
+    let result = export_wmo(input, output, None, dir.join("empty").to_str(), true).unwrap();
+    // OBJ leaves the doodads out that can't be loaded
+    assert_eq!((result.doodads, result.doodad_placeholders), (0, 0));
+    assert!(result.warns.iter().any(|it| matches!(it, MeshExportWarn::MissingDoodad(path) if path == "World\\Generic\\Barrel.m2")));
+
+    let barrel = dir.join("World/Generic");
+    fs::create_dir_all(&barrel).unwrap();
+    fs::write(barrel.join("Barrel.m2"), build_test_m2()).unwrap();
+    fs::write(barrel.join("Barrel00.skin"), build_test_skin()).unwrap();
+    let result = export_wmo(input, output, None, dir.join("").to_str(), true).unwrap();
+    assert_eq!((result.groups, result.doodads, result.doodad_placeholders), (1, 1, 0));
+
+    let obj = fs::read_to_string(output).unwrap();
+    let mtl = fs::read_to_string(dir.join("out/test.mtl")).unwrap();
+    assert!(mtl.contains("newmtl Barrel_0_Test"));
+    let mut doodad_lines = obj.lines().skip_while(|it| !it.starts_with("o doodad_000_"));
+    assert_eq!(doodad_lines.next(), Some("o doodad_000_TestModel_00_0"));
+    // the second model vertex, (1, 0, 1) Y up, ends up at (7, 0, -7) around the barrel at (5, 0, -5)
+    let vertex: Vec<f32> = doodad_lines.nth(1).unwrap()
+        .split(' ')
+        .skip(1)
+        .map(|it| it.parse().unwrap())
+        .collect();
+    for (value, expected) in vertex.iter().zip(&[7.0, 0.0, -7.0]) {
+        assert!((value - expected).abs() < 0.001, "{:?}", vertex);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn wmo_collision_format_follows_the_extension() {
+    let dir = crate::test_utils::TempDir::new("wow-file-tools-wmo-collision-format");
+    let input = crate::formats::wmo::test_utils::write_test_wmo(&dir);
+    let input = input.to_str().unwrap();
+    let formats = [
+        ("test.obj", CollisionFormat::Obj),
+        ("test.wcol", CollisionFormat::Binary),
+        ("test.BIN", CollisionFormat::Binary),
+    ];
+    for (file_name, format) in &formats {
+        let result = export_wmo_collision(input, dir.join(file_name).to_str().unwrap(), None).unwrap();
+        assert_eq!(result.format, *format);
+    }
+    let unknown = export_wmo_collision(input, dir.join("test.txt").to_str().unwrap(), None).unwrap_err();
+    assert!(unknown.to_string().contains("--format obj or wcol"));
+}
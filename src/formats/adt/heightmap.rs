@@ -0,0 +1,219 @@
+use crate::common::{R, err};
+use crate::formats::adt::{AdtFile, UNIT_SIZE};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+// 16 chunks of 8 quads each, neighbouring chunks and tiles share their edge vertices
+pub const TILE_HEIGHTMAP_SIZE: usize = 16 * 8 + 1;
+const MCVT_ROW_STRIDE: usize = 17;
+
+/// Absolute heights of the outer vertices of a tile, row major.
+/// Rows follow MCNK index_y, columns follow index_x.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileHeightmap {
+    pub heights: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum HeightmapFormat {
+    Png,
+    R16,
+}
+
+/// Written next to an exported image, needed to map pixel values back to heights.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeightmapMetadata {
+    pub format: HeightmapFormat,
+    pub width: u32,
+    pub height: u32,
+    pub min_height: f32,
+    pub max_height: f32,
+    // tile at the top left pixel
+    pub origin_tile: [u32; 2],
+    pub tiles: Vec<[u32; 2]>,
+}
+
+impl HeightmapMetadata {
+    pub fn height_to_pixel(&self, height: f32) -> u16 {
+        let range = self.max_height - self.min_height;
+        if range <= 0.0 {
+            return 0;
+        }
+        (((height - self.min_height) / range).clamp(0.0, 1.0) * 65535.0).round() as u16
+    }
+
+    pub fn pixel_to_height(&self, pixel: u16) -> f32 {
+        self.min_height + (pixel as f32 / 65535.0) * (self.max_height - self.min_height)
+    }
+}
+
+impl TileHeightmap {
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.heights[y * TILE_HEIGHTMAP_SIZE + x]
+    }
+
+    fn get_clamped(&self, x: i32, y: i32) -> f32 {
+        let max = TILE_HEIGHTMAP_SIZE as i32 - 1;
+        self.get(x.max(0).min(max) as usize, y.max(0).min(max) as usize)
+    }
+
+    pub fn min_max(&self) -> (f32, f32) {
+        self.heights.iter().fold((f32::MAX, f32::MIN), |(min, max), h| (min.min(*h), max.max(*h)))
+    }
+}
+
+impl AdtFile {
+    pub fn get_heightmap(&self) -> R<TileHeightmap> {
+        let mut heights = vec![0.0; TILE_HEIGHTMAP_SIZE * TILE_HEIGHTMAP_SIZE];
+        for item in &self.mcnk.items {
+            let mcvt = item.mcvt.as_ref()
+                .with_context(|| format!("MCNK {}_{} has no MCVT", item.header.index_x, item.header.index_y))?;
+            let (base_x, base_y) = chunk_origin(item.header.index_x, item.header.index_y)?;
+            for row in 0..9 {
+                for col in 0..9 {
+                    heights[(base_y + row) * TILE_HEIGHTMAP_SIZE + base_x + col] =
+                        mcvt.heights[row * MCVT_ROW_STRIDE + col] + item.header.position[2];
+                }
+            }
+        }
+        Ok(TileHeightmap { heights })
+    }
+
+    /// Writes new outer vertex heights into the MCVTs.
+    /// Inner vertices follow the average change of their four corners so untouched detail survives,
+    /// the MCNK base height and the normals are recomputed for every chunk the change reaches.
+    pub fn set_heightmap(&mut self, heightmap: &TileHeightmap) -> R<()> {
+        if heightmap.heights.len() != TILE_HEIGHTMAP_SIZE * TILE_HEIGHTMAP_SIZE {
+            return err(format!(
+                "Heightmap has {} heights, expected {}",
+                heightmap.heights.len(),
+                TILE_HEIGHTMAP_SIZE * TILE_HEIGHTMAP_SIZE
+            ));
+        }
+        let old = self.get_heightmap()?;
+        let changed: Vec<bool> = old.heights.iter()
+            .zip(heightmap.heights.iter())
+            .map(|(a, b)| a != b)
+            .collect();
+
+        for item in self.mcnk.items.iter_mut() {
+            let (base_x, base_y) = chunk_origin(item.header.index_x, item.header.index_y)?;
+            let touches_change = |margin: usize| {
+                let x_range = base_x.saturating_sub(margin)..(base_x + 9 + margin).min(TILE_HEIGHTMAP_SIZE);
+                let y_range = base_y.saturating_sub(margin)..(base_y + 9 + margin).min(TILE_HEIGHTMAP_SIZE);
+                y_range.into_iter().any(|y| x_range.clone().any(|x| changed[y * TILE_HEIGHTMAP_SIZE + x]))
+            };
+            // normals on the chunk border depend on the neighbouring vertices
+            if !touches_change(1) {
+                continue;
+            }
+
+            if touches_change(0) {
+                let mcvt = item.mcvt.as_mut().unwrap();
+                let base_height = item.header.position[2];
+                let mut absolute: Vec<f32> = mcvt.heights.iter().map(|h| h + base_height).collect();
+                for row in 0..9 {
+                    for col in 0..9 {
+                        absolute[row * MCVT_ROW_STRIDE + col] = heightmap.get(base_x + col, base_y + row);
+                    }
+                }
+                for row in 0..8 {
+                    for col in 0..8 {
+                        let delta = [(0, 0), (1, 0), (0, 1), (1, 1)].iter()
+                            .map(|(dx, dy)| {
+                                let (x, y) = (base_x + col + dx, base_y + row + dy);
+                                heightmap.get(x, y) - old.get(x, y)
+                            })
+                            .sum::<f32>() / 4.0;
+                        absolute[row * MCVT_ROW_STRIDE + 9 + col] += delta;
+                    }
+                }
+                let new_base = absolute.iter().cloned().fold(f32::MAX, f32::min);
+                item.header.position[2] = new_base;
+                mcvt.heights = absolute.iter().map(|h| h - new_base).collect();
+            }
+
+            if let Some(mcnr) = item.mcnr.as_mut() {
+                for row in 0..9 {
+                    for col in 0..9 {
+                        let (x, y) = ((base_x + col) as i32, (base_y + row) as i32);
+                        let d_col = (heightmap.get_clamped(x + 1, y) - heightmap.get_clamped(x - 1, y))
+                            / (span(x) * UNIT_SIZE);
+                        let d_row = (heightmap.get_clamped(x, y + 1) - heightmap.get_clamped(x, y - 1))
+                            / (span(y) * UNIT_SIZE);
+                        mcnr.normals[row * MCVT_ROW_STRIDE + col] = encode_normal(d_row, d_col);
+                    }
+                }
+                for row in 0..8 {
+                    for col in 0..8 {
+                        let (x, y) = (base_x + col, base_y + row);
+                        let top_left = heightmap.get(x, y);
+                        let top_right = heightmap.get(x + 1, y);
+                        let bottom_left = heightmap.get(x, y + 1);
+                        let bottom_right = heightmap.get(x + 1, y + 1);
+                        let d_col = ((top_right + bottom_right) - (top_left + bottom_left)) / (2.0 * UNIT_SIZE);
+                        let d_row = ((bottom_left + bottom_right) - (top_left + top_right)) / (2.0 * UNIT_SIZE);
+                        mcnr.normals[row * MCVT_ROW_STRIDE + 9 + col] = encode_normal(d_row, d_col);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn chunk_origin(index_x: u32, index_y: u32) -> R<(usize, usize)> {
+    if index_x >= 16 || index_y >= 16 {
+        return err(format!("MCNK index {}_{} is outside of the tile", index_x, index_y));
+    }
+    Ok((index_x as usize * 8, index_y as usize * 8))
+}
+
+// number of quads between the two samples of a central difference, one on the tile border
+fn span(v: i32) -> f32 {
+    if v == 0 || v == TILE_HEIGHTMAP_SIZE as i32 - 1 { 1.0 } else { 2.0 }
+}
+
+// rows run towards -X and columns towards -Y in world space, MCNR is stored as X, Y, Z
+fn encode_normal(d_row: f32, d_col: f32) -> [i8; 3] {
+    let length = (d_row * d_row + d_col * d_col + 1.0).sqrt();
+    [
+        (d_row / length * 127.0).round() as i8,
+        (d_col / length * 127.0).round() as i8,
+        (1.0 / length * 127.0).round() as i8,
+    ]
+}
+
+#[cfg(test)]
+#[test]
+fn heightmap_round_trip_keeps_positions_and_normals_consistent() {
+    let original = crate::formats::adt::test_utils::build_test_adt();
+    let mut adt = AdtFile::from_bytes(&original).unwrap();
+    let heightmap = adt.get_heightmap().unwrap();
+    assert_eq!(heightmap.get(0, 0), 10.0);
+    assert_eq!(heightmap.get(128, 128), 10.0 + 144.0 * 0.25);
+
+    // writing back the same heights must not touch the file
+    adt.set_heightmap(&heightmap).unwrap();
+    assert!(adt.to_bytes().unwrap() == original);
+
+    let mut raised = heightmap.clone();
+    for y in 0..TILE_HEIGHTMAP_SIZE {
+        for x in 64..TILE_HEIGHTMAP_SIZE {
+            raised.heights[y * TILE_HEIGHTMAP_SIZE + x] = 200.0;
+        }
+    }
+    adt.set_heightmap(&raised).unwrap();
+    let reparsed = AdtFile::from_bytes(&adt.to_bytes().unwrap()).unwrap();
+    assert_eq!(reparsed.get_heightmap().unwrap(), raised);
+
+    let raised_chunk = &reparsed.mcnk.items[9];
+    assert_eq!(raised_chunk.header.position[2], 200.0);
+    assert_eq!(raised_chunk.header.position[0], adt.mcnk.items[9].header.position[0]);
+    assert_eq!(raised_chunk.mcnr.as_ref().unwrap().normals[20], [0, 0, 127]);
+    // the cliff at column 64 tilts the normals of the chunks on both sides
+    let cliff_normal = reparsed.mcnk.items[7].mcnr.as_ref().unwrap().normals[8];
+    assert!(cliff_normal[1] > 100);
+    // far away chunks keep their original normals
+    assert_eq!(reparsed.mcnk.items[0].mcnr.as_ref().unwrap().normals, adt.mcnk.items[0].mcnr.as_ref().unwrap().normals);
+}
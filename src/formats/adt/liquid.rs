@@ -0,0 +1,340 @@
+use crate::byte_utils::VecUtils;
+use crate::common::{R, err};
+use crate::formats::adt::mcnk::{MCNK_FLAG_LQ_MAGMA, MCNK_FLAG_LQ_OCEAN, MCNK_FLAG_LQ_RIVER, MCNK_FLAG_LQ_SLIME};
+use crate::formats::chunk::Chunk;
+use crate::formats::dbc::dbc::Dbc;
+use crate::formats::dbc::liquid_type::LiquidTypeDbcRow;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+const MH2O_HEADER_SIZE: usize = 12;
+const MH2O_INSTANCE_SIZE: usize = 24;
+const MCLQ_INSTANCE_SIZE: usize = 804;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMh2o {
+    // one entry per MCNK
+    pub chunks: Vec<Mh2oChunk>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Mh2oChunk {
+    pub instances: Vec<Mh2oInstance>,
+    pub attributes: Option<Mh2oAttributes>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Mh2oAttributes {
+    // 8x8 bit masks
+    pub fishable: u64,
+    pub deep: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Mh2oInstance {
+    // LiquidType.dbc
+    pub liquid_type: u16,
+    pub liquid_name: Option<String>,
+    pub liquid_vertex_format: u16,
+    pub min_height_level: f32,
+    pub max_height_level: f32,
+    pub x_offset: u8,
+    pub y_offset: u8,
+    pub width: u8,
+    pub height: u8,
+    // width * height, row by row
+    pub exists: Vec<bool>,
+    // (width + 1) * (height + 1) entries each, when present
+    pub heights: Option<Vec<f32>>,
+    pub depths: Option<Vec<u8>>,
+    pub uvs: Option<Vec<[u16; 2]>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMclq(pub Vec<MclqInstance>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MclqLiquidKind {
+    River,
+    Ocean,
+    Magma,
+    Slime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MclqInstance {
+    pub kind: MclqLiquidKind,
+    pub min_height: f32,
+    pub max_height: f32,
+    // 9x9
+    pub vertices: Vec<MclqVertex>,
+    // 8x8, lower nibble is the liquid type, 0x0F means don't render
+    pub tiles: Vec<u8>,
+    pub n_flowvs: u32,
+    pub flowvs: Vec<MclqFlowv>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum MclqVertex {
+    Water {
+        depth: u8,
+        flow_0_pct: u8,
+        flow_1_pct: u8,
+        filler: u8,
+        height: f32,
+    },
+    Magma {
+        s: u16,
+        t: u16,
+        height: f32,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MclqFlowv {
+    pub position: [f32; 3],
+    pub radius: f32,
+    pub direction: [f32; 3],
+    pub velocity: f32,
+    pub amplitude: f32,
+    pub frequency: f32,
+}
+
+impl ChunkMh2o {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMh2o> {
+        assert_eq!(c.get_id_as_string(), "MH2O");
+        let data = &c.data;
+        let chunks = (0..256)
+            .map(|i| {
+                let offset = i * MH2O_HEADER_SIZE;
+                let offset_instances = data.get_u32(offset)? as usize;
+                let layer_count = data.get_u32(offset + 4)? as usize;
+                let offset_attributes = data.get_u32(offset + 8)? as usize;
+
+                let instances = (0..layer_count)
+                    .map(|layer| Mh2oInstance::from_bytes(data, offset_instances + layer * MH2O_INSTANCE_SIZE))
+                    .collect::<R<Vec<Mh2oInstance>>>()
+                    .with_context(|| format!("Failed to parse MH2O instances of chunk #{}", i))?;
+
+                let attributes = if offset_attributes != 0 && layer_count > 0 {
+                    Some(Mh2oAttributes {
+                        fishable: read_u64(data, offset_attributes)?,
+                        deep: read_u64(data, offset_attributes + 8)?,
+                    })
+                } else {
+                    None
+                };
+
+                Ok(Mh2oChunk {
+                    instances,
+                    attributes,
+                })
+            })
+            .collect::<R<Vec<Mh2oChunk>>>()?;
+
+        Ok(ChunkMh2o { chunks })
+    }
+
+    pub fn resolve_liquid_names(&mut self, liquid_types: &Dbc<LiquidTypeDbcRow>) {
+        for instance in self.chunks.iter_mut().flat_map(|c| c.instances.iter_mut()) {
+            instance.liquid_name = liquid_types.rows
+                .iter()
+                .find(|row| row.id == instance.liquid_type as u32)
+                .map(|row| row.name.clone());
+        }
+    }
+}
+
+impl Mh2oInstance {
+    fn from_bytes(data: &Vec<u8>, offset: usize) -> R<Mh2oInstance> {
+        let liquid_type = data.get_u16(offset)?;
+        let liquid_vertex_format = data.get_u16(offset + 2)?;
+        let min_height_level = data.get_f32(offset + 4)?;
+        let max_height_level = data.get_f32(offset + 8)?;
+        let x_offset = data.get_byte(offset + 12)?;
+        let y_offset = data.get_byte(offset + 13)?;
+        let width = data.get_byte(offset + 14)?;
+        let height = data.get_byte(offset + 15)?;
+        let offset_exists_bitmap = data.get_u32(offset + 16)? as usize;
+        let offset_vertex_data = data.get_u32(offset + 20)? as usize;
+
+        let n_tiles = width as usize * height as usize;
+        let exists = if offset_exists_bitmap == 0 {
+            vec![true; n_tiles]
+        } else {
+            (0..n_tiles)
+                .map(|i| data.get_byte(offset_exists_bitmap + i / 8).map(|b| b & (1 << (i % 8)) != 0))
+                .collect::<R<Vec<bool>>>()?
+        };
+
+        let n_vertices = (width as usize + 1) * (height as usize + 1);
+        let (heights, depths, uvs) = if offset_vertex_data == 0 {
+            (None, None, None)
+        } else {
+            let heights_size = n_vertices * 4;
+            let read_heights = || (0..n_vertices)
+                .map(|i| data.get_f32(offset_vertex_data + i * 4))
+                .collect::<R<Vec<f32>>>();
+            let read_depths = |from: usize| (0..n_vertices)
+                .map(|i| data.get_byte(from + i))
+                .collect::<R<Vec<u8>>>();
+            let read_uvs = |from: usize| (0..n_vertices)
+                .map(|i| Ok([data.get_u16(from + i * 4)?, data.get_u16(from + i * 4 + 2)?]))
+                .collect::<R<Vec<[u16; 2]>>>();
+
+            match liquid_vertex_format {
+                0 => (Some(read_heights()?), Some(read_depths(offset_vertex_data + heights_size)?), None),
+                1 => (Some(read_heights()?), None, Some(read_uvs(offset_vertex_data + heights_size)?)),
+                2 => (None, Some(read_depths(offset_vertex_data)?), None),
+                3 => {
+                    let uvs = read_uvs(offset_vertex_data + heights_size)?;
+                    let depths = read_depths(offset_vertex_data + heights_size + n_vertices * 4)?;
+                    (Some(read_heights()?), Some(depths), Some(uvs))
+                }
+                _ => return err(format!("Unknown MH2O liquid vertex format {}", liquid_vertex_format)),
+            }
+        };
+
+        Ok(Mh2oInstance {
+            liquid_type,
+            liquid_name: None,
+            liquid_vertex_format,
+            min_height_level,
+            max_height_level,
+            x_offset,
+            y_offset,
+            width,
+            height,
+            exists,
+            heights,
+            depths,
+            uvs,
+        })
+    }
+}
+
+impl ChunkMclq {
+    pub fn from_chunk(c: &Chunk, mcnk_flags: u32) -> R<ChunkMclq> {
+        assert_eq!(c.get_id_as_string(), "MCLQ");
+        // one instance per liquid flag set on the MCNK, in this order
+        let kinds: Vec<MclqLiquidKind> = vec![
+            (MCNK_FLAG_LQ_RIVER, MclqLiquidKind::River),
+            (MCNK_FLAG_LQ_OCEAN, MclqLiquidKind::Ocean),
+            (MCNK_FLAG_LQ_MAGMA, MclqLiquidKind::Magma),
+            (MCNK_FLAG_LQ_SLIME, MclqLiquidKind::Slime),
+        ]
+            .into_iter()
+            .filter(|(flag, _)| mcnk_flags & flag != 0)
+            .map(|(_, kind)| kind)
+            .collect();
+
+        let instances = kinds.into_iter()
+            .enumerate()
+            .take_while(|(i, _)| (i + 1) * MCLQ_INSTANCE_SIZE <= c.data.len())
+            .map(|(i, kind)| MclqInstance::from_bytes(&c.data, i * MCLQ_INSTANCE_SIZE, kind))
+            .collect::<R<Vec<MclqInstance>>>()?;
+
+        Ok(ChunkMclq(instances))
+    }
+}
+
+impl MclqInstance {
+    fn from_bytes(data: &Vec<u8>, offset: usize, kind: MclqLiquidKind) -> R<MclqInstance> {
+        let min_height = data.get_f32(offset)?;
+        let max_height = data.get_f32(offset + 4)?;
+
+        let vertices_offset = offset + 8;
+        let vertices = (0..81)
+            .map(|i| {
+                let v = vertices_offset + i * 8;
+                let height = data.get_f32(v + 4)?;
+                Ok(match kind {
+                    MclqLiquidKind::Magma | MclqLiquidKind::Slime => MclqVertex::Magma {
+                        s: data.get_u16(v)?,
+                        t: data.get_u16(v + 2)?,
+                        height,
+                    },
+                    _ => MclqVertex::Water {
+                        depth: data.get_byte(v)?,
+                        flow_0_pct: data.get_byte(v + 1)?,
+                        flow_1_pct: data.get_byte(v + 2)?,
+                        filler: data.get_byte(v + 3)?,
+                        height,
+                    },
+                })
+            })
+            .collect::<R<Vec<MclqVertex>>>()?;
+
+        let tiles_offset = vertices_offset + 81 * 8;
+        let tiles = (0..64)
+            .map(|i| data.get_byte(tiles_offset + i))
+            .collect::<R<Vec<u8>>>()?;
+
+        let n_flowvs = data.get_u32(tiles_offset + 64)?;
+        let flowvs_offset = tiles_offset + 68;
+        let flowvs = (0..2)
+            .map(|i| {
+                let f = flowvs_offset + i * 40;
+                Ok(MclqFlowv {
+                    position: [data.get_f32(f)?, data.get_f32(f + 4)?, data.get_f32(f + 8)?],
+                    radius: data.get_f32(f + 12)?,
+                    direction: [data.get_f32(f + 16)?, data.get_f32(f + 20)?, data.get_f32(f + 24)?],
+                    velocity: data.get_f32(f + 28)?,
+                    amplitude: data.get_f32(f + 32)?,
+                    frequency: data.get_f32(f + 36)?,
+                })
+            })
+            .collect::<R<Vec<MclqFlowv>>>()?;
+
+        Ok(MclqInstance {
+            kind,
+            min_height,
+            max_height,
+            vertices,
+            tiles,
+            n_flowvs,
+            flowvs,
+        })
+    }
+}
+
+fn read_u64(data: &Vec<u8>, offset: usize) -> R<u64> {
+    let low = data.get_u32(offset)? as u64;
+    let high = data.get_u32(offset + 4)? as u64;
+    Ok(low | (high << 32))
+}
+
+#[cfg(test)]
+#[test]
+fn mh2o_instance_with_heights_and_depths() {
+    let mut data = vec![0u8; 256 * MH2O_HEADER_SIZE];
+    let instance_offset = data.len() as u32;
+    // chunk #0 has one layer
+    data[0..4].copy_from_slice(&instance_offset.to_le_bytes());
+    data[4..8].copy_from_slice(&1u32.to_le_bytes());
+
+    let bitmap_offset = instance_offset + MH2O_INSTANCE_SIZE as u32;
+    let vertex_offset = bitmap_offset + 1;
+    data.extend_from_slice(&2u16.to_le_bytes()); // ocean
+    data.extend_from_slice(&0u16.to_le_bytes()); // height + depth
+    data.extend_from_slice(&1.0f32.to_le_bytes());
+    data.extend_from_slice(&2.0f32.to_le_bytes());
+    data.extend_from_slice(&[3, 4, 2, 1]);
+    data.extend_from_slice(&bitmap_offset.to_le_bytes());
+    data.extend_from_slice(&vertex_offset.to_le_bytes());
+    data.push(0b10);
+    for i in 0..6 {
+        data.extend_from_slice(&(i as f32).to_le_bytes());
+    }
+    data.extend_from_slice(&[10, 11, 12, 13, 14, 15]);
+
+    let chunk = Chunk { id: *b"O2HM", size: data.len() as u32, data };
+    let mh2o = ChunkMh2o::from_chunk(&chunk).unwrap();
+    let instance = &mh2o.chunks[0].instances[0];
+    assert_eq!(instance.liquid_type, 2);
+    assert_eq!((instance.x_offset, instance.y_offset, instance.width, instance.height), (3, 4, 2, 1));
+    assert_eq!(instance.exists, vec![false, true]);
+    assert_eq!(instance.heights.as_ref().unwrap()[5], 5.0);
+    assert_eq!(instance.depths.as_ref().unwrap(), &vec![10, 11, 12, 13, 14, 15]);
+    assert!(mh2o.chunks[1].instances.is_empty());
+}
@@ -0,0 +1,744 @@
+use crate::byte_utils::{VecUtils, VecWriteUtils};
+use crate::common::{R, err};
+use crate::formats::adt::liquid::ChunkMclq;
+use crate::formats::chunk::{Chunk, ChunkVecUtils};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+pub const MCNK_HEADER_SIZE: usize = 128;
+// 9x9 outer + 8x8 inner vertices
+pub const MCNK_VERTEX_COUNT: usize = 145;
+pub const ALPHA_MAP_SIZE: usize = 4096;
+
+// MCNR declares 435 bytes but is followed by 13 bytes nobody accounts for
+const MCNR_DECLARED_SIZE: usize = 435;
+const MCNR_PADDING_SIZE: usize = 13;
+
+pub const MCNK_FLAG_HAS_MCSH: u32 = 0x1;
+pub const MCNK_FLAG_IMPASS: u32 = 0x2;
+pub const MCNK_FLAG_LQ_RIVER: u32 = 0x4;
+pub const MCNK_FLAG_LQ_OCEAN: u32 = 0x8;
+pub const MCNK_FLAG_LQ_MAGMA: u32 = 0x10;
+pub const MCNK_FLAG_LQ_SLIME: u32 = 0x20;
+pub const MCNK_FLAG_HAS_MCCV: u32 = 0x40;
+pub const MCNK_FLAG_DO_NOT_FIX_ALPHA_MAP: u32 = 0x8000;
+
+pub const MCLY_FLAG_USE_ALPHA_MAP: u32 = 0x100;
+pub const MCLY_FLAG_ALPHA_MAP_COMPRESSED: u32 = 0x200;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMcnk {
+    pub items: Vec<ChunkMcnkItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMcnkItem {
+    pub header: McnkHeader,
+    pub mcvt: Option<ChunkMcvt>,
+    pub mcnr: Option<ChunkMcnr>,
+    pub mcly: ChunkMcly,
+    pub mcrf: ChunkMcrf,
+    pub mcsh: Option<ChunkMcsh>,
+    pub mcal: Option<ChunkMcal>,
+    pub mclq: Option<ChunkMclq>,
+    pub mcse: Option<ChunkMcse>,
+    // the sub chunks exactly as found in the file, in file order
+    #[serde(skip)]
+    pub sub_chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McnkHeader {
+    pub flags: u32,
+    pub index_x: u32,
+    pub index_y: u32,
+    pub n_layers: u32,
+    pub n_doodad_refs: u32,
+    pub offs_mcvt: u32,
+    pub offs_mcnr: u32,
+    pub offs_mcly: u32,
+    pub offs_mcrf: u32,
+    pub offs_mcal: u32,
+    pub size_mcal: u32,
+    pub offs_mcsh: u32,
+    pub size_mcsh: u32,
+    pub area_id: u32,
+    pub n_map_obj_refs: u32,
+    pub holes: u16,
+    pub unknown_but_used: u16,
+    pub low_quality_texture_map: [u16; 8],
+    pub pred_tex: u32,
+    pub no_effect_doodad: u32,
+    pub offs_mcse: u32,
+    pub n_snd_emitters: u32,
+    pub offs_mclq: u32,
+    pub size_mclq: u32,
+    pub position: [f32; 3],
+    pub offs_mccv: u32,
+    pub offs_mclv: u32,
+    pub unused: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMcvt {
+    // relative to `McnkHeader#position[2]`
+    pub heights: Vec<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMcnr {
+    pub normals: Vec<[i8; 3]>,
+    #[serde(skip)]
+    pub padding: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMcly(pub Vec<McnkLayer>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McnkLayer {
+    pub texture_id: u32,
+    pub flags: u32,
+    pub offset_in_mcal: u32,
+    pub effect_id: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMcrf {
+    // indices into MDDF
+    pub doodad_refs: Vec<u32>,
+    // indices into MODF
+    pub map_obj_refs: Vec<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMcsh {
+    // 64 rows of 64 bits, lowest bit is the left most cell
+    pub rows: Vec<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMcal {
+    pub alpha_maps: Vec<McalAlphaMap>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AlphaMapFormat {
+    Uncompressed2048,
+    Uncompressed4096,
+    Compressed,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct McalAlphaMap {
+    // index into MCLY
+    pub layer: usize,
+    pub format: AlphaMapFormat,
+    // 64x64 values in the 0-255 range, regardless of the stored format
+    pub values: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMcse(pub Vec<McseSoundEmitter>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McseSoundEmitter {
+    pub sound_entries_advanced_id: u32,
+    pub position: [f32; 3],
+    pub size: [f32; 3],
+}
+
+impl ChunkMcnk {
+    pub fn from_chunks(chunks: Vec<&Chunk>, big_alpha: Option<bool>) -> R<ChunkMcnk> {
+        let items = chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, c)| {
+                ChunkMcnkItem::from_chunk(c, big_alpha)
+                    .with_context(|| format!("Failed to parse MCNK #{}", index))
+            })
+            .collect::<R<Vec<ChunkMcnkItem>>>()?;
+        Ok(ChunkMcnk {
+            items
+        })
+    }
+
+    /// Sets the area of every chunk inside `rect`, or of the whole tile. Returns how many chunks changed.
+    pub fn set_area_id(&mut self, area_id: u32, rect: Option<&McnkRect>) -> usize {
+        let mut changed = 0;
+        for item in self.items.iter_mut() {
+            let inside = rect.map(|it| it.contains(item.header.index_x, item.header.index_y)).unwrap_or(true);
+            if inside && item.header.area_id != area_id {
+                item.header.area_id = area_id;
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// Replaces area IDs by the ones they map to. Returns how many chunks changed.
+    pub fn remap_area_ids(&mut self, remap: &HashMap<u32, u32>) -> usize {
+        let mut changed = 0;
+        for item in self.items.iter_mut() {
+            if let Some(area_id) = remap.get(&item.header.area_id).filter(|it| **it != item.header.area_id) {
+                item.header.area_id = *area_id;
+                changed += 1;
+            }
+        }
+        changed
+    }
+}
+
+/// Inclusive rectangle of MCNK indices within a tile.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct McnkRect {
+    pub min_x: u32,
+    pub min_y: u32,
+    pub max_x: u32,
+    pub max_y: u32,
+}
+
+impl McnkRect {
+    pub fn contains(&self, index_x: u32, index_y: u32) -> bool {
+        (self.min_x..=self.max_x).contains(&index_x) && (self.min_y..=self.max_y).contains(&index_y)
+    }
+}
+
+impl ChunkMcnkItem {
+    /// `big_alpha` is the MPHD flag of the map, see [ChunkMcal::from_chunk].
+    pub fn from_chunk(c: &Chunk, big_alpha: Option<bool>) -> R<ChunkMcnkItem> {
+        assert_eq!(c.get_id_as_string(), "MCNK");
+        let header = McnkHeader::from_bytes(&c.data)?;
+        let sub_chunks = header.get_sub_chunks(&c.data)?;
+
+        let mcvt = sub_chunks.get_chunk_of_type_optionally("MCVT")
+            .map(ChunkMcvt::from_chunk)
+            .transpose()?;
+        let mcnr = sub_chunks.get_chunk_of_type_optionally("MCNR")
+            .map(ChunkMcnr::from_chunk)
+            .transpose()?;
+        let mcly = sub_chunks.get_chunk_of_type_optionally("MCLY")
+            .map(ChunkMcly::from_chunk)
+            .transpose()?
+            .unwrap_or(ChunkMcly(vec![]));
+        let mcrf = sub_chunks.get_chunk_of_type_optionally("MCRF")
+            .map(|it| ChunkMcrf::from_chunk(it, &header))
+            .transpose()?
+            .unwrap_or(ChunkMcrf { doodad_refs: vec![], map_obj_refs: vec![] });
+        let mcsh = sub_chunks.get_chunk_of_type_optionally("MCSH")
+            .map(ChunkMcsh::from_chunk)
+            .transpose()?;
+        let mcal = sub_chunks.get_chunk_of_type_optionally("MCAL")
+            .map(|it| ChunkMcal::from_chunk(it, &mcly, header.flags, big_alpha))
+            .transpose()?;
+        let mclq = sub_chunks.get_chunk_of_type_optionally("MCLQ")
+            .map(|it| ChunkMclq::from_chunk(it, header.flags))
+            .transpose()?;
+        let mcse = sub_chunks.get_chunk_of_type_optionally("MCSE")
+            .map(|it| ChunkMcse::from_chunk(it, header.n_snd_emitters))
+            .transpose()?;
+
+        Ok(ChunkMcnkItem {
+            header,
+            mcvt,
+            mcnr,
+            mcly,
+            mcrf,
+            mcsh,
+            mcal,
+            mclq,
+            mcse,
+            sub_chunks,
+        })
+    }
+
+    /// Whether the alpha maps of the chunk take 4096 bytes, judged from the MCAL layout alone for
+    /// when no WDT tells: compressed maps only exist in that mode, otherwise the space between
+    /// the layers decides. None without alpha maps.
+    pub fn guess_big_alpha(&self) -> Option<bool> {
+        let mcal_size = self.sub_chunks.iter()
+            .find(|c| c.get_id_as_string() == "MCAL")
+            .map_or(0, |c| c.data.len());
+        let layers = get_alpha_layers(&self.mcly, mcal_size);
+        if layers.is_empty() {
+            return None;
+        }
+        Some(layers.iter().any(|(_, layer, start, end)| {
+            layer.flags & MCLY_FLAG_ALPHA_MAP_COMPRESSED != 0 || end.saturating_sub(*start) >= ALPHA_MAP_SIZE
+        }))
+    }
+
+    /// Re-encodes the MCNK, typed sub chunks replace their raw counterpart
+    /// and the header offsets / counts get recomputed from the new layout.
+    pub fn to_chunk(&self) -> Chunk {
+        let mut header = self.header.clone();
+        header.n_layers = self.mcly.0.len() as u32;
+        header.n_doodad_refs = self.mcrf.doodad_refs.len() as u32;
+        header.n_map_obj_refs = self.mcrf.map_obj_refs.len() as u32;
+
+        let sub_chunks: Vec<Chunk> = self.sub_chunks.iter()
+            .map(|raw| match raw.get_id_as_string().as_str() {
+                "MCVT" => self.mcvt.as_ref().map(|it| it.to_chunk()),
+                "MCNR" => self.mcnr.as_ref().map(|it| it.to_chunk(raw.size)),
+                "MCLY" => Some(self.mcly.to_chunk()),
+                "MCRF" => Some(self.mcrf.to_chunk()),
+                _ => None,
+            }.unwrap_or_else(|| Chunk {
+                id: raw.id,
+                size: raw.size,
+                data: raw.data.clone(),
+            }))
+            .collect();
+
+        // offsets are relative to the start of the MCNK, including its own chunk header
+        let mut offset = 8 + MCNK_HEADER_SIZE as u32;
+        for sub_chunk in &sub_chunks {
+            match sub_chunk.get_id_as_string().as_str() {
+                "MCVT" => header.offs_mcvt = offset,
+                "MCCV" => header.offs_mccv = offset,
+                "MCNR" => header.offs_mcnr = offset,
+                "MCLY" => header.offs_mcly = offset,
+                "MCRF" => header.offs_mcrf = offset,
+                "MCSH" => header.offs_mcsh = offset,
+                "MCAL" => header.offs_mcal = offset,
+                "MCLQ" => header.offs_mclq = offset,
+                "MCSE" => header.offs_mcse = offset,
+                _ => {}
+            }
+            offset += 8 + sub_chunk.data.len() as u32;
+        }
+
+        let mut data = header.to_bytes();
+        for sub_chunk in &sub_chunks {
+            sub_chunk.write_to(&mut data);
+        }
+        Chunk::new("MCNK", data)
+    }
+
+    pub fn has_flag(&self, flag: u32) -> bool {
+        self.header.flags & flag == flag
+    }
+
+    /// Absolute height of the given MCVT vertex.
+    pub fn get_absolute_height(&self, vertex: usize) -> Option<f32> {
+        self.mcvt.as_ref()
+            .and_then(|mcvt| mcvt.heights.get(vertex))
+            .map(|height| height + self.header.position[2])
+    }
+}
+
+impl McnkHeader {
+    pub fn from_bytes(data: &Vec<u8>) -> R<McnkHeader> {
+        if data.len() < MCNK_HEADER_SIZE {
+            return err(format!("MCNK is only {} bytes long, expected at least a {} byte header", data.len(), MCNK_HEADER_SIZE));
+        }
+        let mut low_quality_texture_map = [0u16; 8];
+        for (i, it) in low_quality_texture_map.iter_mut().enumerate() {
+            *it = data.get_u16(0x40 + i * 2)?;
+        }
+        Ok(McnkHeader {
+            flags: data.get_u32(0x00)?,
+            index_x: data.get_u32(0x04)?,
+            index_y: data.get_u32(0x08)?,
+            n_layers: data.get_u32(0x0C)?,
+            n_doodad_refs: data.get_u32(0x10)?,
+            offs_mcvt: data.get_u32(0x14)?,
+            offs_mcnr: data.get_u32(0x18)?,
+            offs_mcly: data.get_u32(0x1C)?,
+            offs_mcrf: data.get_u32(0x20)?,
+            offs_mcal: data.get_u32(0x24)?,
+            size_mcal: data.get_u32(0x28)?,
+            offs_mcsh: data.get_u32(0x2C)?,
+            size_mcsh: data.get_u32(0x30)?,
+            area_id: data.get_u32(0x34)?,
+            n_map_obj_refs: data.get_u32(0x38)?,
+            holes: data.get_u16(0x3C)?,
+            unknown_but_used: data.get_u16(0x3E)?,
+            low_quality_texture_map,
+            pred_tex: data.get_u32(0x50)?,
+            no_effect_doodad: data.get_u32(0x54)?,
+            offs_mcse: data.get_u32(0x58)?,
+            n_snd_emitters: data.get_u32(0x5C)?,
+            offs_mclq: data.get_u32(0x60)?,
+            size_mclq: data.get_u32(0x64)?,
+            position: [data.get_f32(0x68)?, data.get_f32(0x6C)?, data.get_f32(0x70)?],
+            offs_mccv: data.get_u32(0x74)?,
+            offs_mclv: data.get_u32(0x78)?,
+            unused: data.get_u32(0x7C)?,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(MCNK_HEADER_SIZE);
+        data.push_u32(self.flags);
+        data.push_u32(self.index_x);
+        data.push_u32(self.index_y);
+        data.push_u32(self.n_layers);
+        data.push_u32(self.n_doodad_refs);
+        data.push_u32(self.offs_mcvt);
+        data.push_u32(self.offs_mcnr);
+        data.push_u32(self.offs_mcly);
+        data.push_u32(self.offs_mcrf);
+        data.push_u32(self.offs_mcal);
+        data.push_u32(self.size_mcal);
+        data.push_u32(self.offs_mcsh);
+        data.push_u32(self.size_mcsh);
+        data.push_u32(self.area_id);
+        data.push_u32(self.n_map_obj_refs);
+        data.push_u16(self.holes);
+        data.push_u16(self.unknown_but_used);
+        self.low_quality_texture_map.iter().for_each(|it| data.push_u16(*it));
+        data.push_u32(self.pred_tex);
+        data.push_u32(self.no_effect_doodad);
+        data.push_u32(self.offs_mcse);
+        data.push_u32(self.n_snd_emitters);
+        data.push_u32(self.offs_mclq);
+        data.push_u32(self.size_mclq);
+        self.position.iter().for_each(|it| data.push_f32(*it));
+        data.push_u32(self.offs_mccv);
+        data.push_u32(self.offs_mclv);
+        data.push_u32(self.unused);
+        data
+    }
+
+    /// Holes are stored as a 4x4 grid, each bit covering 2x2 of the 8x8 squares.
+    pub fn is_hole(&self, square_x: usize, square_y: usize) -> bool {
+        let bit = (square_y / 2) * 4 + (square_x / 2);
+        self.holes & (1 << bit) != 0
+    }
+
+    // Walks the sub chunks following the header, MCNR and MCLQ don't declare their real size.
+    fn get_sub_chunks(&self, data: &Vec<u8>) -> R<Vec<Chunk>> {
+        let mut offset = MCNK_HEADER_SIZE;
+        let mut sub_chunks = Vec::new();
+
+        while offset + 8 <= data.len() {
+            let id: [u8; 4] = data[offset..offset + 4].try_into()?;
+            let size = data.get_u32(offset + 4)?;
+            let name: String = id.iter().rev().map(|b| *b as char).collect();
+            let remaining = data.len() - offset - 8;
+
+            let data_size = match name.as_str() {
+                "MCNR" if size as usize == MCNR_DECLARED_SIZE => {
+                    (MCNR_DECLARED_SIZE + MCNR_PADDING_SIZE).min(remaining)
+                }
+                // MCLQ has its size set to 0, the header holds the real one
+                "MCLQ" if size == 0 && self.size_mclq > 8 => self.size_mclq as usize - 8,
+                _ => size as usize,
+            };
+
+            if data_size > remaining {
+                return err(format!("MCNK sub chunk {} at offset {} needs {} bytes but only {} are left", name, offset, data_size, remaining));
+            }
+
+            sub_chunks.push(Chunk {
+                id,
+                size,
+                data: data[offset + 8..offset + 8 + data_size].to_vec(),
+            });
+            offset += 8 + data_size;
+        }
+
+        Ok(sub_chunks)
+    }
+}
+
+impl ChunkMcvt {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMcvt> {
+        assert_eq!(c.get_id_as_string(), "MCVT");
+        let heights = (0..MCNK_VERTEX_COUNT)
+            .map(|i| c.data.get_f32(i * 4))
+            .collect::<R<Vec<f32>>>()?;
+        Ok(ChunkMcvt { heights })
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.heights.len() * 4);
+        self.heights.iter().for_each(|it| data.push_f32(*it));
+        Chunk::new("MCVT", data)
+    }
+}
+
+impl ChunkMcnr {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMcnr> {
+        assert_eq!(c.get_id_as_string(), "MCNR");
+        let normals_size = MCNK_VERTEX_COUNT * 3;
+        if c.data.len() < normals_size {
+            return err(format!("MCNR is {} bytes long, expected at least {}", c.data.len(), normals_size));
+        }
+        let normals = c.data[..normals_size]
+            .chunks(3)
+            .map(|n| [n[0] as i8, n[1] as i8, n[2] as i8])
+            .collect();
+        Ok(ChunkMcnr {
+            normals,
+            padding: c.data[normals_size..].to_vec(),
+        })
+    }
+
+    // the declared size doesn't cover the padding, so it is carried over from the original
+    pub fn to_chunk(&self, declared_size: u32) -> Chunk {
+        let mut data: Vec<u8> = self.normals.iter()
+            .flat_map(|n| n.iter().map(|v| *v as u8))
+            .collect();
+        data.extend_from_slice(&self.padding);
+        let mut chunk = Chunk::new("MCNR", data);
+        chunk.size = declared_size;
+        chunk
+    }
+}
+
+impl ChunkMcly {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMcly> {
+        assert_eq!(c.get_id_as_string(), "MCLY");
+        let layers = c.data.chunks_exact(16)
+            .map(|data| {
+                let data = data.to_vec();
+                Ok(McnkLayer {
+                    texture_id: data.get_u32(0)?,
+                    flags: data.get_u32(4)?,
+                    offset_in_mcal: data.get_u32(8)?,
+                    effect_id: data.get_u32(12)?,
+                })
+            })
+            .collect::<R<Vec<McnkLayer>>>()?;
+        Ok(ChunkMcly(layers))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 16);
+        for layer in &self.0 {
+            data.push_u32(layer.texture_id);
+            data.push_u32(layer.flags);
+            data.push_u32(layer.offset_in_mcal);
+            data.push_u32(layer.effect_id);
+        }
+        Chunk::new("MCLY", data)
+    }
+}
+
+impl ChunkMcrf {
+    pub fn from_chunk(c: &Chunk, header: &McnkHeader) -> R<ChunkMcrf> {
+        assert_eq!(c.get_id_as_string(), "MCRF");
+        let n_doodad_refs = header.n_doodad_refs as usize;
+        let n_map_obj_refs = header.n_map_obj_refs as usize;
+        let doodad_refs = (0..n_doodad_refs)
+            .map(|i| c.data.get_u32(i * 4))
+            .collect::<R<Vec<u32>>>()?;
+        let map_obj_refs = (0..n_map_obj_refs)
+            .map(|i| c.data.get_u32((n_doodad_refs + i) * 4))
+            .collect::<R<Vec<u32>>>()?;
+        Ok(ChunkMcrf {
+            doodad_refs,
+            map_obj_refs,
+        })
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity((self.doodad_refs.len() + self.map_obj_refs.len()) * 4);
+        self.doodad_refs.iter()
+            .chain(self.map_obj_refs.iter())
+            .for_each(|it| data.push_u32(*it));
+        Chunk::new("MCRF", data)
+    }
+}
+
+impl ChunkMcsh {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMcsh> {
+        assert_eq!(c.get_id_as_string(), "MCSH");
+        let rows = c.data.chunks_exact(8)
+            .map(|row| u64::from_le_bytes(row.try_into().unwrap()))
+            .collect();
+        Ok(ChunkMcsh { rows })
+    }
+
+    pub fn is_shadowed(&self, x: usize, y: usize) -> bool {
+        self.rows.get(y)
+            .map(|row| row & (1 << x) != 0)
+            .unwrap_or(false)
+    }
+}
+
+impl ChunkMcal {
+    /// `big_alpha` is the MPHD flag of the map's WDT, it tells whether uncompressed alpha maps take
+    /// 4096 or 2048 bytes. When it is unknown, every layer is judged by the space it takes in MCAL.
+    pub fn from_chunk(c: &Chunk, mcly: &ChunkMcly, mcnk_flags: u32, big_alpha: Option<bool>) -> R<ChunkMcal> {
+        assert_eq!(c.get_id_as_string(), "MCAL");
+        let fix_alpha_map = mcnk_flags & MCNK_FLAG_DO_NOT_FIX_ALPHA_MAP == 0;
+        let alpha_layers = get_alpha_layers(mcly, c.data.len());
+
+        let mut alpha_maps = Vec::with_capacity(alpha_layers.len());
+        for (layer_index, layer, start, end) in alpha_layers {
+            let region = c.data.get(start..end)
+                .with_context(|| format!("MCAL layer {} has an invalid offset {}", layer_index, start))?;
+
+            let is_big = big_alpha.unwrap_or(region.len() >= ALPHA_MAP_SIZE);
+            let (format, values) = if layer.flags & MCLY_FLAG_ALPHA_MAP_COMPRESSED != 0 {
+                (AlphaMapFormat::Compressed, decompress_alpha_map(region)?)
+            } else if is_big {
+                let values = region.get(..ALPHA_MAP_SIZE).with_context(|| format!(
+                    "MCAL layer {} is {} bytes long, too short for a 4096 byte alpha map",
+                    layer_index,
+                    region.len()
+                ))?;
+                (AlphaMapFormat::Uncompressed4096, values.to_vec())
+            } else {
+                (AlphaMapFormat::Uncompressed2048, expand_alpha_map_2048(region, fix_alpha_map)?)
+            };
+
+            alpha_maps.push(McalAlphaMap {
+                layer: layer_index,
+                format,
+                values,
+            });
+        }
+
+        Ok(ChunkMcal { alpha_maps })
+    }
+}
+
+// (MCLY index, layer, start, end in MCAL) of the layers with an alpha map. Layers are stored
+// back to back, the next layer marks the end of this one.
+fn get_alpha_layers(mcly: &ChunkMcly, mcal_size: usize) -> Vec<(usize, &McnkLayer, usize, usize)> {
+    let alpha_layers: Vec<(usize, &McnkLayer)> = mcly.0.iter()
+        .enumerate()
+        .filter(|(_, layer)| layer.flags & MCLY_FLAG_USE_ALPHA_MAP != 0)
+        .collect();
+    alpha_layers.iter()
+        .enumerate()
+        .map(|(i, (layer_index, layer))| {
+            let end = alpha_layers.get(i + 1)
+                .map(|(_, next)| next.offset_in_mcal as usize)
+                .unwrap_or(mcal_size)
+                .min(mcal_size);
+            (*layer_index, *layer, layer.offset_in_mcal as usize, end)
+        })
+        .collect()
+}
+
+impl ChunkMcse {
+    pub fn from_chunk(c: &Chunk, n_snd_emitters: u32) -> R<ChunkMcse> {
+        assert_eq!(c.get_id_as_string(), "MCSE");
+        const EMITTER_SIZE: usize = 28;
+        let emitters = c.data.chunks_exact(EMITTER_SIZE)
+            .take(n_snd_emitters as usize)
+            .map(|data| {
+                let data = data.to_vec();
+                Ok(McseSoundEmitter {
+                    sound_entries_advanced_id: data.get_u32(0)?,
+                    position: [data.get_f32(4)?, data.get_f32(8)?, data.get_f32(12)?],
+                    size: [data.get_f32(16)?, data.get_f32(20)?, data.get_f32(24)?],
+                })
+            })
+            .collect::<R<Vec<McseSoundEmitter>>>()?;
+        Ok(ChunkMcse(emitters))
+    }
+}
+
+/// Each control byte either repeats the following byte (highest bit set)
+/// or copies the following bytes, the lower 7 bits hold the count.
+pub fn decompress_alpha_map(data: &[u8]) -> R<Vec<u8>> {
+    let mut values = Vec::with_capacity(ALPHA_MAP_SIZE);
+    let mut offset = 0;
+    while values.len() < ALPHA_MAP_SIZE && offset < data.len() {
+        let control = data[offset];
+        let fill = control & 0x80 != 0;
+        let count = (control & 0x7F) as usize;
+        offset += 1;
+        if fill {
+            let value = *data.get(offset).context("Compressed alpha map ended in the middle of a fill")?;
+            values.resize(values.len() + count, value);
+            offset += 1;
+        } else {
+            let copied = data.get(offset..offset + count).context("Compressed alpha map ended in the middle of a copy")?;
+            values.extend_from_slice(copied);
+            offset += count;
+        }
+    }
+    values.resize(ALPHA_MAP_SIZE, 0);
+    Ok(values)
+}
+
+/// Expands the 4 bit per value format, lower nibble first.
+/// With `fix_alpha_map` (the MCNK doesn't have the do not fix flag) the last row and column
+/// are overwritten with copies of their neighbours, otherwise they are kept as stored.
+pub fn expand_alpha_map_2048(data: &[u8], fix_alpha_map: bool) -> R<Vec<u8>> {
+    if data.len() < ALPHA_MAP_SIZE / 2 {
+        return err(format!("4 bit alpha map is {} bytes long, expected {}", data.len(), ALPHA_MAP_SIZE / 2));
+    }
+    let mut values: Vec<u8> = data[..ALPHA_MAP_SIZE / 2]
+        .iter()
+        .flat_map(|b| vec![(b & 0x0F) * 17, (b >> 4) * 17])
+        .collect();
+    if fix_alpha_map {
+        for y in 0..64 {
+            values[y * 64 + 63] = values[y * 64 + 62];
+        }
+        for x in 0..64 {
+            values[63 * 64 + x] = values[62 * 64 + x];
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+#[test]
+fn mcnk_decompress_alpha_map() {
+    // fill 4 x 0xFF, copy 2 bytes
+    let values = decompress_alpha_map(&[0x84, 0xFF, 0x02, 0x10, 0x20]).unwrap();
+    assert_eq!(values.len(), ALPHA_MAP_SIZE);
+    assert_eq!(&values[..7], &[0xFF, 0xFF, 0xFF, 0xFF, 0x10, 0x20, 0x00]);
+}
+
+#[cfg(test)]
+#[test]
+fn mcnk_expand_alpha_map_2048() {
+    let mut data = vec![0u8; 2048];
+    data[0] = 0xF1;
+    data[31] = 0x0F;
+    let values = expand_alpha_map_2048(&data, false).unwrap();
+    assert_eq!(&values[..2], &[17, 255]);
+    assert_eq!(values[62], 255);
+    assert_eq!(values[63], 0);
+
+    let fixed = expand_alpha_map_2048(&data, true).unwrap();
+    assert_eq!(fixed[63], 255);
+}
+
+#[cfg(test)]
+#[test]
+fn mcnk_set_and_remap_area_ids() {
+    let adt = crate::formats::adt::test_utils::build_test_adt();
+    let mut mcnk = crate::formats::adt::AdtFile::from_bytes(&adt).unwrap().mcnk;
+    let rect = McnkRect { min_x: 2, min_y: 0, max_x: 3, max_y: 1 };
+    assert_eq!(mcnk.set_area_id(5000, Some(&rect)), 4);
+    assert_eq!(mcnk.items[18].header.area_id, 5000);
+    assert_eq!(mcnk.items[4].header.area_id, 12);
+
+    let remap: HashMap<u32, u32> = vec![(12, 1519), (5000, 5000)].into_iter().collect();
+    assert_eq!(mcnk.remap_area_ids(&remap), 252);
+    assert_eq!(mcnk.set_area_id(1519, None), 4);
+}
+
+#[cfg(test)]
+#[test]
+fn mcnk_alpha_map_format_follows_the_wdt_flag() {
+    let layer = McnkLayer { texture_id: 1, flags: MCLY_FLAG_USE_ALPHA_MAP, offset_in_mcal: 0, effect_id: 0 };
+    let mcly = ChunkMcly(vec![layer]);
+    let mut data = vec![0x11u8; 2048];
+    data.extend_from_slice(&[0x22; 2048]);
+    let mcal = Chunk::new("MCAL", data);
+
+    let guessed = ChunkMcal::from_chunk(&mcal, &mcly, 0, None).unwrap();
+    assert_eq!(guessed.alpha_maps[0].format, AlphaMapFormat::Uncompressed4096);
+    assert_eq!(guessed.alpha_maps[0].values[0], 0x11);
+    let small = ChunkMcal::from_chunk(&mcal, &mcly, 0, Some(false)).unwrap();
+    assert_eq!(small.alpha_maps[0].format, AlphaMapFormat::Uncompressed2048);
+    assert_eq!(&small.alpha_maps[0].values[..2], &[17, 17]);
+
+    let short = Chunk::new("MCAL", vec![0x11; 2048]);
+    assert!(ChunkMcal::from_chunk(&short, &mcly, 0, Some(true)).is_err());
+    assert_eq!(ChunkMcal::from_chunk(&short, &mcly, 0, None).unwrap().alpha_maps[0].format, AlphaMapFormat::Uncompressed2048);
+}
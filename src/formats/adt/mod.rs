@@ -0,0 +1,127 @@
+pub mod heightmap;
+pub mod liquid;
+pub mod mcnk;
+pub mod paths;
+pub mod placement;
+pub mod relocate;
+pub mod writer;
+#[cfg(test)]
+pub(crate) mod test_utils;
+
+use crate::formats::chunk::*;
+use crate::formats::adt::liquid::ChunkMh2o;
+use crate::formats::adt::mcnk::ChunkMcnk;
+use crate::formats::dbc::dbc::Dbc;
+use crate::formats::dbc::liquid_type::LiquidTypeDbcRow;
+use serde::{Deserialize, Serialize};
+use crate::common::R;
+use std::path::Path;
+
+pub const TILE_SIZE: f32 = 1600.0 / 3.0;
+pub const CHUNK_SIZE: f32 = TILE_SIZE / 16.0;
+pub const UNIT_SIZE: f32 = CHUNK_SIZE / 8.0;
+// world coordinate of the top left corner of tile 0_0
+pub const MAP_ORIGIN: f32 = 32.0 * TILE_SIZE;
+
+/// Tile coordinates from a `<map>_<x>_<y>.adt` file name.
+pub fn get_tile_coords<P: AsRef<Path>>(path: P) -> Option<(u32, u32)> {
+    let stem = path.as_ref().file_stem()?.to_str()?;
+    let mut parts = stem.rsplitn(3, '_');
+    let y = parts.next()?.parse().ok()?;
+    let x = parts.next()?.parse().ok()?;
+    parts.next()?;
+    Some((x, y))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdtFile {
+    pub mver: ChunkMver,
+    pub mhdr: ChunkMhdr,
+    pub mcin: Vec<ChunkMcin>,
+    pub mtex: ChunkMtex,
+    pub mmdx: ChunkMmdx,
+    pub mmid: ChunkMmid,
+    pub mwmo: ChunkMwmo,
+    pub mwid: ChunkMwid,
+    pub mddf: ChunkMddf,
+    pub modf: ChunkModf,
+    pub mh2o: Option<ChunkMh2o>,
+    pub mcnk: ChunkMcnk,
+    // every top level chunk in file order, used to write the file back
+    #[serde(skip)]
+    pub chunks: Vec<Chunk>,
+}
+
+impl AdtFile {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> R<AdtFile> {
+        AdtFile::from_path_with_big_alpha(path, None)
+    }
+
+    /// `big_alpha` is the `adt_has_big_alpha` MPHD flag of the map's WDT, when it is unknown
+    /// the format of the alpha maps is guessed from their size.
+    pub fn from_path_with_big_alpha<P: AsRef<Path>>(path: P, big_alpha: Option<bool>) -> R<AdtFile> {
+        let chunks = Chunk::from_path(path)?;
+        AdtFile::new(chunks, big_alpha)
+    }
+
+    pub fn from_bytes(bytes: &Vec<u8>) -> R<AdtFile> {
+        let chunks = Chunk::from_bytes(bytes)?;
+        AdtFile::new(chunks, None)
+    }
+
+    fn new(chunks: Vec<Chunk>, big_alpha: Option<bool>) -> R<AdtFile> {
+        let mver = chunks.get_mver_chunk()?;
+        let mhdr = chunks.get_mhdr();
+        let mcin = chunks.get_mcin();
+        let mtex = chunks.get_mtex();
+        let mmdx = chunks.get_mmdx();
+        let mmid = chunks.get_mmid();
+        let mwmo = chunks.get_mwmo();
+        let mwid = chunks.get_mwid();
+        let mddf = chunks.get_mddf();
+        let modf = chunks.get_modf().unwrap_or(ChunkModf(vec![]));
+        let mh2o = chunks.get_mh2o()?;
+        let mcnk = chunks.get_mcnk(big_alpha)?;
+        let mut adt = AdtFile {
+            mver,
+            mhdr,
+            mcin,
+            mtex,
+            mmdx,
+            mmid,
+            mwmo,
+            mwid,
+            mddf,
+            modf,
+            mh2o,
+            mcnk,
+            chunks,
+        };
+        adt.resolve_placement_names();
+        Ok(adt)
+    }
+
+    pub fn resolve_liquid_names(&mut self, liquid_types: &Dbc<LiquidTypeDbcRow>) {
+        if let Some(mh2o) = self.mh2o.as_mut() {
+            mh2o.resolve_liquid_names(liquid_types);
+        }
+    }
+}
+
+trait AdtChunkExt {
+    fn get_mh2o(&self) -> R<Option<ChunkMh2o>>;
+    fn get_mcnk(&self, big_alpha: Option<bool>) -> R<ChunkMcnk>;
+}
+
+impl AdtChunkExt for Vec<Chunk> {
+    fn get_mh2o(&self) -> R<Option<ChunkMh2o>> {
+        self.get_chunk_of_type_optionally("MH2O")
+            .map(ChunkMh2o::from_chunk)
+            .transpose()
+    }
+
+    fn get_mcnk(&self, big_alpha: Option<bool>) -> R<ChunkMcnk> {
+        let chunks = self.get_all_chunks_of_type("MCNK");
+        ChunkMcnk::from_chunks(chunks, big_alpha)
+    }
+}
@@ -0,0 +1,83 @@
+use crate::formats::adt::AdtFile;
+use crate::formats::chunk::strings_to_offsets;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdtPathReplacement {
+    pub chunk: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl AdtFile {
+    /// Rewrites the MTEX / MMDX / MWMO paths `replace` returns a new path for.
+    /// Strings keep their index so MCLY, MDDF and MODF stay valid, MMID / MWID get rebuilt.
+    pub fn replace_paths<F: Fn(&str) -> Option<String>>(&mut self, replace: F) -> Vec<AdtPathReplacement> {
+        let mut replacements = Vec::new();
+        replace_strings("MTEX", &mut self.mtex.0, &replace, &mut replacements);
+
+        let old_mmdx_offsets = strings_to_offsets(&self.mmdx.0);
+        if replace_strings("MMDX", &mut self.mmdx.0, &replace, &mut replacements) {
+            self.mmid.0 = remap_offsets(&self.mmid.0, &old_mmdx_offsets, &strings_to_offsets(&self.mmdx.0));
+        }
+
+        let old_mwmo_offsets = strings_to_offsets(&self.mwmo.0);
+        if replace_strings("MWMO", &mut self.mwmo.0, &replace, &mut replacements) {
+            self.mwid.0 = remap_offsets(&self.mwid.0, &old_mwmo_offsets, &strings_to_offsets(&self.mwmo.0));
+        }
+        replacements
+    }
+}
+
+fn replace_strings<F: Fn(&str) -> Option<String>>(
+    chunk: &str,
+    strings: &mut [String],
+    replace: &F,
+    replacements: &mut Vec<AdtPathReplacement>,
+) -> bool {
+    let mut changed = false;
+    for string in strings.iter_mut().filter(|it| !it.is_empty()) {
+        if let Some(new) = replace(string).filter(|new| new != string) {
+            replacements.push(AdtPathReplacement {
+                chunk: chunk.to_string(),
+                old: string.clone(),
+                new: new.clone(),
+            });
+            *string = new;
+            changed = true;
+        }
+    }
+    changed
+}
+
+// offsets that don't point at the start of a string are left alone
+fn remap_offsets(offsets: &[u32], old_string_offsets: &[u32], new_string_offsets: &[u32]) -> Vec<u32> {
+    offsets.iter()
+        .map(|offset| old_string_offsets.iter()
+            .position(|it| it == offset)
+            .map(|index| new_string_offsets[index])
+            .unwrap_or(*offset))
+        .collect()
+}
+
+#[cfg(test)]
+#[test]
+fn adt_replace_paths_rebuilds_offsets() {
+    let original = crate::formats::adt::test_utils::build_test_adt();
+    let mut adt = AdtFile::from_bytes(&original).unwrap();
+    adt.mmdx.0 = vec!["World\\a.m2".to_string(), "World\\b.m2".to_string()];
+    adt.mmid.0 = vec![11, 0];
+
+    let replacements = adt.replace_paths(|path| match path {
+        "World\\a.m2" => Some("World\\Longer\\a.m2".to_string()),
+        "Tileset\\Grass\\grass.blp" => Some("Tileset\\Grass\\grass.blp".to_string()),
+        _ => None,
+    });
+    assert_eq!(replacements.len(), 1);
+    assert_eq!(replacements[0].chunk, "MMDX");
+    assert_eq!(adt.mmid.0, vec![18, 0]);
+
+    let reparsed = AdtFile::from_bytes(&adt.to_bytes().unwrap()).unwrap();
+    assert_eq!(reparsed.mmdx.0, vec!["World\\Longer\\a.m2", "World\\b.m2"]);
+    assert_eq!(reparsed.mmid.0, vec![18, 0]);
+}
@@ -0,0 +1,229 @@
+use crate::common::{R, err};
+use crate::formats::adt::{AdtFile, CHUNK_SIZE, MAP_ORIGIN, TILE_SIZE};
+use crate::formats::chunk::{strings_to_offsets, ChunkMddfItem, ChunkModfItem};
+
+// MDDF scale, 1024 = 1.0
+pub const DOODAD_SCALE_ONE: f32 = 1024.0;
+
+/// Tile containing a MDDF / MODF position, these count up from the map corner with the height in the middle.
+pub fn get_placement_tile(position: &[f32; 3]) -> Option<(u32, u32)> {
+    let x = (position[0] / TILE_SIZE).floor();
+    let y = (position[2] / TILE_SIZE).floor();
+    if (0.0..64.0).contains(&x) && (0.0..64.0).contains(&y) {
+        Some((x as u32, y as u32))
+    } else {
+        None
+    }
+}
+
+/// Tiles the horizontal extents of a MODF entry overlap.
+pub fn get_extents_tiles(extents: &[f32; 6]) -> Vec<(u32, u32)> {
+    let to_tile = |v: f32| (v / TILE_SIZE).floor().clamp(0.0, 63.0) as u32;
+    let mut tiles = Vec::new();
+    for y in to_tile(extents[2])..=to_tile(extents[5]) {
+        for x in to_tile(extents[0])..=to_tile(extents[3]) {
+            tiles.push((x, y));
+        }
+    }
+    tiles
+}
+
+impl AdtFile {
+    /// Tile coordinates from the world position of the first MCNK.
+    pub fn get_tile_coords(&self) -> Option<(u32, u32)> {
+        let position = self.mcnk.items.first()?.header.position;
+        let x = ((MAP_ORIGIN - position[1]) / TILE_SIZE).round();
+        let y = ((MAP_ORIGIN - position[0]) / TILE_SIZE).round();
+        if (0.0..64.0).contains(&x) && (0.0..64.0).contains(&y) {
+            Some((x as u32, y as u32))
+        } else {
+            None
+        }
+    }
+
+    pub fn get_doodad_path(&self, item: &ChunkMddfItem) -> Option<&String> {
+        let offset = self.mmid.0.get(item.mmid_entry as usize)?;
+        let index = strings_to_offsets(&self.mmdx.0).iter().position(|it| it == offset)?;
+        self.mmdx.0.get(index)
+    }
+
+    pub fn get_map_object_path(&self, item: &ChunkModfItem) -> Option<&String> {
+        let offset = self.mwid.0.get(item.mwid_entry as usize)?;
+        let index = strings_to_offsets(&self.mwmo.0).iter().position(|it| it == offset)?;
+        self.mwmo.0.get(index)
+    }
+
+    pub fn resolve_placement_names(&mut self) {
+        let doodad_names: Vec<Option<String>> = self.mddf.0.iter()
+            .map(|it| self.get_doodad_path(it).cloned())
+            .collect();
+        for (item, name) in self.mddf.0.iter_mut().zip(doodad_names) {
+            item.name = name;
+        }
+        let map_object_names: Vec<Option<String>> = self.modf.0.iter()
+            .map(|it| self.get_map_object_path(it).cloned())
+            .collect();
+        for (item, name) in self.modf.0.iter_mut().zip(map_object_names) {
+            item.name = name;
+        }
+    }
+
+    /// Adds a doodad, registering its path in MMDX / MMID when needed
+    /// and referencing it from the MCNK it stands in. Returns its MDDF index.
+    /// Doodads of a neighbouring tile can be repeated here, like the client files do near tile edges.
+    pub fn add_doodad(&mut self, path: &str, mut item: ChunkMddfItem) -> R<usize> {
+        let chunk = self.get_chunk_index_at(item.position[0], item.position[2])?;
+        item.mmid_entry = get_or_add_string(&mut self.mmdx.0, &mut self.mmid.0, path);
+        item.name = Some(path.to_string());
+        self.mddf.0.push(item);
+        let index = self.mddf.0.len() - 1;
+        self.mcnk.items[chunk].mcrf.doodad_refs.push(index as u32);
+        Ok(index)
+    }
+
+    /// Adds a WMO, registering its path in MWMO / MWID when needed
+    /// and referencing it from every MCNK its extents overlap. Returns its MODF index.
+    pub fn add_map_object(&mut self, path: &str, mut item: ChunkModfItem) -> R<usize> {
+        let chunks = self.get_chunk_indices_in(&item.extents)?;
+        item.mwid_entry = get_or_add_string(&mut self.mwmo.0, &mut self.mwid.0, path);
+        item.name = Some(path.to_string());
+        self.modf.0.push(item);
+        let index = self.modf.0.len() - 1;
+        for chunk in chunks {
+            self.mcnk.items[chunk].mcrf.map_obj_refs.push(index as u32);
+        }
+        Ok(index)
+    }
+
+    /// Removes a doodad and shifts the MCRF references to the ones after it.
+    pub fn remove_doodad(&mut self, index: usize) -> ChunkMddfItem {
+        let item = self.mddf.0.remove(index);
+        for mcnk in self.mcnk.items.iter_mut() {
+            remove_ref(&mut mcnk.mcrf.doodad_refs, index as u32);
+        }
+        item
+    }
+
+    pub fn remove_map_object(&mut self, index: usize) -> ChunkModfItem {
+        let item = self.modf.0.remove(index);
+        for mcnk in self.mcnk.items.iter_mut() {
+            remove_ref(&mut mcnk.mcrf.map_obj_refs, index as u32);
+        }
+        item
+    }
+
+    pub fn get_unique_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.mddf.0.iter()
+            .map(|it| it.unique_id)
+            .chain(self.modf.0.iter().map(|it| it.unique_id))
+    }
+
+    /// Doodads standing on a neighbouring tile are referenced from the closest edge chunk.
+    fn get_chunk_index_at(&self, x: f32, z: f32) -> R<usize> {
+        let (tile_x, tile_y) = self.get_tile_coords()
+            .ok_or_else(|| anyhow::anyhow!("Can't tell the tile coordinates of the ADT"))?;
+        let index_x = ((x - tile_x as f32 * TILE_SIZE) / CHUNK_SIZE).floor();
+        let index_y = ((z - tile_y as f32 * TILE_SIZE) / CHUNK_SIZE).floor();
+        if !(-16.0..32.0).contains(&index_x) || !(-16.0..32.0).contains(&index_y) {
+            return err(format!("Position {} {} is not on tile {}_{} or its neighbours", x, z, tile_x, tile_y));
+        }
+        self.get_chunk_index(index_x.clamp(0.0, 15.0) as u32, index_y.clamp(0.0, 15.0) as u32)
+    }
+
+    fn get_chunk_indices_in(&self, extents: &[f32; 6]) -> R<Vec<usize>> {
+        let (tile_x, tile_y) = self.get_tile_coords()
+            .ok_or_else(|| anyhow::anyhow!("Can't tell the tile coordinates of the ADT"))?;
+        let to_index = |v: f32, tile: u32| ((v - tile as f32 * TILE_SIZE) / CHUNK_SIZE).floor();
+        let (min_x, max_x) = (to_index(extents[0], tile_x), to_index(extents[3], tile_x));
+        let (min_y, max_y) = (to_index(extents[2], tile_y), to_index(extents[5], tile_y));
+        if max_x < 0.0 || max_y < 0.0 || min_x > 15.0 || min_y > 15.0 {
+            return err(format!("Extents {:?} don't overlap tile {}_{}", extents, tile_x, tile_y));
+        }
+        let clamp = |v: f32| v.clamp(0.0, 15.0) as u32;
+        let mut indices = Vec::new();
+        for index_y in clamp(min_y)..=clamp(max_y) {
+            for index_x in clamp(min_x)..=clamp(max_x) {
+                indices.push(self.get_chunk_index(index_x, index_y)?);
+            }
+        }
+        Ok(indices)
+    }
+
+    fn get_chunk_index(&self, index_x: u32, index_y: u32) -> R<usize> {
+        self.mcnk.items.iter()
+            .position(|it| it.header.index_x == index_x && it.header.index_y == index_y)
+            .ok_or_else(|| anyhow::anyhow!("Missing MCNK {}_{}", index_x, index_y))
+    }
+}
+
+fn get_or_add_string(strings: &mut Vec<String>, offsets: &mut Vec<u32>, path: &str) -> u32 {
+    let string_offsets = strings_to_offsets(strings);
+    let existing = strings.iter()
+        .position(|it| it.eq_ignore_ascii_case(path))
+        .and_then(|index| offsets.iter().position(|it| *it == string_offsets[index]));
+    if let Some(entry) = existing {
+        return entry as u32;
+    }
+    let offset = strings_to_offsets(strings).last()
+        .map(|last| last + strings.last().unwrap().len() as u32 + 1)
+        .unwrap_or(0);
+    strings.push(path.to_string());
+    offsets.push(offset);
+    (offsets.len() - 1) as u32
+}
+
+fn remove_ref(refs: &mut Vec<u32>, index: u32) {
+    refs.retain(|it| *it != index);
+    refs.iter_mut().filter(|it| **it > index).for_each(|it| *it -= 1);
+}
+
+#[cfg(test)]
+#[test]
+fn adt_add_and_remove_placements() {
+    let original = crate::formats::adt::test_utils::build_test_adt();
+    let mut adt = AdtFile::from_bytes(&original).unwrap();
+    assert_eq!(adt.get_tile_coords(), Some((0, 0)));
+    assert_eq!(adt.mddf.0[0].name.as_deref(), Some("World\\a.m2"));
+
+    let doodad = ChunkMddfItem {
+        mmid_entry: 0,
+        name: None,
+        unique_id: 43,
+        position: [CHUNK_SIZE * 2.5, 10.0, CHUNK_SIZE * 1.5],
+        rotation: [0.0; 3],
+        scale: DOODAD_SCALE_ONE as u16,
+        flags: 0,
+    };
+    assert_eq!(adt.add_doodad("World\\B.m2", doodad.clone()).unwrap(), 1);
+    assert_eq!(adt.add_doodad("world\\b.m2", doodad.clone()).unwrap(), 2);
+    assert_eq!(adt.mmdx.0, vec!["World\\a.m2", "World\\B.m2"]);
+    assert_eq!(adt.mmid.0, vec![0, 11]);
+    assert_eq!(adt.mcnk.items[18].mcrf.doodad_refs, vec![0, 1, 2]);
+
+    let map_object = ChunkModfItem {
+        mwid_entry: 0,
+        name: None,
+        unique_id: 44,
+        position: [CHUNK_SIZE, 0.0, CHUNK_SIZE],
+        rotation: [0.0; 3],
+        extents: [CHUNK_SIZE * 0.5, 0.0, CHUNK_SIZE * 0.5, CHUNK_SIZE * 1.5, 10.0, CHUNK_SIZE * 1.5],
+        flags: 0,
+        doodad_set: 0,
+        name_set: 0,
+        scale: 0,
+    };
+    assert_eq!(adt.add_map_object("World\\wmo\\house.wmo", map_object).unwrap(), 0);
+    let referencing: Vec<usize> = (0..256)
+        .filter(|i| adt.mcnk.items[*i].mcrf.map_obj_refs == vec![0])
+        .collect();
+    assert_eq!(referencing, vec![0, 1, 16, 17]);
+
+    adt.remove_doodad(1);
+    assert_eq!(adt.mcnk.items[18].mcrf.doodad_refs, vec![0, 1]);
+    assert_eq!(adt.get_unique_ids().collect::<Vec<u32>>(), vec![42, 43, 44]);
+
+    let reparsed = AdtFile::from_bytes(&adt.to_bytes().unwrap()).unwrap();
+    assert_eq!(reparsed.mddf.0[1].name.as_deref(), Some("World\\B.m2"));
+    assert_eq!(reparsed.modf.0[0].name.as_deref(), Some("World\\wmo\\house.wmo"));
+    assert_eq!(reparsed.mcnk.items[17].mcrf.map_obj_refs, vec![0]);
+}
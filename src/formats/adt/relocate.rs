@@ -0,0 +1,81 @@
+use crate::common::{R, err};
+use crate::formats::adt::{AdtFile, TILE_SIZE};
+
+const MCSE_EMITTER_SIZE: usize = 28;
+
+impl AdtFile {
+    /// Moves the tile from grid position `from` to `to`.
+    /// MCNK positions and sound emitters use world coordinates, where X runs against the tile y
+    /// and Y against the tile x. MDDF / MODF placements count up from the map corner with the
+    /// height in the middle, so x and z follow the tile x and y.
+    pub fn relocate(&mut self, from: (u32, u32), to: (u32, u32)) -> R<()> {
+        if to.0 >= 64 || to.1 >= 64 {
+            return err(format!("Tile {}_{} is outside of the 64x64 map grid", to.0, to.1));
+        }
+        let shift_x = (to.0 as f32 - from.0 as f32) * TILE_SIZE;
+        let shift_y = (to.1 as f32 - from.1 as f32) * TILE_SIZE;
+
+        for item in self.mcnk.items.iter_mut() {
+            item.header.position[0] -= shift_y;
+            item.header.position[1] -= shift_x;
+
+            if let Some(mcse) = item.mcse.as_mut() {
+                for emitter in mcse.0.iter_mut() {
+                    emitter.position[0] -= shift_y;
+                    emitter.position[1] -= shift_x;
+                }
+            }
+            // MCSE is written from the raw sub chunk
+            if let Some(raw) = item.sub_chunks.iter_mut().find(|c| c.get_id_as_string() == "MCSE") {
+                let n_emitters = (item.header.n_snd_emitters as usize).min(raw.data.len() / MCSE_EMITTER_SIZE);
+                for i in 0..n_emitters {
+                    shift_f32(&mut raw.data, i * MCSE_EMITTER_SIZE + 4, -shift_y);
+                    shift_f32(&mut raw.data, i * MCSE_EMITTER_SIZE + 8, -shift_x);
+                }
+            }
+        }
+
+        for doodad in self.mddf.0.iter_mut() {
+            doodad.position[0] += shift_x;
+            doodad.position[2] += shift_y;
+        }
+        for map_object in self.modf.0.iter_mut() {
+            map_object.position[0] += shift_x;
+            map_object.position[2] += shift_y;
+            map_object.extents[0] += shift_x;
+            map_object.extents[3] += shift_x;
+            map_object.extents[2] += shift_y;
+            map_object.extents[5] += shift_y;
+        }
+        Ok(())
+    }
+}
+
+fn shift_f32(data: &mut [u8], offset: usize, by: f32) {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    let value = f32::from_le_bytes(bytes) + by;
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+#[test]
+fn adt_relocate_shifts_world_coordinates() {
+    use crate::formats::adt::{CHUNK_SIZE, MAP_ORIGIN};
+
+    let original = crate::formats::adt::test_utils::build_test_adt();
+    let mut adt = AdtFile::from_bytes(&original).unwrap();
+    adt.relocate((0, 0), (2, 3)).unwrap();
+    let adt = AdtFile::from_bytes(&adt.to_bytes().unwrap()).unwrap();
+
+    let last = &adt.mcnk.items[255];
+    assert!((last.header.position[0] - (MAP_ORIGIN - 3.0 * TILE_SIZE - 15.0 * CHUNK_SIZE)).abs() < 0.01);
+    assert!((last.header.position[1] - (MAP_ORIGIN - 2.0 * TILE_SIZE - 15.0 * CHUNK_SIZE)).abs() < 0.01);
+    assert_eq!(last.header.position[2], 10.0);
+    assert!((adt.mddf.0[0].position[0] - (17066.666 + 2.0 * TILE_SIZE)).abs() < 0.01);
+    assert!((adt.mddf.0[0].position[2] - (17066.666 + 3.0 * TILE_SIZE)).abs() < 0.01);
+    assert_eq!(adt.mddf.0[0].position[1], 50.5);
+
+    let mut adt = adt;
+    assert!(adt.relocate((2, 3), (64, 0)).is_err());
+}
@@ -0,0 +1,239 @@
+use crate::byte_utils::VecWriteUtils;
+use crate::test_utils::push_reversed_chunk;
+
+/// Lays out a small but complete Wrath style ADT the way the client files do,
+/// including the MCNR padding and the zero sized MCLQ.
+pub fn build_test_adt() -> Vec<u8> {
+    build_adt(false)
+}
+
+/// The same tile with water in MH2O and in the MCLQ of its first chunk, MCCV on every chunk
+/// and unknown chunks, top level and MCNK sub chunks are not in the usual order.
+/// The second chunk has an uncompressed and a compressed alpha map and a sound emitter.
+pub fn build_unusual_test_adt() -> Vec<u8> {
+    build_adt(true)
+}
+
+fn build_adt(unusual: bool) -> Vec<u8> {
+    fn push_chunk(out: &mut Vec<u8>, name: &str, declared_size: usize, data: &[u8]) -> usize {
+        let position = out.len();
+        push_reversed_chunk(out, name, data);
+        out.set_u32(position + 4, declared_size as u32);
+        position
+    }
+
+    let mut out = Vec::new();
+    push_chunk(&mut out, "MVER", 4, &18u32.to_le_bytes());
+    let mhdr = push_chunk(&mut out, "MHDR", 64, &[0u8; 64]);
+    let mcin = push_chunk(&mut out, "MCIN", 4096, &[0u8; 4096]);
+    let mtex = push_chunk(&mut out, "MTEX", 25, b"Tileset\\Grass\\grass.blp\0\0");
+    let mmdx = push_chunk(&mut out, "MMDX", 11, b"World\\a.m2\0");
+    let mmid = push_chunk(&mut out, "MMID", 4, &0u32.to_le_bytes());
+    let mwmo = push_chunk(&mut out, "MWMO", 0, &[]);
+    let mwid = push_chunk(&mut out, "MWID", 0, &[]);
+    let mut mddf = Vec::new();
+    mddf.push_u32(0);
+    mddf.push_u32(42);
+    [17066.666f32, 50.5, 17066.666, 0.0, 90.0, 0.0].iter().for_each(|v| mddf.push_f32(*v));
+    mddf.push_u16(1024);
+    mddf.push_u16(0);
+    let mddf_pos = push_chunk(&mut out, "MDDF", mddf.len(), &mddf);
+    let modf = push_chunk(&mut out, "MODF", 0, &[]);
+    let mut mfbo = 0;
+    if unusual {
+        mfbo = push_chunk(&mut out, "MFBO", 36, &[0u8; 36]);
+        push_chunk(&mut out, "MZZZ", 8, &[0xCD; 8]);
+    }
+    let mh2o_data = if unusual { build_mh2o() } else { vec![0u8; 256 * 12] };
+    let mh2o = push_chunk(&mut out, "MH2O", mh2o_data.len(), &mh2o_data);
+
+    let mut mcnk_positions = Vec::new();
+    for i in 0..256u32 {
+        let mut subs: Vec<(&str, usize, Vec<u8>)> = Vec::new();
+        if unusual {
+            subs.push(("MCCV", 580, vec![0x7F; 580]));
+        }
+        let mut mcvt = Vec::new();
+        (0..145).for_each(|v| mcvt.push_f32(v as f32 * 0.25));
+        subs.push(("MCVT", 580, mcvt));
+        let mut mcnr: Vec<u8> = (0..145).flat_map(|_| vec![0u8, 0, 127]).collect();
+        mcnr.extend_from_slice(&[0xAB; 13]);
+        subs.push(("MCNR", 435, mcnr));
+        let has_alpha_maps = unusual && i == 1;
+        let mut mcly = Vec::new();
+        [0u32, 0, 0, 0xFFFF_FFFF].iter().for_each(|v| mcly.push_u32(*v));
+        let mcal = if has_alpha_maps { build_mcal() } else { vec![] };
+        if has_alpha_maps {
+            [0u32, 0x100, 0, 0].iter().for_each(|v| mcly.push_u32(*v));
+            [0u32, 0x300, 4096, 0].iter().for_each(|v| mcly.push_u32(*v));
+        }
+        let n_layers = mcly.len() / 16;
+        subs.push(("MCLY", mcly.len(), mcly));
+        subs.push(("MCRF", 4, 0u32.to_le_bytes().to_vec()));
+        subs.push(("MCSH", 512, vec![0x0F; 512]));
+        // a river in the first chunk, its MCLQ declares no size
+        let has_river = unusual && i == 0;
+        let mclq = if has_river { build_mclq() } else { vec![] };
+        let mclq_size = mclq.len();
+        let mcal_size = mcal.len();
+        let mcse = if has_alpha_maps { build_mcse() } else { vec![] };
+        let (mcse_size, n_snd_emitters) = (mcse.len(), mcse.len() / 28);
+        if unusual {
+            subs.push(("MCSE", mcse_size, mcse));
+            subs.push(("MCAL", mcal_size, mcal));
+            subs.push(("MCLQ", 0, mclq));
+            subs.push(("MCZZ", 4, vec![0xEF; 4]));
+        } else {
+            subs.push(("MCAL", 0, vec![]));
+            subs.push(("MCLQ", 0, mclq));
+            subs.push(("MCSE", 0, vec![]));
+        }
+
+        let mut offsets = Vec::new();
+        let mut offset = 8 + 128;
+        for (_, _, data) in &subs {
+            offsets.push(offset as u32);
+            offset += 8 + data.len();
+        }
+        let offset_of = |name: &str| subs.iter()
+            .position(|(it, _, _)| *it == name)
+            .map_or(0, |index| offsets[index]);
+
+        let mut header = Vec::new();
+        let mut flags = 0x1;
+        if unusual {
+            flags |= 0x40;
+        }
+        if has_river {
+            flags |= 0x4;
+        }
+        header.push_u32(flags);
+        header.push_u32(i % 16);
+        header.push_u32(i / 16);
+        header.push_u32(n_layers as u32);
+        header.push_u32(1); // n_doodad_refs
+        header.push_u32(offset_of("MCVT"));
+        header.push_u32(offset_of("MCNR"));
+        header.push_u32(offset_of("MCLY"));
+        header.push_u32(offset_of("MCRF"));
+        header.push_u32(offset_of("MCAL"));
+        header.push_u32(8 + mcal_size as u32); // size_mcal
+        header.push_u32(offset_of("MCSH"));
+        header.push_u32(512); // size_mcsh
+        header.push_u32(12); // area_id
+        header.push_u32(0); // n_map_obj_refs
+        header.push_u16(0);
+        header.push_u16(0);
+        (0..8).for_each(|_| header.push_u16(0));
+        header.push_u32(0);
+        header.push_u32(0);
+        header.push_u32(offset_of("MCSE"));
+        header.push_u32(n_snd_emitters as u32);
+        header.push_u32(offset_of("MCLQ"));
+        header.push_u32(8 + mclq_size as u32); // size_mclq
+        let chunk_size = 100.0f32 / 3.0;
+        header.push_f32(17066.666 - (i / 16) as f32 * chunk_size);
+        header.push_f32(17066.666 - (i % 16) as f32 * chunk_size);
+        header.push_f32(10.0);
+        header.push_u32(offset_of("MCCV"));
+        header.push_u32(0);
+        header.push_u32(0);
+
+        let mut data = header;
+        for (name, declared_size, sub_data) in &subs {
+            data.extend(name.bytes().rev());
+            data.push_u32(*declared_size as u32);
+            data.extend_from_slice(sub_data);
+        }
+        let position = push_chunk(&mut out, "MCNK", data.len(), &data);
+        mcnk_positions.push((position, data.len() + 8));
+    }
+    if !unusual {
+        mfbo = push_chunk(&mut out, "MFBO", 36, &[0u8; 36]);
+    }
+
+    let base = mhdr + 8;
+    let mhdr_fields = [
+        (0, 1usize),
+        (4, mcin - base),
+        (8, mtex - base),
+        (12, mmdx - base),
+        (16, mmid - base),
+        (20, mwmo - base),
+        (24, mwid - base),
+        (28, mddf_pos - base),
+        (32, modf - base),
+        (36, mfbo - base),
+        (40, mh2o - base),
+    ];
+    for (field, value) in &mhdr_fields {
+        out.set_u32(base + field, *value as u32);
+    }
+    for (i, (position, size)) in mcnk_positions.iter().enumerate() {
+        out.set_u32(mcin + 8 + i * 16, *position as u32);
+        out.set_u32(mcin + 8 + i * 16 + 4, *size as u32);
+    }
+    out
+}
+
+/// Chunk #0 holds one 1x1 tile of water with heights and depths, plus its attributes.
+fn build_mh2o() -> Vec<u8> {
+    let mut data = vec![0u8; 256 * 12];
+    let instance = data.len() as u32;
+    let vertices = instance + 24;
+    let attributes = vertices + 4 * 4 + 4;
+    data.set_u32(0, instance);
+    data.set_u32(4, 1);
+    data.set_u32(8, attributes);
+    data.push_u16(2); // ocean
+    data.push_u16(0); // height + depth
+    data.push_f32(1.0);
+    data.push_f32(2.0);
+    data.extend_from_slice(&[0, 0, 1, 1]);
+    data.push_u32(0); // every tile exists
+    data.push_u32(vertices);
+    [1.0f32, 1.5, 2.0, 1.5].iter().for_each(|v| data.push_f32(*v));
+    data.extend_from_slice(&[10, 20, 30, 40]);
+    data.push_u32(0xFF);
+    data.push_u32(0);
+    data.push_u32(0x1);
+    data.push_u32(0);
+    data
+}
+
+/// One river instance: heights, 9x9 vertices, 8x8 tiles and the two flow vectors.
+fn build_mclq() -> Vec<u8> {
+    let mut data = Vec::new();
+    data.push_f32(5.0);
+    data.push_f32(6.0);
+    for i in 0..81 {
+        data.extend_from_slice(&[i as u8, 0, 0, 0]);
+        data.push_f32(5.0 + (i % 2) as f32);
+    }
+    data.extend_from_slice(&[0x04; 64]);
+    data.push_u32(0);
+    data.extend_from_slice(&[0u8; 80]);
+    data
+}
+
+/// A 4096 byte gradient followed by a compressed map: 2048 copied values then a fill of 0xFF.
+fn build_mcal() -> Vec<u8> {
+    let mut data: Vec<u8> = (0..4096).map(|i| (i % 64 * 4) as u8).collect();
+    for row in 0..32u8 {
+        data.push(64);
+        data.extend((0..64).map(|x| x * 2 + row));
+    }
+    for _ in 0..16 {
+        data.extend_from_slice(&[0x80 | 127, 0xFF]);
+    }
+    data.extend_from_slice(&[0x80 | 16, 0xFF]);
+    data
+}
+
+/// One sound emitter.
+fn build_mcse() -> Vec<u8> {
+    let mut data = Vec::new();
+    data.push_u32(37);
+    [17050.0f32, 17040.0, 12.0, 5.0, 5.0, 5.0].iter().for_each(|v| data.push_f32(*v));
+    data
+}
@@ -0,0 +1,212 @@
+use crate::byte_utils::VecWriteUtils;
+use crate::common::{R, err};
+use crate::formats::adt::AdtFile;
+use crate::formats::chunk::Chunk;
+use anyhow::Context;
+use std::path::Path;
+
+// (chunk, byte offset of the MHDR field pointing at it)
+const MHDR_OFFSET_FIELDS: &[(&str, usize)] = &[
+    ("MCIN", 4),
+    ("MTEX", 8),
+    ("MMDX", 12),
+    ("MMID", 16),
+    ("MWMO", 20),
+    ("MWID", 24),
+    ("MDDF", 28),
+    ("MODF", 32),
+    ("MFBO", 36),
+    ("MH2O", 40),
+    ("MTXF", 44),
+];
+
+impl AdtFile {
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> R<()> {
+        let path = path.as_ref();
+        let bytes = self.to_bytes()?;
+        std::fs::write(path, bytes)
+            .with_context(|| format!("Failed to write adt file {}", path.display()))
+    }
+
+    /// Serializes the file keeping the original chunk order.
+    /// Chunks with a typed representation are re-encoded from it, anything else is copied as is.
+    pub fn to_bytes(&self) -> R<Vec<u8>> {
+        let n_raw_mcnk = self.chunks.iter()
+            .filter(|c| c.get_id_as_string() == "MCNK")
+            .count();
+        if n_raw_mcnk != self.mcnk.items.len() {
+            return err(format!(
+                "AdtFile#to_bytes: file had {} MCNKs but {} are present now, adding or removing MCNKs is not supported",
+                n_raw_mcnk,
+                self.mcnk.items.len()
+            ));
+        }
+
+        let mut mcnk_items = self.mcnk.items.iter();
+        let mut chunks: Vec<Chunk> = self.chunks.iter()
+            .map(|raw| match raw.get_id_as_string().as_str() {
+                "MVER" => self.mver.to_chunk(),
+                "MTEX" => self.mtex.to_chunk(),
+                "MMDX" => self.mmdx.to_chunk(),
+                "MMID" => self.mmid.to_chunk(),
+                "MWMO" => self.mwmo.to_chunk(),
+                "MWID" => self.mwid.to_chunk(),
+                "MDDF" => self.mddf.to_chunk(),
+                "MODF" => self.modf.to_chunk(),
+                "MCNK" => mcnk_items.next().unwrap().to_chunk(),
+                _ => Chunk {
+                    id: raw.id,
+                    size: raw.size,
+                    data: raw.data.clone(),
+                },
+            })
+            .collect();
+
+        let mut positions = Vec::with_capacity(chunks.len());
+        let mut position = 0;
+        for chunk in &chunks {
+            positions.push(position);
+            position += 8 + chunk.data.len();
+        }
+
+        let position_of = |chunk_type: &str| -> Option<usize> {
+            chunks.iter()
+                .position(|c| c.get_id_as_string() == chunk_type)
+                .map(|index| positions[index])
+        };
+
+        // MHDR offsets are relative to the end of the MHDR chunk header
+        let mhdr_index = chunks.iter()
+            .position(|c| c.get_id_as_string() == "MHDR")
+            .context("AdtFile#to_bytes: missing MHDR chunk")?;
+        let mhdr_base = positions[mhdr_index] + 8;
+        let mut mhdr_data = chunks[mhdr_index].data.clone();
+        mhdr_data.set_u32(0, self.mhdr.flags);
+        for (chunk_type, field) in MHDR_OFFSET_FIELDS {
+            let offset = position_of(chunk_type)
+                .map(|pos| (pos - mhdr_base) as u32)
+                .unwrap_or(0);
+            mhdr_data.set_u32(*field, offset);
+        }
+        chunks[mhdr_index].data = mhdr_data;
+
+        // MCIN offsets are absolute
+        let mcnk_positions: Vec<(usize, usize)> = chunks.iter()
+            .enumerate()
+            .filter(|(_, c)| c.get_id_as_string() == "MCNK")
+            .map(|(index, c)| (positions[index], 8 + c.data.len()))
+            .collect();
+        if let Some(mcin_index) = chunks.iter().position(|c| c.get_id_as_string() == "MCIN") {
+            let mut mcin_data = Vec::with_capacity(self.mcin.len() * 16);
+            for (i, entry) in self.mcin.iter().enumerate() {
+                let (offs_mcnk, size) = mcnk_positions.get(i).cloned().unwrap_or((0, 0));
+                mcin_data.push_u32(offs_mcnk as u32);
+                mcin_data.push_u32(size as u32);
+                mcin_data.push_u32(entry.flags);
+                mcin_data.push_u32(entry.async_id);
+            }
+            chunks[mcin_index] = Chunk::new("MCIN", mcin_data);
+        }
+
+        let mut bytes = Vec::with_capacity(position);
+        for chunk in &chunks {
+            chunk.write_to(&mut bytes);
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn adt_round_trip_is_byte_identical() {
+    let original = crate::formats::adt::test_utils::build_test_adt();
+    let adt = AdtFile::from_bytes(&original).unwrap();
+    assert_eq!(adt.mcnk.items.len(), 256);
+    let written = adt.to_bytes().unwrap();
+    assert_eq!(written.len(), original.len());
+    assert!(written == original, "parse-then-write changed the file");
+}
+
+#[cfg(test)]
+#[test]
+fn adt_round_trip_keeps_offsets_consistent_after_edits() {
+    let original = crate::formats::adt::test_utils::build_test_adt();
+    let mut adt = AdtFile::from_bytes(&original).unwrap();
+    adt.mtex.0.push("Tileset\\Longer\\Path\\Than\\Before.blp".to_string());
+    adt.mcnk.items[3].mcrf.doodad_refs.push(7);
+    adt.mcnk.items[3].header.area_id = 5000;
+
+    let written = adt.to_bytes().unwrap();
+    let reparsed = AdtFile::from_bytes(&written).unwrap();
+    assert_eq!(reparsed.mtex.0.last().unwrap(), "Tileset\\Longer\\Path\\Than\\Before.blp");
+    assert_eq!(reparsed.mcnk.items[3].mcrf.doodad_refs, vec![0, 7]);
+    assert_eq!(reparsed.mcnk.items[3].header.n_doodad_refs, 2);
+    assert_eq!(reparsed.mcnk.items[3].header.area_id, 5000);
+
+    for (mcin, mcnk_chunk) in reparsed.mcin.iter().zip(reparsed.chunks.iter().filter(|c| c.get_id_as_string() == "MCNK")) {
+        let at = mcin.offs_mcnk as usize;
+        assert_eq!(&written[at..at + 4], &mcnk_chunk.id);
+        assert_eq!(mcin.size as usize, mcnk_chunk.data.len() + 8);
+    }
+    let mhdr_base = 12 + 8;
+    let mtex_at = mhdr_base + reparsed.mhdr.offs_mtex as usize;
+    assert_eq!(&written[mtex_at..mtex_at + 4], b"XETM");
+}
+
+#[cfg(test)]
+#[test]
+fn adt_round_trip_keeps_liquids_alpha_maps_sound_emitters_and_unknown_chunks() {
+    use crate::formats::adt::mcnk::AlphaMapFormat;
+    let original = crate::formats::adt::test_utils::build_unusual_test_adt();
+    let mut adt = AdtFile::from_bytes(&original).unwrap();
+    let ids: Vec<String> = adt.chunks.iter().take(13).map(|c| c.get_id_as_string()).collect();
+    assert_eq!(&ids[9..], &["MODF", "MFBO", "MZZZ", "MH2O"]);
+    let sub_ids: Vec<String> = adt.mcnk.items[0].sub_chunks.iter().map(|c| c.get_id_as_string()).collect();
+    assert_eq!(sub_ids, vec!["MCCV", "MCVT", "MCNR", "MCLY", "MCRF", "MCSH", "MCSE", "MCAL", "MCLQ", "MCZZ"]);
+    assert_eq!(adt.mh2o.as_ref().unwrap().chunks[0].instances[0].depths, Some(vec![10, 20, 30, 40]));
+    assert_eq!(adt.mcnk.items[0].mclq.as_ref().unwrap().0[0].max_height, 6.0);
+    assert!(adt.mcnk.items[1].mclq.as_ref().unwrap().0.is_empty());
+    let alpha_maps = &adt.mcnk.items[1].mcal.as_ref().unwrap().alpha_maps;
+    let formats: Vec<(usize, AlphaMapFormat)> = alpha_maps.iter().map(|it| (it.layer, it.format)).collect();
+    assert_eq!(formats, vec![(1, AlphaMapFormat::Uncompressed4096), (2, AlphaMapFormat::Compressed)]);
+    assert_eq!(alpha_maps[0].values[65], 4);
+    assert_eq!(alpha_maps[1].values[64 + 3], 7);
+    assert_eq!(alpha_maps[1].values[4095], 0xFF);
+    assert_eq!(adt.mcnk.items[1].mcse.as_ref().unwrap().0[0].sound_entries_advanced_id, 37);
+    assert!(adt.to_bytes().unwrap() == original, "parse-then-write changed the file");
+
+    adt.mtex.0.push("Tileset\\Sand\\sand.blp".to_string());
+    adt.mcnk.items[0].mcrf.doodad_refs.push(0);
+    let written = adt.to_bytes().unwrap();
+    let reparsed = AdtFile::from_bytes(&written).unwrap();
+    assert_eq!(reparsed.mcnk.items[0].mclq.as_ref().unwrap().0[0].vertices.len(), 81);
+    let mcnk_at = reparsed.mcin[0].offs_mcnk as usize;
+    let header = &reparsed.mcnk.items[0].header;
+    for (offset, id) in &[(header.offs_mccv, b"VCCM"), (header.offs_mclq, b"QLCM"), (header.offs_mcse, b"ESCM")] {
+        let at = mcnk_at + *offset as usize;
+        assert_eq!(&written[at..at + 4], *id);
+    }
+    let mhdr_base = 12 + 8;
+    let mh2o_at = mhdr_base + reparsed.mhdr.offs_mh2o as usize;
+    assert_eq!(&written[mh2o_at..mh2o_at + 4], b"O2HM");
+}
+
+#[cfg(test)]
+#[test]
+#[ignore = "needs client ADTs, run with WOW_FILE_TOOLS_TEST_ADT_DIR=<folder> cargo test -- --ignored"]
+fn adt_round_trip_real_files() {
+    let folder = std::env::var("WOW_FILE_TOOLS_TEST_ADT_DIR")
+        .expect("WOW_FILE_TOOLS_TEST_ADT_DIR has to point at a folder of client ADTs");
+    for entry in std::fs::read_dir(folder).unwrap().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let is_adt = path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase() == "adt")
+            .unwrap_or(false);
+        if !is_adt {
+            continue;
+        }
+        let original = std::fs::read(&path).unwrap();
+        let adt = AdtFile::from_bytes(&original).unwrap();
+        assert!(adt.to_bytes().unwrap() == original, "{} did not round trip", path.display());
+    }
+}
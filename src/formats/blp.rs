@@ -0,0 +1,207 @@
+use crate::byte_utils::VecUtils;
+use crate::common::{R, err};
+use crate::formats::png::RgbaImage;
+use anyhow::Context;
+use std::path::Path;
+
+// BLP2 as used by Wrath, only the largest mip level is decoded.
+
+const HEADER_SIZE: usize = 148;
+const PALETTE_SIZE: usize = 256 * 4;
+
+const COMPRESSION_PALETTE: u8 = 1;
+const COMPRESSION_DXT: u8 = 2;
+const COMPRESSION_BGRA: u8 = 3;
+
+const ALPHA_TYPE_DXT1: u8 = 0;
+const ALPHA_TYPE_DXT3: u8 = 1;
+const ALPHA_TYPE_DXT5: u8 = 7;
+
+pub fn read_blp<P: AsRef<Path>>(path: P) -> R<RgbaImage> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).with_context(|| format!("Failed to read blp file {}", path.display()))?;
+    decode_blp(&bytes).with_context(|| format!("Failed to decode blp file {}", path.display()))
+}
+
+pub fn decode_blp(bytes: &[u8]) -> R<RgbaImage> {
+    let bytes = bytes.to_vec();
+    if bytes.len() < HEADER_SIZE || &bytes[..4] != b"BLP2" {
+        return err("Not a BLP2 file".to_string());
+    }
+    let compression = bytes.get_byte(8)?;
+    let alpha_depth = bytes.get_byte(9)?;
+    let alpha_type = bytes.get_byte(10)?;
+    let width = bytes.get_u32(12)?;
+    let height = bytes.get_u32(16)?;
+    let offset = bytes.get_u32(20)? as usize;
+    let size = bytes.get_u32(84)? as usize;
+    let mip = bytes.get(offset..offset + size)
+        .with_context(|| format!("Mip 0 at {} of {} bytes runs past the end of the file", offset, size))?;
+    let n_pixels = (width * height) as usize;
+
+    let pixels = match compression {
+        COMPRESSION_PALETTE => {
+            let palette = bytes.get(HEADER_SIZE..HEADER_SIZE + PALETTE_SIZE).context("Missing palette")?;
+            decode_palette(mip, palette, n_pixels, alpha_depth)?
+        }
+        COMPRESSION_DXT => {
+            let block_size = if alpha_type == ALPHA_TYPE_DXT1 { 8 } else { 16 };
+            let mut pixels = vec![0u8; n_pixels * 4];
+            let (blocks_x, blocks_y) = (div_round_up(width as usize, 4), div_round_up(height as usize, 4));
+            if mip.len() < blocks_x * blocks_y * block_size {
+                return err(format!("Mip 0 is too small for {}x{} DXT blocks", blocks_x, blocks_y));
+            }
+            for block_y in 0..blocks_y {
+                for block_x in 0..blocks_x {
+                    let start = (block_y * blocks_x + block_x) * block_size;
+                    let block = &mip[start..start + block_size];
+                    let texels = match alpha_type {
+                        ALPHA_TYPE_DXT1 => decode_color_block(block, true, alpha_depth > 0),
+                        ALPHA_TYPE_DXT3 => with_alpha(decode_color_block(&block[8..], false, false), &decode_dxt3_alpha(block)),
+                        ALPHA_TYPE_DXT5 => with_alpha(decode_color_block(&block[8..], false, false), &decode_dxt5_alpha(block)),
+                        _ => return err(format!("Unsupported DXT alpha type {}", alpha_type)),
+                    };
+                    for (i, texel) in texels.iter().enumerate() {
+                        let (x, y) = (block_x * 4 + i % 4, block_y * 4 + i / 4);
+                        if x < width as usize && y < height as usize {
+                            let at = (y * width as usize + x) * 4;
+                            pixels[at..at + 4].copy_from_slice(texel);
+                        }
+                    }
+                }
+            }
+            pixels
+        }
+        COMPRESSION_BGRA => {
+            let data = mip.get(..n_pixels * 4).context("Mip 0 is too small")?;
+            let mut pixels = Vec::with_capacity(n_pixels * 4);
+            data.chunks_exact(4).for_each(|px| pixels.extend_from_slice(&[px[2], px[1], px[0], px[3]]));
+            pixels
+        }
+        _ => return err(format!("Unsupported BLP compression {}", compression)),
+    };
+    Ok(RgbaImage { width, height, pixels })
+}
+
+fn decode_palette(mip: &[u8], palette: &[u8], n_pixels: usize, alpha_depth: u8) -> R<Vec<u8>> {
+    let alpha_size = div_round_up(n_pixels * alpha_depth as usize, 8);
+    if mip.len() < n_pixels + alpha_size {
+        return err("Mip 0 is too small".to_string());
+    }
+    let (indices, alpha) = mip.split_at(n_pixels);
+    let mut pixels = Vec::with_capacity(n_pixels * 4);
+    for (i, index) in indices.iter().enumerate() {
+        let color = &palette[*index as usize * 4..*index as usize * 4 + 4];
+        let a = match alpha_depth {
+            1 => if alpha[i / 8] >> (i % 8) & 1 != 0 { 255 } else { 0 },
+            4 => (alpha[i / 2] >> ((i % 2) * 4) & 0xF) * 17,
+            8 => alpha[i],
+            _ => 255,
+        };
+        pixels.extend_from_slice(&[color[2], color[1], color[0], a]);
+    }
+    Ok(pixels)
+}
+
+fn div_round_up(value: usize, by: usize) -> usize {
+    let quotient = value / by;
+    if quotient * by == value { quotient } else { quotient + 1 }
+}
+
+fn rgb565(value: u16) -> [u8; 3] {
+    let r = (value >> 11) & 0x1F;
+    let g = (value >> 5) & 0x3F;
+    let b = value & 0x1F;
+    [(r * 255 / 31) as u8, (g * 255 / 63) as u8, (b * 255 / 31) as u8]
+}
+
+// the 4x4 texels of a DXT color block, row major
+fn decode_color_block(block: &[u8], dxt1: bool, punch_through: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    let mix = |wa: u16, wb: u16, div: u16| -> [u8; 4] {
+        let channel = |i: usize| ((a[i] as u16 * wa + b[i] as u16 * wb) / div) as u8;
+        [channel(0), channel(1), channel(2), 255]
+    };
+    let palette = if !dxt1 || c0 > c1 {
+        [[a[0], a[1], a[2], 255], [b[0], b[1], b[2], 255], mix(2, 1, 3), mix(1, 2, 3)]
+    } else {
+        let transparent = if punch_through { [0, 0, 0, 0] } else { [0, 0, 0, 255] };
+        [[a[0], a[1], a[2], 255], [b[0], b[1], b[2], 255], mix(1, 1, 2), transparent]
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let mut texels = [[0u8; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[(indices >> (i * 2) & 0x3) as usize];
+    }
+    texels
+}
+
+fn decode_dxt3_alpha(block: &[u8]) -> [u8; 16] {
+    let mut alpha = [0u8; 16];
+    for (i, a) in alpha.iter_mut().enumerate() {
+        *a = (block[i / 2] >> ((i % 2) * 4) & 0xF) * 17;
+    }
+    alpha
+}
+
+fn decode_dxt5_alpha(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u16, block[1] as u16);
+    let values: Vec<u8> = (0..8u16)
+        .map(|i| match i {
+            0 => a0,
+            1 => a1,
+            _ if a0 > a1 => (a0 * (8 - i) + a1 * (i - 1)) / 7,
+            6 => 0,
+            7 => 255,
+            _ => (a0 * (6 - i) + a1 * (i - 1)) / 5,
+        } as u8)
+        .collect();
+    let bits = block[2..8].iter().rev().fold(0u64, |acc, b| acc << 8 | *b as u64);
+    let mut alpha = [0u8; 16];
+    for (i, a) in alpha.iter_mut().enumerate() {
+        *a = values[(bits >> (i * 3) & 0x7) as usize];
+    }
+    alpha
+}
+
+fn with_alpha(mut texels: [[u8; 4]; 16], alpha: &[u8; 16]) -> [[u8; 4]; 16] {
+    texels.iter_mut().zip(alpha.iter()).for_each(|(texel, a)| texel[3] = *a);
+    texels
+}
+
+#[cfg(test)]
+#[test]
+fn blp_decodes_dxt1_and_palette() {
+    use crate::byte_utils::VecWriteUtils;
+
+    let build = |compression: u8, alpha_depth: u8, alpha_type: u8, mip: &[u8], palette: &[u8]| {
+        let mut bytes = b"BLP2".to_vec();
+        bytes.push_u32(1);
+        bytes.extend_from_slice(&[compression, alpha_depth, alpha_type, 0]);
+        bytes.push_u32(4);
+        bytes.push_u32(4);
+        bytes.push_u32((HEADER_SIZE + PALETTE_SIZE) as u32);
+        (1..16).for_each(|_| bytes.push_u32(0));
+        bytes.push_u32(mip.len() as u32);
+        (1..16).for_each(|_| bytes.push_u32(0));
+        let mut full_palette = palette.to_vec();
+        full_palette.resize(PALETTE_SIZE, 0);
+        bytes.extend_from_slice(&full_palette);
+        bytes.extend_from_slice(mip);
+        bytes
+    };
+
+    // red and blue endpoints, first row red, the rest blue
+    let dxt1 = build(COMPRESSION_DXT, 0, ALPHA_TYPE_DXT1, &[0x00, 0xF8, 0x1F, 0x00, 0x00, 0x55, 0x55, 0x55], &[]);
+    let image = decode_blp(&dxt1).unwrap();
+    assert_eq!(&image.pixels[..4], &[255, 0, 0, 255]);
+    assert_eq!(&image.pixels[16..20], &[0, 0, 255, 255]);
+
+    let mut mip = vec![1u8; 16];
+    mip.extend_from_slice(&[0x0F; 8]);
+    let palette = build(COMPRESSION_PALETTE, 4, 0, &mip, &[0, 0, 0, 0, 0x10, 0x20, 0x30, 0xFF]);
+    let image = decode_blp(&palette).unwrap();
+    assert_eq!(&image.pixels[..8], &[0x30, 0x20, 0x10, 255, 0x30, 0x20, 0x10, 0]);
+}
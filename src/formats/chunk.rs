@@ -5,7 +5,7 @@ use crate::byte_utils::*;
 use std::convert::TryInto;
 use anyhow::Context;
 use serde::{Serialize, Deserialize};
-use crate::common::{R};
+use crate::common::{R, err};
 use std::path::Path;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -18,6 +18,33 @@ pub struct Chunk {
 impl Chunk {
     pub fn get_id_as_string(&self) -> String { from_utf8(&self.id).unwrap().chars().rev().collect() }
 
+    /// The id as stored, M2 and MDX chunk ids are not reversed.
+    pub fn get_unreversed_id_as_string(&self) -> String {
+        self.id.iter().map(|it| *it as char).collect()
+    }
+
+    /// Creates a chunk from its readable name, ids are stored reversed on disk.
+    pub fn new(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        let id: [u8; 4] = chunk_type.as_bytes()
+            .iter()
+            .rev()
+            .cloned()
+            .collect::<Vec<u8>>()
+            .try_into()
+            .expect("Chunk ids are exactly 4 characters long");
+        Chunk {
+            id,
+            size: data.len() as u32,
+            data,
+        }
+    }
+
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.id);
+        out.push_u32(self.size);
+        out.extend_from_slice(&self.data);
+    }
+
     pub fn from_path<P: AsRef<Path>>(path: P) -> R<Vec<Chunk>> {
         let path = path.as_ref();
         let mut file = File::open(path)
@@ -25,7 +52,11 @@ impl Chunk {
         let file_size = file.metadata()?.len() as usize;
         let mut buffered_file = Vec::with_capacity(file_size);
         file.read_to_end(&mut buffered_file)?;
+        Chunk::from_bytes(&buffered_file)
+            .with_context(|| format!("Could not read chunks of {:?}", path.display()))
+    }
 
+    pub fn from_bytes(buffered_file: &Vec<u8>) -> R<Vec<Chunk>> {
         let mut offset: usize = 0;
         let mut builder: Vec<Chunk> = Vec::new();
         let max_size = buffered_file.len();
@@ -35,8 +66,10 @@ impl Chunk {
                 break;
             }
             let id: [u8; 4] = buffered_file[offset..offset + 4].try_into()?;
-            let size = Chunk::get_chunk_size(&id, &buffered_file, offset)?;
-            let data = buffered_file[offset + 8..offset + 8 + size].to_vec();
+            let size = buffered_file.get_u32(offset + 4)? as usize;
+            let data = buffered_file.get(offset + 8..offset + 8 + size)
+                .with_context(|| format!("Chunk {} at offset {} runs past the end of the file", from_utf8(&id).unwrap_or("????"), offset))?
+                .to_vec();
 
             offset += 8 + size;
 
@@ -53,46 +86,27 @@ impl Chunk {
 
         Ok(builder)
     }
+}
 
-    fn get_chunk_size(chunk_id: &[u8; 4], buffered_file: &Vec<u8>, offset: usize) -> R<usize> {
-        // The MOGP chunk breaks our parsing system
-        // by having an unexpected size provided in the header..
-        // This function adds special casing for a chunk of this type.
-        // If any other chunk happens to have a similar issue, just add
-        // the correct [ChunkFix] entry into the `const` array [#CHUNK_SIZE_FIXES]
-        struct ChunkFix {
-            chunk_id: [u8; 4],
-            chunk_size_correction: usize,
-        }
-
-        const CHUNK_SIZE_FIXES: &[ChunkFix] = &[
-            ChunkFix {
-                chunk_id: [80, 71, 79, 77], // MOGP
-                chunk_size_correction: 68,
-            },
-            ChunkFix {
-                chunk_id: [1, 0, 0, 0], // no clue
-                chunk_size_correction: 40,
-            }
-        ];
-
-        let requires_fix = CHUNK_SIZE_FIXES
-            .iter()
-            .find(|&it| it.chunk_id.ends_with(chunk_id));
-
-        match requires_fix {
-            Some(fix) => {
-                // use correction
-                Ok(fix.chunk_size_correction)
-            }
-            None => {
-
-                // usual size extraction, 99% of the chunks.
-                let size = buffered_file[offset + 4..offset + 8].to_vec().get_u32(0)? as usize;
-                Ok(size)
-            }
-        }
+/// Inverse of `get_null_terminated_strings`, every string gets its own terminator.
+pub fn strings_to_bytes(strings: &[String]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for string in strings {
+        data.push_null_terminated_string(string);
     }
+    data
+}
+
+/// Offsets of each string as laid out by [strings_to_bytes], as used by MMID / MWID.
+pub fn strings_to_offsets(strings: &[String]) -> Vec<u32> {
+    let mut offset = 0;
+    strings.iter()
+        .map(|string| {
+            let current = offset;
+            offset += string.len() as u32 + 1;
+            current
+        })
+        .collect()
 }
 
 pub trait ChunkVecUtils {
@@ -103,6 +117,7 @@ pub trait ChunkVecUtils {
     fn get_mver_chunk(&self) -> R<ChunkMver>;
     fn get_mphd_chunk(&self) -> ChunkMphd;
     fn get_main(&self) -> ChunkMain;
+    fn get_maid(&self) -> Option<ChunkMaid>;
     fn get_modf(&self) -> Option<ChunkModf>;
     fn get_mhdr(&self) -> ChunkMhdr;
     fn get_mcin(&self) -> Vec<ChunkMcin>;
@@ -117,7 +132,6 @@ pub trait ChunkVecUtils {
     fn get_modn(&self) -> ChunkModn;
     fn get_mohd(&self) -> ChunkMohd;
     fn get_molr(&self) -> Option<ChunkMolr>;
-    fn get_mcnk(&self) -> ChunkMcnk;
 }
 
 impl ChunkVecUtils for Vec<Chunk> {
@@ -152,7 +166,9 @@ impl ChunkVecUtils for Vec<Chunk> {
 
     fn get_main(&self) -> ChunkMain { ChunkMain::from_chunk(self.get_chunk_of_type("MAIN")) }
 
-    fn get_modf(&self) -> Option<ChunkModf> { self.get_chunk_of_type_optionally("MODF").and_then(ChunkModf::from_chunk) }
+    fn get_maid(&self) -> Option<ChunkMaid> { self.get_chunk_of_type_optionally("MAID").map(ChunkMaid::from_chunk) }
+
+    fn get_modf(&self) -> Option<ChunkModf> { self.get_chunk_of_type_optionally("MODF").map(ChunkModf::from_chunk) }
 
     fn get_mhdr(&self) -> ChunkMhdr {
         ChunkMhdr::from_chunk(self.get_chunk_of_type("MHDR"))
@@ -184,11 +200,6 @@ impl ChunkVecUtils for Vec<Chunk> {
         self.get_chunk_of_type_optionally("MOLR")
             .map(ChunkMolr::from_chunk)
     }
-
-    fn get_mcnk(&self) -> ChunkMcnk {
-        let chunks = self.get_all_chunks_of_type("MCNK");
-        ChunkMcnk::from_chunks(chunks)
-    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -207,33 +218,120 @@ impl ChunkMver {
             }
         )
     }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(4);
+        data.push_u32(self.map_version);
+        Chunk::new("MVER", data)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChunkMphd {
-    pub flags: u32,
+    pub flags: MphdFlags,
+    // lgtFileDataID etc. from Cataclysm on
     pub something: u32,
-    pub unused: [u8; 6],
+    pub unused: [u32; 6],
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MphdFlags {
+    // 0x1, the map is a single WMO placed by the WDT MODF
+    pub uses_global_map_obj: bool,
+    // 0x2, ADTs have MCCV
+    pub adt_has_vertex_shading: bool,
+    // 0x4, 4096 byte MCAL layers instead of 2048
+    pub adt_has_big_alpha: bool,
+    // 0x8
+    pub adt_has_doodad_refs_sorted_by_size_cat: bool,
+    // 0x10, ADTs have MCLV
+    pub adt_has_lighting_vertices: bool,
+    // 0x20
+    pub adt_has_upside_down_ground: bool,
+    // 0x40
+    pub unk_firelands: bool,
+    // 0x80, ADTs have MTXP
+    pub adt_has_height_texturing: bool,
+    // 0x100
+    pub unk_load_lod: bool,
+    // 0x200
+    pub has_maid: bool,
+    // bits not covered above
+    pub other: u32,
+}
+
+const MPHD_KNOWN_FLAGS: u32 = 0x3ff;
+
+impl MphdFlags {
+    pub fn from_u32(flags: u32) -> MphdFlags {
+        MphdFlags {
+            uses_global_map_obj: flags & 0x1 != 0,
+            adt_has_vertex_shading: flags & 0x2 != 0,
+            adt_has_big_alpha: flags & 0x4 != 0,
+            adt_has_doodad_refs_sorted_by_size_cat: flags & 0x8 != 0,
+            adt_has_lighting_vertices: flags & 0x10 != 0,
+            adt_has_upside_down_ground: flags & 0x20 != 0,
+            unk_firelands: flags & 0x40 != 0,
+            adt_has_height_texturing: flags & 0x80 != 0,
+            unk_load_lod: flags & 0x100 != 0,
+            has_maid: flags & 0x200 != 0,
+            other: flags & !MPHD_KNOWN_FLAGS,
+        }
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        [
+            self.uses_global_map_obj,
+            self.adt_has_vertex_shading,
+            self.adt_has_big_alpha,
+            self.adt_has_doodad_refs_sorted_by_size_cat,
+            self.adt_has_lighting_vertices,
+            self.adt_has_upside_down_ground,
+            self.unk_firelands,
+            self.adt_has_height_texturing,
+            self.unk_load_lod,
+            self.has_maid,
+        ].iter()
+            .enumerate()
+            .filter(|(_, set)| **set)
+            .fold(self.other & !MPHD_KNOWN_FLAGS, |flags, (bit, _)| flags | 1 << bit)
+    }
 }
 
 impl ChunkMphd {
     pub fn from_chunk(c: &Chunk) -> ChunkMphd {
         assert_eq!(c.get_id_as_string(), "MPHD");
         assert_eq!(c.size, 32);
+        let mut unused = [0; 6];
+        for (i, value) in unused.iter_mut().enumerate() {
+            *value = c.data.get_u32(8 + i * 4).unwrap();
+        }
         ChunkMphd {
-            flags: c.data.get_u32(0).unwrap(),
+            flags: MphdFlags::from_u32(c.data.get_u32(0).unwrap()),
             something: c.data.get_u32(4).unwrap(),
-            unused: c.data[8..14].try_into().unwrap(),
+            unused,
         }
     }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(32);
+        data.push_u32(self.flags.to_u32());
+        data.push_u32(self.something);
+        self.unused.iter().for_each(|v| data.push_u32(*v));
+        Chunk::new("MPHD", data)
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+pub const MAIN_FLAG_HAS_ADT: u32 = 0x1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "ChunkMainGrid", from = "ChunkMainGrid")]
 pub struct ChunkMain(pub Vec<ChunkMainItem>);
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ChunkMainItem {
     pub flags: u32,
+    // runtime pointer, always 0 on disk
     pub area: u32,
 }
 
@@ -252,62 +350,218 @@ impl ChunkMain {
 
         ChunkMain(items)
     }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(32768);
+        for item in &self.0 {
+            data.push_u32(item.flags);
+            data.push_u32(item.area);
+        }
+        Chunk::new("MAIN", data)
+    }
+
+    pub fn has_adt(&self, x: u32, y: u32) -> bool {
+        x < 64 && y < 64 && self.0[(y * 64 + x) as usize].flags & MAIN_FLAG_HAS_ADT != 0
+    }
+
+    /// Flags or clears the ADT of tile (x, y), the other MAIN flags are kept.
+    pub fn set_has_adt(&mut self, x: u32, y: u32, has_adt: bool) -> R<()> {
+        if x >= 64 || y >= 64 {
+            return err(format!("Tile {}_{} is outside of the 64x64 map grid", x, y));
+        }
+        let item = &mut self.0[(y * 64 + x) as usize];
+        if has_adt {
+            item.flags |= MAIN_FLAG_HAS_ADT;
+        } else {
+            item.flags &= !MAIN_FLAG_HAS_ADT;
+        }
+        Ok(())
+    }
+
+    /// (x, y) of every tile with an ADT, row by row.
+    pub fn get_tiles(&self) -> Vec<(u32, u32)> {
+        self.0.iter()
+            .enumerate()
+            .filter(|(_, item)| item.flags & MAIN_FLAG_HAS_ADT != 0)
+            .map(|(index, _)| (index as u32 % 64, index as u32 / 64))
+            .collect()
+    }
+}
+
+/// How MAIN is viewed: a 64x64 grid of `#` for tiles with an ADT and `.` for the others,
+/// one row per tile y, followed by the flagged tiles.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMainGrid {
+    pub grid: Vec<String>,
+    pub tiles: Vec<ChunkMainTile>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ChunkModf {
-    id: u32,
-    dynamic_id: u32,
-    pos: [f32; 3],
-    orientation: [f32; 3],
-    bounding_box: [f32; 6],
-    flags: u16,
-    doodad_set_index: u16,
-    name_set: u16,
-    padding: u16,
+pub struct ChunkMainTile {
+    pub x: u32,
+    pub y: u32,
+    pub has_adt: bool,
+    pub flags: u32,
+}
+
+impl From<ChunkMain> for ChunkMainGrid {
+    fn from(main: ChunkMain) -> Self {
+        let grid = main.0.chunks(64)
+            .map(|row| row.iter()
+                .map(|it| if it.flags & MAIN_FLAG_HAS_ADT != 0 { '#' } else { '.' })
+                .collect())
+            .collect();
+        let tiles = main.0.iter()
+            .enumerate()
+            .filter(|(_, item)| item.flags != 0)
+            .map(|(index, item)| ChunkMainTile {
+                x: index as u32 % 64,
+                y: index as u32 / 64,
+                has_adt: item.flags & MAIN_FLAG_HAS_ADT != 0,
+                flags: item.flags,
+            })
+            .collect();
+        ChunkMainGrid { grid, tiles }
+    }
 }
 
+impl From<ChunkMainGrid> for ChunkMain {
+    fn from(grid: ChunkMainGrid) -> Self {
+        let mut items = vec![ChunkMainItem::default(); 4096];
+        for tile in grid.tiles.iter().filter(|it| it.x < 64 && it.y < 64) {
+            items[(tile.y * 64 + tile.x) as usize].flags = tile.flags;
+        }
+        ChunkMain(items)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMaid(pub Vec<ChunkMaidItem>);
+
+/// File data IDs of one tile, only tiles with at least one set are kept.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMaidItem {
+    pub x: u32,
+    pub y: u32,
+    pub root_adt: u32,
+    pub obj0_adt: u32,
+    pub obj1_adt: u32,
+    pub tex0_adt: u32,
+    pub lod_adt: u32,
+    pub map_texture: u32,
+    pub map_texture_n: u32,
+    pub minimap_texture: u32,
+}
+
+impl ChunkMaid {
+    pub fn from_chunk(c: &Chunk) -> ChunkMaid {
+        assert_eq!(c.get_id_as_string(), "MAID");
+        let items = c.data.chunks_exact(32)
+            .enumerate()
+            .filter(|(_, data)| data.iter().any(|b| *b != 0))
+            .map(|(index, data)| {
+                let data = data.to_vec();
+                ChunkMaidItem {
+                    x: index as u32 % 64,
+                    y: index as u32 / 64,
+                    root_adt: data.get_u32(0).unwrap(),
+                    obj0_adt: data.get_u32(4).unwrap(),
+                    obj1_adt: data.get_u32(8).unwrap(),
+                    tex0_adt: data.get_u32(12).unwrap(),
+                    lod_adt: data.get_u32(16).unwrap(),
+                    map_texture: data.get_u32(20).unwrap(),
+                    map_texture_n: data.get_u32(24).unwrap(),
+                    minimap_texture: data.get_u32(28).unwrap(),
+                }
+            })
+            .collect();
+        ChunkMaid(items)
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = vec![0u8; 4096 * 32];
+        for item in self.0.iter().filter(|it| it.x < 64 && it.y < 64) {
+            let mut entry = Vec::with_capacity(32);
+            for value in &[
+                item.root_adt, item.obj0_adt, item.obj1_adt, item.tex0_adt,
+                item.lod_adt, item.map_texture, item.map_texture_n, item.minimap_texture,
+            ] {
+                entry.push_u32(*value);
+            }
+            let offset = (item.y * 64 + item.x) as usize * 32;
+            data[offset..offset + 32].copy_from_slice(&entry);
+        }
+        Chunk::new("MAID", data)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkModfItem {
+    pub mwid_entry: u32,
+    // MWMO path of mwid_entry, filled in by the file owning the MWID
+    #[serde(default)]
+    pub name: Option<String>,
+    pub unique_id: u32,
+    pub position: [f32; 3],
+    pub rotation: [f32; 3],
+    // min xyz then max xyz, same axes as position
+    pub extents: [f32; 6],
+    pub flags: u16,
+    pub doodad_set: u16,
+    pub name_set: u16,
+    // padding before Legion, 1024 = 1.0 since
+    pub scale: u16,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkModf(pub Vec<ChunkModfItem>);
+
 impl ChunkModf {
-    pub fn from_chunk(c: &Chunk) -> Option<ChunkModf> {
+    pub fn from_chunk(c: &Chunk) -> ChunkModf {
         assert_eq!(c.get_id_as_string(), "MODF");
-        assert!([64, 0].contains(&c.size), "MODF size should either be 64 or 0.");
+        assert_eq!(c.size % 64, 0, "MODF size should be a multiple of 64.");
 
-        if c.size == 0 {
-            return None;
-        }
+        let items = c.data.chunks(64).map(|data| {
+            let data = data.to_vec();
+            let mwid_entry = data.get_u32(0).unwrap();
+            let unique_id = data.get_u32(4).unwrap();
+            let position = [data.get_f32(8).unwrap(), data.get_f32(12).unwrap(), data.get_f32(16).unwrap()];
+            let rotation = [data.get_f32(20).unwrap(), data.get_f32(24).unwrap(), data.get_f32(28).unwrap()];
+            let mut extents = [0.0; 6];
+            for (i, extent) in extents.iter_mut().enumerate() {
+                *extent = data.get_f32(32 + i * 4).unwrap();
+            }
+            ChunkModfItem {
+                mwid_entry,
+                name: None,
+                unique_id,
+                position,
+                rotation,
+                extents,
+                flags: data.get_u16(56).unwrap(),
+                doodad_set: data.get_u16(58).unwrap(),
+                name_set: data.get_u16(60).unwrap(),
+                scale: data.get_u16(62).unwrap(),
+            }
+        }).collect();
 
-        let id = c.data.get_u32(0).unwrap();
-        let dynamic_id = c.data.get_u32(4).unwrap();
-        let pos_x = c.data.get_f32(8).unwrap();
-        let pos_y = c.data.get_f32(12).unwrap();
-        let pos_z = c.data.get_f32(16).unwrap();
-        let pos = [pos_x, pos_y, pos_z];
-        let orient_x = c.data.get_f32(20).unwrap();
-        let orient_y = c.data.get_f32(24).unwrap();
-        let orient_z = c.data.get_f32(28).unwrap();
-        let orientation = [orient_x, orient_y, orient_z];
-        let b1 = c.data.get_f32(32).unwrap();
-        let b2 = c.data.get_f32(36).unwrap();
-        let b3 = c.data.get_f32(40).unwrap();
-        let b4 = c.data.get_f32(44).unwrap();
-        let b5 = c.data.get_f32(48).unwrap();
-        let b6 = c.data.get_f32(52).unwrap();
-        let bounding_box = [b1, b2, b3, b4, b5, b6];
-        let flags = c.data.get_u16(56).unwrap();
-        let doodad_set_index = c.data.get_u16(58).unwrap();
-        let name_set = c.data.get_u16(60).unwrap();
-        let padding = c.data.get_u16(62).unwrap();
-        Some(ChunkModf {
-            id,
-            dynamic_id,
-            pos,
-            orientation,
-            bounding_box,
-            flags,
-            doodad_set_index,
-            name_set,
-            padding,
-        })
+        ChunkModf(items)
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 64);
+        for item in &self.0 {
+            data.push_u32(item.mwid_entry);
+            data.push_u32(item.unique_id);
+            item.position.iter().for_each(|v| data.push_f32(*v));
+            item.rotation.iter().for_each(|v| data.push_f32(*v));
+            item.extents.iter().for_each(|v| data.push_f32(*v));
+            data.push_u16(item.flags);
+            data.push_u16(item.doodad_set);
+            data.push_u16(item.name_set);
+            data.push_u16(item.scale);
+        }
+        Chunk::new("MODF", data)
     }
 }
 
@@ -322,6 +576,9 @@ pub struct ChunkMhdr {
     pub offs_mwid: u32,
     pub offs_mddf: u32,
     pub offs_modf: u32,
+    pub offs_mfbo: u32,
+    pub offs_mh2o: u32,
+    pub offs_mtxf: u32,
 }
 
 impl ChunkMhdr {
@@ -338,6 +595,9 @@ impl ChunkMhdr {
             offs_mwid: c.data.get_u32(24).unwrap(),
             offs_mddf: c.data.get_u32(28).unwrap(),
             offs_modf: c.data.get_u32(32).unwrap(),
+            offs_mfbo: c.data.get_u32(36).unwrap(),
+            offs_mh2o: c.data.get_u32(40).unwrap(),
+            offs_mtxf: c.data.get_u32(44).unwrap(),
         }
     }
 }
@@ -377,6 +637,10 @@ impl ChunkMtex {
         assert_eq!(c.get_id_as_string(), "MTEX");
         ChunkMtex(c.data.get_null_terminated_strings().unwrap())
     }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new("MTEX", strings_to_bytes(&self.0))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -387,6 +651,10 @@ impl ChunkMmdx {
         assert_eq!(c.get_id_as_string(), "MMDX");
         ChunkMmdx(c.data.get_null_terminated_strings().unwrap())
     }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new("MMDX", strings_to_bytes(&self.0))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -400,6 +668,14 @@ impl ChunkMmid {
             .collect();
         ChunkMmid(offsets)
     }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 4);
+        for offset in &self.0 {
+            data.push_u32(*offset);
+        }
+        Chunk::new("MMID", data)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -410,6 +686,10 @@ impl ChunkMwmo {
         assert_eq!(c.get_id_as_string(), "MWMO");
         ChunkMwmo(c.data.get_null_terminated_strings().unwrap())
     }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new("MWMO", strings_to_bytes(&self.0))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -424,11 +704,22 @@ impl ChunkMwid {
             .collect();
         ChunkMwid(offsets)
     }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 4);
+        for offset in &self.0 {
+            data.push_u32(*offset);
+        }
+        Chunk::new("MWID", data)
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChunkMddfItem {
     pub mmid_entry: u32,
+    // MMDX path of mmid_entry, filled in by the ADT
+    #[serde(default)]
+    pub name: Option<String>,
     pub unique_id: u32,
     pub position: [f32; 3],
     pub rotation: [f32; 3],
@@ -460,6 +751,7 @@ impl ChunkMddf {
             let flags = data.get_u16(34).unwrap();
             ChunkMddfItem {
                 mmid_entry,
+                name: None,
                 unique_id,
                 position,
                 rotation,
@@ -470,6 +762,19 @@ impl ChunkMddf {
 
         ChunkMddf(items)
     }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 36);
+        for item in &self.0 {
+            data.push_u32(item.mmid_entry);
+            data.push_u32(item.unique_id);
+            item.position.iter().for_each(|v| data.push_f32(*v));
+            item.rotation.iter().for_each(|v| data.push_f32(*v));
+            data.push_u16(item.scale);
+            data.push_u16(item.flags);
+        }
+        Chunk::new("MDDF", data)
+    }
 }
 
 
@@ -532,6 +837,10 @@ pub struct ChunkMohd {
     pub wmo_id: u32,
     pub bounding_box_corner_1: [f32; 3],
     pub bounding_box_corner_2: [f32; 3],
+    #[serde(default)]
+    pub flags: u16,
+    #[serde(default)]
+    pub n_lod: u16,
 }
 
 impl ChunkMohd {
@@ -559,8 +868,31 @@ impl ChunkMohd {
                 let b3 = c.data.get_f32(56).unwrap();
                 [b1, b2, b3]
             },
+            flags: c.data.get_u16(60).unwrap_or(0),
+            n_lod: c.data.get_u16(62).unwrap_or(0),
         }
     }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(64);
+        [
+            self.n_materials,
+            self.n_groups,
+            self.n_ports,
+            self.n_lights,
+            self.n_models,
+            self.n_doodads,
+            self.n_doodad_sets,
+            self.ambient_color,
+            self.wmo_id,
+        ].iter().for_each(|v| data.push_u32(*v));
+        self.bounding_box_corner_1.iter()
+            .chain(self.bounding_box_corner_2.iter())
+            .for_each(|v| data.push_f32(*v));
+        data.push_u16(self.flags);
+        data.push_u16(self.n_lod);
+        Chunk::new("MOHD", data)
+    }
 }
 
 
@@ -578,37 +910,10 @@ impl ChunkMolr {
             .collect();
         ChunkMolr(strings)
     }
-}
-
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ChunkMcnk {
-    pub items: Vec<ChunkMcnkItem>,
-}
-
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ChunkMcnkItem {
-    pub flags: u32,
-    pub area_id: u32,
-}
 
-impl ChunkMcnk {
-    pub fn from_chunks(chunks: Vec<&Chunk>) -> ChunkMcnk {
-        let items = chunks
-            .into_iter()
-            .map(|c| {
-                assert_eq!(c.get_id_as_string(), "MCNK");
-                let flags = c.data.get_u32(0x00).unwrap();
-                let area_id = c.data.get_u32(0x034).unwrap();
-                ChunkMcnkItem {
-                    flags,
-                    area_id,
-                }
-            })
-            .collect();
-        ChunkMcnk {
-            items
-        }
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 2);
+        self.0.iter().for_each(|v| data.push_u16(*v));
+        Chunk::new("MOLR", data)
     }
-}
\ No newline at end of file
+}
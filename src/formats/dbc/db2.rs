@@ -0,0 +1,308 @@
+use crate::byte_utils::*;
+use crate::common::R;
+use crate::formats::dbc::dbc::DbcRowMapper;
+use crate::formats::dbc::item::ItemDb2Row;
+use crate::formats::dbc::item_sparse::ItemSparseDb2Row;
+use crate::formats::dbc::validation::{DbcError, DB2_EXTENDED_HEADER_SIZE, DB2_HEADER_SIZE, validate_db2_header};
+use crate::formats::dbc::DbcFileIteratorRow;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::rc::Rc;
+
+// the extended header (min_id, max_id, locale, copy_table_size) and the id index map
+// only exist for builds newer than this one, Wrath (12340) files have neither
+const DB2_INDEX_MAP_MIN_BUILD: u32 = 12880;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Db2Header {
+    pub magic: [u8; 4],
+    pub record_count: u32,
+    pub field_count: u32,
+    pub record_size: u32,
+    pub string_block_size: u32,
+    pub table_hash: u32,
+    pub build: u32,
+    pub timestamp_last_written: u32,
+    pub min_id: u32,
+    pub max_id: u32,
+    pub locale: u32,
+    pub copy_table_size: u32,
+}
+
+impl Db2Header {
+    pub fn has_extended_header(&self) -> bool {
+        self.build > DB2_INDEX_MAP_MIN_BUILD
+    }
+
+    pub fn get_header_size(&self) -> usize {
+        if self.has_extended_header() {
+            DB2_EXTENDED_HEADER_SIZE
+        } else {
+            DB2_HEADER_SIZE
+        }
+    }
+
+    pub fn has_index_map(&self) -> bool {
+        self.max_id != 0 && self.has_extended_header()
+    }
+
+    /// Size of the id -> row index map + the string length table following the header.
+    pub fn get_index_map_size(&self) -> usize {
+        if self.has_index_map() {
+            let id_span = (self.max_id as usize).saturating_sub(self.min_id as usize) + 1;
+            id_span * (4 + 2)
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Db2CopyTableEntry {
+    pub id: u32,
+    pub source_id: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Db2<T> {
+    pub header: Db2Header,
+    pub index_map: Vec<u32>,
+    pub copy_table: Vec<Db2CopyTableEntry>,
+    pub rows: Vec<T>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Db2File {
+    header: Db2Header,
+    table_name: Rc<String>,
+    file: Rc<Vec<u8>>,
+    index_map: Vec<u32>,
+    copy_table: Vec<Db2CopyTableEntry>,
+}
+
+impl Db2File {
+    pub fn new<P: AsRef<Path>>(path: P) -> R<Db2File> {
+        let path = path.as_ref();
+        let mut f = File::open(path)
+            .with_context(|| format!("Could not open db2 file {}", path.display()))?;
+        let mut db2_content = Vec::new();
+        f.read_to_end(&mut db2_content)?;
+        let table_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        Db2File::from_bytes(table_name, db2_content)
+    }
+
+    pub fn from_bytes(table_name: String, db2_content: Vec<u8>) -> R<Db2File> {
+        let header = get_db2_header(&table_name, &db2_content)?;
+        validate_db2_header(&table_name, &header, db2_content.len())?;
+
+        let index_map = if header.has_index_map() {
+            let id_span = header.get_index_map_size() / 6;
+            (0..id_span)
+                .map(|i| db2_content.get_u32(header.get_header_size() + i * 4))
+                .collect::<R<Vec<u32>>>()?
+        } else {
+            vec![]
+        };
+
+        let copy_table_offset = db2_content.len() - header.copy_table_size as usize;
+        let copy_table = db2_content[copy_table_offset..]
+            .chunks_exact(8)
+            .map(|entry| {
+                let entry = entry.to_vec();
+                Ok(Db2CopyTableEntry {
+                    id: entry.get_u32(0)?,
+                    source_id: entry.get_u32(4)?,
+                })
+            })
+            .collect::<R<Vec<Db2CopyTableEntry>>>()?;
+
+        Ok(Db2File {
+            header,
+            table_name: Rc::new(table_name),
+            file: Rc::new(db2_content),
+            index_map,
+            copy_table,
+        })
+    }
+
+    pub fn get_header(&self) -> &Db2Header {
+        &self.header
+    }
+
+    pub fn get_table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    /// Every stored record followed by the rows produced by the copy table.
+    /// The copied rows share the source record data but report their own id in column 1.
+    pub fn get_rows(&self) -> R<Vec<DbcFileIteratorRow>> {
+        let mut rows: Vec<DbcFileIteratorRow> = (0..self.header.record_count as usize)
+            .map(|row_index| self.get_row(row_index, None))
+            .collect();
+
+        if self.copy_table.is_empty() {
+            return Ok(rows);
+        }
+
+        let mut row_index_by_id = HashMap::with_capacity(rows.len());
+        for row in &rows {
+            row_index_by_id.insert(row.get_number_column(1)?, row.get_row_index());
+        }
+
+        for entry in &self.copy_table {
+            let source_index = row_index_by_id
+                .get(&entry.source_id)
+                .ok_or_else(|| DbcError::CopyTableSourceMissing {
+                    table: self.table_name.to_string(),
+                    id: entry.id,
+                    source_id: entry.source_id,
+                })?;
+            rows.push(self.get_row(*source_index, Some(entry.id)));
+        }
+
+        Ok(rows)
+    }
+
+    fn get_row(&self, row_index: usize, id_override: Option<u32>) -> DbcFileIteratorRow {
+        let records_offset = self.header.get_header_size() + self.header.get_index_map_size();
+        let record_size = self.header.record_size as usize;
+        DbcFileIteratorRow {
+            file_bytes: self.file.clone(),
+            table_name: self.table_name.clone(),
+            row_index,
+            field_count: self.header.field_count,
+            record_size: self.header.record_size,
+            string_block_size: self.header.string_block_size,
+            current_offset: records_offset + row_index * record_size,
+            body_end_offset: records_offset + self.header.record_count as usize * record_size,
+            id_override,
+        }
+    }
+}
+
+fn get_db2_header(table_name: &str, db2_content: &Vec<u8>) -> R<Db2Header> {
+    let truncated = || DbcError::TruncatedHeader {
+        table: table_name.to_string(),
+        file_size: db2_content.len(),
+    };
+    if db2_content.len() < DB2_HEADER_SIZE {
+        return Err(truncated().into());
+    }
+    let mut header = Db2Header {
+        magic: db2_content[0..4].try_into()?,
+        record_count: db2_content.get_u32(4)?,
+        field_count: db2_content.get_u32(8)?,
+        record_size: db2_content.get_u32(12)?,
+        string_block_size: db2_content.get_u32(16)?,
+        table_hash: db2_content.get_u32(20)?,
+        build: db2_content.get_u32(24)?,
+        timestamp_last_written: db2_content.get_u32(28)?,
+        min_id: 0,
+        max_id: 0,
+        locale: 0,
+        copy_table_size: 0,
+    };
+    if header.has_extended_header() {
+        if db2_content.len() < DB2_EXTENDED_HEADER_SIZE {
+            return Err(truncated().into());
+        }
+        header.min_id = db2_content.get_u32(32)?;
+        header.max_id = db2_content.get_u32(36)?;
+        header.locale = db2_content.get_u32(40)?;
+        header.copy_table_size = db2_content.get_u32(44)?;
+    }
+    Ok(header)
+}
+
+fn load_db2<T: DbcRowMapper, P: AsRef<Path>>(path: P) -> R<Db2<T>> {
+    let db2 = Db2File::new(path)?;
+    let rows = db2.get_rows()?;
+    let mut row_builder = Vec::with_capacity(rows.len());
+    for row in &rows {
+        row_builder.push(T::map_dbc_row(row)?);
+    }
+    Ok(Db2 {
+        header: db2.header,
+        index_map: db2.index_map,
+        copy_table: db2.copy_table,
+        rows: row_builder,
+    })
+}
+
+pub fn load_item_db2_from_path<P: AsRef<Path>>(path: P) -> R<Db2<ItemDb2Row>> {
+    load_db2(path)
+}
+
+pub fn load_item_sparse_db2_from_path<P: AsRef<Path>>(path: P) -> R<Db2<ItemSparseDb2Row>> {
+    load_db2(path)
+}
+
+#[cfg(test)]
+#[test]
+fn db2_copy_table_rows() {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"WDB2");
+    // record_count, field_count, record_size, string_block_size
+    for v in &[2u32, 2, 8, 6] {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    // table_hash, build, timestamp, min_id, max_id, locale, copy_table_size
+    for v in &[0u32, 15595, 0, 0, 0, 0, 8] {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    // records: id + string offset
+    for v in &[10u32, 1, 11, 0] {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    bytes.extend_from_slice(b"\0Item\0");
+    // copy table: 20 copies 10
+    for v in &[20u32, 10] {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    let db2 = Db2File::from_bytes("Test.db2".to_string(), bytes).unwrap();
+    let rows = db2.get_rows().unwrap();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0].get_string_column(2).unwrap(), "Item");
+    assert_eq!(rows[1].get_string_column(2).unwrap(), "");
+    assert_eq!(rows[2].get_number_column(1).unwrap(), 20);
+    assert_eq!(rows[2].get_column_raw(1).unwrap(), 20u32.to_le_bytes());
+    assert_eq!(rows[2].get_number_column_signed(1).unwrap(), 20);
+    assert_eq!(rows[2].get_column_bytes_hex(1).unwrap(), "14000000");
+    assert_eq!(rows[0].get_column_raw(1).unwrap(), 10u32.to_le_bytes());
+    assert_eq!(rows[2].get_string_column(2).unwrap(), "Item");
+    assert!(rows[0].get_number_column(3).is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn db2_wrath_header_has_no_extended_fields() {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"WDB2");
+    // record_count, field_count, record_size, string_block_size, table_hash, build, timestamp
+    for v in &[2u32, 2, 8, 6, 0, 12340, 0] {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    // records start right after the 32 byte header
+    for v in &[10u32, 1, 11, 0] {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    bytes.extend_from_slice(b"\0Item\0");
+
+    let db2 = Db2File::from_bytes("Item.db2".to_string(), bytes).unwrap();
+    assert_eq!(db2.get_header().get_header_size(), 32);
+    assert_eq!(db2.get_header().copy_table_size, 0);
+    let rows = db2.get_rows().unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].get_number_column(1).unwrap(), 10);
+    assert_eq!(rows[0].get_string_column(2).unwrap(), "Item");
+    assert_eq!(rows[1].get_number_column(1).unwrap(), 11);
+}
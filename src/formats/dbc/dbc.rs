@@ -7,6 +7,7 @@ use crate::formats::dbc::ground_effect_texture::GroundEffectTextureDbcRow;
 use crate::formats::dbc::light::LightDbcRow;
 use crate::formats::dbc::light_params::LightParamsDbcRow;
 use crate::formats::dbc::light_sky_box::LightSkyBoxDbcRow;
+use crate::formats::dbc::liquid_type::LiquidTypeDbcRow;
 use crate::formats::dbc::loading_screens::LoadingScreenDbcRow;
 use crate::formats::dbc::map::MapDbcRow;
 use crate::formats::dbc::pvp_difficulty::PvpDifficulty;
@@ -39,7 +40,7 @@ where
 
 fn load_dbc<T: DbcRowMapper, P: AsRef<Path>>(path: P) -> R<Dbc<T>> {
     let dbc = DbcFile::new(path)?;
-    let mut row_builder = Vec::with_capacity(dbc.header.field_count as usize);
+    let mut row_builder = Vec::with_capacity(dbc.header.record_count as usize);
     for row in &dbc {
         let record = T::map_dbc_row(&row)?;
         row_builder.push(record);
@@ -74,6 +75,10 @@ pub fn load_light_params_from_path<P: AsRef<Path>>(path: P) -> R<Dbc<LightParams
     load_dbc(path)
 }
 
+pub fn load_liquid_type_from_path<P: AsRef<Path>>(path: P) -> R<Dbc<LiquidTypeDbcRow>> {
+    load_dbc(path)
+}
+
 pub fn load_battle_master_list_from_path<P: AsRef<Path>>(
     path: P,
 ) -> R<Dbc<BattleMasterListDbcRow>> {
@@ -0,0 +1,37 @@
+use crate::common::R;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ItemDb2Row {
+    pub id: u32,
+    pub class_id: u32,
+    pub sub_class_id: u32,
+    pub sound_override_sub_class_id: i32,
+    pub material: i32,
+    pub display_info_id: u32,
+    pub inventory_type: u32,
+    pub sheathe_type: u32,
+}
+
+impl super::dbc::DbcRowMapper for ItemDb2Row {
+    fn map_dbc_row(row: &super::DbcFileIteratorRow) -> R<Self> {
+        let id = row.get_number_column(1)?;
+        let class_id = row.get_number_column(2)?;
+        let sub_class_id = row.get_number_column(3)?;
+        let sound_override_sub_class_id = row.get_number_column_signed(4)?;
+        let material = row.get_number_column_signed(5)?;
+        let display_info_id = row.get_number_column(6)?;
+        let inventory_type = row.get_number_column(7)?;
+        let sheathe_type = row.get_number_column(8)?;
+        Ok(ItemDb2Row {
+            id,
+            class_id,
+            sub_class_id,
+            sound_override_sub_class_id,
+            material,
+            display_info_id,
+            inventory_type,
+            sheathe_type,
+        })
+    }
+}
@@ -0,0 +1,254 @@
+use crate::common::R;
+use serde::{Deserialize, Serialize};
+
+// Item-sparse.db2 as shipped with 4.3.4 (15595), 133 columns.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ItemSparseDb2Row {
+    pub id: u32,
+    pub quality: u32,
+    pub flags: u32,
+    pub flags_2: u32,
+    pub buy_count: u32,
+    pub buy_price: u32,
+    pub sell_price: u32,
+    pub inventory_type: u32,
+    pub allowable_class: i32,
+    pub allowable_race: i32,
+    pub item_level: u32,
+    pub required_level: i32,
+    pub required_skill: u32,
+    pub required_skill_rank: u32,
+    pub required_spell: u32,
+    pub required_reputation_faction: u32,
+    pub required_reputation_rank: u32,
+    pub max_count: i32,
+    pub stackable: i32,
+    pub container_slots: u32,
+    pub stat_type_1: i32,
+    pub stat_type_2: i32,
+    pub stat_type_3: i32,
+    pub stat_type_4: i32,
+    pub stat_type_5: i32,
+    pub stat_type_6: i32,
+    pub stat_type_7: i32,
+    pub stat_type_8: i32,
+    pub stat_type_9: i32,
+    pub stat_type_10: i32,
+    pub stat_value_1: i32,
+    pub stat_value_2: i32,
+    pub stat_value_3: i32,
+    pub stat_value_4: i32,
+    pub stat_value_5: i32,
+    pub stat_value_6: i32,
+    pub stat_value_7: i32,
+    pub stat_value_8: i32,
+    pub stat_value_9: i32,
+    pub stat_value_10: i32,
+    pub scaling_stat_distribution: i32,
+    pub damage_type: u32,
+    pub delay: u32,
+    pub ranged_mod_range: f32,
+    pub spell_id_1: i32,
+    pub spell_id_2: i32,
+    pub spell_id_3: i32,
+    pub spell_id_4: i32,
+    pub spell_id_5: i32,
+    pub spell_trigger_1: i32,
+    pub spell_trigger_2: i32,
+    pub spell_trigger_3: i32,
+    pub spell_trigger_4: i32,
+    pub spell_trigger_5: i32,
+    pub bonding: u32,
+    pub name: String,
+    pub description: String,
+    pub page_text: u32,
+    pub language_id: i32,
+    pub start_quest: u32,
+    pub lock_id: u32,
+    pub material: i32,
+    pub sheathe_type: u32,
+    pub random_property: i32,
+    pub random_suffix: i32,
+    pub item_set: u32,
+    pub area: u32,
+    pub map: u32,
+    pub bag_family: u32,
+    pub totem_category: u32,
+    pub socket_color_1: i32,
+    pub socket_color_2: i32,
+    pub socket_color_3: i32,
+    pub socket_bonus: u32,
+    pub gem_properties: u32,
+    pub armor_damage_modifier: f32,
+    pub duration: u32,
+    pub item_limit_category: u32,
+    pub holiday_id: u32,
+    pub stat_scaling_factor: f32,
+}
+
+impl super::dbc::DbcRowMapper for ItemSparseDb2Row {
+    fn map_dbc_row(row: &super::DbcFileIteratorRow) -> R<Self> {
+        let id = row.get_number_column(1)?;
+        let quality = row.get_number_column(2)?;
+        let flags = row.get_number_column(3)?;
+        let flags_2 = row.get_number_column(4)?;
+        let buy_count = row.get_number_column(7)?;
+        let buy_price = row.get_number_column(8)?;
+        let sell_price = row.get_number_column(9)?;
+        let inventory_type = row.get_number_column(10)?;
+        let allowable_class = row.get_number_column_signed(11)?;
+        let allowable_race = row.get_number_column_signed(12)?;
+        let item_level = row.get_number_column(13)?;
+        let required_level = row.get_number_column_signed(14)?;
+        let required_skill = row.get_number_column(15)?;
+        let required_skill_rank = row.get_number_column(16)?;
+        let required_spell = row.get_number_column(17)?;
+        let required_reputation_faction = row.get_number_column(20)?;
+        let required_reputation_rank = row.get_number_column(21)?;
+        let max_count = row.get_number_column_signed(22)?;
+        let stackable = row.get_number_column_signed(23)?;
+        let container_slots = row.get_number_column(24)?;
+        let stat_type_1 = row.get_number_column_signed(25)?;
+        let stat_type_2 = row.get_number_column_signed(26)?;
+        let stat_type_3 = row.get_number_column_signed(27)?;
+        let stat_type_4 = row.get_number_column_signed(28)?;
+        let stat_type_5 = row.get_number_column_signed(29)?;
+        let stat_type_6 = row.get_number_column_signed(30)?;
+        let stat_type_7 = row.get_number_column_signed(31)?;
+        let stat_type_8 = row.get_number_column_signed(32)?;
+        let stat_type_9 = row.get_number_column_signed(33)?;
+        let stat_type_10 = row.get_number_column_signed(34)?;
+        let stat_value_1 = row.get_number_column_signed(35)?;
+        let stat_value_2 = row.get_number_column_signed(36)?;
+        let stat_value_3 = row.get_number_column_signed(37)?;
+        let stat_value_4 = row.get_number_column_signed(38)?;
+        let stat_value_5 = row.get_number_column_signed(39)?;
+        let stat_value_6 = row.get_number_column_signed(40)?;
+        let stat_value_7 = row.get_number_column_signed(41)?;
+        let stat_value_8 = row.get_number_column_signed(42)?;
+        let stat_value_9 = row.get_number_column_signed(43)?;
+        let stat_value_10 = row.get_number_column_signed(44)?;
+        let scaling_stat_distribution = row.get_number_column_signed(65)?;
+        let damage_type = row.get_number_column(66)?;
+        let delay = row.get_number_column(67)?;
+        let ranged_mod_range = row.get_float_column(68)?;
+        let spell_id_1 = row.get_number_column_signed(69)?;
+        let spell_id_2 = row.get_number_column_signed(70)?;
+        let spell_id_3 = row.get_number_column_signed(71)?;
+        let spell_id_4 = row.get_number_column_signed(72)?;
+        let spell_id_5 = row.get_number_column_signed(73)?;
+        let spell_trigger_1 = row.get_number_column_signed(74)?;
+        let spell_trigger_2 = row.get_number_column_signed(75)?;
+        let spell_trigger_3 = row.get_number_column_signed(76)?;
+        let spell_trigger_4 = row.get_number_column_signed(77)?;
+        let spell_trigger_5 = row.get_number_column_signed(78)?;
+        let bonding = row.get_number_column(99)?;
+        let name = row.get_string_column(100)?;
+        let description = row.get_string_column(104)?;
+        let page_text = row.get_number_column(105)?;
+        let language_id = row.get_number_column_signed(106)?;
+        let start_quest = row.get_number_column(108)?;
+        let lock_id = row.get_number_column(109)?;
+        let material = row.get_number_column_signed(110)?;
+        let sheathe_type = row.get_number_column(111)?;
+        let random_property = row.get_number_column_signed(112)?;
+        let random_suffix = row.get_number_column_signed(113)?;
+        let item_set = row.get_number_column(114)?;
+        let area = row.get_number_column(115)?;
+        let map = row.get_number_column(116)?;
+        let bag_family = row.get_number_column(117)?;
+        let totem_category = row.get_number_column(118)?;
+        let socket_color_1 = row.get_number_column_signed(119)?;
+        let socket_color_2 = row.get_number_column_signed(120)?;
+        let socket_color_3 = row.get_number_column_signed(121)?;
+        let socket_bonus = row.get_number_column(125)?;
+        let gem_properties = row.get_number_column(126)?;
+        let armor_damage_modifier = row.get_float_column(127)?;
+        let duration = row.get_number_column(128)?;
+        let item_limit_category = row.get_number_column(129)?;
+        let holiday_id = row.get_number_column(130)?;
+        let stat_scaling_factor = row.get_float_column(131)?;
+        Ok(ItemSparseDb2Row {
+            id,
+            quality,
+            flags,
+            flags_2,
+            buy_count,
+            buy_price,
+            sell_price,
+            inventory_type,
+            allowable_class,
+            allowable_race,
+            item_level,
+            required_level,
+            required_skill,
+            required_skill_rank,
+            required_spell,
+            required_reputation_faction,
+            required_reputation_rank,
+            max_count,
+            stackable,
+            container_slots,
+            stat_type_1,
+            stat_type_2,
+            stat_type_3,
+            stat_type_4,
+            stat_type_5,
+            stat_type_6,
+            stat_type_7,
+            stat_type_8,
+            stat_type_9,
+            stat_type_10,
+            stat_value_1,
+            stat_value_2,
+            stat_value_3,
+            stat_value_4,
+            stat_value_5,
+            stat_value_6,
+            stat_value_7,
+            stat_value_8,
+            stat_value_9,
+            stat_value_10,
+            scaling_stat_distribution,
+            damage_type,
+            delay,
+            ranged_mod_range,
+            spell_id_1,
+            spell_id_2,
+            spell_id_3,
+            spell_id_4,
+            spell_id_5,
+            spell_trigger_1,
+            spell_trigger_2,
+            spell_trigger_3,
+            spell_trigger_4,
+            spell_trigger_5,
+            bonding,
+            name,
+            description,
+            page_text,
+            language_id,
+            start_quest,
+            lock_id,
+            material,
+            sheathe_type,
+            random_property,
+            random_suffix,
+            item_set,
+            area,
+            map,
+            bag_family,
+            totem_category,
+            socket_color_1,
+            socket_color_2,
+            socket_color_3,
+            socket_bonus,
+            gem_properties,
+            armor_damage_modifier,
+            duration,
+            item_limit_category,
+            holiday_id,
+            stat_scaling_factor,
+        })
+    }
+}
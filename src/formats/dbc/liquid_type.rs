@@ -0,0 +1,76 @@
+use crate::common::R;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LiquidTypeDbcRow {
+    pub id: u32,
+    pub name: String,
+    pub flags: u32,
+    pub sound_bank: u32,
+    pub sound_id: u32,
+    pub spell_id: u32,
+    pub max_darken_depth: f32,
+    pub fog_darken_intensity: f32,
+    pub ambient_darken_intensity: f32,
+    pub dir_darken_intensity: f32,
+    pub light_id: u32,
+    pub particle_scale: f32,
+    pub particle_movement: u32,
+    pub particle_tex_slots: u32,
+    pub material_id: u32,
+    pub texture_1: String,
+    pub texture_2: String,
+    pub texture_3: String,
+    pub texture_4: String,
+    pub texture_5: String,
+    pub texture_6: String,
+}
+
+impl super::dbc::DbcRowMapper for LiquidTypeDbcRow {
+    fn map_dbc_row(row: &super::DbcFileIteratorRow) -> R<Self> {
+        let id = row.get_number_column(1)?;
+        let name = row.get_string_column(2)?;
+        let flags = row.get_number_column(3)?;
+        let sound_bank = row.get_number_column(4)?;
+        let sound_id = row.get_number_column(5)?;
+        let spell_id = row.get_number_column(6)?;
+        let max_darken_depth = row.get_float_column(7)?;
+        let fog_darken_intensity = row.get_float_column(8)?;
+        let ambient_darken_intensity = row.get_float_column(9)?;
+        let dir_darken_intensity = row.get_float_column(10)?;
+        let light_id = row.get_number_column(11)?;
+        let particle_scale = row.get_float_column(12)?;
+        let particle_movement = row.get_number_column(13)?;
+        let particle_tex_slots = row.get_number_column(14)?;
+        let material_id = row.get_number_column(15)?;
+        let texture_1 = row.get_string_column(16)?;
+        let texture_2 = row.get_string_column(17)?;
+        let texture_3 = row.get_string_column(18)?;
+        let texture_4 = row.get_string_column(19)?;
+        let texture_5 = row.get_string_column(20)?;
+        let texture_6 = row.get_string_column(21)?;
+        Ok(LiquidTypeDbcRow {
+            id,
+            name,
+            flags,
+            sound_bank,
+            sound_id,
+            spell_id,
+            max_darken_depth,
+            fog_darken_intensity,
+            ambient_darken_intensity,
+            dir_darken_intensity,
+            light_id,
+            particle_scale,
+            particle_movement,
+            particle_tex_slots,
+            material_id,
+            texture_1,
+            texture_2,
+            texture_3,
+            texture_4,
+            texture_5,
+            texture_6,
+        })
+    }
+}
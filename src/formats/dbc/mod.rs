@@ -19,6 +19,11 @@ pub mod game_object_display_info;
 pub mod spell_visual;
 pub mod talent;
 pub mod talent_tab;
+pub mod liquid_type;
+pub mod db2;
+pub mod item;
+pub mod item_sparse;
+pub mod validation;
 
 use std::clone::Clone;
 use std::fs::File;
@@ -26,8 +31,10 @@ use std::io::Read;
 use std::convert::TryInto;
 use serde::{Serialize, Deserialize};
 use std::rc::Rc;
+use anyhow::Context;
 use crate::byte_utils::*;
 use crate::common::R;
+use crate::formats::dbc::validation::{DbcError, DBC_HEADER_SIZE, read_block_string, validate_header};
 use std::path::Path;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,50 +55,77 @@ pub struct DbcHeader {
 #[derive(Debug, Clone)]
 pub struct DbcFile {
     header: DbcHeader,
+    table_name: Rc<String>,
     file: Rc<Vec<u8>>,
 }
 
 pub struct DbcFileIterator {
     file_bytes: Rc<Vec<u8>>,
-    offset: usize,
-    offset_bump: usize,
-    body_end_offset: usize,
+    table_name: Rc<String>,
+    header: DbcHeader,
+    row_index: usize,
 }
 
 #[derive(Debug)]
 pub struct DbcFileIteratorRow {
     file_bytes: Rc<Vec<u8>>,
+    table_name: Rc<String>,
+    row_index: usize,
+    field_count: u32,
+    record_size: u32,
+    string_block_size: u32,
     current_offset: usize,
     body_end_offset: usize,
+    // rows duplicated through a db2 copy table report their own id
+    id_override: Option<u32>,
 }
 
 impl DbcFileIteratorRow {
     pub fn get_string_column(&self, column: usize) -> R<String> {
-        let bytes = self.file_bytes.clone();
-        let str_location = bytes.get_u32(self.get_col_offset(column))? as usize;
-        Ok(bytes.get_string_null_terminated(self.body_end_offset + str_location)?)
+        let str_location = u32::from_le_bytes(self.get_column_raw(column)?);
+        if str_location >= self.string_block_size {
+            return Err(DbcError::StringOffsetOutOfRange {
+                table: self.table_name.to_string(),
+                row: self.row_index,
+                column,
+                offset: str_location,
+                string_block_size: self.string_block_size,
+            }.into());
+        }
+        let string_block = &self.file_bytes[self.body_end_offset..self.body_end_offset + self.string_block_size as usize];
+        let value = read_block_string(
+            &self.table_name,
+            string_block,
+            str_location,
+            Some(self.row_index),
+            Some(column),
+        )?;
+        Ok(value)
     }
 
     pub fn get_column_bytes_hex(&self, column: usize) -> R<String> {
-        let bytes = self.file_bytes.clone().get_four_bytes(self.get_col_offset(column))?;
-        Ok(hex::encode(bytes))
+        Ok(hex::encode(self.get_column_raw(column)?))
     }
 
+    /// Every getter reads through here, so copied rows report their own id whatever reads it.
     pub fn get_column_raw(&self, column: usize) -> R<[u8; 4]> {
-        self.file_bytes.clone().get_four_bytes(self.get_col_offset(column))
+        let offset = self.get_col_offset(column)?;
+        if let (1, Some(id)) = (column, self.id_override) {
+            return Ok(id.to_le_bytes());
+        }
+        self.file_bytes.get_four_bytes(offset)
     }
 
-
     pub fn get_number_column(&self, column: usize) -> R<u32> {
-        self.file_bytes.get_u32(self.get_col_offset(column))
+        Ok(u32::from_le_bytes(self.get_column_raw(column)?))
     }
 
     pub fn get_number_column_signed(&self, column: usize) -> R<i32> {
-        self.file_bytes.get_i32(self.get_col_offset(column))
+        Ok(i32::from_le_bytes(self.get_column_raw(column)?))
     }
 
     pub fn get_float_column(&self, column: usize) -> R<f32> {
-        self.file_bytes.get_f32(self.get_col_offset(column))
+        Ok(f32::from_le_bytes(self.get_column_raw(column)?))
     }
 
     pub fn get_bool_column(&self, column: usize) -> R<bool> {
@@ -99,44 +133,70 @@ impl DbcFileIteratorRow {
         Ok(res == 1)
     }
 
-    fn get_col_offset(&self, column: usize) -> usize {
-        self.current_offset + (column - 1) * 4
+    pub fn get_row_index(&self) -> usize {
+        self.row_index
+    }
+
+    // columns are 1-indexed, just like in the dbc definitions
+    fn get_col_offset(&self, column: usize) -> Result<usize, DbcError> {
+        if column == 0 || column > self.field_count as usize || column * 4 > self.record_size as usize {
+            return Err(DbcError::ColumnOutOfRange {
+                table: self.table_name.to_string(),
+                row: self.row_index,
+                column,
+                field_count: self.field_count,
+            });
+        }
+        Ok(self.current_offset + (column - 1) * 4)
     }
 }
 
 impl DbcFile {
     pub fn new<P: AsRef<Path>>(path: P) -> R<DbcFile> {
-        let mut f = File::open(path)?;
+        let path = path.as_ref();
+        let mut f = File::open(path)
+            .with_context(|| format!("Could not open dbc file {}", path.display()))?;
         let mut dbc_content = Vec::new();
         f.read_to_end(&mut dbc_content)?;
-        let header = get_dbc_header(&dbc_content)?;
+        let table_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        DbcFile::from_bytes(table_name, dbc_content)
+    }
+
+    pub fn from_bytes(table_name: String, dbc_content: Vec<u8>) -> R<DbcFile> {
+        let header = get_dbc_header(&table_name, &dbc_content)?;
+        validate_header(&table_name, &header, dbc_content.len())?;
         Ok(DbcFile {
             header,
+            table_name: Rc::new(table_name),
             file: Rc::new(dbc_content),
         })
     }
 
-    pub fn get_strings(&self) -> Vec<String> {
-        let grant_offset = 20 + (self.header.record_size * self.header.record_count) as usize;
-        let g: Vec<u8> = self.file
-            .iter()
-            .skip(grant_offset)
-            .map(|it| *it)
-            .collect();
+    pub fn get_header(&self) -> &DbcHeader {
+        &self.header
+    }
+
+    pub fn get_table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    pub fn get_strings(&self) -> R<Vec<String>> {
+        let string_block = &self.file[self.body_end_offset()..];
         let mut strs = Vec::new();
-        let mut from: usize = 0;
-        let mut to: usize = from + 1;
-        for x in &g {
-            if *x == 0 {
-                let sub_string_bytes: Vec<u8> = g[from..(to - 1)].to_vec();
-                let found_string = String::from_utf8(sub_string_bytes)
-                    .unwrap();
-                strs.push(found_string);
-                from = to
-            }
-            to += 1;
+        let mut offset = 0;
+        while offset < string_block.len() {
+            let found_string = read_block_string(&self.table_name, string_block, offset as u32, None, None)?;
+            offset += found_string.len() + 1;
+            strs.push(found_string);
         }
-        strs
+        Ok(strs)
+    }
+
+    fn body_end_offset(&self) -> usize {
+        DBC_HEADER_SIZE + (self.header.record_size * self.header.record_count) as usize
     }
 }
 
@@ -145,13 +205,11 @@ impl IntoIterator for &DbcFile {
     type IntoIter = DbcFileIterator;
 
     fn into_iter(self) -> Self::IntoIter {
-        let body_end_offset = (self.header.record_size * self.header.record_count + 20) as usize;
-
         DbcFileIterator {
             file_bytes: self.file.clone(),
-            offset: 20,
-            offset_bump: self.header.record_size as usize,
-            body_end_offset,
+            table_name: self.table_name.clone(),
+            header: self.header.clone(),
+            row_index: 0,
         }
     }
 }
@@ -160,13 +218,20 @@ impl Iterator for DbcFileIterator {
     type Item = DbcFileIteratorRow;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.offset != self.body_end_offset {
-            let current_offset = self.offset;
-            self.offset += self.offset_bump;
+        if self.row_index < self.header.record_count as usize {
+            let row_index = self.row_index;
+            self.row_index += 1;
+            let record_size = self.header.record_size as usize;
             Some(DbcFileIteratorRow {
                 file_bytes: self.file_bytes.clone(),
-                current_offset,
-                body_end_offset: self.body_end_offset,
+                table_name: self.table_name.clone(),
+                row_index,
+                field_count: self.header.field_count,
+                record_size: self.header.record_size,
+                string_block_size: self.header.string_block_size,
+                current_offset: DBC_HEADER_SIZE + row_index * record_size,
+                body_end_offset: DBC_HEADER_SIZE + self.header.record_count as usize * record_size,
+                id_override: None,
             })
         } else {
             None
@@ -174,8 +239,14 @@ impl Iterator for DbcFileIterator {
     }
 }
 
-fn get_dbc_header(dbc_content: &Vec<u8>) -> R<DbcHeader> {
-    let magic = dbc_content.get_string(0, 4)?.as_bytes().try_into()?;
+fn get_dbc_header(table_name: &str, dbc_content: &Vec<u8>) -> R<DbcHeader> {
+    if dbc_content.len() < DBC_HEADER_SIZE {
+        return Err(DbcError::TruncatedHeader {
+            table: table_name.to_string(),
+            file_size: dbc_content.len(),
+        }.into());
+    }
+    let magic = dbc_content[0..4].try_into()?;
     let record_count = dbc_content.get_u32(4)?;
     let field_count = dbc_content.get_u32(8)?;
     let record_size = dbc_content.get_u32(12)?;
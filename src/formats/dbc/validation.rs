@@ -0,0 +1,273 @@
+use std::fmt::{Display, Formatter};
+use crate::formats::dbc::DbcHeader;
+use crate::formats::dbc::db2::Db2Header;
+
+pub const DBC_MAGIC: &[u8; 4] = b"WDBC";
+pub const DBC_HEADER_SIZE: usize = 20;
+pub const DB2_MAGIC: &[u8; 4] = b"WDB2";
+pub const DB2_HEADER_SIZE: usize = 32;
+// with min_id, max_id, locale and copy_table_size, see Db2Header::get_header_size
+pub const DB2_EXTENDED_HEADER_SIZE: usize = 48;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DbcError {
+    TruncatedHeader {
+        table: String,
+        file_size: usize,
+    },
+    InvalidMagic {
+        table: String,
+        magic: [u8; 4],
+        expected: [u8; 4],
+    },
+    RecordSizeMismatch {
+        table: String,
+        field_count: u32,
+        record_size: u32,
+    },
+    FileSizeMismatch {
+        table: String,
+        expected: usize,
+        actual: usize,
+    },
+    ColumnOutOfRange {
+        table: String,
+        row: usize,
+        column: usize,
+        field_count: u32,
+    },
+    CopyTableSourceMissing {
+        table: String,
+        id: u32,
+        source_id: u32,
+    },
+    StringOffsetOutOfRange {
+        table: String,
+        row: usize,
+        column: usize,
+        offset: u32,
+        string_block_size: u32,
+    },
+    UnterminatedString {
+        table: String,
+        row: Option<usize>,
+        column: Option<usize>,
+        offset: u32,
+    },
+    InvalidUtf8String {
+        table: String,
+        row: Option<usize>,
+        column: Option<usize>,
+        offset: u32,
+    },
+}
+
+impl Display for DbcError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbcError::TruncatedHeader { table, file_size } => write!(
+                f,
+                "{}: file is {} bytes long, too short to contain a header",
+                table, file_size
+            ),
+            DbcError::InvalidMagic { table, magic, expected } => write!(
+                f,
+                "{}: invalid magic `{}`, expected `{}`",
+                table,
+                String::from_utf8_lossy(magic),
+                String::from_utf8_lossy(expected)
+            ),
+            DbcError::RecordSizeMismatch { table, field_count, record_size } => write!(
+                f,
+                "{}: record_size {} doesn't match field_count {} * 4",
+                table, record_size, field_count
+            ),
+            DbcError::FileSizeMismatch { table, expected, actual } => write!(
+                f,
+                "{}: header describes {} bytes but the file is {} bytes long",
+                table, expected, actual
+            ),
+            DbcError::ColumnOutOfRange { table, row, column, field_count } => write!(
+                f,
+                "{}: row {} column {} is out of range, the table has {} columns",
+                table, row, column, field_count
+            ),
+            DbcError::CopyTableSourceMissing { table, id, source_id } => write!(
+                f,
+                "{}: copy table entry {} references missing row {}",
+                table, id, source_id
+            ),
+            DbcError::StringOffsetOutOfRange { table, row, column, offset, string_block_size } => write!(
+                f,
+                "{}: row {} column {} points to string offset {} outside of the {} byte string block",
+                table, row, column, offset, string_block_size
+            ),
+            DbcError::UnterminatedString { table, row, column, offset } => write!(
+                f,
+                "{}: string at offset {}{} is not null terminated",
+                table, offset, describe_location(*row, *column)
+            ),
+            DbcError::InvalidUtf8String { table, row, column, offset } => write!(
+                f,
+                "{}: string at offset {}{} is not valid UTF-8",
+                table, offset, describe_location(*row, *column)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DbcError {}
+
+fn describe_location(row: Option<usize>, column: Option<usize>) -> String {
+    match (row, column) {
+        (Some(row), Some(column)) => format!(" (row {} column {})", row, column),
+        _ => String::new(),
+    }
+}
+
+/// Checks the header against the actual file contents before any row gets read.
+pub fn validate_header(table: &str, header: &DbcHeader, file_size: usize) -> Result<(), DbcError> {
+    if &header.magic != DBC_MAGIC {
+        return Err(DbcError::InvalidMagic {
+            table: table.to_string(),
+            magic: header.magic,
+            expected: *DBC_MAGIC,
+        });
+    }
+
+    if header.field_count as u64 * 4 != header.record_size as u64 {
+        return Err(DbcError::RecordSizeMismatch {
+            table: table.to_string(),
+            field_count: header.field_count,
+            record_size: header.record_size,
+        });
+    }
+
+    let expected = DBC_HEADER_SIZE as u64
+        + header.record_count as u64 * header.record_size as u64
+        + header.string_block_size as u64;
+    if expected != file_size as u64 {
+        return Err(DbcError::FileSizeMismatch {
+            table: table.to_string(),
+            expected: expected as usize,
+            actual: file_size,
+        });
+    }
+
+    Ok(())
+}
+
+/// Same as [validate_header] but for the WDB2 layout, which additionally carries
+/// an optional id index map and a copy table.
+pub fn validate_db2_header(table: &str, header: &Db2Header, file_size: usize) -> Result<(), DbcError> {
+    if &header.magic != DB2_MAGIC {
+        return Err(DbcError::InvalidMagic {
+            table: table.to_string(),
+            magic: header.magic,
+            expected: *DB2_MAGIC,
+        });
+    }
+
+    if header.field_count as u64 * 4 > header.record_size as u64 {
+        return Err(DbcError::RecordSizeMismatch {
+            table: table.to_string(),
+            field_count: header.field_count,
+            record_size: header.record_size,
+        });
+    }
+
+    let expected = header.get_header_size() as u64
+        + header.get_index_map_size() as u64
+        + header.record_count as u64 * header.record_size as u64
+        + header.string_block_size as u64
+        + header.copy_table_size as u64;
+    if expected != file_size as u64 {
+        return Err(DbcError::FileSizeMismatch {
+            table: table.to_string(),
+            expected: expected as usize,
+            actual: file_size,
+        });
+    }
+
+    Ok(())
+}
+
+/// Reads a null terminated string starting at `offset` of the given string block.
+pub fn read_block_string(
+    table: &str,
+    string_block: &[u8],
+    offset: u32,
+    row: Option<usize>,
+    column: Option<usize>,
+) -> Result<String, DbcError> {
+    let start = offset as usize;
+    let tail = &string_block[start.min(string_block.len())..];
+    let end = tail.iter().position(|b| *b == 0).ok_or(DbcError::UnterminatedString {
+        table: table.to_string(),
+        row,
+        column,
+        offset,
+    })?;
+    std::str::from_utf8(&tail[..end])
+        .map(|s| s.to_string())
+        .map_err(|_| DbcError::InvalidUtf8String {
+            table: table.to_string(),
+            row,
+            column,
+            offset,
+        })
+}
+
+#[cfg(test)]
+fn header(record_count: u32, field_count: u32, record_size: u32, string_block_size: u32) -> DbcHeader {
+    DbcHeader {
+        magic: *DBC_MAGIC,
+        record_count,
+        field_count,
+        record_size,
+        string_block_size,
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn dbc_validate_header() {
+    assert_eq!(validate_header("Test.dbc", &header(2, 3, 12, 5), 20 + 24 + 5), Ok(()));
+
+    let mut bad_magic = header(0, 1, 4, 1);
+    bad_magic.magic = *b"WDB2";
+    assert!(matches!(
+        validate_header("Test.dbc", &bad_magic, 21),
+        Err(DbcError::InvalidMagic { .. })
+    ));
+
+    assert!(matches!(
+        validate_header("Test.dbc", &header(2, 3, 13, 5), 20 + 26 + 5),
+        Err(DbcError::RecordSizeMismatch { field_count: 3, record_size: 13, .. })
+    ));
+
+    assert_eq!(
+        validate_header("Test.dbc", &header(2, 3, 12, 5), 20 + 24 + 4),
+        Err(DbcError::FileSizeMismatch {
+            table: "Test.dbc".to_string(),
+            expected: 49,
+            actual: 48,
+        })
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn dbc_read_block_string() {
+    let block = b"\0first\0bad\xff\0open".to_vec();
+    assert_eq!(read_block_string("Test.dbc", &block, 0, None, None), Ok("".to_string()));
+    assert_eq!(read_block_string("Test.dbc", &block, 1, None, None), Ok("first".to_string()));
+    assert!(matches!(
+        read_block_string("Test.dbc", &block, 7, Some(1), Some(2)),
+        Err(DbcError::InvalidUtf8String { row: Some(1), column: Some(2), .. })
+    ));
+    assert!(matches!(
+        read_block_string("Test.dbc", &block, 12, None, None),
+        Err(DbcError::UnterminatedString { .. })
+    ));
+}
@@ -0,0 +1,49 @@
+use crate::formats::m2::array::{M2Reader, SEQUENCE_FLAG_ALIAS, SEQUENCE_FLAG_DATA_IN_M2};
+use crate::formats::m2::types::M2Sequence;
+use crate::formats::m2::M2File;
+use crate::common::R;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+/// `<model><id>-<variation>.anim` next to the model, where Wrath keeps the keys of most sequences.
+pub fn get_anim_path(m2_path: &Path, sequence: &M2Sequence) -> PathBuf {
+    let stem = m2_path.file_stem().map(|it| it.to_string_lossy().to_string()).unwrap_or_default();
+    m2_path.with_file_name(format!("{}{:04}-{:02}.anim", stem, sequence.id, sequence.variation_index))
+}
+
+impl M2File {
+    /// Indices of the sequences with keys in an .anim file, aliases have no keys at all.
+    pub fn get_external_sequences(&self) -> Vec<usize> {
+        self.sequences.iter()
+            .enumerate()
+            .filter(|(_, it)| it.flags & (SEQUENCE_FLAG_DATA_IN_M2 | SEQUENCE_FLAG_ALIAS) == 0)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Reads the bone keys of every external sequence from its .anim file,
+    /// returns the files that could not be read and why.
+    pub fn load_anims<P: AsRef<Path>>(&mut self, m2_path: P) -> Vec<(PathBuf, String)> {
+        let mut failed = Vec::new();
+        for sequence in self.get_external_sequences() {
+            let path = get_anim_path(m2_path.as_ref(), &self.sequences[sequence]);
+            if let Err(e) = self.load_anim(sequence, &path) {
+                failed.push((path, format!("{:#}", e)));
+            }
+        }
+        failed
+    }
+
+    fn load_anim(&mut self, sequence: usize, path: &Path) -> R<()> {
+        let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        // the v264 .anim is the raw key data, the offsets of the M2 point into it
+        let r = M2Reader { bytes: &bytes, external_sequences: Vec::new() };
+        for (i, bone) in self.bones.iter_mut().enumerate() {
+            bone.translation.read_external_keys(sequence, &r)
+                .and_then(|_| bone.rotation.read_external_keys(sequence, &r))
+                .and_then(|_| bone.scale.read_external_keys(sequence, &r))
+                .with_context(|| format!("Failed to read the keys of bone {}", i))?;
+        }
+        Ok(())
+    }
+}
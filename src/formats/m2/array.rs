@@ -0,0 +1,395 @@
+use crate::byte_utils::{VecUtils, VecWriteUtils};
+use crate::common::{R, err};
+use serde::{Deserialize, Serialize};
+
+// M2SequenceFlags, set when the keys of the sequence are stored in the .m2 rather than a .anim file
+pub const SEQUENCE_FLAG_DATA_IN_M2: u32 = 0x20;
+// the sequence has no keys of its own and plays `alias_next`
+pub const SEQUENCE_FLAG_ALIAS: u32 = 0x40;
+
+/// Resolves offsets against the whole file, the way every M2Array of the header does.
+pub struct M2Reader<'a> {
+    pub bytes: &'a Vec<u8>,
+    // per sequence, true when its animation keys live in a .anim file
+    pub external_sequences: Vec<bool>,
+}
+
+/// Lays out a file again: the header, then the elements of every M2Array 16 aligned.
+pub struct M2Writer {
+    pub data: Vec<u8>,
+    // same as the reader, the lists of these sequences keep their .anim offsets
+    pub external_sequences: Vec<bool>,
+}
+
+/// A record of a fixed size an M2Array can point at.
+pub trait M2Element: Sized {
+    const SIZE: usize;
+    fn read(r: &M2Reader, offset: usize) -> R<Self>;
+    /// Appends the record to `out`, what it points at goes to the writer.
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>);
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2Array<T> {
+    pub size: u32,
+    pub offset: u32,
+    pub elements: Vec<T>,
+}
+
+/// Animated value, one list of timestamps and values per sequence,
+/// or per global sequence when `global_sequence` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2Track<T> {
+    pub interpolation_type: u16,
+    // -1 when the track follows the sequences
+    pub global_sequence: i16,
+    pub timestamps: Vec<M2Array<u32>>,
+    pub values: Vec<M2Array<T>>,
+}
+
+/// Keys of the hermite and bezier interpolated camera tracks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2SplineKey<T> {
+    pub value: T,
+    pub in_tan: T,
+    pub out_tan: T,
+}
+
+impl<'a> M2Reader<'a> {
+    fn is_external(&self, sequence: usize) -> bool {
+        self.external_sequences.get(sequence).cloned().unwrap_or(false)
+    }
+}
+
+impl M2Writer {
+    pub fn new(header_size: usize, external_sequences: Vec<bool>) -> M2Writer {
+        M2Writer { data: vec![0; header_size], external_sequences }
+    }
+
+    /// Writes the elements and appends the size and offset of their M2Array to `out`.
+    pub fn write_array<T: M2Element>(&mut self, elements: &[T], out: &mut Vec<u8>) {
+        let mut records = Vec::with_capacity(elements.len() * T::SIZE);
+        for element in elements {
+            element.write(self, &mut records);
+        }
+        self.write_raw(elements.len(), &records, out);
+    }
+
+    /// M2Array<char>, null terminated.
+    pub fn write_string(&mut self, value: &str, out: &mut Vec<u8>) {
+        if value.is_empty() {
+            self.write_raw(0, &[], out);
+            return;
+        }
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+        self.write_raw(bytes.len(), &bytes, out);
+    }
+
+    // empty arrays point at 0
+    fn write_raw(&mut self, count: usize, bytes: &[u8], out: &mut Vec<u8>) {
+        if count == 0 {
+            out.push_u32(0);
+            out.push_u32(0);
+            return;
+        }
+        self.data.resize(self.data.len() + (16 - self.data.len() % 16) % 16, 0);
+        out.push_u32(count as u32);
+        out.push_u32(self.data.len() as u32);
+        self.data.extend_from_slice(bytes);
+    }
+}
+
+impl<T: M2Element> M2Array<T> {
+    pub fn read(r: &M2Reader, offset: usize) -> R<M2Array<T>> {
+        let size = r.bytes.get_u32(offset)?;
+        let array_offset = r.bytes.get_u32(offset + 4)?;
+        M2Array::read_elements(r, size, array_offset)
+    }
+
+    pub fn read_elements(r: &M2Reader, size: u32, offset: u32) -> R<M2Array<T>> {
+        let end = offset as u64 + size as u64 * T::SIZE as u64;
+        if size > 0 && end > r.bytes.len() as u64 {
+            return err(format!(
+                "M2Array of {} elements of {} bytes at {} runs past the end of the file ({} bytes)",
+                size,
+                T::SIZE,
+                offset,
+                r.bytes.len()
+            ));
+        }
+        let elements = (0..size as usize)
+            .map(|i| T::read(r, offset as usize + i * T::SIZE))
+            .collect::<R<Vec<T>>>()?;
+        Ok(M2Array { size, offset, elements })
+    }
+}
+
+impl<T: M2Element> M2Element for M2Array<T> {
+    const SIZE: usize = 8;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        M2Array::read(r, offset)
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        w.write_array(&self.elements, out);
+    }
+}
+
+/// Reads an M2Array<char>, up to the first null.
+pub fn read_string(r: &M2Reader, offset: usize) -> R<String> {
+    let chars: M2Array<u8> = M2Array::read(r, offset)?;
+    Ok(chars.elements.iter()
+        .take_while(|it| **it != 0)
+        .map(|it| *it as char)
+        .collect())
+}
+
+// the per sequence lists of keys stored in .anim files are only kept as offsets
+fn read_track_lists<T: M2Element>(r: &M2Reader, offset: usize, global: bool) -> R<Vec<M2Array<T>>> {
+    let lists: M2Array<(u32, u32)> = M2Array::read(r, offset)?;
+    lists.elements.into_iter()
+        .enumerate()
+        .map(|(i, (size, offset))| {
+            if !global && r.is_external(i) {
+                Ok(M2Array { size, offset, elements: Vec::new() })
+            } else {
+                M2Array::read_elements(r, size, offset)
+            }
+        })
+        .collect()
+}
+
+fn write_track_lists<T: M2Element>(w: &mut M2Writer, lists: &[M2Array<T>], global: bool, out: &mut Vec<u8>) {
+    let mut headers = Vec::with_capacity(lists.len() * 8);
+    for (i, list) in lists.iter().enumerate() {
+        if !global && w.external_sequences.get(i).cloned().unwrap_or(false) {
+            headers.push_u32(list.size);
+            headers.push_u32(list.offset);
+        } else {
+            w.write_array(&list.elements, &mut headers);
+        }
+    }
+    w.write_raw(lists.len(), &headers, out);
+}
+
+impl<T: M2Element> M2Element for M2Track<T> {
+    const SIZE: usize = 20;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        let global_sequence = r.bytes.get_i16(offset + 2)?;
+        Ok(M2Track {
+            interpolation_type: r.bytes.get_u16(offset)?,
+            global_sequence,
+            timestamps: read_track_lists(r, offset + 4, global_sequence >= 0)?,
+            values: read_track_lists(r, offset + 12, global_sequence >= 0)?,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_u16(self.interpolation_type);
+        out.push_i16(self.global_sequence);
+        write_track_lists(w, &self.timestamps, self.global_sequence >= 0, out);
+        write_track_lists(w, &self.values, self.global_sequence >= 0, out);
+    }
+}
+
+impl<T: M2Element> M2Track<T> {
+    /// Fills the keys of an external sequence, the offsets kept for it point into `anim`, the reader of its .anim file.
+    pub fn read_external_keys(&mut self, sequence: usize, anim: &M2Reader) -> R<()> {
+        if self.global_sequence >= 0 {
+            return Ok(());
+        }
+        if let Some(timestamps) = self.timestamps.get_mut(sequence) {
+            *timestamps = M2Array::read_elements(anim, timestamps.size, timestamps.offset)?;
+        }
+        if let Some(values) = self.values.get_mut(sequence) {
+            *values = M2Array::read_elements(anim, values.size, values.offset)?;
+        }
+        Ok(())
+    }
+}
+
+/// Timestamps only, as used by events.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2TrackBase {
+    pub interpolation_type: u16,
+    pub global_sequence: i16,
+    pub timestamps: Vec<M2Array<u32>>,
+}
+
+impl M2Element for M2TrackBase {
+    const SIZE: usize = 12;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        let global_sequence = r.bytes.get_i16(offset + 2)?;
+        Ok(M2TrackBase {
+            interpolation_type: r.bytes.get_u16(offset)?,
+            global_sequence,
+            timestamps: read_track_lists(r, offset + 4, global_sequence >= 0)?,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_u16(self.interpolation_type);
+        out.push_i16(self.global_sequence);
+        write_track_lists(w, &self.timestamps, self.global_sequence >= 0, out);
+    }
+}
+
+/// Particle tracks, a single list of keys with fixed16 timestamps over the particle lifetime.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2FakeTrack<T> {
+    pub timestamps: Vec<u16>,
+    pub values: Vec<T>,
+}
+
+impl<T: M2Element> M2Element for M2FakeTrack<T> {
+    const SIZE: usize = 16;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok(M2FakeTrack {
+            timestamps: M2Array::read(r, offset)?.elements,
+            values: M2Array::read(r, offset + 8)?.elements,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        w.write_array(&self.timestamps, out);
+        w.write_array(&self.values, out);
+    }
+}
+
+impl<T: M2Element> M2Element for M2SplineKey<T> {
+    const SIZE: usize = T::SIZE * 3;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok(M2SplineKey {
+            value: T::read(r, offset)?,
+            in_tan: T::read(r, offset + T::SIZE)?,
+            out_tan: T::read(r, offset + T::SIZE * 2)?,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        self.value.write(w, out);
+        self.in_tan.write(w, out);
+        self.out_tan.write(w, out);
+    }
+}
+
+impl M2Element for u8 {
+    const SIZE: usize = 1;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        r.bytes.get_byte(offset)
+    }
+    fn write(&self, _: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push(*self);
+    }
+}
+
+impl M2Element for u16 {
+    const SIZE: usize = 2;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        r.bytes.get_u16(offset)
+    }
+    fn write(&self, _: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_u16(*self);
+    }
+}
+
+// fixed16 values, alpha and texture weights
+impl M2Element for i16 {
+    const SIZE: usize = 2;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        r.bytes.get_i16(offset)
+    }
+    fn write(&self, _: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_i16(*self);
+    }
+}
+
+impl M2Element for u32 {
+    const SIZE: usize = 4;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        r.bytes.get_u32(offset)
+    }
+    fn write(&self, _: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_u32(*self);
+    }
+}
+
+impl M2Element for f32 {
+    const SIZE: usize = 4;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        r.bytes.get_f32(offset)
+    }
+    fn write(&self, _: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_f32(*self);
+    }
+}
+
+// the header of an M2Array, size then offset
+impl M2Element for (u32, u32) {
+    const SIZE: usize = 8;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok((r.bytes.get_u32(offset)?, r.bytes.get_u32(offset + 4)?))
+    }
+    fn write(&self, _: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_u32(self.0);
+        out.push_u32(self.1);
+    }
+}
+
+// bone weights and indices of vertices, bones of skins
+impl M2Element for [u8; 4] {
+    const SIZE: usize = 4;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        r.bytes.get_four_bytes(offset)
+    }
+    fn write(&self, _: &mut M2Writer, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+impl M2Element for [f32; 2] {
+    const SIZE: usize = 8;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok([r.bytes.get_f32(offset)?, r.bytes.get_f32(offset + 4)?])
+    }
+    fn write(&self, _: &mut M2Writer, out: &mut Vec<u8>) {
+        self.iter().for_each(|it| out.push_f32(*it));
+    }
+}
+
+impl M2Element for [f32; 3] {
+    const SIZE: usize = 12;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok([r.bytes.get_f32(offset)?, r.bytes.get_f32(offset + 4)?, r.bytes.get_f32(offset + 8)?])
+    }
+    fn write(&self, _: &mut M2Writer, out: &mut Vec<u8>) {
+        self.iter().for_each(|it| out.push_f32(*it));
+    }
+}
+
+impl M2Element for [f32; 4] {
+    const SIZE: usize = 16;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok([
+            r.bytes.get_f32(offset)?,
+            r.bytes.get_f32(offset + 4)?,
+            r.bytes.get_f32(offset + 8)?,
+            r.bytes.get_f32(offset + 12)?,
+        ])
+    }
+    fn write(&self, _: &mut M2Writer, out: &mut Vec<u8>) {
+        self.iter().for_each(|it| out.push_f32(*it));
+    }
+}
+
+// M2CompQuat, each component maps -32767..32767 to -1..1
+impl M2Element for [i16; 4] {
+    const SIZE: usize = 8;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok([
+            r.bytes.get_i16(offset)?,
+            r.bytes.get_i16(offset + 2)?,
+            r.bytes.get_i16(offset + 4)?,
+            r.bytes.get_i16(offset + 6)?,
+        ])
+    }
+    fn write(&self, _: &mut M2Writer, out: &mut Vec<u8>) {
+        self.iter().for_each(|it| out.push_i16(*it));
+    }
+}
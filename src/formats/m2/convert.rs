@@ -0,0 +1,309 @@
+use crate::byte_utils::{VecUtils, VecWriteUtils};
+use crate::common::{err, R};
+use crate::formats::chunk::Chunk;
+use crate::formats::m2::anim::get_anim_path;
+use crate::formats::m2::{M2File, M2_MAGIC, M2_VERSION_WRATH};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// Cataclysm up to Legion keep the Wrath header, the cameras and the records listed below changed
+pub const M2_VERSION_MAX: u32 = 274;
+// Cataclysm replaced the fov of cameras by a FoV track at the end of the record
+pub const M2_VERSION_CATA: u32 = 272;
+const WRATH_CAMERA_SIZE: usize = 100;
+const CATA_CAMERA_SIZE: usize = 116;
+// global flags Wrath knows: tilt x, tilt y, an unused one and texture_combiner_combos
+pub const M2_WRATH_GLOBAL_FLAGS: u32 = 0xF;
+// Legion particles use a longer record
+pub const M2_FLAG_NEW_PARTICLE_RECORD: u32 = 0x200;
+// file data id chunks of Legion, the converted files keep the on disk names instead
+const ID_CHUNKS: [&str; 3] = ["SFID", "AFID", "TXID"];
+
+/// What a Wrath client can not get back from a converted model.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum M2ConversionLoss {
+    GlobalFlagsCleared(u32),
+    ParticlesDropped(u32),
+    // along with the camera lookup, when a FoV track has no key to take the fov from
+    CamerasDropped(u32),
+    // chunks of MD21 models other than the model itself and the file data ids
+    ChunkDropped(String),
+    // textures only known by their file data id (TXID), left without a file name
+    TextureWithoutName { index: usize, file_data_id: u32 },
+    ShadowBatchesDropped { skin: PathBuf, count: u32 },
+    AnimChunkDropped { anim: PathBuf, chunk: String },
+    SkinNotConverted { skin: PathBuf, error: String },
+    AnimNotConverted { anim: PathBuf, error: String },
+}
+
+/// Rewrites a model of version 264 to 274, plain or MD21 chunked, as a Wrath MD20.
+pub fn convert_m2_to_wrath(bytes: &Vec<u8>) -> R<(Vec<u8>, Vec<M2ConversionLoss>)> {
+    let mut losses = Vec::new();
+    let mut md20 = if bytes.get_string(0, 4)? == "MD21" {
+        let chunks = Chunk::from_bytes(bytes)?;
+        let mut md20 = None;
+        for chunk in &chunks {
+            match chunk.get_unreversed_id_as_string().as_str() {
+                // M2Array offsets are relative to the start of MD21, the MD20 header
+                "MD21" => md20 = Some(chunk.data.clone()),
+                name if ID_CHUNKS.contains(&name) => {}
+                name => losses.push(M2ConversionLoss::ChunkDropped(name.to_string())),
+            }
+        }
+        let md20 = md20.context("The chunked model has no MD21 chunk")?;
+        if let Some(txid) = chunks.iter().find(|it| it.get_unreversed_id_as_string() == "TXID") {
+            let m2 = M2File::from_newer_bytes(md20.clone())?;
+            for (index, texture) in m2.textures.iter().enumerate() {
+                let file_data_id = txid.data.get_u32(index * 4).unwrap_or(0);
+                if texture.texture_type == 0 && texture.file_name.is_empty() && file_data_id != 0 {
+                    losses.push(M2ConversionLoss::TextureWithoutName { index, file_data_id });
+                }
+            }
+        }
+        md20
+    } else {
+        bytes.clone()
+    };
+
+    M2File::from_newer_bytes(md20.clone())?;
+    let dropped_cameras = convert_cameras_to_wrath(&mut md20)?;
+    if dropped_cameras > 0 {
+        losses.push(M2ConversionLoss::CamerasDropped(dropped_cameras));
+    }
+    let flags = md20.get_u32(0x10)?;
+    let n_particles = md20.get_u32(0x128)?;
+    if flags & M2_FLAG_NEW_PARTICLE_RECORD != 0 && n_particles > 0 {
+        losses.push(M2ConversionLoss::ParticlesDropped(n_particles));
+        md20.set_u32(0x128, 0);
+        md20.set_u32(0x12C, 0);
+    }
+    if flags & !M2_WRATH_GLOBAL_FLAGS != 0 {
+        losses.push(M2ConversionLoss::GlobalFlagsCleared(flags & !M2_WRATH_GLOBAL_FLAGS));
+        md20.set_u32(0x10, flags & M2_WRATH_GLOBAL_FLAGS);
+    }
+    md20.set_u32(4, M2_VERSION_WRATH);
+    M2File::from_bytes(md20.clone()).context("The converted model is not a valid Wrath model")?;
+    Ok((md20, losses))
+}
+
+/// Rewrites the cameras of Cataclysm and later models in place with the Wrath record,
+/// the fov is the first key of their FoV track. Returns how many were dropped, all of them
+/// when one has no key since the camera lookup indexes them.
+fn convert_cameras_to_wrath(md20: &mut Vec<u8>) -> R<u32> {
+    let (count, offset) = (md20.get_u32(0x110)? as usize, md20.get_u32(0x114)? as usize);
+    if md20.get_u32(4)? < M2_VERSION_CATA || count == 0 {
+        return Ok(0);
+    }
+    if offset + count * CATA_CAMERA_SIZE > md20.len() {
+        return err(format!("The {} cameras at {:#x} don't fit in the model", count, offset));
+    }
+    let mut cameras = Vec::with_capacity(count * WRATH_CAMERA_SIZE);
+    for i in 0..count {
+        let record = offset + i * CATA_CAMERA_SIZE;
+        let fov = match get_first_spline_value(md20, record + 96)? {
+            Some(fov) => fov,
+            None => {
+                (0x110..0x120).for_each(|it| md20[it] = 0);
+                return Ok(count as u32);
+            }
+        };
+        // the fov goes back behind the type, the clips and tracks keep their layout
+        cameras.extend_from_slice(&md20[record..record + 4]);
+        cameras.push_f32(fov);
+        cameras.extend_from_slice(&md20[record + 4..record + 96]);
+    }
+    md20[offset..offset + cameras.len()].copy_from_slice(&cameras);
+    Ok(0)
+}
+
+// value of the first key in the first non empty list of an M2Track<M2SplineKey<f32>>
+fn get_first_spline_value(bytes: &Vec<u8>, track: usize) -> R<Option<f32>> {
+    let (lists, values) = (bytes.get_u32(track + 12)? as usize, bytes.get_u32(track + 16)? as usize);
+    for list in 0..lists {
+        if bytes.get_u32(values + list * 8)? > 0 {
+            return Ok(Some(bytes.get_f32(bytes.get_u32(values + list * 8 + 4)? as usize)?));
+        }
+    }
+    Ok(None)
+}
+
+/// Cataclysm added shadow batches after the Wrath header of skins, the array is emptied.
+pub fn convert_skin_to_wrath(bytes: &Vec<u8>, path: &Path) -> R<(Vec<u8>, Vec<M2ConversionLoss>)> {
+    if bytes.get_string(0, 4)? != "SKIN" {
+        return err(format!("{} is not a skin profile", path.display()));
+    }
+    let mut skin = bytes.clone();
+    let mut losses = Vec::new();
+    // the shadow batches are only there when the data starts after them
+    let header_end = (0..5)
+        .filter(|i| skin.get_u32(0x04 + i * 8).unwrap_or(0) > 0)
+        .map(|i| skin.get_u32(0x08 + i * 8).unwrap_or(0))
+        .min()
+        .unwrap_or(0x30);
+    if header_end >= 0x38 {
+        let count = skin.get_u32(0x30)?;
+        if count > 0 {
+            losses.push(M2ConversionLoss::ShadowBatchesDropped { skin: path.to_path_buf(), count });
+        }
+        skin.set_u32(0x30, 0);
+        skin.set_u32(0x34, 0);
+    }
+    Ok((skin, losses))
+}
+
+/// Legion chunks .anim files (AFM2 holds the keys), Wrath reads the keys directly.
+pub fn convert_anim_to_wrath(bytes: &Vec<u8>, path: &Path) -> R<(Vec<u8>, Vec<M2ConversionLoss>)> {
+    if bytes.get_string(0, 4).ok().as_deref() != Some("AFM2") {
+        return Ok((bytes.clone(), Vec::new()));
+    }
+    let mut keys = None;
+    let mut losses = Vec::new();
+    for chunk in Chunk::from_bytes(bytes)? {
+        match chunk.get_unreversed_id_as_string().as_str() {
+            "AFM2" => keys = Some(chunk.data),
+            name => losses.push(M2ConversionLoss::AnimChunkDropped { anim: path.to_path_buf(), chunk: name.to_string() }),
+        }
+    }
+    Ok((keys.with_context(|| format!("{} has no AFM2 chunk", path.display()))?, losses))
+}
+
+/// Converts a model with its skin profiles and .anim files, written next to `output` under its name.
+/// Returns the written files and what got lost on the way.
+pub fn convert_m2_file_to_wrath<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output: Q) -> R<(Vec<PathBuf>, Vec<M2ConversionLoss>)> {
+    let (input, output) = (input.as_ref(), output.as_ref());
+    let bytes = std::fs::read(input).with_context(|| format!("Failed to read {}", input.display()))?;
+    let (converted, mut losses) = convert_m2_to_wrath(&bytes)
+        .with_context(|| format!("Failed to convert {}", input.display()))?;
+    let m2 = M2File::from_bytes(converted.clone())?;
+
+    let mut files = Vec::new();
+    let mut outputs = Vec::new();
+    for (skin, target) in m2.get_skin_paths(input).into_iter().zip(m2.get_skin_paths(output)) {
+        match std::fs::read(&skin).map_err(anyhow::Error::from).and_then(|it| convert_skin_to_wrath(&it, &skin)) {
+            Ok((data, skin_losses)) => {
+                losses.extend(skin_losses);
+                outputs.push((target, data));
+            }
+            Err(e) => losses.push(M2ConversionLoss::SkinNotConverted { skin, error: format!("{:#}", e) }),
+        }
+    }
+    for sequence in m2.get_external_sequences() {
+        let anim = get_anim_path(input, &m2.sequences[sequence]);
+        let target = get_anim_path(output, &m2.sequences[sequence]);
+        match std::fs::read(&anim).map_err(anyhow::Error::from).and_then(|it| convert_anim_to_wrath(&it, &anim)) {
+            Ok((data, anim_losses)) => {
+                losses.extend(anim_losses);
+                outputs.push((target, data));
+            }
+            Err(e) => losses.push(M2ConversionLoss::AnimNotConverted { anim, error: format!("{:#}", e) }),
+        }
+    }
+
+    if let Some(parent) = output.parent().filter(|it| !it.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(output, converted).with_context(|| format!("Failed to write {}", output.display()))?;
+    files.push(output.to_path_buf());
+    for (path, data) in outputs {
+        std::fs::write(&path, data).with_context(|| format!("Failed to write {}", path.display()))?;
+        files.push(path);
+    }
+    Ok((files, losses))
+}
+
+impl M2File {
+    /// Reads a model of a later expansion with the Wrath layout, the header is the same up to Legion.
+    /// Cameras are read back with the Wrath record, dropped when their fov can't be told.
+    pub fn from_newer_bytes(mut bytes: Vec<u8>) -> R<M2File> {
+        let version = bytes.get_u32(4)?;
+        if bytes.get_string(0, 4)? != M2_MAGIC || !(M2_VERSION_WRATH..=M2_VERSION_MAX).contains(&version) {
+            return err(format!(
+                "Expected an {} model of version {} to {}, found {:?} version {}",
+                M2_MAGIC, M2_VERSION_WRATH, M2_VERSION_MAX, bytes.get_string(0, 4)?, version
+            ));
+        }
+        // the particles of the new record can't be read with the Wrath one, they are left out
+        if bytes.get_u32(0x10)? & M2_FLAG_NEW_PARTICLE_RECORD != 0 {
+            bytes.set_u32(0x128, 0);
+        }
+        convert_cameras_to_wrath(&mut bytes)?;
+        bytes.set_u32(4, M2_VERSION_WRATH);
+        let mut m2 = M2File::from_bytes(bytes)?;
+        m2.version = version;
+        Ok(m2)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn legion_model_converts_to_a_wrath_one() {
+    use crate::formats::m2::test_utils::{build_cata_test_skin, build_legion_test_m2, build_test_anim};
+    use crate::test_utils::push_chunk;
+
+    let dir = crate::test_utils::TempDir::new("wow-file-tools-m2-convert");
+    let mut md20 = build_legion_test_m2();
+    md20.set_u32(0x10, 0x20 | 0x80);
+    let mut model = Vec::new();
+    push_chunk(&mut model, b"MD21", &md20);
+    push_chunk(&mut model, b"SFID", &[1, 0, 0, 0]);
+    push_chunk(&mut model, b"PFID", &[2, 0, 0, 0]);
+    std::fs::write(dir.join("Legion.m2"), model).unwrap();
+    std::fs::write(dir.join("Legion00.skin"), build_cata_test_skin()).unwrap();
+    let mut anim = Vec::new();
+    push_chunk(&mut anim, b"AFM2", &build_test_anim());
+    push_chunk(&mut anim, b"AFSA", &[]);
+    std::fs::write(dir.join("Legion0001-00.anim"), anim).unwrap();
+
+    let (files, losses) = convert_m2_file_to_wrath(dir.join("Legion.m2"), dir.join("out/Wrath.m2")).unwrap();
+    assert_eq!(files, vec![
+        dir.join("out/Wrath.m2"),
+        dir.join("out/Wrath00.skin"),
+        dir.join("out/Wrath0001-00.anim"),
+    ]);
+    let losses = serde_json::to_string(&losses).unwrap();
+    assert!(losses.contains(r#"{"ChunkDropped":"PFID"}"#));
+    assert!(!losses.contains("SFID"));
+    assert!(losses.contains(r#"{"GlobalFlagsCleared":160}"#));
+    assert!(losses.contains(r#""count":1"#));
+    assert!(losses.contains(r#""chunk":"AFSA""#));
+
+    // the 116 byte camera became a 100 byte Wrath one with the fov behind the type
+    let converted = std::fs::read(dir.join("out/Wrath.m2")).unwrap();
+    assert_eq!(converted.get_u32(0x110).unwrap(), 1);
+    let camera = converted.get_u32(0x114).unwrap() as usize;
+    assert_eq!(converted.get_u32(camera).unwrap(), 0);
+    let floats: Vec<f32> = [4, 8, 12].iter().map(|it| converted.get_f32(camera + it).unwrap()).collect();
+    assert_eq!(floats, vec![0.95, 100.0, 0.2]);
+    // the positions track, its one key, the target position base and the empty roll track
+    assert_eq!((converted.get_u16(camera + 16).unwrap(), converted.get_i16(camera + 18).unwrap()), (1, -1));
+    assert_eq!((converted.get_u32(camera + 20).unwrap(), converted.get_u32(camera + 28).unwrap()), (1, 1));
+    let key_list = converted.get_u32(camera + 32).unwrap() as usize;
+    let key = converted.get_u32(key_list + 4).unwrap() as usize;
+    assert_eq!(converted.get_f32(key).unwrap(), 5.0);
+    assert_eq!(converted.get_f32(camera + 76).unwrap(), 1.0);
+    assert_eq!((converted.get_u32(camera + 84).unwrap(), converted.get_u32(camera + 92).unwrap()), (0, 0));
+
+    let mut m2 = M2File::from_path(dir.join("out/Wrath.m2")).unwrap();
+    assert_eq!((m2.version, m2.global_flags), (264, 0));
+    assert_eq!((m2.cameras[0].fov, m2.cameras[0].target_position_base), (0.95, [0.0, 0.0, 1.0]));
+    assert_eq!(m2.cameras[0].positions.values[0].elements[0].value, [5.0, 0.0, 1.0]);
+    assert!(m2.load_anims(dir.join("out/Wrath.m2")).is_empty());
+    assert_eq!(m2.bones[0].translation.values[1].elements[1], [0.0, 0.0, 3.0]);
+    m2.load_skins(dir.join("out/Wrath.m2")).unwrap();
+    assert_eq!(m2.skins[0].submeshes.len(), 1);
+}
+
+#[cfg(test)]
+#[test]
+fn cameras_without_fov_keys_are_dropped() {
+    let mut md20 = crate::formats::m2::test_utils::build_legion_test_m2();
+    let camera = md20.get_u32(0x114).unwrap() as usize;
+    // no lists in the FoV track
+    md20.set_u32(camera + 96 + 12, 0);
+    let (converted, losses) = convert_m2_to_wrath(&md20).unwrap();
+    assert!(matches!(losses.as_slice(), [M2ConversionLoss::CamerasDropped(1)]));
+    let m2 = M2File::from_bytes(converted).unwrap();
+    assert!(m2.cameras.is_empty() && m2.camera_lookup.is_empty());
+    assert_eq!(M2File::from_newer_bytes(md20).unwrap().cameras.len(), 0);
+}
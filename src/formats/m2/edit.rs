@@ -0,0 +1,72 @@
+use crate::common::{R, err};
+use crate::formats::m2::types::M2Texture;
+use crate::formats::m2::M2File;
+
+impl M2File {
+    /// Makes texture `index` load `path`. Replaceable textures become plain ones (type 0),
+    /// returns the texture as it was.
+    pub fn set_texture(&mut self, index: usize, path: &str) -> R<M2Texture> {
+        let count = self.textures.len();
+        let texture = match self.textures.get_mut(index) {
+            Some(texture) => texture,
+            None => return err(format!("The model has {} textures, there is no texture {}", count, index)),
+        };
+        let previous = texture.clone();
+        texture.texture_type = 0;
+        texture.file_name = path.to_string();
+        Ok(previous)
+    }
+
+    /// Sets the geometry and recursion models of particle emitter `index`, empty for none.
+    pub fn set_particle_models(&mut self, index: usize, model: &str, recursion_model: &str) -> R<()> {
+        let count = self.particles.len();
+        let particle = match self.particles.get_mut(index) {
+            Some(particle) => particle,
+            None => return err(format!("The model has {} particle emitters, there is no emitter {}", count, index)),
+        };
+        particle.model_file_name = model.to_string();
+        particle.particle_name = recursion_model.to_string();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn m2_is_rewritten_with_longer_strings() {
+    use crate::byte_utils::VecUtils;
+    use crate::formats::m2::test_utils::build_test_m2;
+
+    let mut m2 = M2File::from_bytes(build_test_m2()).unwrap();
+    let previous = m2.set_texture(1, "Creature\\Test\\TestSkinBlue.blp").unwrap();
+    assert_eq!(previous.texture_type, 11);
+    assert!(m2.set_texture(2, "Test.blp").is_err());
+    m2.set_particle_models(0, "Spells\\Fire_Ball_Missile.m2", "").unwrap();
+    m2.name = "TestModelWithALongerName".to_string();
+
+    let bytes = m2.to_bytes();
+    // the name is the first array behind the 0x130 byte header, its count includes the null
+    assert_eq!((bytes.get_u32(0x08).unwrap(), bytes.get_u32(0x0C).unwrap()), (25, 0x130));
+    assert_eq!(bytes.get_string_null_terminated(0x130).unwrap(), "TestModelWithALongerName");
+    assert_eq!(bytes.get_u32(0x50).unwrap(), 2);
+    let textures = bytes.get_u32(0x54).unwrap() as usize;
+    assert_eq!(textures % 16, 0);
+    // type, flags, then the name array of the second 16 byte texture
+    assert_eq!(bytes.get_u32(textures + 16 + 8).unwrap(), 31);
+    let second_name = bytes.get_u32(textures + 16 + 12).unwrap() as usize;
+    assert_eq!(bytes.get_string_null_terminated(second_name).unwrap(), "Creature\\Test\\TestSkinBlue.blp");
+
+    let written = M2File::from_bytes(bytes).unwrap();
+    assert_eq!(written.name, "TestModelWithALongerName");
+    assert_eq!(written.textures[1].texture_type, 0);
+    assert_eq!(written.get_texture_names(), vec!["Creature\\Test\\Test.blp", "Creature\\Test\\TestSkinBlue.blp"]);
+    assert_eq!(written.particles[0].model_file_name, "Spells\\Fire_Ball_Missile.m2");
+    assert_eq!(written.particles[0].lifespan.values[0].elements, vec![2.5]);
+    assert_eq!(written.vertices, m2.vertices);
+    // the external keys still point into the .anim file
+    let translation = &written.bones[0].translation;
+    assert_eq!((translation.values[1].size, translation.values[1].offset), (2, 0x20));
+    assert_eq!(translation.values[0].elements, vec![[0.0, 0.0, 0.0], [0.0, 0.0, 1.0]]);
+    assert_eq!(written.events[0].identifier, "$DTH");
+    assert_eq!(serde_json::to_value(&written.ribbon_emitters).unwrap(), serde_json::to_value(&m2.ribbon_emitters).unwrap());
+    assert_eq!(written.to_bytes(), M2File::from_bytes(written.to_bytes()).unwrap().to_bytes());
+}
@@ -0,0 +1,170 @@
+use crate::common::R;
+use crate::formats::m2::array::M2Track;
+use crate::formats::m2::skin::SkinFile;
+use crate::formats::m2::M2File;
+use crate::formats::mesh::{
+    z_up_to_y_up, z_up_to_y_up_rotation, z_up_to_y_up_scale, AnimationChannel, AnimationPath, Mesh, MeshBatch,
+    MeshMaterial, Scene, SceneAnimation, SceneJoint,
+};
+use std::collections::HashMap;
+
+// M2CompQuat components map -32767..32767 to -1..1
+fn decode_comp_quat(q: &[i16; 4]) -> [f32; 4] {
+    let mut out = [0.0; 4];
+    for (value, it) in out.iter_mut().zip(q.iter()) {
+        let it = *it as f32;
+        *value = if it < 0.0 { it + 32768.0 } else { it - 32767.0 } / 32767.0;
+    }
+    out
+}
+
+// keys of one sequence, tracks on a global sequence have a single list
+fn get_channel<T, F: Fn(&T) -> Vec<f32>>(
+    track: &M2Track<T>,
+    sequence: usize,
+    joint: usize,
+    path: AnimationPath,
+    convert: F,
+) -> Option<AnimationChannel> {
+    let list = if track.global_sequence >= 0 { 0 } else { sequence };
+    let timestamps = &track.timestamps.get(list)?.elements;
+    let values = &track.values.get(list)?.elements;
+    if timestamps.is_empty() || timestamps.len() != values.len() {
+        return None;
+    }
+    Some(AnimationChannel {
+        joint,
+        path,
+        step: track.interpolation_type == 0,
+        times: timestamps.iter().map(|it| *it as f32 / 1000.0).collect(),
+        values: values.iter().flat_map(convert).collect(),
+    })
+}
+
+impl M2File {
+    /// One mesh per submesh of `skin`, bound to a joint per bone, and one animation per sequence with keys.
+    /// `texture_uri` gives what a material references for a texture path, if anything.
+    pub fn to_scene<F: Fn(&str) -> Option<String>>(&self, skin: &SkinFile, texture_uri: F) -> R<Scene> {
+        let materials = self.textures.iter()
+            .enumerate()
+            .map(|(i, texture)| {
+                if texture.file_name.is_empty() {
+                    // replaced at runtime, by the creature display info for instance
+                    return MeshMaterial { name: format!("{}_type_{}", i, texture.texture_type), color: [1.0; 4], texture: None };
+                }
+                let stem = texture.file_name.rsplit(&['\\', '/'][..])
+                    .next()
+                    .and_then(|it| it.split('.').next())
+                    .unwrap_or_default();
+                MeshMaterial {
+                    name: format!("{}_{}", i, stem),
+                    color: [1.0; 4],
+                    texture: texture_uri(&texture.file_name),
+                }
+            })
+            .collect();
+
+        let mut meshes = Vec::new();
+        for (i, submesh) in skin.submeshes.iter().enumerate() {
+            // the first layer gives the texture, the others blend over it in the client
+            let texture = skin.texture_units.iter()
+                .filter(|it| it.skin_section_index as usize == i)
+                .min_by_key(|it| it.material_layer)
+                .and_then(|it| self.texture_lookup.get(it.texture_combo_index as usize))
+                .map(|it| *it as usize);
+
+            let mut local_indices = HashMap::new();
+            let mut mesh = Mesh { name: format!("{}_{:02}_{}", self.name, i, submesh.skin_section_id), ..Default::default() };
+            let mut indices = Vec::new();
+            for vertex_index in skin.get_submesh_triangles(submesh)?.iter().flatten() {
+                let vertex = match self.vertices.get(*vertex_index as usize) {
+                    Some(vertex) => vertex,
+                    None => continue,
+                };
+                let next = mesh.positions.len() as u32;
+                let local = *local_indices.entry(*vertex_index).or_insert(next);
+                if local == next {
+                    mesh.positions.push(z_up_to_y_up(vertex.position));
+                    mesh.normals.push(z_up_to_y_up(vertex.normal));
+                    mesh.uvs.push(vertex.tex_coords[0]);
+                    mesh.joints.push(vertex.bone_indices);
+                    mesh.weights.push(vertex.bone_weights);
+                }
+                indices.push(local);
+            }
+            mesh.batches.push(MeshBatch { material: texture, indices });
+            meshes.push(mesh);
+        }
+
+        let bind_positions: Vec<[f32; 3]> = self.bones.iter().map(|it| z_up_to_y_up(it.pivot)).collect();
+        let joints: Vec<SceneJoint> = self.bones.iter()
+            .enumerate()
+            .map(|(i, bone)| {
+                let parent = Some(bone.parent_bone as usize).filter(|it| bone.parent_bone >= 0 && *it < self.bones.len());
+                let parent_position = parent.map(|it| bind_positions[it]).unwrap_or([0.0; 3]);
+                let p = bind_positions[i];
+                SceneJoint {
+                    name: format!("bone_{:03}", i),
+                    parent,
+                    translation: [p[0] - parent_position[0], p[1] - parent_position[1], p[2] - parent_position[2]],
+                    bind_position: p,
+                }
+            })
+            .collect();
+
+        let animations = self.sequences.iter()
+            .enumerate()
+            .map(|(sequence, it)| {
+                let mut channels = Vec::new();
+                for (i, bone) in self.bones.iter().enumerate() {
+                    let rest = joints[i].translation;
+                    channels.extend(get_channel(&bone.translation, sequence, i, AnimationPath::Translation, |v| {
+                        let v = z_up_to_y_up(*v);
+                        vec![rest[0] + v[0], rest[1] + v[1], rest[2] + v[2]]
+                    }));
+                    channels.extend(get_channel(&bone.rotation, sequence, i, AnimationPath::Rotation, |v| {
+                        z_up_to_y_up_rotation(decode_comp_quat(v)).to_vec()
+                    }));
+                    channels.extend(get_channel(&bone.scale, sequence, i, AnimationPath::Scale, |v| {
+                        z_up_to_y_up_scale(*v).to_vec()
+                    }));
+                }
+                SceneAnimation { name: format!("{:04}-{:02}", it.id, it.variation_index), channels }
+            })
+            .collect();
+
+        Ok(Scene { meshes, materials, joints, animations, ..Default::default() })
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn m2_to_scene_binds_the_skin_and_keeps_the_animations() {
+    use crate::formats::m2::test_utils;
+    use crate::formats::mesh::gltf::write_gltf;
+
+    let dir = crate::test_utils::TempDir::new("wow-file-tools-m2-export");
+    let path = test_utils::write_test_m2(&dir);
+    std::fs::write(path.with_file_name("TestModel0001-00.anim"), test_utils::build_test_anim()).unwrap();
+    let mut m2 = M2File::from_path(&path).unwrap();
+    assert!(m2.load_anims(&path).is_empty());
+    m2.load_skins(&path).unwrap();
+
+    let scene = m2.to_scene(&m2.skins[0], |path| Some(format!("textures/{}.png", path))).unwrap();
+    assert_eq!(scene.meshes[0].name, "TestModel_00_0");
+    assert_eq!(scene.meshes[0].batches[0].indices, vec![0, 1, 2, 0, 2, 3]);
+    assert_eq!(scene.meshes[0].positions[1], [1.0, 0.0, 1.0]);
+    assert_eq!(scene.materials[1].name, "1_type_11");
+    assert_eq!(scene.joints[0].parent, None);
+    // the second sequence comes from the .anim file
+    assert_eq!(scene.animations[1].name, "0001-00");
+    assert_eq!(scene.animations[1].channels[0].values, vec![0.0, 0.0, 0.0, 0.0, 3.0, 0.0]);
+
+    let (gltf, bin) = write_gltf(&scene, "test.bin");
+    let gltf: serde_json::Value = serde_json::from_str(&gltf).unwrap();
+    assert_eq!(gltf["buffers"][0]["byteLength"], bin.len());
+    assert_eq!(gltf["skins"][0]["joints"], serde_json::json!([1]));
+    assert_eq!(gltf["nodes"][0]["skin"], 0);
+    assert_eq!(gltf["animations"].as_array().unwrap().len(), 2);
+    assert_eq!(gltf["scenes"][0]["nodes"], serde_json::json!([0, 1]));
+}
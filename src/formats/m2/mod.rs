@@ -0,0 +1,225 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use crate::common::{R, err};
+use std::path::{Path, PathBuf};
+use crate::byte_utils::VecUtils;
+use crate::formats::m2::array::{read_string, M2Array, M2Element, M2Reader, M2Track, SEQUENCE_FLAG_DATA_IN_M2};
+use crate::formats::m2::skin::SkinFile;
+use crate::formats::m2::types::*;
+
+pub mod anim;
+pub mod array;
+pub mod convert;
+pub mod edit;
+pub mod export;
+pub mod skin;
+pub mod types;
+pub mod writer;
+
+#[cfg(test)]
+pub(crate) mod test_utils;
+
+pub const M2_MAGIC: &str = "MD20";
+// Wrath of the Lich King, the only version the header below matches
+pub const M2_VERSION_WRATH: u32 = 264;
+// global flag, the header ends with texture_combiner_combos
+pub const M2_FLAG_USE_TEXTURE_COMBINER_COMBOS: u32 = 0x8;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct M2File {
+    pub magic: String,
+    pub version: u32,
+    pub name: String,
+    pub global_flags: u32,
+    pub global_sequences: Vec<u32>,
+    pub sequences: Vec<M2Sequence>,
+    pub sequence_lookups: Vec<i16>,
+    pub bones: Vec<M2Bone>,
+    pub key_bone_lookup: Vec<i16>,
+    pub vertices: Vec<M2Vertex>,
+    pub n_skin_profiles: u32,
+    pub colors: Vec<M2Color>,
+    pub textures: Vec<M2Texture>,
+    pub texture_weights: Vec<M2Track<i16>>,
+    pub texture_transforms: Vec<M2TextureTransform>,
+    pub replaceable_texture_lookup: Vec<i16>,
+    pub materials: Vec<M2Material>,
+    pub bone_lookup: Vec<u16>,
+    pub texture_lookup: Vec<u16>,
+    pub tex_unit_lookup: Vec<i16>,
+    pub transparency_lookup: Vec<u16>,
+    pub texture_transform_lookup: Vec<i16>,
+    pub bounding_box: [f32; 6],
+    pub bounding_sphere_radius: f32,
+    pub collision_box: [f32; 6],
+    pub collision_sphere_radius: f32,
+    pub collision_triangles: Vec<u16>,
+    pub collision_vertices: Vec<[f32; 3]>,
+    pub collision_normals: Vec<[f32; 3]>,
+    pub attachments: Vec<M2Attachment>,
+    pub attachment_lookup: Vec<i16>,
+    pub events: Vec<M2Event>,
+    pub lights: Vec<M2Light>,
+    pub cameras: Vec<M2Camera>,
+    pub camera_lookup: Vec<i16>,
+    pub ribbon_emitters: Vec<M2Ribbon>,
+    pub particles: Vec<M2Particle>,
+    pub texture_combiner_combos: Vec<u16>,
+    // only filled by `load_skins`
+    #[serde(default)]
+    pub skins: Vec<SkinFile>,
+}
+
+impl M2File {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> R<M2File> {
+        let path = path.as_ref().to_path_buf();
+        let data = std::fs::read(path.clone())
+            .with_context(|| format!("Failed to m2 file '{}'", path.display()))?;
+        M2File::from_bytes(data)
+            .context("Failed to read M2 file.")
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> R<M2File> {
+        let magic = bytes.get_string(0, 4)?;
+        if magic != M2_MAGIC {
+            return err(format!("Expected the {} magic, found {:?}", M2_MAGIC, magic));
+        }
+        let version = bytes.get_u32(4)?;
+        if version != M2_VERSION_WRATH {
+            return err(format!("Only Wrath M2 ({}) are supported, found version {}", M2_VERSION_WRATH, version));
+        }
+
+        // the sequences tell which animation keys are stored in .anim files, read them first
+        let mut r = M2Reader { bytes: &bytes, external_sequences: Vec::new() };
+        let sequences: Vec<M2Sequence> = M2Array::read(&r, 0x1C)?.elements;
+        r.external_sequences = sequences.iter()
+            .map(|it| it.flags & SEQUENCE_FLAG_DATA_IN_M2 == 0)
+            .collect();
+
+        let global_flags = bytes.get_u32(0x10)?;
+        let texture_combiner_combos = if global_flags & M2_FLAG_USE_TEXTURE_COMBINER_COMBOS != 0 {
+            read_array(&r, 0x130, "texture_combiner_combos")?
+        } else {
+            Vec::new()
+        };
+
+        Ok(M2File {
+            magic,
+            version,
+            name: read_string(&r, 0x08).context("Failed to read the name")?,
+            global_flags,
+            global_sequences: read_array(&r, 0x14, "global_sequences")?,
+            sequences,
+            sequence_lookups: read_array(&r, 0x24, "sequence_lookups")?,
+            bones: read_array(&r, 0x2C, "bones")?,
+            key_bone_lookup: read_array(&r, 0x34, "key_bone_lookup")?,
+            vertices: read_array(&r, 0x3C, "vertices")?,
+            n_skin_profiles: bytes.get_u32(0x44)?,
+            colors: read_array(&r, 0x48, "colors")?,
+            textures: read_array(&r, 0x50, "textures")?,
+            texture_weights: read_array(&r, 0x58, "texture_weights")?,
+            texture_transforms: read_array(&r, 0x60, "texture_transforms")?,
+            replaceable_texture_lookup: read_array(&r, 0x68, "replaceable_texture_lookup")?,
+            materials: read_array(&r, 0x70, "materials")?,
+            bone_lookup: read_array(&r, 0x78, "bone_lookup")?,
+            texture_lookup: read_array(&r, 0x80, "texture_lookup")?,
+            tex_unit_lookup: read_array(&r, 0x88, "tex_unit_lookup")?,
+            transparency_lookup: read_array(&r, 0x90, "transparency_lookup")?,
+            texture_transform_lookup: read_array(&r, 0x98, "texture_transform_lookup")?,
+            bounding_box: read_box(&r, 0xA0)?,
+            bounding_sphere_radius: bytes.get_f32(0xB8)?,
+            collision_box: read_box(&r, 0xBC)?,
+            collision_sphere_radius: bytes.get_f32(0xD4)?,
+            collision_triangles: read_array(&r, 0xD8, "collision_triangles")?,
+            collision_vertices: read_array(&r, 0xE0, "collision_vertices")?,
+            collision_normals: read_array(&r, 0xE8, "collision_normals")?,
+            attachments: read_array(&r, 0xF0, "attachments")?,
+            attachment_lookup: read_array(&r, 0xF8, "attachment_lookup")?,
+            events: read_array(&r, 0x100, "events")?,
+            lights: read_array(&r, 0x108, "lights")?,
+            cameras: read_array(&r, 0x110, "cameras")?,
+            camera_lookup: read_array(&r, 0x118, "camera_lookup")?,
+            ribbon_emitters: read_array(&r, 0x120, "ribbon_emitters")?,
+            particles: read_array(&r, 0x128, "particles")?,
+            texture_combiner_combos,
+            skins: Vec::new(),
+        })
+    }
+
+    /// Paths of the `<model>0N.skin` profiles next to the model, whether they exist or not.
+    pub fn get_skin_paths<P: AsRef<Path>>(&self, m2_path: P) -> Vec<PathBuf> {
+        let m2_path = m2_path.as_ref();
+        let stem = m2_path.file_stem().map(|it| it.to_string_lossy().to_string()).unwrap_or_default();
+        (0..self.n_skin_profiles)
+            .map(|i| m2_path.with_file_name(format!("{}{:02}.skin", stem, i)))
+            .collect()
+    }
+
+    /// Loads every skin profile of the model from next to it.
+    pub fn load_skins<P: AsRef<Path>>(&mut self, m2_path: P) -> R<()> {
+        self.skins = self.get_skin_paths(m2_path)
+            .iter()
+            .map(SkinFile::from_path)
+            .collect::<R<Vec<SkinFile>>>()?;
+        Ok(())
+    }
+
+    /// File names of the textures the model references directly, the replaceable ones have none.
+    pub fn get_texture_names(&self) -> Vec<String> {
+        self.textures.iter()
+            .filter(|it| !it.file_name.is_empty())
+            .map(|it| it.file_name.clone())
+            .collect()
+    }
+}
+
+fn read_array<T: M2Element>(r: &M2Reader, offset: usize, name: &str) -> R<Vec<T>> {
+    Ok(M2Array::read(r, offset)
+        .with_context(|| format!("Failed to read the {} of the header", name))?
+        .elements)
+}
+
+#[cfg(test)]
+#[test]
+fn m2_header_is_fully_decoded() {
+    let m2 = M2File::from_bytes(test_utils::build_test_m2()).unwrap();
+    assert_eq!(m2.name, "TestModel");
+    assert_eq!(m2.global_sequences, vec![1000]);
+    assert_eq!(m2.sequences.len(), 2);
+    assert_eq!(m2.sequences[1].duration, 2000);
+
+    // the second sequence lives in TestModel0001-00.anim, only its offsets are known
+    let translation = &m2.bones[0].translation;
+    assert_eq!(translation.timestamps[0].elements, vec![0, 500]);
+    assert_eq!(translation.values[0].elements, vec![[0.0, 0.0, 0.0], [0.0, 0.0, 1.0]]);
+    assert_eq!((translation.values[1].size, translation.values[1].elements.len()), (2, 0));
+    assert_eq!(m2.bones[0].parent_bone, -1);
+
+    assert_eq!(m2.vertices.len(), 4);
+    assert_eq!(m2.vertices[2].tex_coords[0], [1.0, 1.0]);
+    assert_eq!(m2.textures.len(), 2);
+    assert_eq!(m2.textures[1].texture_type, 11);
+    assert_eq!(m2.get_texture_names(), vec!["Creature\\Test\\Test.blp"]);
+    assert_eq!(m2.materials[0].blending_mode, 1);
+    assert_eq!(m2.bounding_box, [-1.0, -1.0, 0.0, 1.0, 1.0, 2.0]);
+    assert_eq!(m2.collision_triangles, vec![0, 1, 2, 0, 2, 3]);
+    assert_eq!(m2.attachments[0].position, [0.0, 0.0, 1.5]);
+    assert_eq!(m2.events[0].identifier, "$DTH");
+    assert_eq!(m2.lights[0].ambient_intensity.values[0].elements, vec![0.5]);
+    assert_eq!(m2.cameras[0].positions.values[0].elements[0].value, [5.0, 0.0, 1.0]);
+    assert_eq!(m2.ribbon_emitters[0].texture_indices, vec![0]);
+    assert_eq!(m2.particles[0].model_file_name, "Spells\\Spark.m2");
+    assert_eq!(m2.particles[0].alpha.values, vec![0x7FFF]);
+}
+
+#[cfg(test)]
+#[test]
+fn m2_loads_its_skin_profiles() {
+    let dir = crate::test_utils::TempDir::new("wow-file-tools-m2-skins");
+    let path = test_utils::write_test_m2(&dir);
+    let mut m2 = M2File::from_path(&path).unwrap();
+    assert_eq!(m2.get_skin_paths(&path), vec![path.with_file_name("TestModel00.skin")]);
+    m2.load_skins(&path).unwrap();
+    assert_eq!(m2.skins.len(), 1);
+    assert_eq!(m2.skins[0].submeshes[0].index_count, 6);
+}
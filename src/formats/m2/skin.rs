@@ -0,0 +1,205 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use crate::byte_utils::{VecUtils, VecWriteUtils};
+use crate::common::{R, err};
+use crate::formats::m2::array::{M2Array, M2Element, M2Reader, M2Writer};
+use std::path::Path;
+
+pub const SKIN_MAGIC: &str = "SKIN";
+
+/// A skin profile (level of detail) of a Wrath model, `<model>0N.skin`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkinFile {
+    pub magic: String,
+    // indices into the vertices of the M2
+    pub indices: Vec<u16>,
+    // three indices into `indices` per triangle
+    pub triangles: Vec<u16>,
+    pub bones: Vec<[u8; 4]>,
+    pub submeshes: Vec<M2SkinSection>,
+    pub texture_units: Vec<M2Batch>,
+    pub bone_count_max: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2SkinSection {
+    pub skin_section_id: u16,
+    pub level: u16,
+    pub vertex_start: u16,
+    pub vertex_count: u16,
+    pub index_start: u16,
+    pub index_count: u16,
+    pub bone_count: u16,
+    pub bone_combo_index: u16,
+    pub bone_influences: u16,
+    pub center_bone_index: u16,
+    pub center_position: [f32; 3],
+    pub sort_center_position: [f32; 3],
+    pub sort_radius: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2Batch {
+    pub flags: u8,
+    pub priority_plane: i8,
+    pub shader_id: u16,
+    pub skin_section_index: u16,
+    pub geoset_index: u16,
+    // -1 for none
+    pub color_index: i16,
+    pub material_index: u16,
+    pub material_layer: u16,
+    pub texture_count: u16,
+    // indices into the texture lookup of the M2, the same for the following lookups
+    pub texture_combo_index: u16,
+    pub texture_coord_combo_index: u16,
+    pub texture_weight_combo_index: u16,
+    pub texture_transform_combo_index: u16,
+}
+
+impl SkinFile {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> R<SkinFile> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .with_context(|| format!("Failed to read skin file '{}'", path.display()))?;
+        SkinFile::from_bytes(data)
+            .with_context(|| format!("Failed to parse skin file '{}'", path.display()))
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> R<SkinFile> {
+        let magic = bytes.get_string(0, 4)?;
+        if magic != SKIN_MAGIC {
+            return err(format!("Expected the {} magic, found {:?}", SKIN_MAGIC, magic));
+        }
+        let r = M2Reader { bytes: &bytes, external_sequences: Vec::new() };
+        let skin = SkinFile {
+            magic,
+            indices: M2Array::read(&r, 0x04)?.elements,
+            triangles: M2Array::read(&r, 0x0C)?.elements,
+            bones: M2Array::read(&r, 0x14)?.elements,
+            submeshes: M2Array::read(&r, 0x1C)?.elements,
+            texture_units: M2Array::read(&r, 0x24)?.elements,
+            bone_count_max: bytes.get_u32(0x2C)?,
+        };
+        if !skin.triangles.chunks_exact(3).remainder().is_empty() {
+            return err(format!("The skin has {} triangle indices, not a multiple of 3", skin.triangles.len()));
+        }
+        Ok(skin)
+    }
+
+    /// Triangles of a submesh as indices into the vertices of the M2.
+    pub fn get_submesh_triangles(&self, submesh: &M2SkinSection) -> R<Vec<[u16; 3]>> {
+        let (start, end) = submesh.get_triangle_range();
+        let triangles = match self.triangles.get(start..end) {
+            Some(triangles) => triangles,
+            None => return err(format!(
+                "Submesh {} uses the triangle indices {}..{} of {}",
+                submesh.skin_section_id, start, end, self.triangles.len()
+            )),
+        };
+        triangles.chunks(3)
+            .map(|it| {
+                let mut triangle = [0u16; 3];
+                for (corner, index) in triangle.iter_mut().zip(it.iter()) {
+                    *corner = match self.indices.get(*index as usize) {
+                        Some(vertex) => *vertex,
+                        None => return err(format!("Triangle index {} is past the {} indices", index, self.indices.len())),
+                    };
+                }
+                Ok(triangle)
+            })
+            .collect()
+    }
+}
+
+impl M2SkinSection {
+    // the starts are u16, models past 65535 indices carry the high bits in `level`
+    pub fn get_vertex_range(&self) -> (usize, usize) {
+        let start = self.vertex_start as usize + ((self.level as usize) << 16);
+        (start, start + self.vertex_count as usize)
+    }
+
+    pub fn get_triangle_range(&self) -> (usize, usize) {
+        let start = self.index_start as usize + ((self.level as usize) << 16);
+        (start, start + self.index_count as usize)
+    }
+}
+
+impl M2Element for M2SkinSection {
+    const SIZE: usize = 48;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        let b = r.bytes;
+        Ok(M2SkinSection {
+            skin_section_id: b.get_u16(offset)?,
+            level: b.get_u16(offset + 2)?,
+            vertex_start: b.get_u16(offset + 4)?,
+            vertex_count: b.get_u16(offset + 6)?,
+            index_start: b.get_u16(offset + 8)?,
+            index_count: b.get_u16(offset + 10)?,
+            bone_count: b.get_u16(offset + 12)?,
+            bone_combo_index: b.get_u16(offset + 14)?,
+            bone_influences: b.get_u16(offset + 16)?,
+            center_bone_index: b.get_u16(offset + 18)?,
+            center_position: M2Element::read(r, offset + 20)?,
+            sort_center_position: M2Element::read(r, offset + 32)?,
+            sort_radius: b.get_f32(offset + 44)?,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        for it in &[
+            self.skin_section_id, self.level, self.vertex_start, self.vertex_count, self.index_start, self.index_count,
+            self.bone_count, self.bone_combo_index, self.bone_influences, self.center_bone_index,
+        ] {
+            out.push_u16(*it);
+        }
+        self.center_position.write(w, out);
+        self.sort_center_position.write(w, out);
+        out.push_f32(self.sort_radius);
+    }
+}
+
+impl M2Element for M2Batch {
+    const SIZE: usize = 24;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        let b = r.bytes;
+        Ok(M2Batch {
+            flags: b.get_byte(offset)?,
+            priority_plane: b.get_byte(offset + 1)? as i8,
+            shader_id: b.get_u16(offset + 2)?,
+            skin_section_index: b.get_u16(offset + 4)?,
+            geoset_index: b.get_u16(offset + 6)?,
+            color_index: b.get_i16(offset + 8)?,
+            material_index: b.get_u16(offset + 10)?,
+            material_layer: b.get_u16(offset + 12)?,
+            texture_count: b.get_u16(offset + 14)?,
+            texture_combo_index: b.get_u16(offset + 16)?,
+            texture_coord_combo_index: b.get_u16(offset + 18)?,
+            texture_weight_combo_index: b.get_u16(offset + 20)?,
+            texture_transform_combo_index: b.get_u16(offset + 22)?,
+        })
+    }
+    fn write(&self, _: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push(self.flags);
+        out.push(self.priority_plane as u8);
+        out.push_u16(self.shader_id);
+        out.push_u16(self.skin_section_index);
+        out.push_u16(self.geoset_index);
+        out.push_i16(self.color_index);
+        for it in &[
+            self.material_index, self.material_layer, self.texture_count, self.texture_combo_index,
+            self.texture_coord_combo_index, self.texture_weight_combo_index, self.texture_transform_combo_index,
+        ] {
+            out.push_u16(*it);
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn skin_submesh_triangles_resolve_to_m2_vertices() {
+    let skin = SkinFile::from_bytes(crate::formats::m2::test_utils::build_test_skin()).unwrap();
+    assert_eq!(skin.indices, vec![0, 1, 2, 3]);
+    assert_eq!(skin.submeshes.len(), 1);
+    assert_eq!(skin.texture_units[0].texture_combo_index, 0);
+    assert_eq!(skin.get_submesh_triangles(&skin.submeshes[0]).unwrap(), vec![[0, 1, 2], [0, 2, 3]]);
+}
@@ -0,0 +1,297 @@
+use crate::byte_utils::VecWriteUtils;
+use crate::test_utils::TempDir;
+use std::path::PathBuf;
+
+pub const HEADER_SIZE: usize = 0x130;
+
+/// Appends the data an M2Array points at after a zeroed header.
+pub struct M2Builder {
+    pub out: Vec<u8>,
+}
+
+pub fn f32s(values: &[f32]) -> Vec<u8> {
+    let mut out = Vec::new();
+    values.iter().for_each(|v| out.push_f32(*v));
+    out
+}
+
+pub fn u16s(values: &[u16]) -> Vec<u8> {
+    let mut out = Vec::new();
+    values.iter().for_each(|v| out.push_u16(*v));
+    out
+}
+
+pub fn u32s(values: &[u32]) -> Vec<u8> {
+    let mut out = Vec::new();
+    values.iter().for_each(|v| out.push_u32(*v));
+    out
+}
+
+impl M2Builder {
+    pub fn new(version: u32) -> M2Builder {
+        let mut b = M2Builder::with_header(b"MD20", HEADER_SIZE);
+        b.out.set_u32(4, version);
+        b
+    }
+
+    pub fn with_header(magic: &[u8], header_size: usize) -> M2Builder {
+        let mut out = vec![0u8; header_size];
+        out[0..4].copy_from_slice(magic);
+        M2Builder { out }
+    }
+
+    /// Stores the data 16 aligned, returns the size and offset of its M2Array.
+    pub fn array(&mut self, count: usize, data: &[u8]) -> Vec<u8> {
+        let offset = self.out.len();
+        self.out.extend_from_slice(data);
+        self.out.resize(self.out.len() + (16 - self.out.len() % 16) % 16, 0);
+        let mut array = Vec::new();
+        array.push_u32(count as u32);
+        array.push_u32(if count == 0 { 0 } else { offset as u32 });
+        array
+    }
+
+    pub fn set_array(&mut self, at: usize, count: usize, data: &[u8]) {
+        let array = self.array(count, data);
+        self.out[at..at + 8].copy_from_slice(&array);
+    }
+
+    /// Linear M2Track, one list of timestamps and values per sequence.
+    pub fn track(&mut self, global_sequence: i16, keys: &[(Vec<u32>, Vec<u8>)]) -> Vec<u8> {
+        let lists: Vec<(Vec<u8>, Vec<u8>)> = keys.iter()
+            .map(|(timestamps, values)| (
+                self.array(timestamps.len(), &u32s(timestamps)),
+                self.array(timestamps.len(), values),
+            ))
+            .collect();
+        self.track_from_lists(global_sequence, &lists)
+    }
+
+    pub fn track_from_lists(&mut self, global_sequence: i16, lists: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+        let timestamps: Vec<u8> = lists.iter().flat_map(|it| it.0.clone()).collect();
+        let values: Vec<u8> = lists.iter().flat_map(|it| it.1.clone()).collect();
+        let mut track = Vec::new();
+        track.push_u16(1);
+        track.push_i16(global_sequence);
+        track.extend(self.array(lists.len(), &timestamps));
+        track.extend(self.array(lists.len(), &values));
+        track
+    }
+
+    pub fn empty_track(&mut self) -> Vec<u8> {
+        self.track(-1, &[])
+    }
+}
+
+/// Wrath model of a 2x2 quad on one bone, with two textures, two sequences (the second in an .anim file)
+/// and one of each attachment, event, light, camera, ribbon and particle emitter.
+pub fn build_test_m2() -> Vec<u8> {
+    build_m2(264)
+}
+
+/// The same model as a Legion one (274), its camera has the Cataclysm record with a FoV track of one key.
+pub fn build_legion_test_m2() -> Vec<u8> {
+    build_m2(274)
+}
+
+fn build_m2(version: u32) -> Vec<u8> {
+    let mut b = M2Builder::new(version);
+    b.set_array(0x08, 10, b"TestModel\0");
+    b.set_array(0x14, 1, &u32s(&[1000]));
+
+    let mut sequences = Vec::new();
+    for (id, duration, flags) in &[(0u16, 1000u32, 0x20u32), (1, 2000, 0)] {
+        sequences.push_u16(*id);
+        sequences.push_u16(0);
+        sequences.push_u32(*duration);
+        sequences.push_f32(0.0);
+        sequences.push_u32(*flags);
+        sequences.push_i16(0x7FFF);
+        sequences.push_u16(0);
+        sequences.extend(u32s(&[0, 0, 150]));
+        sequences.extend(f32s(&[-1.0, -1.0, 0.0, 1.0, 1.0, 2.0, 2.0]));
+        sequences.push_i16(-1);
+        sequences.push_u16(0);
+    }
+    b.set_array(0x1C, 2, &sequences);
+    b.set_array(0x24, 2, &u16s(&[0, 1]));
+
+    let internal = (
+        b.array(2, &u32s(&[0, 500])),
+        b.array(2, &f32s(&[0.0, 0.0, 0.0, 0.0, 0.0, 1.0])),
+    );
+    // offsets into TestModel0001-00.anim, see build_test_anim
+    let external = (u32s(&[2, 0x10]), u32s(&[2, 0x20]));
+    let mut bone = Vec::new();
+    bone.push_i32(-1);
+    bone.push_u32(0);
+    bone.push_i16(-1);
+    bone.push_u16(0);
+    bone.push_u32(0);
+    bone.extend(b.track_from_lists(-1, &[internal, external]));
+    bone.extend(b.empty_track());
+    bone.extend(b.empty_track());
+    bone.extend(f32s(&[0.0, 0.0, 0.0]));
+    b.set_array(0x2C, 1, &bone);
+    b.set_array(0x34, 1, &u16s(&[0xFFFF]));
+
+    let mut vertices = Vec::new();
+    for (x, y) in &[(-1.0f32, -1.0f32), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+        vertices.extend(f32s(&[*x, *y, 0.0]));
+        vertices.extend_from_slice(&[255, 0, 0, 0, 0, 0, 0, 0]);
+        vertices.extend(f32s(&[0.0, 0.0, 1.0]));
+        vertices.extend(f32s(&[(x + 1.0) / 2.0, (y + 1.0) / 2.0, 0.0, 0.0]));
+    }
+    b.set_array(0x3C, 4, &vertices);
+    b.out.set_u32(0x44, 1);
+
+    let texture_name = b"Creature\\Test\\Test.blp\0";
+    let mut textures = Vec::new();
+    textures.extend(u32s(&[0, 0]));
+    textures.extend(b.array(texture_name.len(), texture_name));
+    textures.extend(u32s(&[11, 0, 0, 0]));
+    b.set_array(0x50, 2, &textures);
+    b.set_array(0x68, 1, &u16s(&[0xFFFF]));
+    b.set_array(0x70, 1, &u16s(&[0, 1]));
+    b.set_array(0x78, 1, &u16s(&[0]));
+    b.set_array(0x80, 1, &u16s(&[0]));
+    b.set_array(0x88, 1, &u16s(&[0]));
+    b.set_array(0x90, 1, &u16s(&[0]));
+    b.set_array(0x98, 1, &u16s(&[0xFFFF]));
+
+    let bounds = f32s(&[-1.0, -1.0, 0.0, 1.0, 1.0, 2.0, 2.0]);
+    b.out[0xA0..0xBC].copy_from_slice(&bounds);
+    b.out[0xBC..0xD8].copy_from_slice(&bounds);
+    b.set_array(0xD8, 6, &u16s(&[0, 1, 2, 0, 2, 3]));
+    b.set_array(0xE0, 4, &f32s(&[-1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 1.0, 1.0, 0.0, -1.0, 1.0, 0.0]));
+    b.set_array(0xE8, 2, &f32s(&[0.0, 0.0, 1.0, 0.0, 0.0, 1.0]));
+
+    let mut attachment = u32s(&[0, 0]);
+    attachment.extend(f32s(&[0.0, 0.0, 1.5]));
+    attachment.extend(b.empty_track());
+    b.set_array(0xF0, 1, &attachment);
+    b.set_array(0xF8, 1, &u16s(&[0]));
+
+    let mut event = b"$DTH".to_vec();
+    event.extend(u32s(&[0, 0]));
+    event.extend(f32s(&[0.0, 0.0, 0.0]));
+    event.extend(b.empty_track()[..12].to_vec());
+    b.set_array(0x100, 1, &event);
+
+    let mut light = Vec::new();
+    light.push_u16(1);
+    light.push_i16(-1);
+    light.extend(f32s(&[0.0, 0.0, 2.0]));
+    light.extend(b.empty_track());
+    light.extend(b.track(-1, &[(vec![0], f32s(&[0.5]))]));
+    for _ in 0..5 {
+        light.extend(b.empty_track());
+    }
+    b.set_array(0x108, 1, &light);
+
+    let mut camera = u32s(&[0]);
+    if version < 272 {
+        camera.extend(f32s(&[0.95]));
+    }
+    camera.extend(f32s(&[100.0, 0.2]));
+    camera.extend(b.track(-1, &[(vec![0], f32s(&[5.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]))]));
+    camera.extend(f32s(&[0.0, 0.0, 0.0]));
+    camera.extend(b.empty_track());
+    camera.extend(f32s(&[0.0, 0.0, 1.0]));
+    camera.extend(b.empty_track());
+    if version >= 272 {
+        camera.extend(b.track(-1, &[(vec![0], f32s(&[0.95, 0.0, 0.0]))]));
+    }
+    b.set_array(0x110, 1, &camera);
+    b.set_array(0x118, 1, &u16s(&[0]));
+
+    let mut ribbon = u32s(&[0, 0]);
+    ribbon.extend(f32s(&[0.0, 0.0, 1.0]));
+    ribbon.extend(b.array(1, &u16s(&[0])));
+    ribbon.extend(b.array(1, &u16s(&[0])));
+    for _ in 0..4 {
+        ribbon.extend(b.empty_track());
+    }
+    ribbon.extend(f32s(&[30.0, 1.0, 0.0]));
+    ribbon.extend(u16s(&[1, 1]));
+    ribbon.extend(b.empty_track());
+    ribbon.extend(b.empty_track());
+    ribbon.extend(u16s(&[0, 0]));
+    b.set_array(0x120, 1, &ribbon);
+
+    let model_name = b"Spells\\Spark.m2\0";
+    let mut particle = u32s(&[7, 0]);
+    particle.extend(f32s(&[0.0, 0.0, 1.0]));
+    particle.extend(u16s(&[0, 0]));
+    particle.extend(b.array(model_name.len(), model_name));
+    particle.extend(b.array(0, &[]));
+    particle.extend_from_slice(&[2, 1, 0, 0, 0, 0, 0, 0]);
+    particle.extend(u16s(&[1, 1]));
+    for _ in 0..5 {
+        particle.extend(b.empty_track());
+    }
+    particle.extend(b.track(-1, &[(vec![0], f32s(&[2.5]))]));
+    particle.extend(f32s(&[0.0]));
+    particle.extend(b.empty_track());
+    particle.extend(f32s(&[0.0]));
+    for _ in 0..3 {
+        particle.extend(b.empty_track());
+    }
+    let mut alpha = b.array(1, &u16s(&[0]));
+    alpha.extend(b.array(1, &u16s(&[0x7FFF])));
+    particle.extend(vec![0; 16]);
+    particle.extend(alpha);
+    particle.extend(vec![0; 16]);
+    particle.extend(f32s(&[0.0, 0.0]));
+    particle.extend(vec![0; 32]);
+    particle.extend(f32s(&[0.0; 25]));
+    particle.extend(b.array(0, &[]));
+    particle.extend(b.empty_track());
+    assert_eq!(particle.len(), 476);
+    b.set_array(0x128, 1, &particle);
+    b.out
+}
+
+/// Keys of the second sequence of `build_test_m2`, the bone rises by 3 in a second.
+pub fn build_test_anim() -> Vec<u8> {
+    let mut out = vec![0u8; 0x10];
+    out.extend(u32s(&[0, 1000, 0, 0]));
+    out.extend(f32s(&[0.0, 0.0, 0.0, 0.0, 0.0, 3.0]));
+    out
+}
+
+/// Skin profile drawing the quad of `build_test_m2` as one submesh with one texture unit.
+pub fn build_test_skin() -> Vec<u8> {
+    build_skin(M2Builder::with_header(b"SKIN", 0x30))
+}
+
+/// Same skin with the Cataclysm header, one shadow batch follows the Wrath fields.
+pub fn build_cata_test_skin() -> Vec<u8> {
+    let mut b = M2Builder::with_header(b"SKIN", 0x38);
+    b.set_array(0x30, 1, &[0u8; 16]);
+    build_skin(b)
+}
+
+fn build_skin(mut b: M2Builder) -> Vec<u8> {
+    b.set_array(0x04, 4, &u16s(&[0, 1, 2, 3]));
+    b.set_array(0x0C, 6, &u16s(&[0, 1, 2, 0, 2, 3]));
+    b.set_array(0x14, 4, &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+    let mut submesh = u16s(&[0, 0, 0, 4, 0, 6, 1, 0, 1, 0]);
+    submesh.extend(f32s(&[0.0, 0.0, 0.5, 0.0, 0.0, 0.5, 1.5]));
+    b.set_array(0x1C, 1, &submesh);
+
+    let mut batch = vec![0x10, 0];
+    batch.extend(u16s(&[0, 0, 0, 0xFFFF, 0, 0, 1, 0, 0, 0, 0]));
+    b.set_array(0x24, 1, &batch);
+    b.out.set_u32(0x2C, 1);
+    b.out
+}
+
+/// Writes TestModel.m2 and TestModel00.skin to `dir`, returns the path of the M2.
+pub fn write_test_m2(dir: &TempDir) -> PathBuf {
+    std::fs::write(dir.join("TestModel00.skin"), build_test_skin()).unwrap();
+    let path = dir.join("TestModel.m2");
+    std::fs::write(&path, build_test_m2()).unwrap();
+    path
+}
@@ -0,0 +1,629 @@
+use crate::byte_utils::{VecUtils, VecWriteUtils};
+use crate::common::R;
+use crate::formats::m2::array::{
+    read_string, M2Array, M2Element, M2FakeTrack, M2Reader, M2SplineKey, M2Track, M2TrackBase, M2Writer,
+};
+use serde::{Deserialize, Serialize};
+
+// Records of the Wrath (264) header, in header order.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2Sequence {
+    // AnimationData.dbc
+    pub id: u16,
+    pub variation_index: u16,
+    pub duration: u32,
+    pub move_speed: f32,
+    pub flags: u32,
+    pub frequency: i16,
+    pub padding: u16,
+    pub replay: [u32; 2],
+    pub blend_time: u32,
+    pub bounding_box: [f32; 6],
+    pub bounding_radius: f32,
+    // -1 for none, next variation and the sequence this one aliases
+    pub variation_next: i16,
+    pub alias_next: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2Bone {
+    // -1 for none
+    pub key_bone_id: i32,
+    pub flags: u32,
+    // -1 for the root bones
+    pub parent_bone: i16,
+    pub submesh_id: u16,
+    pub bone_name_crc: u32,
+    pub translation: M2Track<[f32; 3]>,
+    pub rotation: M2Track<[i16; 4]>,
+    pub scale: M2Track<[f32; 3]>,
+    pub pivot: [f32; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct M2Vertex {
+    pub position: [f32; 3],
+    pub bone_weights: [u8; 4],
+    pub bone_indices: [u8; 4],
+    pub normal: [f32; 3],
+    pub tex_coords: [[f32; 2]; 2],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2Color {
+    pub color: M2Track<[f32; 3]>,
+    // fixed16, 0x7FFF is opaque
+    pub alpha: M2Track<i16>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2Texture {
+    // 0 uses `file_name`, the other types are replaced at runtime (skin, hair, creature display skins...)
+    pub texture_type: u32,
+    // 1 wrap x, 2 wrap y
+    pub flags: u32,
+    pub file_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2TextureTransform {
+    pub translation: M2Track<[f32; 3]>,
+    pub rotation: M2Track<[f32; 4]>,
+    pub scaling: M2Track<[f32; 3]>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct M2Material {
+    pub flags: u16,
+    pub blending_mode: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2Attachment {
+    pub id: u32,
+    pub bone: u16,
+    pub unknown: u16,
+    pub position: [f32; 3],
+    pub animate_attached: M2Track<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2Event {
+    // four characters such as $DTH
+    pub identifier: String,
+    pub data: u32,
+    pub bone: u32,
+    pub position: [f32; 3],
+    pub enabled: M2TrackBase,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2Light {
+    // 0 directional, 1 point
+    pub light_type: u16,
+    pub bone: i16,
+    pub position: [f32; 3],
+    pub ambient_color: M2Track<[f32; 3]>,
+    pub ambient_intensity: M2Track<f32>,
+    pub diffuse_color: M2Track<[f32; 3]>,
+    pub diffuse_intensity: M2Track<f32>,
+    pub attenuation_start: M2Track<f32>,
+    pub attenuation_end: M2Track<f32>,
+    pub visibility: M2Track<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2Camera {
+    pub camera_type: u32,
+    pub fov: f32,
+    pub far_clip: f32,
+    pub near_clip: f32,
+    pub positions: M2Track<M2SplineKey<[f32; 3]>>,
+    pub position_base: [f32; 3],
+    pub target_positions: M2Track<M2SplineKey<[f32; 3]>>,
+    pub target_position_base: [f32; 3],
+    pub roll: M2Track<M2SplineKey<f32>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2Ribbon {
+    pub ribbon_id: u32,
+    pub bone_index: u32,
+    pub position: [f32; 3],
+    pub texture_indices: Vec<u16>,
+    pub material_indices: Vec<u16>,
+    pub color: M2Track<[f32; 3]>,
+    pub alpha: M2Track<i16>,
+    pub height_above: M2Track<f32>,
+    pub height_below: M2Track<f32>,
+    pub edges_per_second: f32,
+    pub edge_lifetime: f32,
+    pub gravity: f32,
+    pub texture_rows: u16,
+    pub texture_cols: u16,
+    pub tex_slot: M2Track<u16>,
+    pub visibility: M2Track<u8>,
+    pub priority_plane: i16,
+    pub padding: u16,
+}
+
+impl M2Element for M2Sequence {
+    const SIZE: usize = 64;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        let b = r.bytes;
+        Ok(M2Sequence {
+            id: b.get_u16(offset)?,
+            variation_index: b.get_u16(offset + 2)?,
+            duration: b.get_u32(offset + 4)?,
+            move_speed: b.get_f32(offset + 8)?,
+            flags: b.get_u32(offset + 12)?,
+            frequency: b.get_i16(offset + 16)?,
+            padding: b.get_u16(offset + 18)?,
+            replay: [b.get_u32(offset + 20)?, b.get_u32(offset + 24)?],
+            blend_time: b.get_u32(offset + 28)?,
+            bounding_box: read_box(r, offset + 32)?,
+            bounding_radius: b.get_f32(offset + 56)?,
+            variation_next: b.get_i16(offset + 60)?,
+            alias_next: b.get_u16(offset + 62)?,
+        })
+    }
+    fn write(&self, _: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_u16(self.id);
+        out.push_u16(self.variation_index);
+        out.push_u32(self.duration);
+        out.push_f32(self.move_speed);
+        out.push_u32(self.flags);
+        out.push_i16(self.frequency);
+        out.push_u16(self.padding);
+        out.push_u32(self.replay[0]);
+        out.push_u32(self.replay[1]);
+        out.push_u32(self.blend_time);
+        write_box(&self.bounding_box, out);
+        out.push_f32(self.bounding_radius);
+        out.push_i16(self.variation_next);
+        out.push_u16(self.alias_next);
+    }
+}
+
+impl M2Element for M2Bone {
+    const SIZE: usize = 88;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        let b = r.bytes;
+        Ok(M2Bone {
+            key_bone_id: b.get_i32(offset)?,
+            flags: b.get_u32(offset + 4)?,
+            parent_bone: b.get_i16(offset + 8)?,
+            submesh_id: b.get_u16(offset + 10)?,
+            bone_name_crc: b.get_u32(offset + 12)?,
+            translation: M2Track::read(r, offset + 16)?,
+            rotation: M2Track::read(r, offset + 36)?,
+            scale: M2Track::read(r, offset + 56)?,
+            pivot: M2Element::read(r, offset + 76)?,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_i32(self.key_bone_id);
+        out.push_u32(self.flags);
+        out.push_i16(self.parent_bone);
+        out.push_u16(self.submesh_id);
+        out.push_u32(self.bone_name_crc);
+        self.translation.write(w, out);
+        self.rotation.write(w, out);
+        self.scale.write(w, out);
+        self.pivot.write(w, out);
+    }
+}
+
+impl M2Element for M2Vertex {
+    const SIZE: usize = 48;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        let b = r.bytes;
+        Ok(M2Vertex {
+            position: M2Element::read(r, offset)?,
+            bone_weights: b.get_four_bytes(offset + 12)?,
+            bone_indices: b.get_four_bytes(offset + 16)?,
+            normal: M2Element::read(r, offset + 20)?,
+            tex_coords: [M2Element::read(r, offset + 32)?, M2Element::read(r, offset + 40)?],
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        self.position.write(w, out);
+        out.extend_from_slice(&self.bone_weights);
+        out.extend_from_slice(&self.bone_indices);
+        self.normal.write(w, out);
+        self.tex_coords[0].write(w, out);
+        self.tex_coords[1].write(w, out);
+    }
+}
+
+impl M2Element for M2Color {
+    const SIZE: usize = 40;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok(M2Color {
+            color: M2Track::read(r, offset)?,
+            alpha: M2Track::read(r, offset + 20)?,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        self.color.write(w, out);
+        self.alpha.write(w, out);
+    }
+}
+
+impl M2Element for M2Texture {
+    const SIZE: usize = 16;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok(M2Texture {
+            texture_type: r.bytes.get_u32(offset)?,
+            flags: r.bytes.get_u32(offset + 4)?,
+            file_name: read_string(r, offset + 8)?,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_u32(self.texture_type);
+        out.push_u32(self.flags);
+        w.write_string(&self.file_name, out);
+    }
+}
+
+impl M2Element for M2TextureTransform {
+    const SIZE: usize = 60;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok(M2TextureTransform {
+            translation: M2Track::read(r, offset)?,
+            rotation: M2Track::read(r, offset + 20)?,
+            scaling: M2Track::read(r, offset + 40)?,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        self.translation.write(w, out);
+        self.rotation.write(w, out);
+        self.scaling.write(w, out);
+    }
+}
+
+impl M2Element for M2Material {
+    const SIZE: usize = 4;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok(M2Material {
+            flags: r.bytes.get_u16(offset)?,
+            blending_mode: r.bytes.get_u16(offset + 2)?,
+        })
+    }
+    fn write(&self, _: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_u16(self.flags);
+        out.push_u16(self.blending_mode);
+    }
+}
+
+impl M2Element for M2Attachment {
+    const SIZE: usize = 40;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok(M2Attachment {
+            id: r.bytes.get_u32(offset)?,
+            bone: r.bytes.get_u16(offset + 4)?,
+            unknown: r.bytes.get_u16(offset + 6)?,
+            position: M2Element::read(r, offset + 8)?,
+            animate_attached: M2Track::read(r, offset + 20)?,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_u32(self.id);
+        out.push_u16(self.bone);
+        out.push_u16(self.unknown);
+        self.position.write(w, out);
+        self.animate_attached.write(w, out);
+    }
+}
+
+impl M2Element for M2Event {
+    const SIZE: usize = 36;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok(M2Event {
+            identifier: r.bytes.get_four_bytes(offset)?.iter().map(|it| *it as char).collect(),
+            data: r.bytes.get_u32(offset + 4)?,
+            bone: r.bytes.get_u32(offset + 8)?,
+            position: M2Element::read(r, offset + 12)?,
+            enabled: M2TrackBase::read(r, offset + 24)?,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        let mut identifier = self.identifier.as_bytes().to_vec();
+        identifier.resize(4, 0);
+        out.extend_from_slice(&identifier);
+        out.push_u32(self.data);
+        out.push_u32(self.bone);
+        self.position.write(w, out);
+        self.enabled.write(w, out);
+    }
+}
+
+impl M2Element for M2Light {
+    const SIZE: usize = 156;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok(M2Light {
+            light_type: r.bytes.get_u16(offset)?,
+            bone: r.bytes.get_i16(offset + 2)?,
+            position: M2Element::read(r, offset + 4)?,
+            ambient_color: M2Track::read(r, offset + 16)?,
+            ambient_intensity: M2Track::read(r, offset + 36)?,
+            diffuse_color: M2Track::read(r, offset + 56)?,
+            diffuse_intensity: M2Track::read(r, offset + 76)?,
+            attenuation_start: M2Track::read(r, offset + 96)?,
+            attenuation_end: M2Track::read(r, offset + 116)?,
+            visibility: M2Track::read(r, offset + 136)?,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_u16(self.light_type);
+        out.push_i16(self.bone);
+        self.position.write(w, out);
+        self.ambient_color.write(w, out);
+        self.ambient_intensity.write(w, out);
+        self.diffuse_color.write(w, out);
+        self.diffuse_intensity.write(w, out);
+        self.attenuation_start.write(w, out);
+        self.attenuation_end.write(w, out);
+        self.visibility.write(w, out);
+    }
+}
+
+impl M2Element for M2Camera {
+    const SIZE: usize = 100;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        Ok(M2Camera {
+            camera_type: r.bytes.get_u32(offset)?,
+            fov: r.bytes.get_f32(offset + 4)?,
+            far_clip: r.bytes.get_f32(offset + 8)?,
+            near_clip: r.bytes.get_f32(offset + 12)?,
+            positions: M2Track::read(r, offset + 16)?,
+            position_base: M2Element::read(r, offset + 36)?,
+            target_positions: M2Track::read(r, offset + 48)?,
+            target_position_base: M2Element::read(r, offset + 68)?,
+            roll: M2Track::read(r, offset + 80)?,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_u32(self.camera_type);
+        out.push_f32(self.fov);
+        out.push_f32(self.far_clip);
+        out.push_f32(self.near_clip);
+        self.positions.write(w, out);
+        self.position_base.write(w, out);
+        self.target_positions.write(w, out);
+        self.target_position_base.write(w, out);
+        self.roll.write(w, out);
+    }
+}
+
+impl M2Element for M2Ribbon {
+    const SIZE: usize = 176;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        let b = r.bytes;
+        Ok(M2Ribbon {
+            ribbon_id: b.get_u32(offset)?,
+            bone_index: b.get_u32(offset + 4)?,
+            position: M2Element::read(r, offset + 8)?,
+            texture_indices: M2Array::read(r, offset + 20)?.elements,
+            material_indices: M2Array::read(r, offset + 28)?.elements,
+            color: M2Track::read(r, offset + 36)?,
+            alpha: M2Track::read(r, offset + 56)?,
+            height_above: M2Track::read(r, offset + 76)?,
+            height_below: M2Track::read(r, offset + 96)?,
+            edges_per_second: b.get_f32(offset + 116)?,
+            edge_lifetime: b.get_f32(offset + 120)?,
+            gravity: b.get_f32(offset + 124)?,
+            texture_rows: b.get_u16(offset + 128)?,
+            texture_cols: b.get_u16(offset + 130)?,
+            tex_slot: M2Track::read(r, offset + 132)?,
+            visibility: M2Track::read(r, offset + 152)?,
+            priority_plane: b.get_i16(offset + 172)?,
+            padding: b.get_u16(offset + 174)?,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_u32(self.ribbon_id);
+        out.push_u32(self.bone_index);
+        self.position.write(w, out);
+        w.write_array(&self.texture_indices, out);
+        w.write_array(&self.material_indices, out);
+        self.color.write(w, out);
+        self.alpha.write(w, out);
+        self.height_above.write(w, out);
+        self.height_below.write(w, out);
+        out.push_f32(self.edges_per_second);
+        out.push_f32(self.edge_lifetime);
+        out.push_f32(self.gravity);
+        out.push_u16(self.texture_rows);
+        out.push_u16(self.texture_cols);
+        self.tex_slot.write(w, out);
+        self.visibility.write(w, out);
+        out.push_i16(self.priority_plane);
+        out.push_u16(self.padding);
+    }
+}
+
+/// CAaBox, min xyz then max xyz.
+pub fn read_box(r: &M2Reader, offset: usize) -> R<[f32; 6]> {
+    let min: [f32; 3] = M2Element::read(r, offset)?;
+    let max: [f32; 3] = M2Element::read(r, offset + 12)?;
+    Ok([min[0], min[1], min[2], max[0], max[1], max[2]])
+}
+
+pub fn write_box(value: &[f32; 6], out: &mut Vec<u8>) {
+    value.iter().for_each(|it| out.push_f32(*it));
+}
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M2Particle {
+    pub particle_id: u32,
+    pub flags_1: u16,
+    pub flags_2: u16,
+    pub pos: [f32; 3],
+    pub bone: u16,
+    pub texture_id: u16,
+    // geometry model, emitted instead of quads
+    pub model_file_name: String,
+    // recursion model, its particle emitters are used
+    pub particle_name: String,
+    pub blending_type: u8,
+    pub emitter_type: u8,
+    pub particle_dbc_color: u16,
+    pub particle_type: u8,
+    pub head_or_tail: u8,
+    pub texture_tile_rotation: i16,
+    pub texture_dimension_rows: u16,
+    pub texture_dimension_columns: u16,
+    pub emission_speed: M2Track<f32>,
+    pub speed_variation: M2Track<f32>,
+    pub vertical_range: M2Track<f32>,
+    pub horizontal_range: M2Track<f32>,
+    pub gravity: M2Track<f32>,
+    pub lifespan: M2Track<f32>,
+    pub lifespan_vary: f32,
+    pub emission_rate: M2Track<f32>,
+    pub emission_rate_vary: f32,
+    pub emission_area_length: M2Track<f32>,
+    pub emission_area_width: M2Track<f32>,
+    pub z_source: M2Track<f32>,
+    pub color: M2FakeTrack<[f32; 3]>,
+    // fixed16
+    pub alpha: M2FakeTrack<i16>,
+    pub scale: M2FakeTrack<[f32; 2]>,
+    pub scale_vary: [f32; 2],
+    pub head_cell: M2FakeTrack<u16>,
+    pub tail_cell: M2FakeTrack<u16>,
+    pub tail_length: f32,
+    pub twinkle_speed: f32,
+    pub twinkle_percent: f32,
+    pub twinkle_scale: [f32; 2],
+    pub burst_multiplier: f32,
+    pub drag: f32,
+    pub base_spin: f32,
+    pub base_spin_vary: f32,
+    pub spin: f32,
+    pub spin_vary: f32,
+    pub tumble: [f32; 6],
+    pub wind_vector: [f32; 3],
+    pub wind_time: f32,
+    pub follow_speed_1: f32,
+    pub follow_scale_1: f32,
+    pub follow_speed_2: f32,
+    pub follow_scale_2: f32,
+    pub spline_points: Vec<[f32; 3]>,
+    pub enabled_in: M2Track<u8>,
+}
+
+impl M2Element for M2Particle {
+    const SIZE: usize = 476;
+    fn read(r: &M2Reader, offset: usize) -> R<Self> {
+        let b = r.bytes;
+        Ok(M2Particle {
+            particle_id: b.get_u32(offset)?,
+            flags_1: b.get_u16(offset + 4)?,
+            flags_2: b.get_u16(offset + 6)?,
+            pos: M2Element::read(r, offset + 8)?,
+            bone: b.get_u16(offset + 20)?,
+            texture_id: b.get_u16(offset + 22)?,
+            model_file_name: read_string(r, offset + 24)?,
+            particle_name: read_string(r, offset + 32)?,
+            blending_type: b.get_byte(offset + 40)?,
+            emitter_type: b.get_byte(offset + 41)?,
+            particle_dbc_color: b.get_u16(offset + 42)?,
+            particle_type: b.get_byte(offset + 44)?,
+            head_or_tail: b.get_byte(offset + 45)?,
+            texture_tile_rotation: b.get_i16(offset + 46)?,
+            texture_dimension_rows: b.get_u16(offset + 48)?,
+            texture_dimension_columns: b.get_u16(offset + 50)?,
+            emission_speed: M2Track::read(r, offset + 52)?,
+            speed_variation: M2Track::read(r, offset + 72)?,
+            vertical_range: M2Track::read(r, offset + 92)?,
+            horizontal_range: M2Track::read(r, offset + 112)?,
+            gravity: M2Track::read(r, offset + 132)?,
+            lifespan: M2Track::read(r, offset + 152)?,
+            lifespan_vary: b.get_f32(offset + 172)?,
+            emission_rate: M2Track::read(r, offset + 176)?,
+            emission_rate_vary: b.get_f32(offset + 196)?,
+            emission_area_length: M2Track::read(r, offset + 200)?,
+            emission_area_width: M2Track::read(r, offset + 220)?,
+            z_source: M2Track::read(r, offset + 240)?,
+            color: M2FakeTrack::read(r, offset + 260)?,
+            alpha: M2FakeTrack::read(r, offset + 276)?,
+            scale: M2FakeTrack::read(r, offset + 292)?,
+            scale_vary: M2Element::read(r, offset + 308)?,
+            head_cell: M2FakeTrack::read(r, offset + 316)?,
+            tail_cell: M2FakeTrack::read(r, offset + 332)?,
+            tail_length: b.get_f32(offset + 348)?,
+            twinkle_speed: b.get_f32(offset + 352)?,
+            twinkle_percent: b.get_f32(offset + 356)?,
+            twinkle_scale: M2Element::read(r, offset + 360)?,
+            burst_multiplier: b.get_f32(offset + 368)?,
+            drag: b.get_f32(offset + 372)?,
+            base_spin: b.get_f32(offset + 376)?,
+            base_spin_vary: b.get_f32(offset + 380)?,
+            spin: b.get_f32(offset + 384)?,
+            spin_vary: b.get_f32(offset + 388)?,
+            tumble: read_box(r, offset + 392)?,
+            wind_vector: M2Element::read(r, offset + 416)?,
+            wind_time: b.get_f32(offset + 428)?,
+            follow_speed_1: b.get_f32(offset + 432)?,
+            follow_scale_1: b.get_f32(offset + 436)?,
+            follow_speed_2: b.get_f32(offset + 440)?,
+            follow_scale_2: b.get_f32(offset + 444)?,
+            spline_points: M2Array::read(r, offset + 448)?.elements,
+            enabled_in: M2Track::read(r, offset + 456)?,
+        })
+    }
+    fn write(&self, w: &mut M2Writer, out: &mut Vec<u8>) {
+        out.push_u32(self.particle_id);
+        out.push_u16(self.flags_1);
+        out.push_u16(self.flags_2);
+        self.pos.write(w, out);
+        out.push_u16(self.bone);
+        out.push_u16(self.texture_id);
+        w.write_string(&self.model_file_name, out);
+        w.write_string(&self.particle_name, out);
+        out.push(self.blending_type);
+        out.push(self.emitter_type);
+        out.push_u16(self.particle_dbc_color);
+        out.push(self.particle_type);
+        out.push(self.head_or_tail);
+        out.push_i16(self.texture_tile_rotation);
+        out.push_u16(self.texture_dimension_rows);
+        out.push_u16(self.texture_dimension_columns);
+        self.emission_speed.write(w, out);
+        self.speed_variation.write(w, out);
+        self.vertical_range.write(w, out);
+        self.horizontal_range.write(w, out);
+        self.gravity.write(w, out);
+        self.lifespan.write(w, out);
+        out.push_f32(self.lifespan_vary);
+        self.emission_rate.write(w, out);
+        out.push_f32(self.emission_rate_vary);
+        self.emission_area_length.write(w, out);
+        self.emission_area_width.write(w, out);
+        self.z_source.write(w, out);
+        self.color.write(w, out);
+        self.alpha.write(w, out);
+        self.scale.write(w, out);
+        self.scale_vary.write(w, out);
+        self.head_cell.write(w, out);
+        self.tail_cell.write(w, out);
+        for it in &[self.tail_length, self.twinkle_speed, self.twinkle_percent] {
+            out.push_f32(*it);
+        }
+        self.twinkle_scale.write(w, out);
+        for it in &[self.burst_multiplier, self.drag, self.base_spin, self.base_spin_vary, self.spin, self.spin_vary] {
+            out.push_f32(*it);
+        }
+        write_box(&self.tumble, out);
+        self.wind_vector.write(w, out);
+        for it in &[self.wind_time, self.follow_speed_1, self.follow_scale_1, self.follow_speed_2, self.follow_scale_2] {
+            out.push_f32(*it);
+        }
+        w.write_array(&self.spline_points, out);
+        self.enabled_in.write(w, out);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::byte_utils::VecWriteUtils;
+use crate::common::R;
+use crate::formats::m2::array::{M2Writer, SEQUENCE_FLAG_DATA_IN_M2};
+use crate::formats::m2::types::write_box;
+use crate::formats::m2::{M2File, M2_FLAG_USE_TEXTURE_COMBINER_COMBOS, M2_MAGIC, M2_VERSION_WRATH};
+use anyhow::Context;
+use std::path::Path;
+
+impl M2File {
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> R<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_bytes())
+            .with_context(|| format!("Failed to write m2 file {}", path.display()))
+    }
+
+    /// Serializes the model as a Wrath M2. The header comes first and every M2Array is laid out
+    /// again behind it, so strings can change length. The key lists of sequences stored in .anim
+    /// files keep their offsets, the .anim files stay valid.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let use_combos = self.global_flags & M2_FLAG_USE_TEXTURE_COMBINER_COMBOS != 0;
+        let header_size = if use_combos { 0x138 } else { 0x130 };
+        let external_sequences = self.sequences.iter()
+            .map(|it| it.flags & SEQUENCE_FLAG_DATA_IN_M2 == 0)
+            .collect();
+        let mut w = M2Writer::new(header_size, external_sequences);
+
+        let mut h = Vec::with_capacity(header_size);
+        h.extend_from_slice(M2_MAGIC.as_bytes());
+        h.push_u32(M2_VERSION_WRATH);
+        w.write_string(&self.name, &mut h);
+        h.push_u32(self.global_flags);
+        w.write_array(&self.global_sequences, &mut h);
+        w.write_array(&self.sequences, &mut h);
+        w.write_array(&self.sequence_lookups, &mut h);
+        w.write_array(&self.bones, &mut h);
+        w.write_array(&self.key_bone_lookup, &mut h);
+        w.write_array(&self.vertices, &mut h);
+        h.push_u32(self.n_skin_profiles);
+        w.write_array(&self.colors, &mut h);
+        w.write_array(&self.textures, &mut h);
+        w.write_array(&self.texture_weights, &mut h);
+        w.write_array(&self.texture_transforms, &mut h);
+        w.write_array(&self.replaceable_texture_lookup, &mut h);
+        w.write_array(&self.materials, &mut h);
+        w.write_array(&self.bone_lookup, &mut h);
+        w.write_array(&self.texture_lookup, &mut h);
+        w.write_array(&self.tex_unit_lookup, &mut h);
+        w.write_array(&self.transparency_lookup, &mut h);
+        w.write_array(&self.texture_transform_lookup, &mut h);
+        write_box(&self.bounding_box, &mut h);
+        h.push_f32(self.bounding_sphere_radius);
+        write_box(&self.collision_box, &mut h);
+        h.push_f32(self.collision_sphere_radius);
+        w.write_array(&self.collision_triangles, &mut h);
+        w.write_array(&self.collision_vertices, &mut h);
+        w.write_array(&self.collision_normals, &mut h);
+        w.write_array(&self.attachments, &mut h);
+        w.write_array(&self.attachment_lookup, &mut h);
+        w.write_array(&self.events, &mut h);
+        w.write_array(&self.lights, &mut h);
+        w.write_array(&self.cameras, &mut h);
+        w.write_array(&self.camera_lookup, &mut h);
+        w.write_array(&self.ribbon_emitters, &mut h);
+        w.write_array(&self.particles, &mut h);
+        if use_combos {
+            w.write_array(&self.texture_combiner_combos, &mut h);
+        }
+
+        w.data[..header_size].copy_from_slice(&h);
+        w.data
+    }
+}
@@ -1,51 +1,614 @@
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
-use crate::common::R;
-use std::path::Path;
 use crate::byte_utils::VecUtils;
-use crate::formats::chunk::{Chunk, ChunkVecUtils};
+use crate::common::{R, err};
+use crate::formats::chunk::Chunk;
+use std::path::Path;
 
+pub const MDX_MAGIC: &str = "MDLX";
+// Warcraft III layout, the chunks below are read with it
+pub const MDX_VERSION: u32 = 800;
+
+const MODL_SIZE: usize = 372;
+const SEQUENCE_SIZE: usize = 132;
+const TEXTURE_SIZE: usize = 268;
+// interpolation types above linear store in and out tangents with every key
+const INTERPOLATION_HERMITE: u32 = 2;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MdxFile {
-    pub texs: ChunkTexs,
+    pub version: u32,
+    pub model: MdxModel,
+    pub sequences: Vec<MdxSequence>,
+    pub materials: Vec<MdxMaterial>,
+    pub textures: Vec<MdxTexture>,
+    pub geosets: Vec<MdxGeoset>,
+    pub bones: Vec<MdxBone>,
+    pub pivots: Vec<[f32; 3]>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MdxExtent {
+    pub bounds_radius: f32,
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MdxModel {
+    pub name: String,
+    pub animation_file_name: String,
+    pub extent: MdxExtent,
+    pub blend_time: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MdxSequence {
+    pub name: String,
+    // start and end, in milliseconds
+    pub interval: [u32; 2],
+    pub move_speed: f32,
+    // 1 non looping
+    pub flags: u32,
+    pub rarity: f32,
+    pub sync_point: u32,
+    pub extent: MdxExtent,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MdxMaterial {
+    pub priority_plane: i32,
+    pub flags: u32,
+    pub layers: Vec<MdxLayer>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MdxLayer {
+    // 0 none, 1 transparent, 2 blend, 3 additive, 4 add alpha, 5 modulate, 6 modulate 2x
+    pub filter_mode: u32,
+    pub shading_flags: u32,
+    pub texture_id: u32,
+    // -1 for none
+    pub texture_animation_id: i32,
+    pub coord_id: u32,
+    pub alpha: f32,
+    pub alpha_track: Option<MdxTrack<f32>>,
+    pub texture_id_track: Option<MdxTrack<u32>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MdxTexture {
+    // 0 uses `file_name`, 1 team color, 2 team glow, 11+ replaceable skins
+    pub replaceable_id: u32,
+    pub file_name: String,
+    // 1 wrap width, 2 wrap height
+    pub flags: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MdxGeoset {
+    pub vertices: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    // 4 for triangles, the only type models use
+    pub face_types: Vec<u32>,
+    pub face_groups: Vec<u32>,
+    pub faces: Vec<u16>,
+    // per vertex, index of its matrix group
+    pub vertex_groups: Vec<u8>,
+    pub matrix_group_sizes: Vec<u32>,
+    // bone object ids of the matrix groups, one after the other
+    pub matrix_indices: Vec<u32>,
+    pub material_id: u32,
+    pub selection_group: u32,
+    // 4 unselectable
+    pub selection_flags: u32,
+    pub extent: MdxExtent,
+    // one per sequence
+    pub sequence_extents: Vec<MdxExtent>,
+    pub uv_sets: Vec<Vec<[f32; 2]>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MdxBone {
+    pub name: String,
+    pub object_id: u32,
+    // -1 for the root bones
+    pub parent_id: i32,
+    pub flags: u32,
+    pub translation: Option<MdxTrack<[f32; 3]>>,
+    pub rotation: Option<MdxTrack<[f32; 4]>>,
+    pub scale: Option<MdxTrack<[f32; 3]>>,
+    // -1 for none
+    pub geoset_id: i32,
+    pub geoset_animation_id: i32,
+}
+
+/// Keyed value, times are in milliseconds of the global sequence or of the sequence intervals.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MdxTrack<T> {
+    // 0 none, 1 linear, 2 hermite, 3 bezier
+    pub interpolation: u32,
+    // -1 when the keys follow the sequences
+    pub global_sequence_id: i32,
+    pub keys: Vec<MdxKey<T>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ChunkTexs {
-    pub texture_list: Vec<String>,
+pub struct MdxKey<T> {
+    pub time: u32,
+    pub value: T,
+    // in and out tangents of hermite and bezier keys
+    pub tangents: Option<(T, T)>,
+}
+
+/// Reads the variable sized records of a chunk one field after the other.
+struct MdxCursor<'a> {
+    data: &'a Vec<u8>,
+    offset: usize,
+}
+
+impl<'a> MdxCursor<'a> {
+    fn new(data: &'a Vec<u8>, offset: usize) -> MdxCursor<'a> {
+        MdxCursor { data, offset }
+    }
+
+    fn u8(&mut self) -> R<u8> {
+        self.offset += 1;
+        self.data.get_byte(self.offset - 1)
+    }
+
+    fn u16(&mut self) -> R<u16> {
+        self.offset += 2;
+        self.data.get_u16(self.offset - 2)
+    }
+
+    fn u32(&mut self) -> R<u32> {
+        self.offset += 4;
+        self.data.get_u32(self.offset - 4)
+    }
+
+    fn i32(&mut self) -> R<i32> {
+        self.offset += 4;
+        self.data.get_i32(self.offset - 4)
+    }
+
+    fn f32(&mut self) -> R<f32> {
+        self.offset += 4;
+        self.data.get_f32(self.offset - 4)
+    }
+
+    fn vec2(&mut self) -> R<[f32; 2]> {
+        Ok([self.f32()?, self.f32()?])
+    }
+
+    fn vec3(&mut self) -> R<[f32; 3]> {
+        Ok([self.f32()?, self.f32()?, self.f32()?])
+    }
+
+    fn vec4(&mut self) -> R<[f32; 4]> {
+        Ok([self.f32()?, self.f32()?, self.f32()?, self.f32()?])
+    }
+
+    fn extent(&mut self) -> R<MdxExtent> {
+        Ok(MdxExtent { bounds_radius: self.f32()?, min: self.vec3()?, max: self.vec3()? })
+    }
+
+    // fixed size, null padded
+    fn string(&mut self, length: usize) -> R<String> {
+        let bytes = self.data.get(self.offset..self.offset + length)
+            .with_context(|| format!("A string of {} bytes at {} runs past the end of the chunk", length, self.offset))?;
+        self.offset += length;
+        bytes.to_vec().get_string_null_terminated(0)
+    }
+
+    fn tag(&mut self) -> R<String> {
+        let tag = self.data.get_four_bytes(self.offset)?.iter().map(|it| *it as char).collect();
+        self.offset += 4;
+        Ok(tag)
+    }
+
+    fn expect_tag(&mut self, expected: &str) -> R<()> {
+        let at = self.offset;
+        let tag = self.tag()?;
+        if tag != expected {
+            return err(format!("Expected {} at {}, found {:?}", expected, at, tag));
+        }
+        Ok(())
+    }
+
+    /// Tag, count, then `count` values.
+    fn tagged_list<T, F: Fn(&mut Self) -> R<T>>(&mut self, tag: &str, read: F) -> R<Vec<T>> {
+        self.expect_tag(tag)?;
+        let count = self.u32()? as usize;
+        (0..count).map(|_| read(self)).collect()
+    }
+
+    /// Size of the record starting here, counting its own field, returns where it ends.
+    fn inclusive_size(&mut self) -> R<usize> {
+        let start = self.offset;
+        let end = start + self.u32()? as usize;
+        if end > self.data.len() || end < self.offset {
+            return err(format!("The record at {} has an invalid size of {} bytes", start, end - start));
+        }
+        Ok(end)
+    }
+
+    fn track<T, F: Fn(&mut Self) -> R<T>>(&mut self, read: F) -> R<MdxTrack<T>> {
+        let count = self.u32()?;
+        let interpolation = self.u32()?;
+        let global_sequence_id = self.i32()?;
+        let mut keys = Vec::new();
+        for _ in 0..count {
+            let time = self.u32()?;
+            let value = read(self)?;
+            let tangents = if interpolation >= INTERPOLATION_HERMITE {
+                Some((read(self)?, read(self)?))
+            } else {
+                None
+            };
+            keys.push(MdxKey { time, value, tangents });
+        }
+        Ok(MdxTrack { interpolation, global_sequence_id, keys })
+    }
+}
+
+// records of a fixed size, the chunk must hold a whole number of them
+fn read_records<T, F: Fn(&mut MdxCursor) -> R<T>>(chunk: &Chunk, size: usize, read: F) -> R<Vec<T>> {
+    if !chunk.data.chunks_exact(size).remainder().is_empty() {
+        return err(format!("{} holds {} bytes, not a multiple of its {} byte records", chunk.get_unreversed_id_as_string(), chunk.data.len(), size));
+    }
+    let mut cursor = MdxCursor::new(&chunk.data, 0);
+    (0..chunk.data.len() / size).map(|_| read(&mut cursor)).collect()
+}
+
+// records starting with their inclusive size, until the end of the chunk
+fn read_sized_records<T, F: Fn(&mut MdxCursor, usize) -> R<T>>(chunk: &Chunk, read: F) -> R<Vec<T>> {
+    let mut cursor = MdxCursor::new(&chunk.data, 0);
+    let mut records = Vec::new();
+    while cursor.offset < chunk.data.len() {
+        let start = cursor.offset;
+        let end = cursor.inclusive_size()?;
+        records.push(read(&mut cursor, end).with_context(|| format!("Failed to read the {} record at {}", chunk.get_unreversed_id_as_string(), start))?);
+        cursor.offset = end;
+    }
+    Ok(records)
 }
 
 impl MdxFile {
     pub fn from_path<P: AsRef<Path>>(path: P) -> R<MdxFile> {
-        let _chunks = Chunk::from_path(path)?;
-        todo!("Mdx file handling is not implemented yet")
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read mdx file '{}'", path.display()))?;
+        MdxFile::from_bytes(bytes)
+            .with_context(|| format!("Failed to read mdx file '{}'", path.display()))
+    }
+
+    /// Reads the VERS, MODL, SEQS, MTLS, TEXS, GEOS, BONE and PIVT chunks, the others are skipped.
+    pub fn from_bytes(bytes: Vec<u8>) -> R<MdxFile> {
+        if bytes.get(0..4) != Some(MDX_MAGIC.as_bytes()) {
+            return err(format!("Expected the {} magic", MDX_MAGIC));
+        }
+        let chunks = Chunk::from_bytes(&bytes[4..].to_vec())?;
+        let version = match chunks.iter().find(|it| it.get_unreversed_id_as_string() == "VERS") {
+            Some(chunk) => chunk.data.get_u32(0)?,
+            None => return err("The model has no VERS chunk".to_string()),
+        };
+        if version != MDX_VERSION {
+            return err(format!("Only version {} MDX are supported, found version {}", MDX_VERSION, version));
+        }
+
+        let mut model = None;
+        let mut mdx = MdxFile {
+            version,
+            model: MdxModel {
+                name: String::new(),
+                animation_file_name: String::new(),
+                extent: MdxExtent { bounds_radius: 0.0, min: [0.0; 3], max: [0.0; 3] },
+                blend_time: 0,
+            },
+            sequences: Vec::new(),
+            materials: Vec::new(),
+            textures: Vec::new(),
+            geosets: Vec::new(),
+            bones: Vec::new(),
+            pivots: Vec::new(),
+        };
+        for chunk in &chunks {
+            let name = chunk.get_unreversed_id_as_string();
+            match name.as_str() {
+                "MODL" => model = Some(read_model(chunk)?),
+                "SEQS" => mdx.sequences = read_records(chunk, SEQUENCE_SIZE, read_sequence)?,
+                "MTLS" => mdx.materials = read_sized_records(chunk, read_material)?,
+                "TEXS" => mdx.textures = read_records(chunk, TEXTURE_SIZE, read_texture)?,
+                "GEOS" => mdx.geosets = read_sized_records(chunk, read_geoset)?,
+                "BONE" => mdx.bones = read_bones(chunk)?,
+                "PIVT" => mdx.pivots = read_records(chunk, 12, |c| c.vec3())?,
+                _ => continue,
+            }
+        }
+        mdx.model = model.context("The model has no MODL chunk")?;
+        Ok(mdx)
+    }
+
+    /// File names of the textures the model references directly, the replaceable ones have none.
+    pub fn get_texture_names(&self) -> Vec<String> {
+        self.textures.iter()
+            .filter(|it| !it.file_name.is_empty())
+            .map(|it| it.file_name.clone())
+            .collect()
     }
+}
 
-    // fn from_bytes(chunks: Vec<Chunk>) -> R<MdxFile> {
-    //     let texs = chunks.get_texs()?;
-    //     Ok(MdxFile {
-    //         texs,
-    //     })
-    // }
+fn read_model(chunk: &Chunk) -> R<MdxModel> {
+    if chunk.data.len() < MODL_SIZE {
+        return err(format!("MODL holds {} bytes, expected {}", chunk.data.len(), MODL_SIZE));
+    }
+    let mut c = MdxCursor::new(&chunk.data, 0);
+    Ok(MdxModel {
+        name: c.string(80)?,
+        animation_file_name: c.string(260)?,
+        extent: c.extent()?,
+        blend_time: c.u32()?,
+    })
 }
 
+fn read_sequence(c: &mut MdxCursor) -> R<MdxSequence> {
+    Ok(MdxSequence {
+        name: c.string(80)?,
+        interval: [c.u32()?, c.u32()?],
+        move_speed: c.f32()?,
+        flags: c.u32()?,
+        rarity: c.f32()?,
+        sync_point: c.u32()?,
+        extent: c.extent()?,
+    })
+}
 
-trait ChunkExt {
-    fn get_texs(&self) -> R<ChunkTexs>;
+fn read_texture(c: &mut MdxCursor) -> R<MdxTexture> {
+    Ok(MdxTexture {
+        replaceable_id: c.u32()?,
+        file_name: c.string(260)?,
+        flags: c.u32()?,
+    })
 }
 
-impl ChunkExt for Vec<Chunk> {
-    fn get_texs(&self) -> R<ChunkTexs> {
-        ChunkTexs::from_chunk(self.get_chunk_of_type("TEXS"))
+fn read_material(c: &mut MdxCursor, end: usize) -> R<MdxMaterial> {
+    let priority_plane = c.i32()?;
+    let flags = c.u32()?;
+    c.expect_tag("LAYS")?;
+    let layer_count = c.u32()?;
+    let mut layers = Vec::new();
+    for _ in 0..layer_count {
+        let layer_end = c.inclusive_size()?;
+        let mut layer = MdxLayer {
+            filter_mode: c.u32()?,
+            shading_flags: c.u32()?,
+            texture_id: c.u32()?,
+            texture_animation_id: c.i32()?,
+            coord_id: c.u32()?,
+            alpha: c.f32()?,
+            alpha_track: None,
+            texture_id_track: None,
+        };
+        while c.offset < layer_end {
+            match c.tag()?.as_str() {
+                "KMTA" => layer.alpha_track = Some(c.track(|c| c.f32())?),
+                "KMTF" => layer.texture_id_track = Some(c.track(|c| c.u32())?),
+                _ => break,
+            }
+        }
+        c.offset = layer_end;
+        layers.push(layer);
+    }
+    if c.offset > end {
+        return err(format!("The layers run {} bytes past the material", c.offset - end));
     }
+    Ok(MdxMaterial { priority_plane, flags, layers })
 }
 
-impl ChunkTexs {
-    fn from_chunk(chunk: &Chunk) -> R<ChunkTexs> {
-        let strs = chunk.data.get_null_terminated_strings()?;
-        println!("{:?}", strs);
-        Ok(ChunkTexs {
-            texture_list: vec![]
-        })
+fn read_geoset(c: &mut MdxCursor, _: usize) -> R<MdxGeoset> {
+    let vertices = c.tagged_list("VRTX", |c| c.vec3())?;
+    let normals = c.tagged_list("NRMS", |c| c.vec3())?;
+    let face_types = c.tagged_list("PTYP", |c| c.u32())?;
+    let face_groups = c.tagged_list("PCNT", |c| c.u32())?;
+    let faces = c.tagged_list("PVTX", |c| c.u16())?;
+    let vertex_groups = c.tagged_list("GNDX", |c| c.u8())?;
+    let matrix_group_sizes = c.tagged_list("MTGC", |c| c.u32())?;
+    let matrix_indices = c.tagged_list("MATS", |c| c.u32())?;
+    let material_id = c.u32()?;
+    let selection_group = c.u32()?;
+    let selection_flags = c.u32()?;
+    let extent = c.extent()?;
+    let extent_count = c.u32()?;
+    let sequence_extents = (0..extent_count).map(|_| c.extent()).collect::<R<Vec<MdxExtent>>>()?;
+    c.expect_tag("UVAS")?;
+    let uv_set_count = c.u32()?;
+    let uv_sets = (0..uv_set_count)
+        .map(|_| c.tagged_list("UVBS", |c| c.vec2()))
+        .collect::<R<Vec<Vec<[f32; 2]>>>>()?;
+    Ok(MdxGeoset {
+        vertices,
+        normals,
+        face_types,
+        face_groups,
+        faces,
+        vertex_groups,
+        matrix_group_sizes,
+        matrix_indices,
+        material_id,
+        selection_group,
+        selection_flags,
+        extent,
+        sequence_extents,
+        uv_sets,
+    })
+}
+
+// a node with its own inclusive size, followed by the geoset ids
+fn read_bones(chunk: &Chunk) -> R<Vec<MdxBone>> {
+    let mut c = MdxCursor::new(&chunk.data, 0);
+    let mut bones = Vec::new();
+    while c.offset < chunk.data.len() {
+        let start = c.offset;
+        let node_end = c.inclusive_size()?;
+        let mut bone = MdxBone {
+            name: c.string(80)?,
+            object_id: c.u32()?,
+            parent_id: c.i32()?,
+            flags: c.u32()?,
+            translation: None,
+            rotation: None,
+            scale: None,
+            geoset_id: -1,
+            geoset_animation_id: -1,
+        };
+        while c.offset < node_end {
+            match c.tag()?.as_str() {
+                "KGTR" => bone.translation = Some(c.track(|c| c.vec3())?),
+                "KGRT" => bone.rotation = Some(c.track(|c| c.vec4())?),
+                "KGSC" => bone.scale = Some(c.track(|c| c.vec3())?),
+                _ => break,
+            }
+        }
+        c.offset = node_end;
+        bone.geoset_id = c.i32().with_context(|| format!("Failed to read the bone at {}", start))?;
+        bone.geoset_animation_id = c.i32()?;
+        bones.push(bone);
     }
-}
\ No newline at end of file
+    Ok(bones)
+}
+
+#[cfg(test)]
+#[test]
+fn mdx_chunks_are_read() {
+    use crate::byte_utils::VecWriteUtils;
+    use crate::formats::m2::test_utils::f32s;
+    use crate::test_utils::push_chunk;
+
+    fn fixed(value: &str, length: usize) -> Vec<u8> {
+        let mut out = value.as_bytes().to_vec();
+        out.resize(length, 0);
+        out
+    }
+    fn sized(body: Vec<u8>) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.push_u32(body.len() as u32 + 4);
+        out.extend(body);
+        out
+    }
+
+    let mut bytes = b"MDLX".to_vec();
+    push_chunk(&mut bytes, b"VERS", &800u32.to_le_bytes());
+    let mut modl = fixed("Footman", 80);
+    modl.extend(fixed("", 260));
+    modl.extend(f32s(&[1.5, -1.0, -1.0, 0.0, 1.0, 1.0, 2.0]));
+    modl.push_u32(150);
+    push_chunk(&mut bytes, b"MODL", &modl);
+
+    let mut seqs = fixed("Stand", 80);
+    seqs.extend(vec![0; 4]);
+    seqs.push_u32(1000);
+    seqs.extend(f32s(&[0.0]));
+    seqs.push_u32(0);
+    seqs.extend(f32s(&[0.0]));
+    seqs.push_u32(0);
+    seqs.extend(f32s(&[1.5, -1.0, -1.0, 0.0, 1.0, 1.0, 2.0]));
+    push_chunk(&mut bytes, b"SEQS", &seqs);
+    push_chunk(&mut bytes, b"GLBS", &[0, 0, 0, 0]);
+
+    let mut layer = Vec::new();
+    for it in &[2u32, 0, 0, 0xFFFF_FFFF, 0] {
+        layer.push_u32(*it);
+    }
+    layer.extend(f32s(&[1.0]));
+    layer.extend_from_slice(b"KMTA");
+    for it in &[1u32, 1, 0xFFFF_FFFF, 0] {
+        layer.push_u32(*it);
+    }
+    layer.extend(f32s(&[0.5]));
+    let mut material = Vec::new();
+    material.push_u32(0);
+    material.push_u32(0);
+    material.extend_from_slice(b"LAYS");
+    material.push_u32(1);
+    material.extend(sized(layer));
+    push_chunk(&mut bytes, b"MTLS", &sized(material));
+
+    let mut texs = Vec::new();
+    texs.push_u32(0);
+    texs.extend(fixed("Textures\\Footman.blp", 260));
+    texs.push_u32(0);
+    texs.push_u32(1);
+    texs.extend(fixed("", 260));
+    texs.push_u32(0);
+    push_chunk(&mut bytes, b"TEXS", &texs);
+
+    let mut geoset = Vec::new();
+    geoset.extend_from_slice(b"VRTX");
+    geoset.push_u32(3);
+    geoset.extend(f32s(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]));
+    geoset.extend_from_slice(b"NRMS");
+    geoset.push_u32(3);
+    geoset.extend(f32s(&[0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0]));
+    for (tag, value) in &[(b"PTYP", 4u32), (b"PCNT", 3)] {
+        geoset.extend_from_slice(*tag);
+        geoset.push_u32(1);
+        geoset.push_u32(*value);
+    }
+    geoset.extend_from_slice(b"PVTX");
+    geoset.push_u32(3);
+    [0u16, 1, 2].iter().for_each(|it| geoset.push_u16(*it));
+    geoset.extend_from_slice(b"GNDX");
+    geoset.push_u32(3);
+    geoset.extend_from_slice(&[0, 0, 0]);
+    for tag in &[b"MTGC", b"MATS"] {
+        geoset.extend_from_slice(*tag);
+        geoset.push_u32(1);
+        geoset.push_u32(if *tag == b"MTGC" { 1 } else { 0 });
+    }
+    geoset.extend(vec![0; 12]);
+    geoset.extend(f32s(&[1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0]));
+    geoset.push_u32(0);
+    geoset.extend_from_slice(b"UVAS");
+    geoset.push_u32(1);
+    geoset.extend_from_slice(b"UVBS");
+    geoset.push_u32(3);
+    geoset.extend(f32s(&[0.0, 0.0, 1.0, 0.0, 0.0, 1.0]));
+    push_chunk(&mut bytes, b"GEOS", &sized(geoset));
+
+    let mut node = fixed("Bone_Root", 80);
+    node.push_u32(0);
+    node.push_i32(-1);
+    node.push_u32(0x100);
+    node.extend_from_slice(b"KGTR");
+    for it in &[1u32, 2, 0xFFFF_FFFF, 500] {
+        node.push_u32(*it);
+    }
+    node.extend(f32s(&[0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]));
+    let mut bone = sized(node);
+    bone.push_i32(0);
+    bone.push_i32(-1);
+    push_chunk(&mut bytes, b"BONE", &bone);
+    let mut pivots = Vec::new();
+    pivots.extend(f32s(&[0.0, 0.0, 0.5]));
+    push_chunk(&mut bytes, b"PIVT", &pivots);
+
+    let mdx = MdxFile::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(mdx.model.name, "Footman");
+    assert_eq!(mdx.sequences[0].interval, [0, 1000]);
+    assert_eq!(mdx.materials[0].layers[0].filter_mode, 2);
+    assert_eq!(mdx.materials[0].layers[0].texture_animation_id, -1);
+    assert_eq!(mdx.materials[0].layers[0].alpha_track.as_ref().unwrap().keys[0].value, 0.5);
+    assert_eq!(mdx.get_texture_names(), vec!["Textures\\Footman.blp"]);
+    assert_eq!(mdx.textures[1].replaceable_id, 1);
+    assert_eq!(mdx.geosets[0].faces, vec![0, 1, 2]);
+    assert_eq!(mdx.geosets[0].uv_sets[0][1], [1.0, 0.0]);
+    assert_eq!(mdx.bones[0].parent_id, -1);
+    let translation = mdx.bones[0].translation.as_ref().unwrap();
+    assert_eq!((translation.keys[0].time, translation.keys[0].value), (500, [0.0, 0.0, 1.0]));
+    assert!(translation.keys[0].tangents.is_some());
+    assert_eq!(mdx.pivots, vec![[0.0, 0.0, 0.5]]);
+
+    bytes[12..16].copy_from_slice(&1300u32.to_le_bytes());
+    assert!(MdxFile::from_bytes(bytes).is_err());
+}
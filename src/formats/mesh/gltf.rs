@@ -0,0 +1,276 @@
+use crate::formats::mesh::{AnimationPath, Scene};
+use serde_json::{json, Value};
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_BYTE: u32 = 5121;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+struct BufferBuilder {
+    bin: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl BufferBuilder {
+    // every view starts 4 byte aligned so any component type can follow,
+    // views of inverse bind matrices and animations have no target
+    fn push(&mut self, data: &[u8], target: Option<u32>, accessor: Value) -> usize {
+        let padding = (4 - self.bin.len() % 4) % 4;
+        self.bin.resize(self.bin.len() + padding, 0);
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.bin.len(),
+            "byteLength": data.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.buffer_views.push(view);
+        self.bin.extend_from_slice(data);
+        let mut accessor = accessor;
+        accessor["bufferView"] = json!(self.buffer_views.len() - 1);
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+}
+
+fn floats<T: AsRef<[f32]>>(values: &[T]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for value in values {
+        value.as_ref().iter().for_each(|v| bytes.extend_from_slice(&v.to_le_bytes()));
+    }
+    bytes
+}
+
+/// glTF 2.0 JSON and its binary buffer, which has to be written as `bin_file_name` next to it.
+pub fn write_gltf(scene: &Scene, bin_file_name: &str) -> (String, Vec<u8>) {
+    let mut buffer = BufferBuilder { bin: Vec::new(), buffer_views: Vec::new(), accessors: Vec::new() };
+    let mut meshes = Vec::new();
+    let mut nodes = Vec::new();
+
+    for mesh in scene.meshes.iter().filter(|it| it.batches.iter().any(|b| !b.indices.is_empty())) {
+        let count = mesh.positions.len();
+        let (min, max) = mesh.positions.iter().fold(
+            ([f32::MAX; 3], [f32::MIN; 3]),
+            |(mut min, mut max), p| {
+                for i in 0..3 {
+                    min[i] = min[i].min(p[i]);
+                    max[i] = max[i].max(p[i]);
+                }
+                (min, max)
+            },
+        );
+        let mut attributes = json!({
+            "POSITION": buffer.push(&floats(&mesh.positions), Some(ARRAY_BUFFER), json!({
+                "componentType": FLOAT, "count": count, "type": "VEC3", "min": min, "max": max,
+            })),
+        });
+        if mesh.normals.len() == count {
+            attributes["NORMAL"] = json!(buffer.push(&floats(&mesh.normals), Some(ARRAY_BUFFER), json!({
+                "componentType": FLOAT, "count": count, "type": "VEC3",
+            })));
+        }
+        if mesh.uvs.len() == count {
+            attributes["TEXCOORD_0"] = json!(buffer.push(&floats(&mesh.uvs), Some(ARRAY_BUFFER), json!({
+                "componentType": FLOAT, "count": count, "type": "VEC2",
+            })));
+        }
+        if mesh.colors.len() == count {
+            let colors: Vec<u8> = mesh.colors.iter().flatten().cloned().collect();
+            attributes["COLOR_0"] = json!(buffer.push(&colors, Some(ARRAY_BUFFER), json!({
+                "componentType": UNSIGNED_BYTE, "normalized": true, "count": count, "type": "VEC4",
+            })));
+        }
+        let skinned = !scene.joints.is_empty() && mesh.joints.len() == count && mesh.weights.len() == count;
+        if skinned {
+            let joints: Vec<u8> = mesh.joints.iter().flatten().cloned().collect();
+            attributes["JOINTS_0"] = json!(buffer.push(&joints, Some(ARRAY_BUFFER), json!({
+                "componentType": UNSIGNED_BYTE, "count": count, "type": "VEC4",
+            })));
+            let weights: Vec<u8> = mesh.weights.iter().flatten().cloned().collect();
+            attributes["WEIGHTS_0"] = json!(buffer.push(&weights, Some(ARRAY_BUFFER), json!({
+                "componentType": UNSIGNED_BYTE, "normalized": true, "count": count, "type": "VEC4",
+            })));
+        }
+
+        let primitives: Vec<Value> = mesh.batches.iter()
+            .filter(|it| !it.indices.is_empty())
+            .map(|batch| {
+                let mut indices = Vec::with_capacity(batch.indices.len() * 4);
+                batch.indices.iter().for_each(|i| indices.extend_from_slice(&i.to_le_bytes()));
+                let accessor = buffer.push(&indices, Some(ELEMENT_ARRAY_BUFFER), json!({
+                    "componentType": UNSIGNED_INT, "count": batch.indices.len(), "type": "SCALAR",
+                }));
+                let mut primitive = json!({ "attributes": attributes.clone(), "indices": accessor });
+                if let Some(material) = batch.material.filter(|it| *it < scene.materials.len()) {
+                    primitive["material"] = json!(material);
+                }
+                primitive
+            })
+            .collect();
+        meshes.push(json!({ "name": mesh.name, "primitives": primitives }));
+        let mut node = json!({ "name": mesh.name, "mesh": meshes.len() - 1 });
+        if skinned {
+            node["skin"] = json!(0);
+        }
+        nodes.push(node);
+    }
+
+    for placeholder in &scene.placeholders {
+        let s = placeholder.scale;
+        nodes.push(json!({
+            "name": placeholder.name,
+            "translation": placeholder.translation,
+            "rotation": placeholder.rotation,
+            "scale": [s, s, s],
+        }));
+    }
+
+    let mut root_nodes: Vec<usize> = (0..nodes.len()).collect();
+    let first_joint = nodes.len();
+    let mut skins = Vec::new();
+    if !scene.joints.is_empty() {
+        for (i, joint) in scene.joints.iter().enumerate() {
+            let children: Vec<usize> = scene.joints.iter()
+                .enumerate()
+                .filter(|(_, it)| it.parent == Some(i))
+                .map(|(child, _)| first_joint + child)
+                .collect();
+            let mut node = json!({ "name": joint.name, "translation": joint.translation });
+            if !children.is_empty() {
+                node["children"] = json!(children);
+            }
+            nodes.push(node);
+            if joint.parent.filter(|it| *it < scene.joints.len()).is_none() {
+                root_nodes.push(first_joint + i);
+            }
+        }
+        // column major translations by the opposite of the bind positions
+        let mut matrices = Vec::new();
+        for joint in &scene.joints {
+            let p = joint.bind_position;
+            matrices.push([1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0 - p[0], 0.0 - p[1], 0.0 - p[2], 1.0]);
+        }
+        let inverse_bind_matrices = buffer.push(&floats(&matrices), None, json!({
+            "componentType": FLOAT, "count": matrices.len(), "type": "MAT4",
+        }));
+        skins.push(json!({
+            "joints": (first_joint..first_joint + scene.joints.len()).collect::<Vec<usize>>(),
+            "inverseBindMatrices": inverse_bind_matrices,
+        }));
+    }
+
+    let mut animations = Vec::new();
+    for animation in &scene.animations {
+        let mut samplers = Vec::new();
+        let mut channels = Vec::new();
+        for channel in animation.channels.iter().filter(|it| !it.times.is_empty() && it.joint < scene.joints.len()) {
+            let (path, value_type) = match channel.path {
+                AnimationPath::Translation => ("translation", "VEC3"),
+                AnimationPath::Rotation => ("rotation", "VEC4"),
+                AnimationPath::Scale => ("scale", "VEC3"),
+            };
+            let min = channel.times.iter().cloned().fold(f32::MAX, f32::min);
+            let max = channel.times.iter().cloned().fold(f32::MIN, f32::max);
+            let input = buffer.push(&floats(&[&channel.times[..]]), None, json!({
+                "componentType": FLOAT, "count": channel.times.len(), "type": "SCALAR", "min": [min], "max": [max],
+            }));
+            let output = buffer.push(&floats(&[&channel.values[..]]), None, json!({
+                "componentType": FLOAT, "count": channel.times.len(), "type": value_type,
+            }));
+            samplers.push(json!({
+                "input": input,
+                "output": output,
+                "interpolation": if channel.step { "STEP" } else { "LINEAR" },
+            }));
+            channels.push(json!({
+                "sampler": samplers.len() - 1,
+                "target": { "node": first_joint + channel.joint, "path": path },
+            }));
+        }
+        if !channels.is_empty() {
+            animations.push(json!({ "name": animation.name, "samplers": samplers, "channels": channels }));
+        }
+    }
+
+    let mut images = Vec::new();
+    let mut materials = Vec::new();
+    for material in &scene.materials {
+        let mut pbr = json!({
+            "baseColorFactor": material.color,
+            "metallicFactor": 0.0,
+            "roughnessFactor": 1.0,
+        });
+        if let Some(texture) = &material.texture {
+            images.push(json!({ "uri": texture }));
+            pbr["baseColorTexture"] = json!({ "index": images.len() - 1 });
+        }
+        materials.push(json!({ "name": material.name, "pbrMetallicRoughness": pbr }));
+    }
+    let textures: Vec<Value> = (0..images.len()).map(|i| json!({ "source": i, "sampler": 0 })).collect();
+
+    // glTF doesn't allow empty arrays, what the scene doesn't have is left out
+    let mut gltf = json!({
+        "asset": { "version": "2.0", "generator": "wow-file-tools" },
+        "scene": 0,
+        "scenes": [{}],
+    });
+    if !root_nodes.is_empty() {
+        gltf["scenes"][0]["nodes"] = json!(root_nodes);
+        gltf["nodes"] = json!(nodes);
+    }
+    if !meshes.is_empty() {
+        gltf["meshes"] = json!(meshes);
+    }
+    if !materials.is_empty() {
+        gltf["materials"] = json!(materials);
+    }
+    if !buffer.bin.is_empty() {
+        gltf["buffers"] = json!([{ "uri": bin_file_name, "byteLength": buffer.bin.len() }]);
+        gltf["bufferViews"] = json!(buffer.buffer_views);
+        gltf["accessors"] = json!(buffer.accessors);
+    }
+    if !skins.is_empty() {
+        gltf["skins"] = json!(skins);
+    }
+    if !animations.is_empty() {
+        gltf["animations"] = json!(animations);
+    }
+    if !images.is_empty() {
+        gltf["images"] = json!(images);
+        gltf["textures"] = json!(textures);
+        // repeat on both axes
+        gltf["samplers"] = json!([{ "wrapS": 10497, "wrapT": 10497 }]);
+    }
+    (serde_json::to_string_pretty(&gltf).unwrap(), buffer.bin)
+}
+
+#[cfg(test)]
+#[test]
+fn gltf_leaves_out_empty_arrays() {
+    use crate::formats::mesh::ScenePlaceholder;
+
+    let (gltf, bin) = write_gltf(&Scene::default(), "empty.bin");
+    let gltf: Value = serde_json::from_str(&gltf).unwrap();
+    assert!(bin.is_empty());
+    assert_eq!(gltf["scenes"], json!([{}]));
+    for key in &["nodes", "meshes", "materials", "buffers", "bufferViews", "accessors", "images", "skins"] {
+        assert!(gltf.get(key).is_none(), "{}", key);
+    }
+
+    let scene = Scene {
+        placeholders: vec![ScenePlaceholder {
+            name: "World\\Generic\\Barrel.m2".to_string(),
+            translation: [1.0, 2.0, 3.0],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: 1.0,
+        }],
+        ..Default::default()
+    };
+    let gltf: Value = serde_json::from_str(&write_gltf(&scene, "doodads.bin").0).unwrap();
+    assert_eq!(gltf["scenes"][0]["nodes"], json!([0]));
+    assert_eq!(gltf["nodes"][0]["translation"], json!([1.0, 2.0, 3.0]));
+    assert!(gltf.get("meshes").is_none() && gltf.get("buffers").is_none());
+}
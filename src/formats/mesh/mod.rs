@@ -0,0 +1,173 @@
+pub mod gltf;
+pub mod obj;
+
+use serde::{Deserialize, Serialize};
+
+// Geometry ready to be written as OBJ or glTF. Both are Y up while the client files are Z up,
+// converters go through `z_up_to_y_up`. Texture coordinates keep the client convention,
+// (0, 0) is the top left corner of the texture, as in glTF.
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MeshFormat {
+    Obj,
+    Gltf,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Scene {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<MeshMaterial>,
+    // models positioned in the scene but not part of it, doodads for instance
+    pub placeholders: Vec<ScenePlaceholder>,
+    // skeleton the meshes with joints are bound to, only written to glTF
+    pub joints: Vec<SceneJoint>,
+    pub animations: Vec<SceneAnimation>,
+}
+
+impl Scene {
+    /// Adds the materials of a model placed in the scene, their names get `prefix`.
+    /// Returns the index of the first one, to give to [Scene::add_instance].
+    pub fn add_materials(&mut self, prefix: &str, materials: &[MeshMaterial]) -> usize {
+        let first = self.materials.len();
+        self.materials.extend(materials.iter().map(|it| MeshMaterial {
+            name: format!("{}_{}", prefix, it.name),
+            ..it.clone()
+        }));
+        first
+    }
+
+    /// Adds the meshes of `model` transformed by `placement`, unbound from its skeleton,
+    /// their names get `prefix`.
+    pub fn add_instance(&mut self, prefix: &str, model: &Scene, first_material: usize, placement: &ScenePlaceholder) {
+        for mesh in &model.meshes {
+            self.meshes.push(Mesh {
+                name: format!("{}_{}", prefix, mesh.name),
+                positions: mesh.positions.iter()
+                    .map(|it| {
+                        let scaled = [it[0] * placement.scale, it[1] * placement.scale, it[2] * placement.scale];
+                        let rotated = rotate(placement.rotation, scaled);
+                        [
+                            rotated[0] + placement.translation[0],
+                            rotated[1] + placement.translation[1],
+                            rotated[2] + placement.translation[2],
+                        ]
+                    })
+                    .collect(),
+                normals: mesh.normals.iter().map(|it| rotate(placement.rotation, *it)).collect(),
+                uvs: mesh.uvs.clone(),
+                colors: mesh.colors.clone(),
+                joints: Vec::new(),
+                weights: Vec::new(),
+                batches: mesh.batches.iter()
+                    .map(|it| MeshBatch {
+                        material: it.material.map(|material| material + first_material),
+                        indices: it.indices.clone(),
+                    })
+                    .collect(),
+            });
+        }
+    }
+}
+
+// v + 2w (q x v) + 2 q x (q x v) with the vector part q of the xyzw quaternion
+fn rotate(q: [f32; 4], v: [f32; 3]) -> [f32; 3] {
+    let cross = |a: [f32; 3], b: [f32; 3]| [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ];
+    let axis = [q[0], q[1], q[2]];
+    let t = cross(axis, v);
+    let u = cross(axis, t);
+    [
+        v[0] + 2.0 * (q[3] * t[0] + u[0]),
+        v[1] + 2.0 * (q[3] * t[1] + u[1]),
+        v[2] + 2.0 * (q[3] * t[2] + u[2]),
+    ]
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub name: String,
+    pub positions: Vec<[f32; 3]>,
+    // empty or one per position, same for uvs and colors
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub colors: Vec<[u8; 4]>,
+    // indices into the joints of the scene, weights sum to 255
+    pub joints: Vec<[u8; 4]>,
+    pub weights: Vec<[u8; 4]>,
+    pub batches: Vec<MeshBatch>,
+}
+
+/// Triangles sharing a material.
+#[derive(Debug, Clone)]
+pub struct MeshBatch {
+    pub material: Option<usize>,
+    pub indices: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MeshMaterial {
+    pub name: String,
+    pub color: [f32; 4],
+    // relative to the written file
+    pub texture: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScenePlaceholder {
+    pub name: String,
+    pub translation: [f32; 3],
+    // quaternion, xyzw
+    pub rotation: [f32; 4],
+    pub scale: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct SceneJoint {
+    pub name: String,
+    pub parent: Option<usize>,
+    // rest pose relative to the parent
+    pub translation: [f32; 3],
+    // where the joint sits in the bind pose, the inverse bind matrix is a translation by its opposite
+    pub bind_position: [f32; 3],
+}
+
+#[derive(Debug, Clone)]
+pub struct SceneAnimation {
+    pub name: String,
+    pub channels: Vec<AnimationChannel>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnimationPath {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+/// Keys of one joint property, `values` holds 4 floats per key for rotations (xyzw), 3 otherwise.
+#[derive(Debug, Clone)]
+pub struct AnimationChannel {
+    pub joint: usize,
+    pub path: AnimationPath,
+    pub step: bool,
+    // seconds
+    pub times: Vec<f32>,
+    pub values: Vec<f32>,
+}
+
+// 0 - y rather than -y keeps -0 out of the text formats
+pub fn z_up_to_y_up(v: [f32; 3]) -> [f32; 3] {
+    [v[0], v[2], 0.0 - v[1]]
+}
+
+/// Same rotation expressed in the Y up space.
+pub fn z_up_to_y_up_rotation(q: [f32; 4]) -> [f32; 4] {
+    [q[0], q[2], 0.0 - q[1], q[3]]
+}
+
+pub fn z_up_to_y_up_scale(v: [f32; 3]) -> [f32; 3] {
+    [v[0], v[2], v[1]]
+}
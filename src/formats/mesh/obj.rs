@@ -0,0 +1,66 @@
+use crate::formats::mesh::Scene;
+use std::fmt::Write;
+
+/// Wavefront OBJ and its MTL, vertex colors are appended to the positions.
+pub fn write_obj(scene: &Scene, mtl_file_name: &str) -> (String, String) {
+    let mut obj = String::new();
+    writeln!(obj, "# wow-file-tools").unwrap();
+    writeln!(obj, "mtllib {}", mtl_file_name).unwrap();
+
+    // OBJ indices are 1 based and shared by every object of the file
+    let (mut position_base, mut uv_base, mut normal_base) = (1, 1, 1);
+    for mesh in &scene.meshes {
+        writeln!(obj, "o {}", mesh.name).unwrap();
+        for (i, p) in mesh.positions.iter().enumerate() {
+            match mesh.colors.get(i) {
+                Some(c) => writeln!(
+                    obj,
+                    "v {} {} {} {:.4} {:.4} {:.4}",
+                    p[0], p[1], p[2], c[0] as f32 / 255.0, c[1] as f32 / 255.0, c[2] as f32 / 255.0
+                ),
+                None => writeln!(obj, "v {} {} {}", p[0], p[1], p[2]),
+            }.unwrap();
+        }
+        for uv in &mesh.uvs {
+            writeln!(obj, "vt {} {}", uv[0], 1.0 - uv[1]).unwrap();
+        }
+        for n in &mesh.normals {
+            writeln!(obj, "vn {} {} {}", n[0], n[1], n[2]).unwrap();
+        }
+
+        let has_uvs = !mesh.uvs.is_empty();
+        let has_normals = !mesh.normals.is_empty();
+        for batch in &mesh.batches {
+            if let Some(material) = batch.material.and_then(|it| scene.materials.get(it)) {
+                writeln!(obj, "usemtl {}", material.name).unwrap();
+            }
+            for triangle in batch.indices.chunks_exact(3) {
+                let corners: Vec<String> = triangle.iter()
+                    .map(|i| match (has_uvs, has_normals) {
+                        (true, true) => format!("{}/{}/{}", i + position_base, i + uv_base, i + normal_base),
+                        (true, false) => format!("{}/{}", i + position_base, i + uv_base),
+                        (false, true) => format!("{}//{}", i + position_base, i + normal_base),
+                        (false, false) => format!("{}", i + position_base),
+                    })
+                    .collect();
+                writeln!(obj, "f {}", corners.join(" ")).unwrap();
+            }
+        }
+        position_base += mesh.positions.len() as u32;
+        uv_base += mesh.uvs.len() as u32;
+        normal_base += mesh.normals.len() as u32;
+    }
+
+    let mut mtl = String::new();
+    for material in &scene.materials {
+        writeln!(mtl, "newmtl {}", material.name).unwrap();
+        let c = material.color;
+        writeln!(mtl, "Kd {} {} {}", c[0], c[1], c[2]).unwrap();
+        writeln!(mtl, "d {}", c[3]).unwrap();
+        if let Some(texture) = &material.texture {
+            writeln!(mtl, "map_Kd {}", texture).unwrap();
+        }
+        writeln!(mtl).unwrap();
+    }
+    (obj, mtl)
+}
@@ -6,3 +6,6 @@ pub mod dbc;
 pub mod wmo;
 pub mod m2;
 pub mod mdx;
+pub mod png;
+pub mod blp;
+pub mod mesh;
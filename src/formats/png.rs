@@ -0,0 +1,110 @@
+use crate::common::{R, err};
+use anyhow::Context;
+
+// Grayscale images (heightmaps) exchanged with image editors and converted textures,
+// encoded and decoded by the png crate.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrayscaleImage {
+    pub width: u32,
+    pub height: u32,
+    // row major, 8 bit images are scaled up to the 16 bit range
+    pub pixels: Vec<u16>,
+}
+
+impl GrayscaleImage {
+    pub fn new(width: u32, height: u32) -> GrayscaleImage {
+        GrayscaleImage {
+            width,
+            height,
+            pixels: vec![0; (width * height) as usize],
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> u16 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, value: u16) {
+        self.pixels[(y * self.width + x) as usize] = value;
+    }
+
+    /// Encodes as a 16 bit grayscale PNG.
+    pub fn to_png(&self) -> R<Vec<u8>> {
+        let data: Vec<u8> = self.pixels.iter().flat_map(|px| px.to_be_bytes().to_vec()).collect();
+        encode(self.width, self.height, png::ColorType::Grayscale, png::BitDepth::Sixteen, &data)
+    }
+
+    /// Decodes a 8 or 16 bit grayscale PNG.
+    pub fn from_png(bytes: &[u8]) -> R<GrayscaleImage> {
+        let mut reader = png::Decoder::new(bytes).read_info().context("Failed to read the PNG header")?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).context("Failed to read the PNG image data")?;
+        let bit_depth = info.bit_depth as u8;
+        if info.color_type != png::ColorType::Grayscale || !(bit_depth == 8 || bit_depth == 16) {
+            return err(format!(
+                "Only 8 or 16 bit grayscale PNGs are supported, got {:?} with bit depth {}",
+                info.color_type, bit_depth
+            ));
+        }
+
+        let data = &data[..info.buffer_size()];
+        let pixels = if bit_depth == 16 {
+            data.chunks(2).map(|px| u16::from_be_bytes([px[0], px[1]])).collect()
+        } else {
+            data.iter().map(|px| *px as u16 * 257).collect()
+        };
+        Ok(GrayscaleImage { width: info.width, height: info.height, pixels })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    // row major, 4 bytes per pixel
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Encodes as a 8 bit RGBA PNG.
+    pub fn to_png(&self) -> R<Vec<u8>> {
+        encode(self.width, self.height, png::ColorType::Rgba, png::BitDepth::Eight, &self.pixels)
+    }
+}
+
+fn encode(width: u32, height: u32, color_type: png::ColorType, bit_depth: png::BitDepth, data: &[u8]) -> R<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width, height);
+    encoder.set_color(color_type);
+    encoder.set_depth(bit_depth);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data).context("Failed to encode the PNG image data")?;
+    writer.finish()?;
+    Ok(out)
+}
+
+#[cfg(test)]
+#[test]
+fn png_round_trip() {
+    let mut image = GrayscaleImage::new(129, 3);
+    for y in 0..3 {
+        for x in 0..129 {
+            image.set(x, y, (x * 500 + y * 7) as u16);
+        }
+    }
+    let png = image.to_png().unwrap();
+    assert_eq!(&png[1..4], b"PNG");
+    assert_eq!(GrayscaleImage::from_png(&png).unwrap(), image);
+    // the image data is deflated, not only stored
+    let flat = GrayscaleImage::new(129, 129).to_png().unwrap();
+    assert!(flat.len() < 129 * 129 * 2 / 10);
+    assert_eq!(GrayscaleImage::from_png(&flat).unwrap(), GrayscaleImage::new(129, 129));
+
+    let rgba = RgbaImage { width: 2, height: 1, pixels: vec![255, 0, 0, 255, 0, 0, 255, 128] };
+    let encoded = rgba.to_png().unwrap();
+    let mut reader = png::Decoder::new(&encoded[..]).read_info().unwrap();
+    let mut data = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut data).unwrap();
+    assert_eq!(data, rgba.pixels);
+}
@@ -1,20 +1,245 @@
 use serde::{Deserialize, Serialize};
-use crate::common::R;
+use crate::byte_utils::{VecUtils, VecWriteUtils};
+use crate::common::{R, err};
+use crate::formats::adt::heightmap::TileHeightmap;
+use crate::formats::chunk::{Chunk, ChunkVecUtils, ChunkMver, ChunkMwmo, ChunkMwid, ChunkModf};
+use anyhow::Context;
+use std::collections::HashMap;
 use std::path::{Path};
-use crate::formats::chunk::{Chunk, ChunkVecUtils, ChunkMwmo};
+
+pub const WDL_VERSION: u32 = 18;
+// 17x17 heights on the MCNK corners followed by 16x16 on their centers
+const MARE_OUTER_SIZE: usize = 17;
+const MARE_INNER_SIZE: usize = 16;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WdlFile {
+    pub mver: ChunkMver,
     pub mwmo: ChunkMwmo,
+    pub mwid: Option<ChunkMwid>,
+    pub modf: Option<ChunkModf>,
+    // tiles MAOF points at, MAOF itself is rebuilt when writing
+    pub tiles: Vec<WdlTile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WdlTile {
+    pub x: u32,
+    pub y: u32,
+    pub mare: ChunkMare,
+    pub maho: Option<ChunkMaho>,
+}
+
+/// Low resolution heights of a tile, row major.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkMare {
+    pub outer: Vec<i16>,
+    pub inner: Vec<i16>,
 }
 
+/// One row per MCNK row, bit x is set when MCNK x of that row is a hole.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkMaho(pub [u16; 16]);
+
 impl WdlFile {
     pub fn from_path<P: AsRef<Path>>(path: P) -> R<WdlFile> {
         let chunks = Chunk::from_path(path)?;
+        WdlFile::new(chunks)
+    }
+
+    fn new(chunks: Vec<Chunk>) -> R<WdlFile> {
+        let mver = chunks.get_mver_chunk()?;
         let mwmo = chunks.get_mwmo();
+        let mwid = chunks.get_chunk_of_type_optionally("MWID").map(ChunkMwid::from_chunk);
+        let modf = chunks.get_modf();
+        let tiles = match chunks.get_chunk_of_type_optionally("MAOF") {
+            Some(maof) => read_tiles(&chunks, maof)?,
+            None => Vec::new(),
+        };
         Ok(WdlFile {
+            mver,
             mwmo,
+            mwid,
+            modf,
+            tiles,
+        })
+    }
+
+    pub fn new_empty() -> WdlFile {
+        WdlFile {
+            mver: ChunkMver { map_version: WDL_VERSION },
+            mwmo: ChunkMwmo(Vec::new()),
+            mwid: Some(ChunkMwid(Vec::new())),
+            modf: Some(ChunkModf(Vec::new())),
+            tiles: Vec::new(),
+        }
+    }
+
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> R<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_bytes()?)
+            .with_context(|| format!("Failed to write wdl file {}", path.display()))
+    }
+
+    pub fn to_bytes(&self) -> R<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.mver.to_chunk().write_to(&mut bytes);
+        self.mwmo.to_chunk().write_to(&mut bytes);
+        if let Some(mwid) = &self.mwid {
+            mwid.to_chunk().write_to(&mut bytes);
+        }
+        if let Some(modf) = &self.modf {
+            modf.to_chunk().write_to(&mut bytes);
+        }
+
+        // MAOF holds the absolute offset of every MARE, tiles follow it in grid order
+        let maof_start = bytes.len();
+        Chunk::new("MAOF", vec![0; 4096 * 4]).write_to(&mut bytes);
+        let mut tiles: Vec<&WdlTile> = self.tiles.iter().collect();
+        tiles.sort_by_key(|it| (it.y, it.x));
+        for tile in tiles {
+            if tile.x >= 64 || tile.y >= 64 {
+                return err(format!("WDL tile {}_{} is outside of the 64x64 map grid", tile.x, tile.y));
+            }
+            let index = (tile.y * 64 + tile.x) as usize;
+            let offset = bytes.len() as u32;
+            bytes.set_u32(maof_start + 8 + index * 4, offset);
+            tile.mare.to_chunk()?.write_to(&mut bytes);
+            if let Some(maho) = &tile.maho {
+                maho.to_chunk().write_to(&mut bytes);
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+impl WdlTile {
+    /// Samples the MCNK corners and centers of a tile, a MAHO bit is set for MCNKs entirely made of holes.
+    pub fn from_heightmap(x: u32, y: u32, heightmap: &TileHeightmap, mcnk_holes: &[(u32, u32, u16)]) -> WdlTile {
+        let to_i16 = |h: f32| h.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        let mut outer = Vec::with_capacity(MARE_OUTER_SIZE * MARE_OUTER_SIZE);
+        for row in 0..MARE_OUTER_SIZE {
+            for col in 0..MARE_OUTER_SIZE {
+                outer.push(to_i16(heightmap.get(col * 8, row * 8)));
+            }
+        }
+        let mut inner = Vec::with_capacity(MARE_INNER_SIZE * MARE_INNER_SIZE);
+        for row in 0..MARE_INNER_SIZE {
+            for col in 0..MARE_INNER_SIZE {
+                inner.push(to_i16(heightmap.get(col * 8 + 4, row * 8 + 4)));
+            }
+        }
+
+        let mut maho = [0u16; 16];
+        for (index_x, index_y, holes) in mcnk_holes {
+            if *holes == 0xFFFF && *index_x < 16 && *index_y < 16 {
+                maho[*index_y as usize] |= 1 << index_x;
+            }
+        }
+        WdlTile {
+            x,
+            y,
+            mare: ChunkMare { outer, inner },
+            maho: Some(ChunkMaho(maho)),
+        }
+    }
+}
+
+impl ChunkMare {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMare> {
+        assert_eq!(c.get_id_as_string(), "MARE");
+        let n_outer = MARE_OUTER_SIZE * MARE_OUTER_SIZE;
+        let n_inner = MARE_INNER_SIZE * MARE_INNER_SIZE;
+        let heights = (0..n_outer + n_inner)
+            .map(|i| c.data.get_i16(i * 2))
+            .collect::<R<Vec<i16>>>()
+            .context("MARE is too short")?;
+        Ok(ChunkMare {
+            outer: heights[..n_outer].to_vec(),
+            inner: heights[n_outer..].to_vec(),
         })
     }
+
+    pub fn to_chunk(&self) -> R<Chunk> {
+        if self.outer.len() != MARE_OUTER_SIZE * MARE_OUTER_SIZE || self.inner.len() != MARE_INNER_SIZE * MARE_INNER_SIZE {
+            return err(format!("MARE needs 289 outer and 256 inner heights, got {} and {}", self.outer.len(), self.inner.len()));
+        }
+        let mut data = Vec::with_capacity((self.outer.len() + self.inner.len()) * 2);
+        self.outer.iter().chain(self.inner.iter()).for_each(|h| data.push_i16(*h));
+        Ok(Chunk::new("MARE", data))
+    }
 }
 
+impl ChunkMaho {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMaho> {
+        assert_eq!(c.get_id_as_string(), "MAHO");
+        let mut rows = [0; 16];
+        for (i, row) in rows.iter_mut().enumerate() {
+            *row = c.data.get_u16(i * 2).context("MAHO is too short")?;
+        }
+        Ok(ChunkMaho(rows))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(32);
+        self.0.iter().for_each(|row| data.push_u16(*row));
+        Chunk::new("MAHO", data)
+    }
+}
+
+// MAOF offsets point at the MARE chunk headers, MAHO directly follows its MARE
+fn read_tiles(chunks: &[Chunk], maof: &Chunk) -> R<Vec<WdlTile>> {
+    let mut chunk_offsets = HashMap::new();
+    let mut offset = 0;
+    for (index, chunk) in chunks.iter().enumerate() {
+        chunk_offsets.insert(offset, index);
+        offset += 8 + chunk.data.len() as u32;
+    }
+
+    let mut tiles = Vec::new();
+    for index in 0..4096 {
+        let offset = maof.data.get_u32(index * 4).context("MAOF is too short")?;
+        if offset == 0 {
+            continue;
+        }
+        let (x, y) = (index as u32 % 64, index as u32 / 64);
+        let chunk_index = *chunk_offsets.get(&offset)
+            .with_context(|| format!("MAOF offset {} of tile {}_{} doesn't point at a chunk", offset, x, y))?;
+        let mare = &chunks[chunk_index];
+        if mare.get_id_as_string() != "MARE" {
+            return err(format!("MAOF offset {} of tile {}_{} points at {} instead of MARE", offset, x, y, mare.get_id_as_string()));
+        }
+        let maho = chunks.get(chunk_index + 1)
+            .filter(|it| it.get_id_as_string() == "MAHO")
+            .map(ChunkMaho::from_chunk)
+            .transpose()?;
+        tiles.push(WdlTile {
+            x,
+            y,
+            mare: ChunkMare::from_chunk(mare)?,
+            maho,
+        });
+    }
+    Ok(tiles)
+}
+
+#[cfg(test)]
+#[test]
+fn wdl_tile_from_heightmap_round_trips() {
+    use crate::formats::adt::AdtFile;
+
+    let adt = AdtFile::from_bytes(&crate::formats::adt::test_utils::build_test_adt()).unwrap();
+    let heightmap = adt.get_heightmap().unwrap();
+    let tile = WdlTile::from_heightmap(3, 5, &heightmap, &[(2, 1, 0xFFFF), (4, 1, 0x0001)]);
+    assert_eq!(tile.mare.outer[0], heightmap.get(0, 0).round() as i16);
+    assert_eq!(tile.mare.inner[17], heightmap.get(12, 12).round() as i16);
+    assert_eq!(tile.maho.as_ref().unwrap().0[1], 1 << 2);
+
+    let mut wdl = WdlFile::new_empty();
+    wdl.tiles.push(tile.clone());
+    let reparsed = WdlFile::new(Chunk::from_bytes(&wdl.to_bytes().unwrap()).unwrap()).unwrap();
+    assert_eq!(reparsed.tiles.len(), 1);
+    assert_eq!((reparsed.tiles[0].x, reparsed.tiles[0].y), (3, 5));
+    assert_eq!(reparsed.tiles[0].mare, tile.mare);
+    assert_eq!(reparsed.tiles[0].maho, tile.maho);
+}
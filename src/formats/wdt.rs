@@ -1,13 +1,20 @@
-use crate::formats::chunk::{Chunk, ChunkVecUtils, ChunkMver, ChunkMphd, ChunkMain, ChunkMwmo, ChunkModf};
+use crate::formats::chunk::{
+    Chunk, ChunkVecUtils, ChunkMver, ChunkMphd, MphdFlags, ChunkMain, ChunkMainItem, ChunkMaid, ChunkMwmo, ChunkModf,
+    strings_to_offsets,
+};
 use crate::common::R;
+use anyhow::Context;
 use serde::{Serialize, Deserialize};
 use std::path::Path;
 
+pub const WDT_VERSION: u32 = 18;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WdtFile {
     pub mver: ChunkMver,
     pub mphd: ChunkMphd,
     pub main: ChunkMain,
+    pub maid: Option<ChunkMaid>,
     pub mwmo: ChunkMwmo,
     pub modf: Option<ChunkModf>,
 }
@@ -22,14 +29,118 @@ impl WdtFile {
         let mver = chunks.get_mver_chunk()?;
         let mphd = chunks.get_mphd_chunk();
         let main = chunks.get_main();
+        let maid = chunks.get_maid();
         let mwmo = chunks.get_mwmo();
         let modf = chunks.get_modf();
-        Ok(WdtFile {
+        let mut wdt = WdtFile {
             mver,
             mphd,
             main,
+            maid,
             mwmo,
             modf,
-        })
+        };
+        wdt.resolve_map_object_names();
+        Ok(wdt)
+    }
+
+    /// Empty map with the given MPHD flags, tiles are added through `main`.
+    pub fn new_empty(flags: MphdFlags) -> WdtFile {
+        WdtFile {
+            mver: ChunkMver { map_version: WDT_VERSION },
+            mphd: ChunkMphd { flags, something: 0, unused: [0; 6] },
+            main: ChunkMain(vec![ChunkMainItem::default(); 4096]),
+            maid: None,
+            mwmo: ChunkMwmo(Vec::new()),
+            modf: None,
+        }
+    }
+
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> R<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_bytes())
+            .with_context(|| format!("Failed to write wdt file {}", path.display()))
+    }
+
+    /// MWMO is always written, empty unless the map is a single WMO.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut chunks = vec![self.mver.to_chunk(), self.mphd.to_chunk(), self.main.to_chunk()];
+        if let Some(maid) = &self.maid {
+            chunks.push(maid.to_chunk());
+        }
+        chunks.push(self.mwmo.to_chunk());
+        if let Some(modf) = &self.modf {
+            chunks.push(modf.to_chunk());
+        }
+        let mut bytes = Vec::new();
+        chunks.iter().for_each(|it| it.write_to(&mut bytes));
+        bytes
+    }
+
+    // there is no MWID, mwid_entry is the offset of the name in MWMO
+    fn resolve_map_object_names(&mut self) {
+        let names = &self.mwmo.0;
+        let offsets = strings_to_offsets(names);
+        if let Some(modf) = self.modf.as_mut() {
+            for item in modf.0.iter_mut() {
+                item.name = offsets.iter()
+                    .position(|it| *it == item.mwid_entry)
+                    .and_then(|index| names.get(index))
+                    .cloned();
+            }
+        }
     }
 }
+
+#[cfg(test)]
+#[test]
+fn wdt_decodes_flags_grid_and_map_object() {
+    use crate::formats::chunk::ChunkModfItem;
+
+    let mut flags = MphdFlags::from_u32(0x1 | 0x4 | 0x1000);
+    assert!(flags.uses_global_map_obj && flags.adt_has_big_alpha && !flags.adt_has_vertex_shading);
+    assert_eq!(flags.other, 0x1000);
+    flags.has_maid = true;
+    assert_eq!(flags.to_u32(), 0x1205);
+
+    let mphd = ChunkMphd { flags, something: 0, unused: [0; 6] };
+    let mut main = ChunkMain(vec![ChunkMainItem::default(); 4096]);
+    main.0[3 * 64 + 2].flags = 1;
+    let mut maid = vec![0u8; 4096 * 32];
+    maid[(3 * 64 + 2) * 32..(3 * 64 + 2) * 32 + 4].copy_from_slice(&775971u32.to_le_bytes());
+    let modf = ChunkModf(vec![ChunkModfItem {
+        mwid_entry: 0,
+        name: None,
+        unique_id: 1,
+        position: [17066.666, 0.0, 17066.666],
+        rotation: [0.0; 3],
+        extents: [0.0; 6],
+        flags: 0,
+        doodad_set: 0,
+        name_set: 0,
+        scale: 0,
+    }]);
+    let wdt = WdtFile::new(vec![
+        ChunkMver { map_version: 18 }.to_chunk(),
+        mphd.to_chunk(),
+        main.to_chunk(),
+        Chunk::new("MAID", maid),
+        ChunkMwmo(vec!["World\\wmo\\Dungeon\\Test.wmo".to_string()]).to_chunk(),
+        modf.to_chunk(),
+    ]).unwrap();
+
+    assert_eq!(wdt.mphd.flags.to_u32(), 0x1205);
+    assert_eq!(wdt.main.get_tiles(), vec![(2, 3)]);
+    assert!(wdt.main.has_adt(2, 3) && !wdt.main.has_adt(3, 2));
+    let maid = wdt.maid.as_ref().unwrap();
+    assert_eq!((maid.0.len(), maid.0[0].x, maid.0[0].y, maid.0[0].root_adt), (1, 2, 3, 775971));
+    assert_eq!(wdt.modf.as_ref().unwrap().0[0].name.as_deref(), Some("World\\wmo\\Dungeon\\Test.wmo"));
+
+    let reparsed = WdtFile::new(Chunk::from_bytes(&wdt.to_bytes()).unwrap()).unwrap();
+    assert_eq!(reparsed.maid.unwrap().0[0].root_adt, 775971);
+    assert_eq!(reparsed.modf.unwrap().0[0].position, [17066.666, 0.0, 17066.666]);
+
+    let grid: crate::formats::chunk::ChunkMainGrid = wdt.main.clone().into();
+    assert_eq!(&grid.grid[3][..4], "..#.");
+    assert_eq!(ChunkMain::from(grid).get_tiles(), vec![(2, 3)]);
+}
@@ -0,0 +1,355 @@
+use crate::byte_utils::VecWriteUtils;
+use crate::formats::adt::UNIT_SIZE;
+use crate::formats::mesh::{z_up_to_y_up, Mesh, MeshBatch, Scene};
+use crate::formats::wmo::group::*;
+use crate::formats::wmo::root::ChunkMopr;
+use crate::formats::wmo::{WmoFile, WmoGroupFile};
+use serde::{Deserialize, Serialize};
+
+pub const COLLISION_MAGIC: &[u8; 4] = b"WCOL";
+pub const COLLISION_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CollisionFormat {
+    Obj,
+    // see `WmoCollision::to_bytes`
+    Binary,
+}
+
+/// What the server side needs of a WMO: the collidable triangles of every group with their
+/// BSP tree, the liquid and the portals between groups. Coordinates are the client ones (Z up).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WmoCollision {
+    pub groups: Vec<CollisionGroup>,
+    pub portals: Vec<CollisionPortal>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CollisionGroup {
+    pub index: u32,
+    pub flags: u32,
+    // WMOAreaTable
+    pub unique_id: u32,
+    pub bounding_box: [f32; 6],
+    // only the vertices the triangles use
+    pub vertices: Vec<[f32; 3]>,
+    pub triangles: Vec<CollisionTriangle>,
+    // MOBN with the leaves pointing into `bsp_faces`
+    pub bsp_nodes: Vec<ChunkMobnItem>,
+    // indices of `triangles`
+    pub bsp_faces: Vec<u32>,
+    // indices of `WmoCollision::portals`
+    pub portals: Vec<u32>,
+    pub liquid: Option<CollisionLiquid>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CollisionTriangle {
+    pub indices: [u32; 3],
+    // MOPY flags
+    pub flags: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CollisionLiquid {
+    pub x_vertices: u32,
+    pub y_vertices: u32,
+    pub x_tiles: u32,
+    pub y_tiles: u32,
+    pub base: [f32; 3],
+    // MOGP group liquid
+    pub liquid_type: u32,
+    // x_vertices * y_vertices, row major
+    pub heights: Vec<f32>,
+    // x_tiles * y_tiles, MLIQ_TILE_NO_LIQUID in the low nibble for dry tiles
+    pub tiles: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CollisionPortal {
+    pub vertices: Vec<[f32; 3]>,
+    pub normal: [f32; 3],
+    pub distance: f32,
+    // (group index, side of the plane the group is on)
+    pub groups: Vec<(u16, i16)>,
+}
+
+impl WmoFile {
+    pub fn to_collision(&self) -> WmoCollision {
+        let root = &self.root;
+        let portals = root.mopt.0.iter()
+            .enumerate()
+            .map(|(i, portal)| {
+                let start = portal.start_vertex as usize;
+                let end = (start + portal.n_vertices as usize).min(root.mopv.0.len());
+                CollisionPortal {
+                    vertices: root.mopv.0.get(start..end).unwrap_or(&[]).to_vec(),
+                    normal: portal.normal,
+                    distance: portal.distance,
+                    groups: root.mopr.0.iter()
+                        .filter(|it| it.portal_index as usize == i)
+                        .map(|it| (it.group_index, it.side))
+                        .collect(),
+                }
+            })
+            .collect();
+
+        let groups = self.groups.iter()
+            .zip(self.get_loaded_group_indices())
+            .map(|(group, index)| group.to_collision(index as u32, &root.mopr))
+            .collect();
+        WmoCollision { groups, portals }
+    }
+}
+
+impl WmoGroupFile {
+    /// `mopr` is the root's, the portal range of MOGP indexes it rather than the portals.
+    pub fn to_collision(&self, index: u32, mopr: &ChunkMopr) -> CollisionGroup {
+        let mut vertices = Vec::new();
+        let mut vertex_map = vec![None; self.movt.0.len()];
+        let mut triangles = Vec::new();
+        // MOPY index -> index in `triangles`
+        let mut triangle_map = vec![None; self.mopy.0.len()];
+        for (i, (material, corners)) in self.mopy.0.iter().zip(self.movi.0.chunks_exact(3)).enumerate() {
+            if !material.is_collidable() || corners.iter().any(|it| *it as usize >= self.movt.0.len()) {
+                continue;
+            }
+            let mut indices = [0; 3];
+            for (index, corner) in indices.iter_mut().zip(corners) {
+                let corner = *corner as usize;
+                *index = *vertex_map[corner].get_or_insert_with(|| {
+                    vertices.push(self.movt.0[corner]);
+                    vertices.len() as u32 - 1
+                });
+            }
+            triangle_map[i] = Some(triangles.len() as u32);
+            triangles.push(CollisionTriangle { indices, flags: material.flags });
+        }
+
+        // leaves are pointed at the remaining triangles
+        let refs = self.mobr.as_ref().map(|it| it.0.as_slice()).unwrap_or(&[]);
+        let mut bsp_faces = Vec::new();
+        let bsp_nodes = self.mobn.as_ref()
+            .map(|it| it.0.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|mut node| {
+                if node.is_leaf() {
+                    let start = (node.face_start as usize).min(refs.len());
+                    let end = (start + node.n_faces as usize).min(refs.len());
+                    let face_start = bsp_faces.len();
+                    bsp_faces.extend(refs[start..end].iter().filter_map(|it| triangle_map.get(*it as usize).cloned().flatten()));
+                    node.face_start = face_start as u32;
+                    node.n_faces = (bsp_faces.len() - face_start) as u16;
+                }
+                node
+            })
+            .collect();
+
+        let liquid = self.mliq.as_ref().map(|mliq| CollisionLiquid {
+            x_vertices: mliq.x_vertices,
+            y_vertices: mliq.y_vertices,
+            x_tiles: mliq.x_tiles,
+            y_tiles: mliq.y_tiles,
+            base: mliq.base,
+            liquid_type: self.mogp.group_liquid,
+            heights: mliq.vertices.iter().map(|it| it.height).collect(),
+            tiles: mliq.tiles.clone(),
+        });
+
+        let mogp = &self.mogp;
+        let portal_start = (mogp.portal_start as usize).min(mopr.0.len());
+        let portal_end = (portal_start + mogp.portal_count as usize).min(mopr.0.len());
+        CollisionGroup {
+            index,
+            flags: mogp.flags,
+            unique_id: mogp.unique_id,
+            bounding_box: mogp.bounding_box,
+            vertices,
+            triangles,
+            bsp_nodes,
+            bsp_faces,
+            portals: mopr.0[portal_start..portal_end].iter().map(|it| it.portal_index as u32).collect(),
+            liquid,
+        }
+    }
+}
+
+impl WmoCollision {
+    /// Little endian, counts are u32 and precede their items:
+    /// `"WCOL" version n_groups group* n_portals portal*`
+    /// - group: index flags unique_id bounding_box[6] n_vertices vertex[3]* n_triangles (index[3] flags)*
+    ///   n_bsp_nodes node* n_bsp_faces face* n_portals portal_index* has_liquid liquid?
+    /// - node: the 16 bytes of MOBN, leaves index the group's face list
+    /// - liquid: x_vertices y_vertices x_tiles y_tiles base[3] liquid_type height* tile(u8)*
+    /// - portal: n_vertices vertex[3]* normal[3] distance n_groups (group_index u16, side i16)*
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(COLLISION_MAGIC);
+        out.push_u32(COLLISION_VERSION);
+
+        out.push_u32(self.groups.len() as u32);
+        for group in &self.groups {
+            [group.index, group.flags, group.unique_id].iter().for_each(|v| out.push_u32(*v));
+            group.bounding_box.iter().for_each(|v| out.push_f32(*v));
+            out.push_u32(group.vertices.len() as u32);
+            group.vertices.iter().flatten().for_each(|v| out.push_f32(*v));
+            out.push_u32(group.triangles.len() as u32);
+            for triangle in &group.triangles {
+                triangle.indices.iter().for_each(|v| out.push_u32(*v));
+                out.push_u32(triangle.flags as u32);
+            }
+            out.push_u32(group.bsp_nodes.len() as u32);
+            group.bsp_nodes.iter().for_each(|it| it.write_to(&mut out));
+            out.push_u32(group.bsp_faces.len() as u32);
+            group.bsp_faces.iter().for_each(|v| out.push_u32(*v));
+            out.push_u32(group.portals.len() as u32);
+            group.portals.iter().for_each(|v| out.push_u32(*v));
+
+            out.push_u32(group.liquid.is_some() as u32);
+            if let Some(liquid) = &group.liquid {
+                [liquid.x_vertices, liquid.y_vertices, liquid.x_tiles, liquid.y_tiles].iter().for_each(|v| out.push_u32(*v));
+                liquid.base.iter().for_each(|v| out.push_f32(*v));
+                out.push_u32(liquid.liquid_type);
+                liquid.heights.iter().for_each(|v| out.push_f32(*v));
+                out.extend_from_slice(&liquid.tiles);
+            }
+        }
+
+        out.push_u32(self.portals.len() as u32);
+        for portal in &self.portals {
+            out.push_u32(portal.vertices.len() as u32);
+            portal.vertices.iter().flatten().for_each(|v| out.push_f32(*v));
+            portal.normal.iter().for_each(|v| out.push_f32(*v));
+            out.push_f32(portal.distance);
+            out.push_u32(portal.groups.len() as u32);
+            for (group, side) in &portal.groups {
+                out.push_u16(*group);
+                out.push_i16(*side);
+            }
+        }
+        out
+    }
+
+    /// One object per group for the collision triangles, one per group liquid and one per portal.
+    pub fn to_scene(&self) -> Scene {
+        let mut meshes = Vec::new();
+        for group in &self.groups {
+            meshes.push(Mesh {
+                name: format!("{:03}_collision", group.index),
+                positions: group.vertices.iter().map(|it| z_up_to_y_up(*it)).collect(),
+                batches: vec![MeshBatch {
+                    material: None,
+                    indices: group.triangles.iter().flat_map(|it| it.indices.to_vec()).collect(),
+                }],
+                ..Default::default()
+            });
+            if let Some(liquid) = &group.liquid {
+                meshes.push(liquid.to_mesh(format!("{:03}_liquid", group.index)));
+            }
+        }
+        for (i, portal) in self.portals.iter().enumerate() {
+            // portals are convex, a fan covers them
+            let indices = (1..portal.vertices.len().max(2) as u32 - 1)
+                .flat_map(|it| vec![0, it, it + 1])
+                .collect();
+            meshes.push(Mesh {
+                name: format!("portal_{:03}", i),
+                positions: portal.vertices.iter().map(|it| z_up_to_y_up(*it)).collect(),
+                batches: vec![MeshBatch { material: None, indices }],
+                ..Default::default()
+            });
+        }
+        Scene { meshes, ..Default::default() }
+    }
+}
+
+impl CollisionLiquid {
+    fn to_mesh(&self, name: String) -> Mesh {
+        let positions = self.heights.iter()
+            .enumerate()
+            .map(|(i, height)| {
+                let x = (i as u32 % self.x_vertices) as f32;
+                let y = (i as u32 / self.x_vertices) as f32;
+                z_up_to_y_up([self.base[0] + x * UNIT_SIZE, self.base[1] + y * UNIT_SIZE, *height])
+            })
+            .collect();
+        let mut indices = Vec::new();
+        for (i, tile) in self.tiles.iter().enumerate() {
+            if tile & 0x0F == MLIQ_TILE_NO_LIQUID {
+                continue;
+            }
+            let (x, y) = (i as u32 % self.x_tiles, i as u32 / self.x_tiles);
+            let corner = y * self.x_vertices + x;
+            let (right, below) = (corner + 1, corner + self.x_vertices);
+            indices.extend_from_slice(&[corner, right, below, below, right, below + 1]);
+        }
+        Mesh {
+            name,
+            positions,
+            batches: vec![MeshBatch { material: None, indices }],
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn wmo_collision_keeps_collidable_triangles_and_remaps_the_bsp() {
+    use crate::formats::wmo::test_utils;
+    use crate::test_utils::TempDir;
+
+    let dir = TempDir::new("wow-file-tools-wmo-collision");
+    let mut wmo = WmoFile::from_path(test_utils::write_test_wmo(&dir)).unwrap();
+    // the second rendered triangle becomes a detail one, which does not collide
+    wmo.groups[0].mopy.0[1].flags |= MOPY_FLAG_DETAIL;
+    let collision = wmo.to_collision();
+
+    let group = &collision.groups[0];
+    assert_eq!(group.triangles.len(), 2);
+    assert_eq!(group.triangles[1].flags, MOPY_FLAG_COLLISION);
+    // vertex 3 was only used by the detail triangle
+    assert_eq!(group.vertices.len(), 4);
+    assert_eq!(group.triangles[1].indices, [0, 2, 3]);
+    assert_eq!(group.vertices[3], [0.0, 0.0, 5.0]);
+    assert_eq!((group.bsp_nodes[0].face_start, group.bsp_nodes[0].n_faces), (0, 2));
+    assert_eq!(group.bsp_faces, vec![0, 1]);
+    let liquid = group.liquid.as_ref().unwrap();
+    assert_eq!((liquid.liquid_type, liquid.heights.clone()), (15, vec![1.0; 4]));
+
+    let bytes = collision.to_bytes();
+    assert_eq!(&bytes[..4], COLLISION_MAGIC);
+    // header, group header, 4 vertices, 2 triangles, 1 node, 2 faces, no portals, 1 liquid, no portals
+    let expected = 12 + 36 + 4 + 48 + 4 + 32 + 4 + 16 + 4 + 8 + 4 + 4 + (16 + 12 + 4 + 16 + 1) + 4;
+    assert_eq!(bytes.len(), expected);
+
+    let scene = collision.to_scene();
+    assert_eq!(scene.meshes[0].batches[0].indices, vec![0, 1, 2, 0, 2, 3]);
+    assert_eq!(scene.meshes[1].name, "000_liquid");
+    assert_eq!(scene.meshes[1].batches[0].indices, vec![0, 1, 2, 2, 1, 3]);
+}
+
+#[cfg(test)]
+#[test]
+fn wmo_collision_group_portals_go_through_mopr() {
+    use crate::formats::wmo::root::{ChunkMoprItem, ChunkMoptItem};
+    use crate::formats::wmo::test_utils;
+    use crate::test_utils::TempDir;
+
+    let dir = TempDir::new("wow-file-tools-wmo-collision-portals");
+    let mut wmo = WmoFile::from_path(test_utils::write_test_wmo(&dir)).unwrap();
+    for _ in 0..2 {
+        wmo.root.mopt.0.push(ChunkMoptItem { start_vertex: 0, n_vertices: 0, normal: [1.0, 0.0, 0.0], distance: 0.0 });
+    }
+    // the first reference belongs to another group, the group's range starts at 1
+    for (portal_index, group_index, side) in &[(0u16, 1u16, 1i16), (1, 0, -1), (0, 0, 1)] {
+        wmo.root.mopr.0.push(ChunkMoprItem { portal_index: *portal_index, group_index: *group_index, side: *side, filler: 0 });
+    }
+    wmo.groups[0].mogp.portal_start = 1;
+    wmo.groups[0].mogp.portal_count = 2;
+
+    let collision = wmo.to_collision();
+    assert_eq!(collision.groups[0].portals, vec![1, 0]);
+    assert_eq!(collision.portals[0].groups, vec![(1, 1), (0, 1)]);
+    assert_eq!(collision.portals[1].groups, vec![(0, -1)]);
+}
@@ -0,0 +1,110 @@
+use crate::common::{R, err};
+use crate::formats::wmo::root::{ChunkModdItem, ChunkModsItem};
+use crate::formats::wmo::{WmoFile, WmoRootFile};
+
+fn same_path(a: &str, b: &str) -> bool {
+    a.replace('/', "\\").eq_ignore_ascii_case(&b.replace('/', "\\"))
+}
+
+impl WmoRootFile {
+    /// Points every material using the `from` texture at `to`, returns the number of replaced references.
+    pub fn replace_texture(&mut self, from: &str, to: &str) -> usize {
+        let mut replaced = 0;
+        for material in self.momt.0.iter_mut() {
+            for name in [&mut material.texture_1_name, &mut material.texture_2_name, &mut material.texture_3_name].iter_mut() {
+                if name.as_deref().filter(|it| same_path(it, from)).is_some() {
+                    **name = Some(to.to_string());
+                    replaced += 1;
+                }
+            }
+        }
+        for name in self.motx.0.iter_mut().filter(|it| same_path(it, from)) {
+            *name = to.to_string();
+        }
+        replaced
+    }
+
+    /// Appends a doodad set with its doodads at the end of MODD, returns the index of the set.
+    pub fn add_doodad_set(&mut self, name: &str, doodads: Vec<ChunkModdItem>) -> R<usize> {
+        if self.mods.0.iter().any(|it| it.name == name) {
+            return err(format!("The WMO already has a doodad set named {}", name));
+        }
+        self.mods.0.push(ChunkModsItem {
+            name: name.to_string(),
+            start_index: self.modd.0.len() as u32,
+            n_doodads: doodads.len() as u32,
+            padding: 0,
+        });
+        self.modd.0.extend(doodads);
+        Ok(self.mods.0.len() - 1)
+    }
+
+    /// Removes a doodad set and its doodads, the ranges of the following sets are shifted down.
+    pub fn remove_doodad_set(&mut self, name: &str) -> R<()> {
+        let index = match self.mods.0.iter().position(|it| it.name == name) {
+            Some(index) => index,
+            None => return err(format!("The WMO has no doodad set named {}", name)),
+        };
+        let start = self.mods.0[index].start_index;
+        let n_doodads = self.mods.0[index].n_doodads;
+        let end = start + n_doodads;
+        if end as usize > self.modd.0.len() {
+            return err(format!("Doodad set {} ends past the {} doodads of MODD", name, self.modd.0.len()));
+        }
+        let overlapping = self.mods.0.iter()
+            .enumerate()
+            .find(|(i, it)| *i != index && it.start_index < end && start < it.start_index + it.n_doodads);
+        if let Some((_, other)) = overlapping {
+            return err(format!("Doodad set {} shares doodads with {}", name, other.name));
+        }
+
+        self.mods.0.remove(index);
+        self.modd.0.drain(start as usize..end as usize);
+        for set in self.mods.0.iter_mut().filter(|it| it.start_index >= end) {
+            set.start_index -= n_doodads;
+        }
+        Ok(())
+    }
+}
+
+impl WmoFile {
+    pub fn set_group_name(&mut self, index: usize, name: &str) -> R<()> {
+        let info = match self.root.mogi.0.get_mut(index) {
+            Some(info) => info,
+            None => return err(format!("The WMO has {} groups, there is no group {}", self.root.mogi.0.len(), index)),
+        };
+        info.name = Some(name.to_string());
+        if let Some(position) = self.get_loaded_group_indices().iter().position(|it| *it == index) {
+            self.groups[position].mogp.name = Some(name.to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn wmo_edits_survive_a_round_trip() {
+    let dir = crate::test_utils::TempDir::new("wow-file-tools-wmo-edit");
+    let path = crate::formats::wmo::test_utils::write_test_wmo(&dir);
+    let mut wmo = WmoFile::from_path(&path).unwrap();
+
+    assert_eq!(wmo.root.replace_texture("dungeons/textures/wall.blp", "Custom\\Stone.blp"), 3);
+    wmo.set_group_name(0, "Great Hall").unwrap();
+    let mut doodad = wmo.root.modd.0[0].clone();
+    doodad.name = Some("World\\Generic\\Crate.m2".to_string());
+    assert_eq!(wmo.root.add_doodad_set("Set_Crates", vec![doodad]).unwrap(), 1);
+    wmo.root.remove_doodad_set("Set_$DefaultGlobal").unwrap();
+    wmo.write_to_path(path.with_file_name("edited.wmo")).unwrap();
+
+    let edited = WmoFile::from_path(path.with_file_name("edited.wmo")).unwrap();
+    assert_eq!(edited.root.motx.0, vec!["Custom\\Stone.blp"]);
+    assert_eq!(edited.root.momt.0[0].texture_1_name.as_deref(), Some("Custom\\Stone.blp"));
+    assert_eq!(edited.root.mogi.0[0].name.as_deref(), Some("Great Hall"));
+    assert_eq!(edited.groups[0].mogp.name.as_deref(), Some("Great Hall"));
+    assert_eq!(edited.root.mogn.0, vec!["Great Hall"]);
+    assert_eq!(edited.root.mods.0.len(), 1);
+    assert_eq!((edited.root.mods.0[0].start_index, edited.root.mods.0[0].n_doodads), (0, 1));
+    assert_eq!(edited.root.modd.0[0].name.as_deref(), Some("World\\Generic\\Crate.m2"));
+    assert_eq!(edited.root.modn.0, vec!["World\\Generic\\Crate.m2"]);
+    assert_eq!((edited.root.mohd.n_doodads, edited.root.mohd.n_models, edited.root.mohd.n_doodad_sets), (1, 1, 1));
+}
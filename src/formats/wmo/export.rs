@@ -0,0 +1,105 @@
+use crate::formats::mesh::{z_up_to_y_up, z_up_to_y_up_rotation, Mesh, MeshBatch, MeshMaterial, Scene, ScenePlaceholder};
+use crate::formats::wmo::WmoFile;
+
+impl WmoFile {
+    /// One mesh per group with a batch per MOBA, collision only triangles are left out.
+    /// `texture_uri` gives what a material references for a MOTX path, if anything.
+    /// With `include_doodads` the doodads of the first set, the default one, become placeholders.
+    pub fn to_scene<F: Fn(&str) -> Option<String>>(&self, texture_uri: F, include_doodads: bool) -> Scene {
+        let materials = self.root.momt.0.iter()
+            .enumerate()
+            .map(|(i, material)| {
+                let texture = material.texture_1_name.as_deref();
+                let stem = texture
+                    .and_then(|it| it.rsplit(&['\\', '/'][..]).next())
+                    .and_then(|it| it.split('.').next())
+                    .unwrap_or("untextured");
+                MeshMaterial {
+                    name: format!("{}_{}", i, stem),
+                    color: [1.0; 4],
+                    texture: texture.and_then(&texture_uri),
+                }
+            })
+            .collect();
+
+        let meshes = self.groups.iter()
+            .zip(self.get_loaded_group_indices())
+            .map(|(group, i)| {
+                let name = self.root.mogi.0.get(i)
+                    .and_then(|it| it.name.clone())
+                    .map(|it| format!("{:03}_{}", i, it))
+                    .unwrap_or_else(|| format!("{:03}", i));
+                let batches = group.moba.0.iter()
+                    .map(|batch| {
+                        let start = batch.start_index as usize;
+                        let end = (start + batch.n_indices as usize).min(group.movi.0.len());
+                        MeshBatch {
+                            material: Some(batch.material_id as usize),
+                            indices: group.movi.0.get(start..end)
+                                .unwrap_or(&[])
+                                .iter()
+                                .map(|it| *it as u32)
+                                .collect(),
+                        }
+                    })
+                    .collect();
+                Mesh {
+                    name,
+                    positions: group.movt.0.iter().map(|it| z_up_to_y_up(*it)).collect(),
+                    normals: group.monr.0.iter().map(|it| z_up_to_y_up(*it)).collect(),
+                    uvs: group.motv.first().map(|it| it.0.clone()).unwrap_or_default(),
+                    colors: group.mocv.as_ref()
+                        .map(|it| it.0.iter().map(|c| [c.r, c.g, c.b, c.a]).collect())
+                        .unwrap_or_default(),
+                    batches,
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        let mut placeholders = Vec::new();
+        if let Some(set) = self.root.mods.0.first().filter(|_| include_doodads) {
+            let start = set.start_index as usize;
+            let end = (start + set.n_doodads as usize).min(self.root.modd.0.len());
+            for (i, doodad) in self.root.modd.0.get(start..end).unwrap_or(&[]).iter().enumerate() {
+                placeholders.push(ScenePlaceholder {
+                    name: doodad.name.clone().unwrap_or_else(|| format!("doodad_{}", start + i)),
+                    translation: z_up_to_y_up(doodad.position),
+                    rotation: z_up_to_y_up_rotation(doodad.rotation),
+                    scale: doodad.scale,
+                });
+            }
+        }
+        Scene { meshes, materials, placeholders, ..Default::default() }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn wmo_to_scene_keeps_rendered_triangles() {
+    use crate::formats::chunk::Chunk;
+    use crate::formats::mesh::{gltf::write_gltf, obj::write_obj};
+    use crate::formats::wmo::{test_utils, WmoGroupFile, WmoRootFile};
+
+    let root = WmoRootFile::new(Chunk::from_bytes(&test_utils::build_test_wmo_root()).unwrap()).unwrap();
+    let group = WmoGroupFile::new(Chunk::from_bytes(&test_utils::build_test_wmo_group()).unwrap()).unwrap();
+    let wmo = WmoFile { root, groups: vec![group], loaded_group_files: vec![], group_errors: vec![] };
+
+    let scene = wmo.to_scene(|path| Some(format!("textures/{}.png", path)), true);
+    assert_eq!(scene.meshes[0].name, "000_Hall");
+    assert_eq!(scene.meshes[0].batches[0].indices, vec![0, 1, 2, 2, 1, 3]);
+    assert_eq!(scene.meshes[0].positions[2], [0.0, 0.0, -10.0]);
+    assert_eq!(scene.materials[0].name, "0_Wall");
+    assert_eq!(scene.placeholders[0].translation, [5.0, 0.0, -5.0]);
+
+    let (obj, mtl) = write_obj(&scene, "test.mtl");
+    assert!(obj.contains("f 1/1/1 2/2/2 3/3/3\n"));
+    assert!(!obj.contains(" 5/5/5"));
+    assert!(mtl.contains("map_Kd textures/Dungeons\\Textures\\Wall.blp.png"));
+
+    let (gltf, bin) = write_gltf(&scene, "test.bin");
+    let gltf: serde_json::Value = serde_json::from_str(&gltf).unwrap();
+    assert_eq!(gltf["buffers"][0]["byteLength"], bin.len());
+    assert_eq!(gltf["accessors"][0]["max"], serde_json::json!([10.0, 5.0, 0.0]));
+    assert_eq!(gltf["nodes"][1]["name"], "World\\Generic\\Barrel.m2");
+}
@@ -0,0 +1,504 @@
+use crate::byte_utils::{VecUtils, VecWriteUtils};
+use crate::common::{R, err};
+use crate::formats::chunk::Chunk;
+use crate::formats::wmo::root::{records, get_vec3, vec3s_to_chunk, WmoColor};
+use serde::{Deserialize, Serialize};
+
+pub const MOGP_HEADER_SIZE: usize = 68;
+
+// MOGP flags following which optional sub chunks are present
+pub const MOGP_FLAG_HAS_BSP: u32 = 0x1;
+pub const MOGP_FLAG_HAS_VERTEX_COLORS: u32 = 0x4;
+pub const MOGP_FLAG_HAS_LIGHTS: u32 = 0x200;
+pub const MOGP_FLAG_HAS_DOODADS: u32 = 0x800;
+pub const MOGP_FLAG_HAS_WATER: u32 = 0x1000;
+pub const MOGP_FLAG_HAS_TWO_MOTV: u32 = 0x2000000;
+
+// MOPY flags
+pub const MOPY_FLAG_NO_CAMERA_COLLIDE: u8 = 0x02;
+pub const MOPY_FLAG_DETAIL: u8 = 0x04;
+pub const MOPY_FLAG_COLLISION: u8 = 0x08;
+pub const MOPY_FLAG_RENDER: u8 = 0x20;
+// material id of collision only triangles
+pub const MOPY_MATERIAL_NONE: u8 = 0xFF;
+
+// MOBN flags, the low bits give the axis of the splitting plane
+pub const MOBN_FLAG_AXIS_MASK: u16 = 0x3;
+pub const MOBN_FLAG_LEAF: u16 = 0x4;
+pub const MOBN_NO_CHILD: i16 = -1;
+
+// MLIQ tiles use the low nibble for the liquid type, 0xF means no liquid
+pub const MLIQ_TILE_NO_LIQUID: u8 = 0x0F;
+
+/// Header of the MOGP chunk, the remainder of the chunk holds the group sub chunks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMogp {
+    // MOGN offsets, the names are resolved when loaded along with the root
+    pub group_name: u32,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub descriptive_group_name: u32,
+    #[serde(default)]
+    pub descriptive_name: Option<String>,
+    pub flags: u32,
+    pub bounding_box: [f32; 6],
+    // range of MOPR
+    pub portal_start: u16,
+    pub portal_count: u16,
+    pub trans_batch_count: u16,
+    pub int_batch_count: u16,
+    pub ext_batch_count: u16,
+    pub padding: u16,
+    // MFOG indices
+    pub fog_ids: [u8; 4],
+    pub group_liquid: u32,
+    // WMOAreaTable
+    pub unique_id: u32,
+    pub flags_2: u32,
+    pub unused: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMopy(pub Vec<ChunkMopyItem>);
+
+/// One per triangle.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ChunkMopyItem {
+    pub flags: u8,
+    // MOMT index, MOPY_MATERIAL_NONE for collision only triangles
+    pub material_id: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMovi(pub Vec<u16>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMovt(pub Vec<[f32; 3]>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMonr(pub Vec<[f32; 3]>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMotv(pub Vec<[f32; 2]>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMoba(pub Vec<ChunkMobaItem>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMobaItem {
+    // rough min xyz then max xyz used for culling
+    pub bounding_box: [i16; 6],
+    // range of MOVI
+    pub start_index: u32,
+    pub n_indices: u16,
+    // range of MOVT
+    pub min_index: u16,
+    pub max_index: u16,
+    pub flags: u8,
+    pub material_id: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkModr(pub Vec<u16>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMobn(pub Vec<ChunkMobnItem>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMobnItem {
+    // 0 YZ plane, 1 XZ plane, 2 XY plane, 4 leaf
+    pub flags: u16,
+    // MOBN_NO_CHILD for none
+    pub neg_child: i16,
+    pub pos_child: i16,
+    // range of MOBR, leaves only
+    pub n_faces: u16,
+    pub face_start: u32,
+    pub plane_distance: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMobr(pub Vec<u16>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMocv(pub Vec<WmoColor>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMliq {
+    pub x_vertices: u32,
+    pub y_vertices: u32,
+    pub x_tiles: u32,
+    pub y_tiles: u32,
+    pub base: [f32; 3],
+    pub material_id: u16,
+    // x_vertices * y_vertices, row major
+    pub vertices: Vec<MliqVertex>,
+    // x_tiles * y_tiles, row major
+    pub tiles: Vec<u8>,
+}
+
+/// Flow data for water, texture coordinates for magma and slime.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MliqVertex {
+    pub data: [u8; 4],
+    pub height: f32,
+}
+
+impl ChunkMogp {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMogp> {
+        assert_eq!(c.get_id_as_string(), "MOGP");
+        let data = &c.data;
+        if data.len() < MOGP_HEADER_SIZE {
+            return err(format!("MOGP is {} bytes long, its header alone takes {}", data.len(), MOGP_HEADER_SIZE));
+        }
+        let mut bounding_box = [0.0; 6];
+        for (i, value) in bounding_box.iter_mut().enumerate() {
+            *value = data.get_f32(12 + i * 4)?;
+        }
+        Ok(ChunkMogp {
+            group_name: data.get_u32(0)?,
+            name: None,
+            descriptive_group_name: data.get_u32(4)?,
+            descriptive_name: None,
+            flags: data.get_u32(8)?,
+            bounding_box,
+            portal_start: data.get_u16(36)?,
+            portal_count: data.get_u16(38)?,
+            trans_batch_count: data.get_u16(40)?,
+            int_batch_count: data.get_u16(42)?,
+            ext_batch_count: data.get_u16(44)?,
+            padding: data.get_u16(46)?,
+            fog_ids: data.get_four_bytes(48)?,
+            group_liquid: data.get_u32(52)?,
+            unique_id: data.get_u32(56)?,
+            flags_2: data.get_u32(60)?,
+            unused: data.get_u32(64)?,
+        })
+    }
+
+    /// Sub chunks following the header.
+    pub fn get_sub_chunks(c: &Chunk) -> R<Vec<Chunk>> {
+        match c.data.get(MOGP_HEADER_SIZE..) {
+            Some(rest) if !rest.is_empty() => Chunk::from_bytes(&rest.to_vec()),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    pub fn to_chunk(&self, sub_chunks: &[Chunk]) -> Chunk {
+        let mut data = Vec::with_capacity(MOGP_HEADER_SIZE);
+        data.push_u32(self.group_name);
+        data.push_u32(self.descriptive_group_name);
+        data.push_u32(self.flags);
+        self.bounding_box.iter().for_each(|v| data.push_f32(*v));
+        [
+            self.portal_start,
+            self.portal_count,
+            self.trans_batch_count,
+            self.int_batch_count,
+            self.ext_batch_count,
+            self.padding,
+        ].iter().for_each(|v| data.push_u16(*v));
+        data.extend_from_slice(&self.fog_ids);
+        [self.group_liquid, self.unique_id, self.flags_2, self.unused].iter().for_each(|v| data.push_u32(*v));
+        for chunk in sub_chunks {
+            chunk.write_to(&mut data);
+        }
+        Chunk::new("MOGP", data)
+    }
+}
+
+impl ChunkMopyItem {
+    pub fn is_render(&self) -> bool {
+        self.flags & MOPY_FLAG_RENDER != 0 && self.flags & MOPY_FLAG_DETAIL == 0
+    }
+
+    /// Same test as the client, collision flagged triangles plus every non detail rendered one.
+    pub fn is_collidable(&self) -> bool {
+        self.flags & MOPY_FLAG_COLLISION != 0 || self.is_render()
+    }
+}
+
+impl ChunkMobnItem {
+    pub fn is_leaf(&self) -> bool {
+        self.flags & MOBN_FLAG_LEAF != 0
+    }
+
+    /// 0 for x, 1 for y and 2 for z, the plane is `axis = plane_distance`.
+    pub fn get_axis(&self) -> usize {
+        (self.flags & MOBN_FLAG_AXIS_MASK) as usize
+    }
+
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        out.push_u16(self.flags);
+        out.push_i16(self.neg_child);
+        out.push_i16(self.pos_child);
+        out.push_u16(self.n_faces);
+        out.push_u32(self.face_start);
+        out.push_f32(self.plane_distance);
+    }
+}
+
+impl ChunkMopy {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMopy> {
+        assert_eq!(c.get_id_as_string(), "MOPY");
+        let items = records(c, 2)?
+            .map(|data| ChunkMopyItem { flags: data[0], material_id: data[1] })
+            .collect();
+        Ok(ChunkMopy(items))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = self.0.iter().flat_map(|it| vec![it.flags, it.material_id]).collect();
+        Chunk::new("MOPY", data)
+    }
+}
+
+fn get_u16s(c: &Chunk) -> R<Vec<u16>> {
+    records(c, 2)?.map(|data| data.get_u16(0)).collect()
+}
+
+fn u16s_to_chunk(chunk_type: &str, values: &[u16]) -> Chunk {
+    let mut data = Vec::with_capacity(values.len() * 2);
+    values.iter().for_each(|v| data.push_u16(*v));
+    Chunk::new(chunk_type, data)
+}
+
+impl ChunkMovi {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMovi> {
+        assert_eq!(c.get_id_as_string(), "MOVI");
+        Ok(ChunkMovi(get_u16s(c)?))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        u16s_to_chunk("MOVI", &self.0)
+    }
+}
+
+impl ChunkMovt {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMovt> {
+        assert_eq!(c.get_id_as_string(), "MOVT");
+        Ok(ChunkMovt(records(c, 12)?.map(|data| get_vec3(&data, 0)).collect::<R<_>>()?))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        vec3s_to_chunk("MOVT", &self.0)
+    }
+}
+
+impl ChunkMonr {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMonr> {
+        assert_eq!(c.get_id_as_string(), "MONR");
+        Ok(ChunkMonr(records(c, 12)?.map(|data| get_vec3(&data, 0)).collect::<R<_>>()?))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        vec3s_to_chunk("MONR", &self.0)
+    }
+}
+
+impl ChunkMotv {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMotv> {
+        assert_eq!(c.get_id_as_string(), "MOTV");
+        let uvs = records(c, 8)?
+            .map(|data| Ok([data.get_f32(0)?, data.get_f32(4)?]))
+            .collect::<R<_>>()?;
+        Ok(ChunkMotv(uvs))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 8);
+        self.0.iter().flatten().for_each(|v| data.push_f32(*v));
+        Chunk::new("MOTV", data)
+    }
+}
+
+impl ChunkMoba {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMoba> {
+        assert_eq!(c.get_id_as_string(), "MOBA");
+        let items = records(c, 24)?
+            .map(|data| {
+                let mut bounding_box = [0; 6];
+                for (i, value) in bounding_box.iter_mut().enumerate() {
+                    *value = data.get_i16(i * 2)?;
+                }
+                Ok(ChunkMobaItem {
+                    bounding_box,
+                    start_index: data.get_u32(12)?,
+                    n_indices: data.get_u16(16)?,
+                    min_index: data.get_u16(18)?,
+                    max_index: data.get_u16(20)?,
+                    flags: data[22],
+                    material_id: data[23],
+                })
+            })
+            .collect::<R<_>>()?;
+        Ok(ChunkMoba(items))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 24);
+        for item in &self.0 {
+            item.bounding_box.iter().for_each(|v| data.push_i16(*v));
+            data.push_u32(item.start_index);
+            [item.n_indices, item.min_index, item.max_index].iter().for_each(|v| data.push_u16(*v));
+            data.push(item.flags);
+            data.push(item.material_id);
+        }
+        Chunk::new("MOBA", data)
+    }
+}
+
+impl ChunkModr {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkModr> {
+        assert_eq!(c.get_id_as_string(), "MODR");
+        Ok(ChunkModr(get_u16s(c)?))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        u16s_to_chunk("MODR", &self.0)
+    }
+}
+
+impl ChunkMobn {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMobn> {
+        assert_eq!(c.get_id_as_string(), "MOBN");
+        let items = records(c, 16)?
+            .map(|data| Ok(ChunkMobnItem {
+                flags: data.get_u16(0)?,
+                neg_child: data.get_i16(2)?,
+                pos_child: data.get_i16(4)?,
+                n_faces: data.get_u16(6)?,
+                face_start: data.get_u32(8)?,
+                plane_distance: data.get_f32(12)?,
+            }))
+            .collect::<R<_>>()?;
+        Ok(ChunkMobn(items))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 16);
+        self.0.iter().for_each(|it| it.write_to(&mut data));
+        Chunk::new("MOBN", data)
+    }
+}
+
+impl ChunkMobr {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMobr> {
+        assert_eq!(c.get_id_as_string(), "MOBR");
+        Ok(ChunkMobr(get_u16s(c)?))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        u16s_to_chunk("MOBR", &self.0)
+    }
+}
+
+impl ChunkMocv {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMocv> {
+        assert_eq!(c.get_id_as_string(), "MOCV");
+        let colors = records(c, 4)?
+            .map(|data| Ok(WmoColor::from_bgra(data.get_u32(0)?)))
+            .collect::<R<_>>()?;
+        Ok(ChunkMocv(colors))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 4);
+        self.0.iter().for_each(|it| data.push_u32(it.to_bgra()));
+        Chunk::new("MOCV", data)
+    }
+}
+
+impl ChunkMliq {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMliq> {
+        assert_eq!(c.get_id_as_string(), "MLIQ");
+        const HEADER_SIZE: usize = 30;
+        let data = &c.data;
+        let x_vertices = data.get_u32(0)?;
+        let y_vertices = data.get_u32(4)?;
+        let x_tiles = data.get_u32(8)?;
+        let y_tiles = data.get_u32(12)?;
+        // the counts come from the file, a corrupt one must not overflow before the size check
+        let n_vertices = x_vertices as u64 * y_vertices as u64;
+        let n_tiles = x_tiles as u64 * y_tiles as u64;
+        let needed = n_vertices.checked_mul(8)
+            .and_then(|it| it.checked_add(HEADER_SIZE as u64))
+            .and_then(|it| it.checked_add(n_tiles));
+        match needed {
+            Some(needed) if needed <= data.len() as u64 => {}
+            _ => return err(format!(
+                "MLIQ of {}x{} vertices and {}x{} tiles needs {} bytes, has {}",
+                x_vertices, y_vertices, x_tiles, y_tiles,
+                needed.map(|it| it.to_string()).unwrap_or_else(|| "more".to_string()), data.len()
+            )),
+        }
+        let (n_vertices, n_tiles) = (n_vertices as usize, n_tiles as usize);
+        let tiles_start = HEADER_SIZE + n_vertices * 8;
+        let vertices = (0..n_vertices)
+            .map(|i| {
+                let offset = HEADER_SIZE + i * 8;
+                Ok(MliqVertex {
+                    data: data.get_four_bytes(offset)?,
+                    height: data.get_f32(offset + 4)?,
+                })
+            })
+            .collect::<R<_>>()?;
+        Ok(ChunkMliq {
+            x_vertices,
+            y_vertices,
+            x_tiles,
+            y_tiles,
+            base: get_vec3(data, 16)?,
+            material_id: data.get_u16(28)?,
+            vertices,
+            tiles: data[tiles_start..tiles_start + n_tiles].to_vec(),
+        })
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(30 + self.vertices.len() * 8 + self.tiles.len());
+        [self.x_vertices, self.y_vertices, self.x_tiles, self.y_tiles].iter().for_each(|v| data.push_u32(*v));
+        self.base.iter().for_each(|v| data.push_f32(*v));
+        data.push_u16(self.material_id);
+        for vertex in &self.vertices {
+            data.extend_from_slice(&vertex.data);
+            data.push_f32(vertex.height);
+        }
+        data.extend_from_slice(&self.tiles);
+        Chunk::new("MLIQ", data)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn wmo_group_parses_nested_chunks() {
+    use crate::formats::wmo::WmoGroupFile;
+
+    let chunks = Chunk::from_bytes(&crate::formats::wmo::test_utils::build_test_wmo_group()).unwrap();
+    assert_eq!(chunks.len(), 2);
+    let group = WmoGroupFile::new(chunks).unwrap();
+
+    assert_eq!(group.mogp.ext_batch_count, 1);
+    assert_eq!(group.mogp.group_liquid, 15);
+    assert_eq!(group.mopy.0.len(), 3);
+    assert_eq!(group.mopy.0[2].material_id, MOPY_MATERIAL_NONE);
+    assert_eq!(group.movi.0.len(), 9);
+    assert_eq!(group.movt.0[3], [10.0, 10.0, 0.0]);
+    assert_eq!(group.motv[0].0[3], [1.0, 1.0]);
+    assert_eq!((group.moba.0[0].n_indices, group.moba.0[0].max_index), (6, 3));
+    assert_eq!(group.mobn.as_ref().unwrap().0[0].neg_child, -1);
+    assert_eq!(group.mobr.as_ref().unwrap().0, vec![0, 1, 2]);
+    assert_eq!(group.mocv.as_ref().unwrap().0[0], WmoColor { r: 0x80, g: 0x40, b: 0x20, a: 0xFF });
+    let mliq = group.mliq.as_ref().unwrap();
+    assert_eq!((mliq.vertices.len(), mliq.tiles.clone(), mliq.base), (4, vec![0], [0.0, 0.0, 1.0]));
+}
+
+#[cfg(test)]
+#[test]
+fn wmo_mliq_rejects_counts_past_its_data() {
+    let mut data = Vec::new();
+    [0x10000u32, 0x10000, 0xFFFF_FFFF, 0xFFFF_FFFF].iter().for_each(|v| data.push_u32(*v));
+    data.extend_from_slice(&[0; 14]);
+    let error = ChunkMliq::from_chunk(&Chunk::new("MLIQ", data)).unwrap_err();
+    assert!(error.to_string().contains("65536x65536 vertices"));
+}
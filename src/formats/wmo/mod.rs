@@ -0,0 +1,471 @@
+use crate::common::{R, err};
+use crate::formats::chunk::{Chunk, ChunkMver, ChunkVecUtils, ChunkMotx, ChunkMogn, ChunkModn, ChunkMohd, ChunkMolr};
+use serde::{Serialize, Deserialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use crate::byte_utils::{VecUtils, VecWriteUtils};
+use crate::formats::wmo::group::*;
+use crate::formats::wmo::root::*;
+use anyhow::Context;
+
+pub mod collision;
+pub mod edit;
+pub mod export;
+pub mod group;
+pub mod root;
+pub mod writer;
+#[cfg(test)]
+pub(crate) mod test_utils;
+
+const ROOT_FILE_CHUNKS: &[&str] = &["MOMT", "MOGI", "MOSB", "MOVV", "MODN"];
+// the geometry chunks of a group file are nested in MOGP
+const GROUP_FILE_CHUNKS: &[&str] = &["MOGP"];
+// chunks with a typed representation, anything else is kept as is for the writer
+const PARSED_ROOT_CHUNKS: &[&str] = &[
+    "MVER", "MOHD", "MOTX", "MOMT", "MOGN", "MOGI", "MOSB", "MOPV", "MOPT", "MOPR", "MOVV", "MOVB", "MOLT",
+    "MODS", "MODN", "MODD", "MFOG",
+];
+const PARSED_GROUP_CHUNKS: &[&str] = &[
+    "MOPY", "MOVI", "MOVT", "MONR", "MOTV", "MOBA", "MOLR", "MODR", "MOBN", "MOBR", "MOCV", "MLIQ",
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WmoFile {
+    pub root: WmoRootFile,
+    // the groups that loaded, in group order
+    pub groups: Vec<WmoGroupFile>,
+    pub loaded_group_files: Vec<PathBuf>,
+    #[serde(default)]
+    pub group_errors: Vec<WmoGroupError>,
+}
+
+/// A group of the root that could not be loaded, it is left out of `WmoFile::groups`.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum WmoGroupError {
+    Missing { index: usize, path: PathBuf },
+    Corrupt { index: usize, path: PathBuf, error: String },
+}
+
+impl WmoGroupError {
+    pub fn index(&self) -> usize {
+        match self {
+            WmoGroupError::Missing { index, .. } => *index,
+            WmoGroupError::Corrupt { index, .. } => *index,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            WmoGroupError::Missing { path, .. } => path,
+            WmoGroupError::Corrupt { path, .. } => path,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum WmoFileVariant {
+    ROOT(Box<WmoRootFile>),
+    GROUP(Box<WmoGroupFile>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WmoRootFile {
+    pub mver: ChunkMver,
+    pub motx: ChunkMotx,
+    pub mohd: ChunkMohd,
+    pub momt: ChunkMomt,
+    pub mogn: ChunkMogn,
+    pub mogi: ChunkMogi,
+    pub mosb: ChunkMosb,
+    pub mopv: ChunkMopv,
+    pub mopt: ChunkMopt,
+    pub mopr: ChunkMopr,
+    pub movv: ChunkMovv,
+    pub movb: ChunkMovb,
+    pub molt: ChunkMolt,
+    pub mods: ChunkMods,
+    pub modn: ChunkModn,
+    pub modd: ChunkModd,
+    pub mfog: ChunkMfog,
+    #[serde(skip)]
+    pub other_chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WmoGroupFile {
+    pub mver: ChunkMver,
+    pub mogp: ChunkMogp,
+    pub mopy: ChunkMopy,
+    pub movi: ChunkMovi,
+    pub movt: ChunkMovt,
+    pub monr: ChunkMonr,
+    // a second set follows the first for some shaders
+    pub motv: Vec<ChunkMotv>,
+    pub moba: ChunkMoba,
+    pub molr: Option<ChunkMolr>,
+    pub modr: Option<ChunkModr>,
+    pub mobn: Option<ChunkMobn>,
+    pub mobr: Option<ChunkMobr>,
+    pub mpbv: Option<()>,
+    pub mpbp: Option<()>,
+    pub mpbi: Option<()>,
+    pub mpbg: Option<()>,
+    pub mocv: Option<ChunkMocv>,
+    pub mliq: Option<ChunkMliq>,
+    pub mori: Option<()>,
+    pub morb: Option<()>,
+    // MOGP sub chunks without a typed representation
+    #[serde(skip)]
+    pub other_chunks: Vec<Chunk>,
+}
+
+impl WmoFile {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> R<WmoFile> {
+        let path = path.as_ref().to_path_buf();
+        let chunks = Chunk::from_path(&path)?;
+        let raw_mogn = chunks.get_chunk_of_type_optionally("MOGN")
+            .map(|c| Chunk::new("MOGN", c.data.clone()));
+        let variant = WmoFileVariant::new(chunks)?;
+        match variant {
+            WmoFileVariant::ROOT(root_file) => {
+                // ok we have a root file,
+                // load the dependent groups
+                let parent_dir = path.parent().unwrap();
+                let original_file_name = path.file_name().unwrap().to_str().unwrap();
+
+                let mut groups = Vec::new();
+                let mut loaded_group_files = Vec::new();
+                let mut group_errors = Vec::new();
+                for (index, (group_path, group)) in WmoFile::load_groups(parent_dir, original_file_name, &root_file)
+                    .into_iter()
+                    .enumerate()
+                {
+                    match group {
+                        Ok(group) => {
+                            groups.push(group);
+                            loaded_group_files.push(group_path);
+                        }
+                        Err(_) if !group_path.exists() => {
+                            group_errors.push(WmoGroupError::Missing { index, path: group_path });
+                        }
+                        Err(e) => {
+                            group_errors.push(WmoGroupError::Corrupt { index, path: group_path, error: format!("{:#}", e) });
+                        }
+                    }
+                }
+                if let Some(raw_mogn) = raw_mogn {
+                    for group in groups.iter_mut() {
+                        let mogp = &mut group.mogp;
+                        mogp.name = get_string_at(&raw_mogn, mogp.group_name);
+                        mogp.descriptive_name = get_string_at(&raw_mogn, mogp.descriptive_group_name)
+                            .filter(|it| !it.is_empty());
+                    }
+                }
+
+                Ok(WmoFile {
+                    root: *root_file,
+                    groups,
+                    loaded_group_files,
+                    group_errors,
+                })
+            }
+            WmoFileVariant::GROUP(_) => {
+                return err("WmoFile#from_path: Given WMO must a be root type WMO.".to_string())
+            }
+        }
+    }
+
+    /// Loads every group of the root, each with its own result.
+    pub fn load_groups(
+        parent_path: &Path,
+        original_file_name: &str,
+        root_file: &WmoRootFile,
+    ) -> Vec<(PathBuf, R<WmoGroupFile>)> {
+        WmoRootFile::get_group_names(root_file.mohd.n_groups, original_file_name)
+            .iter()
+            .map(|group_name| {
+                let path = parent_path.join(group_name);
+                let group = Self::load_group_wmo(&path)
+                    .with_context(|| format!("Failed to load group WMO {}", path.display()));
+                (path, group)
+            })
+            .collect()
+    }
+
+    /// MOGI index of each entry of `groups`.
+    pub fn get_loaded_group_indices(&self) -> Vec<usize> {
+        let failed: HashSet<usize> = self.group_errors.iter().map(|it| it.index()).collect();
+        (0..self.root.mohd.n_groups as usize)
+            .filter(|it| !failed.contains(it))
+            .collect()
+    }
+
+    fn load_group_wmo(path: &Path) -> R<WmoGroupFile> {
+        let chunks = Chunk::from_path(path)?;
+        WmoGroupFile::new(chunks)
+    }
+}
+
+impl WmoFileVariant {
+    fn new(chunks: Vec<Chunk>) -> R<WmoFileVariant> {
+        fn matches_file_type(marker_chunks: &[&str], lookup: &HashSet<String>) -> bool {
+            marker_chunks
+                .iter()
+                .all(|c| {
+                    lookup.contains(*c)
+                })
+        }
+
+        let chunk_names_lookup: HashSet<String> = chunks.iter()
+            .map(|v| v.get_id_as_string())
+            .collect();
+
+        if matches_file_type(ROOT_FILE_CHUNKS, &chunk_names_lookup) {
+            Ok(WmoFileVariant::ROOT(Box::new(WmoRootFile::new(chunks)?)))
+        } else if matches_file_type(GROUP_FILE_CHUNKS, &chunk_names_lookup) {
+            Ok(WmoFileVariant::GROUP(Box::new(WmoGroupFile::new(chunks)?)))
+        } else {
+            err("WmoFile#new: Cannot create a root or group WMO from given chunks!".to_string())
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMogiItem {
+    pub flags: u32,
+    pub bounding_box: [f32; 6],
+    // MOGN offset, -1 when the group has no name
+    pub name_offset: i32,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkMogi(pub Vec<ChunkMogiItem>);
+
+impl WmoRootFile {
+    pub fn get_group_names(n_groups: u32, original_file_name: &str) -> Vec<String> {
+        let file_name = original_file_name
+            .split("/")
+            .last()
+            .unwrap()
+            .split(".wmo")
+            .nth(0)
+            .unwrap();
+
+        (0..n_groups)
+            .map(|index: u32| {
+                format!("{}_{:0>3}.wmo", file_name, index)
+            })
+            .collect()
+    }
+
+    fn new(chunks: Vec<Chunk>) -> R<WmoRootFile> {
+        let mver = chunks.get_mver_chunk()?;
+        let motx = chunks.get_motx();
+        let mohd = chunks.get_mohd();
+        let mogn = chunks.get_mogn();
+        let modn = chunks.get_modn();
+        let mut mogi = chunks.get_mogi();
+
+        // string offsets point into the raw blocks, empty padding strings included
+        let mut momt = parse_or(&chunks, "MOMT", ChunkMomt::from_chunk, ChunkMomt(Vec::new()))?;
+        if let Some(raw_motx) = chunks.get_chunk_of_type_optionally("MOTX") {
+            momt.resolve_texture_names(raw_motx);
+        }
+        let mut modd = parse_or(&chunks, "MODD", ChunkModd::from_chunk, ChunkModd(Vec::new()))?;
+        if let Some(raw_modn) = chunks.get_chunk_of_type_optionally("MODN") {
+            modd.resolve_names(raw_modn);
+        }
+        if let Some(raw_mogn) = chunks.get_chunk_of_type_optionally("MOGN") {
+            for item in mogi.0.iter_mut().filter(|it| it.name_offset >= 0) {
+                item.name = get_string_at(raw_mogn, item.name_offset as u32);
+            }
+        }
+
+        Ok(WmoRootFile {
+            mver,
+            motx,
+            mohd,
+            momt,
+            mogn,
+            mogi,
+            mosb: parse_or(&chunks, "MOSB", ChunkMosb::from_chunk, ChunkMosb(String::new()))?,
+            mopv: parse_or(&chunks, "MOPV", ChunkMopv::from_chunk, ChunkMopv(Vec::new()))?,
+            mopt: parse_or(&chunks, "MOPT", ChunkMopt::from_chunk, ChunkMopt(Vec::new()))?,
+            mopr: parse_or(&chunks, "MOPR", ChunkMopr::from_chunk, ChunkMopr(Vec::new()))?,
+            movv: parse_or(&chunks, "MOVV", ChunkMovv::from_chunk, ChunkMovv(Vec::new()))?,
+            movb: parse_or(&chunks, "MOVB", ChunkMovb::from_chunk, ChunkMovb(Vec::new()))?,
+            molt: parse_or(&chunks, "MOLT", ChunkMolt::from_chunk, ChunkMolt(Vec::new()))?,
+            mods: parse_or(&chunks, "MODS", ChunkMods::from_chunk, ChunkMods(Vec::new()))?,
+            modn,
+            modd,
+            mfog: parse_or(&chunks, "MFOG", ChunkMfog::from_chunk, ChunkMfog(Vec::new()))?,
+            other_chunks: get_other_chunks(&chunks, PARSED_ROOT_CHUNKS),
+        })
+    }
+}
+
+// chunks that may be left out of a file parse to `default`
+fn parse_or<T>(chunks: &[Chunk], chunk_type: &str, parse: fn(&Chunk) -> R<T>, default: T) -> R<T> {
+    match chunks.iter().find(|it| it.get_id_as_string() == chunk_type) {
+        Some(chunk) => parse(chunk).with_context(|| format!("Failed to parse {}", chunk_type)),
+        None => Ok(default),
+    }
+}
+
+impl WmoGroupFile {
+    fn new(chunks: Vec<Chunk>) -> R<WmoGroupFile> {
+        let mver = chunks.get_mver_chunk()?;
+        let raw_mogp = chunks.get_chunk_of_type_checked("MOGP")?;
+        let mogp = ChunkMogp::from_chunk(raw_mogp)?;
+        let sub_chunks = ChunkMogp::get_sub_chunks(raw_mogp).context("Failed to read the MOGP sub chunks")?;
+
+        let motv = sub_chunks.get_all_chunks_of_type("MOTV")
+            .into_iter()
+            .map(ChunkMotv::from_chunk)
+            .collect::<R<Vec<ChunkMotv>>>()
+            .context("Failed to parse MOTV")?;
+        Ok(WmoGroupFile {
+            mver,
+            mogp,
+            mopy: parse_or(&sub_chunks, "MOPY", ChunkMopy::from_chunk, ChunkMopy(Vec::new()))?,
+            movi: parse_or(&sub_chunks, "MOVI", ChunkMovi::from_chunk, ChunkMovi(Vec::new()))?,
+            movt: parse_or(&sub_chunks, "MOVT", ChunkMovt::from_chunk, ChunkMovt(Vec::new()))?,
+            monr: parse_or(&sub_chunks, "MONR", ChunkMonr::from_chunk, ChunkMonr(Vec::new()))?,
+            motv,
+            moba: parse_or(&sub_chunks, "MOBA", ChunkMoba::from_chunk, ChunkMoba(Vec::new()))?,
+            molr: sub_chunks.get_molr(),
+            modr: parse_optional(&sub_chunks, "MODR", ChunkModr::from_chunk)?,
+            mobn: parse_optional(&sub_chunks, "MOBN", ChunkMobn::from_chunk)?,
+            mobr: parse_optional(&sub_chunks, "MOBR", ChunkMobr::from_chunk)?,
+            mpbv: None,
+            mpbp: None,
+            mpbi: None,
+            mpbg: None,
+            mocv: parse_optional(&sub_chunks, "MOCV", ChunkMocv::from_chunk)?,
+            mliq: parse_optional(&sub_chunks, "MLIQ", ChunkMliq::from_chunk)?,
+            mori: None,
+            morb: None,
+            other_chunks: get_other_chunks(&sub_chunks, PARSED_GROUP_CHUNKS),
+        })
+    }
+}
+
+fn get_other_chunks(chunks: &[Chunk], parsed: &[&str]) -> Vec<Chunk> {
+    chunks.iter()
+        .filter(|it| !parsed.contains(&it.get_id_as_string().as_str()))
+        .map(|it| Chunk { id: it.id, size: it.size, data: it.data.clone() })
+        .collect()
+}
+
+fn parse_optional<T>(chunks: &[Chunk], chunk_type: &str, parse: fn(&Chunk) -> R<T>) -> R<Option<T>> {
+    chunks.iter()
+        .find(|it| it.get_id_as_string() == chunk_type)
+        .map(|chunk| parse(chunk).with_context(|| format!("Failed to parse {}", chunk_type)))
+        .transpose()
+}
+
+trait WmoChunkExt {
+    fn get_mogi(&self) -> ChunkMogi;
+}
+
+impl WmoChunkExt for Vec<Chunk> {
+    fn get_mogi(&self) -> ChunkMogi { ChunkMogi::from_chunk(self.get_chunk_of_type("MOGI")) }
+}
+
+impl ChunkMogi {
+    fn from_chunk(chunk: &Chunk) -> ChunkMogi {
+        let mapped = chunk.data.chunks(32)
+            .map(|segment| {
+                let segment = segment.to_vec();
+                let flags = segment.get_u32(0).unwrap();
+                let b1 = segment.get_f32(4).unwrap();
+                let b2 = segment.get_f32(8).unwrap();
+                let b3 = segment.get_f32(12).unwrap();
+                let b4 = segment.get_f32(16).unwrap();
+                let b5 = segment.get_f32(20).unwrap();
+                let b6 = segment.get_f32(24).unwrap();
+                let bounding_box = [b1, b2, b3, b4, b5, b6];
+                let name_offset = segment.get_i32(28).unwrap();
+                ChunkMogiItem {
+                    flags,
+                    bounding_box,
+                    name_offset,
+                    name: None,
+                }
+            })
+            .collect();
+
+        ChunkMogi(mapped)
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 32);
+        for item in &self.0 {
+            data.push_u32(item.flags);
+            item.bounding_box.iter().for_each(|v| data.push_f32(*v));
+            data.push_i32(item.name_offset);
+        }
+        Chunk::new("MOGI", data)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn wmo_root_get_group_names() {
+    let group_names = WmoRootFile::get_group_names(2, "test.wmo");
+    assert_eq!(group_names, vec![
+        "test_000.wmo",
+        "test_001.wmo",
+    ]);
+
+    let group_names = WmoRootFile::get_group_names(5, "test00.wmo");
+    assert_eq!(group_names, vec![
+        "test00_000.wmo",
+        "test00_001.wmo",
+        "test00_002.wmo",
+        "test00_003.wmo",
+        "test00_004.wmo",
+    ]);
+
+    let group_names = WmoRootFile::get_group_names(5, "WMO/woRlD//whatever//test00.wmo");
+    assert_eq!(group_names, vec![
+        "test00_000.wmo",
+        "test00_001.wmo",
+        "test00_002.wmo",
+        "test00_003.wmo",
+        "test00_004.wmo",
+    ]);
+}
+
+#[cfg(test)]
+#[test]
+fn wmo_file_loads_root_and_groups() {
+    let dir = crate::test_utils::TempDir::new("wow-file-tools-wmo-load");
+    let wmo = WmoFile::from_path(test_utils::write_test_wmo(&dir)).unwrap();
+    assert_eq!(wmo.root.mogi.0[0].name.as_deref(), Some("Hall"));
+    assert_eq!(wmo.root.modd.0[0].name.as_deref(), Some("World\\Generic\\Barrel.m2"));
+    assert_eq!(wmo.groups.len(), 1);
+    assert_eq!(wmo.groups[0].movt.0.len(), 5);
+}
+
+#[cfg(test)]
+#[test]
+fn wmo_file_records_missing_and_corrupt_groups() {
+    let dir = crate::test_utils::TempDir::new("wow-file-tools-wmo-bad-groups");
+    let path = test_utils::write_test_wmo(&dir);
+    std::fs::remove_file(path.with_file_name("test_000.wmo")).unwrap();
+    let wmo = WmoFile::from_path(&path).unwrap();
+    assert!(wmo.groups.is_empty());
+    assert!(matches!(wmo.group_errors.as_slice(), [WmoGroupError::Missing { index: 0, .. }]));
+
+    // a MOGP too short for its header
+    std::fs::write(path.with_file_name("test_000.wmo"), b"REVM\x04\0\0\0\x11\0\0\0PGOM\x04\0\0\0\0\0\0\0").unwrap();
+    let wmo = WmoFile::from_path(&path).unwrap();
+    match wmo.group_errors.as_slice() {
+        [WmoGroupError::Corrupt { index: 0, error, .. }] => assert!(error.contains("MOGP is 4 bytes long")),
+        other => panic!("unexpected group errors {:?}", other),
+    }
+    assert!(wmo.get_loaded_group_indices().is_empty());
+}
@@ -0,0 +1,548 @@
+use crate::byte_utils::{VecUtils, VecWriteUtils};
+use crate::common::{R, err};
+use crate::formats::chunk::Chunk;
+use serde::{Deserialize, Serialize};
+
+/// CImVector, stored as BGRA.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct WmoColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl WmoColor {
+    pub fn from_bgra(value: u32) -> WmoColor {
+        let [b, g, r, a] = value.to_le_bytes();
+        WmoColor { r, g, b, a }
+    }
+
+    pub fn to_bgra(&self) -> u32 {
+        u32::from_le_bytes([self.b, self.g, self.r, self.a])
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMomt(pub Vec<ChunkMomtItem>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMomtItem {
+    pub flags: u32,
+    pub shader: u32,
+    pub blend_mode: u32,
+    // MOTX offsets, the names are resolved when the root is loaded
+    pub texture_1: u32,
+    #[serde(default)]
+    pub texture_1_name: Option<String>,
+    pub sidn_color: WmoColor,
+    pub frame_sidn_color: WmoColor,
+    pub texture_2: u32,
+    #[serde(default)]
+    pub texture_2_name: Option<String>,
+    pub diff_color: WmoColor,
+    pub ground_type: u32,
+    pub texture_3: u32,
+    #[serde(default)]
+    pub texture_3_name: Option<String>,
+    pub color_2: u32,
+    pub flags_2: u32,
+    pub runtime_data: [u32; 4],
+}
+
+/// Skybox model, empty when the WMO has none.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMosb(pub String);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMopv(pub Vec<[f32; 3]>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMopt(pub Vec<ChunkMoptItem>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMoptItem {
+    // range of MOPV
+    pub start_vertex: u16,
+    pub n_vertices: u16,
+    pub normal: [f32; 3],
+    pub distance: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMopr(pub Vec<ChunkMoprItem>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMoprItem {
+    pub portal_index: u16,
+    pub group_index: u16,
+    // which side of the portal plane the group is on
+    pub side: i16,
+    pub filler: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMovv(pub Vec<[f32; 3]>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMovb(pub Vec<ChunkMovbItem>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMovbItem {
+    // range of MOVV
+    pub start_vertex: u16,
+    pub n_vertices: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMolt(pub Vec<ChunkMoltItem>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMoltItem {
+    // 0 omni, 1 spot, 2 directional, 3 ambient
+    pub light_type: u8,
+    pub use_attenuation: bool,
+    pub padding: [u8; 2],
+    pub color: WmoColor,
+    pub position: [f32; 3],
+    pub intensity: f32,
+    pub unknown: [f32; 4],
+    pub attenuation_start: f32,
+    pub attenuation_end: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMods(pub Vec<ChunkModsItem>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkModsItem {
+    pub name: String,
+    // range of MODD
+    pub start_index: u32,
+    pub n_doodads: u32,
+    pub padding: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkModd(pub Vec<ChunkModdItem>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkModdItem {
+    // MODN offset, 24 bits shared with the flags
+    pub name_offset: u32,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub flags: u8,
+    pub position: [f32; 3],
+    // quaternion, xyzw
+    pub rotation: [f32; 4],
+    pub scale: f32,
+    pub color: WmoColor,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMfog(pub Vec<ChunkMfogItem>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMfogItem {
+    pub flags: u32,
+    pub position: [f32; 3],
+    pub small_radius: f32,
+    pub large_radius: f32,
+    pub fog_end: f32,
+    pub fog_start_multiplier: f32,
+    pub fog_color: WmoColor,
+    pub underwater_fog_end: f32,
+    pub underwater_fog_start_multiplier: f32,
+    pub underwater_fog_color: WmoColor,
+}
+
+// every record of these chunks has a fixed size, a trailing partial record is an error
+pub(super) fn records(c: &Chunk, record_size: usize) -> R<impl Iterator<Item = Vec<u8>> + '_> {
+    if !c.data.chunks_exact(record_size).remainder().is_empty() {
+        return err(format!(
+            "{} size {} is not a multiple of {}",
+            c.get_id_as_string(),
+            c.data.len(),
+            record_size
+        ));
+    }
+    Ok(c.data.chunks_exact(record_size).map(|it| it.to_vec()))
+}
+
+pub(super) fn get_vec3(data: &Vec<u8>, offset: usize) -> R<[f32; 3]> {
+    Ok([data.get_f32(offset)?, data.get_f32(offset + 4)?, data.get_f32(offset + 8)?])
+}
+
+/// Null terminated string starting at `offset` of a string block (MOTX, MOGN, MODN).
+pub fn get_string_at(block: &Chunk, offset: u32) -> Option<String> {
+    if offset as usize >= block.data.len() {
+        return None;
+    }
+    block.data.get_string_null_terminated(offset as usize).ok()
+}
+
+pub(super) fn vec3s_to_chunk(chunk_type: &str, values: &[[f32; 3]]) -> Chunk {
+    let mut data = Vec::with_capacity(values.len() * 12);
+    values.iter().flatten().for_each(|v| data.push_f32(*v));
+    Chunk::new(chunk_type, data)
+}
+
+impl ChunkMomt {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMomt> {
+        assert_eq!(c.get_id_as_string(), "MOMT");
+        let items = records(c, 64)?
+            .map(|data| Ok(ChunkMomtItem {
+                flags: data.get_u32(0)?,
+                shader: data.get_u32(4)?,
+                blend_mode: data.get_u32(8)?,
+                texture_1: data.get_u32(12)?,
+                texture_1_name: None,
+                sidn_color: WmoColor::from_bgra(data.get_u32(16)?),
+                frame_sidn_color: WmoColor::from_bgra(data.get_u32(20)?),
+                texture_2: data.get_u32(24)?,
+                texture_2_name: None,
+                diff_color: WmoColor::from_bgra(data.get_u32(28)?),
+                ground_type: data.get_u32(32)?,
+                texture_3: data.get_u32(36)?,
+                texture_3_name: None,
+                color_2: data.get_u32(40)?,
+                flags_2: data.get_u32(44)?,
+                runtime_data: [data.get_u32(48)?, data.get_u32(52)?, data.get_u32(56)?, data.get_u32(60)?],
+            }))
+            .collect::<R<Vec<ChunkMomtItem>>>()?;
+        Ok(ChunkMomt(items))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 64);
+        for item in &self.0 {
+            [
+                item.flags,
+                item.shader,
+                item.blend_mode,
+                item.texture_1,
+                item.sidn_color.to_bgra(),
+                item.frame_sidn_color.to_bgra(),
+                item.texture_2,
+                item.diff_color.to_bgra(),
+                item.ground_type,
+                item.texture_3,
+                item.color_2,
+                item.flags_2,
+            ].iter().chain(item.runtime_data.iter()).for_each(|v| data.push_u32(*v));
+        }
+        Chunk::new("MOMT", data)
+    }
+
+    pub fn resolve_texture_names(&mut self, motx: &Chunk) {
+        for item in self.0.iter_mut() {
+            item.texture_1_name = get_string_at(motx, item.texture_1).filter(|it| !it.is_empty());
+            item.texture_2_name = get_string_at(motx, item.texture_2).filter(|it| !it.is_empty());
+            item.texture_3_name = get_string_at(motx, item.texture_3).filter(|it| !it.is_empty());
+        }
+    }
+}
+
+impl ChunkMosb {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMosb> {
+        assert_eq!(c.get_id_as_string(), "MOSB");
+        Ok(ChunkMosb(c.data.get_string_null_terminated(0)?))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::new();
+        data.push_null_terminated_string(&self.0);
+        data.resize(data.len() + (4 - data.len() % 4) % 4, 0);
+        Chunk::new("MOSB", data)
+    }
+}
+
+impl ChunkMopv {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMopv> {
+        assert_eq!(c.get_id_as_string(), "MOPV");
+        Ok(ChunkMopv(records(c, 12)?.map(|data| get_vec3(&data, 0)).collect::<R<_>>()?))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        vec3s_to_chunk("MOPV", &self.0)
+    }
+}
+
+impl ChunkMopt {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMopt> {
+        assert_eq!(c.get_id_as_string(), "MOPT");
+        let items = records(c, 20)?
+            .map(|data| Ok(ChunkMoptItem {
+                start_vertex: data.get_u16(0)?,
+                n_vertices: data.get_u16(2)?,
+                normal: get_vec3(&data, 4)?,
+                distance: data.get_f32(16)?,
+            }))
+            .collect::<R<_>>()?;
+        Ok(ChunkMopt(items))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 20);
+        for item in &self.0 {
+            data.push_u16(item.start_vertex);
+            data.push_u16(item.n_vertices);
+            item.normal.iter().for_each(|v| data.push_f32(*v));
+            data.push_f32(item.distance);
+        }
+        Chunk::new("MOPT", data)
+    }
+}
+
+impl ChunkMopr {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMopr> {
+        assert_eq!(c.get_id_as_string(), "MOPR");
+        let items = records(c, 8)?
+            .map(|data| Ok(ChunkMoprItem {
+                portal_index: data.get_u16(0)?,
+                group_index: data.get_u16(2)?,
+                side: data.get_u16(4)? as i16,
+                filler: data.get_u16(6)?,
+            }))
+            .collect::<R<_>>()?;
+        Ok(ChunkMopr(items))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 8);
+        for item in &self.0 {
+            data.push_u16(item.portal_index);
+            data.push_u16(item.group_index);
+            data.push_i16(item.side);
+            data.push_u16(item.filler);
+        }
+        Chunk::new("MOPR", data)
+    }
+}
+
+impl ChunkMovv {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMovv> {
+        assert_eq!(c.get_id_as_string(), "MOVV");
+        Ok(ChunkMovv(records(c, 12)?.map(|data| get_vec3(&data, 0)).collect::<R<_>>()?))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        vec3s_to_chunk("MOVV", &self.0)
+    }
+}
+
+impl ChunkMovb {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMovb> {
+        assert_eq!(c.get_id_as_string(), "MOVB");
+        let items = records(c, 4)?
+            .map(|data| Ok(ChunkMovbItem {
+                start_vertex: data.get_u16(0)?,
+                n_vertices: data.get_u16(2)?,
+            }))
+            .collect::<R<_>>()?;
+        Ok(ChunkMovb(items))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 4);
+        for item in &self.0 {
+            data.push_u16(item.start_vertex);
+            data.push_u16(item.n_vertices);
+        }
+        Chunk::new("MOVB", data)
+    }
+}
+
+impl ChunkMolt {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMolt> {
+        assert_eq!(c.get_id_as_string(), "MOLT");
+        let items = records(c, 48)?
+            .map(|data| Ok(ChunkMoltItem {
+                light_type: data.get_byte(0)?,
+                use_attenuation: data.get_byte(1)? != 0,
+                padding: [data.get_byte(2)?, data.get_byte(3)?],
+                color: WmoColor::from_bgra(data.get_u32(4)?),
+                position: get_vec3(&data, 8)?,
+                intensity: data.get_f32(20)?,
+                unknown: [data.get_f32(24)?, data.get_f32(28)?, data.get_f32(32)?, data.get_f32(36)?],
+                attenuation_start: data.get_f32(40)?,
+                attenuation_end: data.get_f32(44)?,
+            }))
+            .collect::<R<_>>()?;
+        Ok(ChunkMolt(items))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 48);
+        for item in &self.0 {
+            data.push(item.light_type);
+            data.push(item.use_attenuation as u8);
+            data.extend_from_slice(&item.padding);
+            data.push_u32(item.color.to_bgra());
+            item.position.iter().for_each(|v| data.push_f32(*v));
+            data.push_f32(item.intensity);
+            item.unknown.iter().for_each(|v| data.push_f32(*v));
+            data.push_f32(item.attenuation_start);
+            data.push_f32(item.attenuation_end);
+        }
+        Chunk::new("MOLT", data)
+    }
+}
+
+impl ChunkMods {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMods> {
+        assert_eq!(c.get_id_as_string(), "MODS");
+        let items = records(c, 32)?
+            .map(|data| Ok(ChunkModsItem {
+                name: data[..20].iter()
+                    .take_while(|it| **it != 0)
+                    .map(|it| *it as char)
+                    .collect(),
+                start_index: data.get_u32(20)?,
+                n_doodads: data.get_u32(24)?,
+                padding: data.get_u32(28)?,
+            }))
+            .collect::<R<_>>()?;
+        Ok(ChunkMods(items))
+    }
+
+    pub fn to_chunk(&self) -> R<Chunk> {
+        let mut data = Vec::with_capacity(self.0.len() * 32);
+        for item in &self.0 {
+            // the name field keeps room for its terminator
+            if item.name.len() >= 20 {
+                return err(format!("Doodad set name {} is longer than 19 characters", item.name));
+            }
+            let mut name = item.name.as_bytes().to_vec();
+            name.resize(20, 0);
+            data.extend_from_slice(&name);
+            data.push_u32(item.start_index);
+            data.push_u32(item.n_doodads);
+            data.push_u32(item.padding);
+        }
+        Ok(Chunk::new("MODS", data))
+    }
+}
+
+impl ChunkModd {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkModd> {
+        assert_eq!(c.get_id_as_string(), "MODD");
+        let items = records(c, 40)?
+            .map(|data| {
+                let name_and_flags = data.get_u32(0)?;
+                Ok(ChunkModdItem {
+                    name_offset: name_and_flags & 0xFFFFFF,
+                    name: None,
+                    flags: (name_and_flags >> 24) as u8,
+                    position: get_vec3(&data, 4)?,
+                    rotation: [data.get_f32(16)?, data.get_f32(20)?, data.get_f32(24)?, data.get_f32(28)?],
+                    scale: data.get_f32(32)?,
+                    color: WmoColor::from_bgra(data.get_u32(36)?),
+                })
+            })
+            .collect::<R<_>>()?;
+        Ok(ChunkModd(items))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 40);
+        for item in &self.0 {
+            data.push_u32((item.name_offset & 0xFFFFFF) | ((item.flags as u32) << 24));
+            item.position.iter()
+                .chain(item.rotation.iter())
+                .for_each(|v| data.push_f32(*v));
+            data.push_f32(item.scale);
+            data.push_u32(item.color.to_bgra());
+        }
+        Chunk::new("MODD", data)
+    }
+
+    pub fn resolve_names(&mut self, modn: &Chunk) {
+        for item in self.0.iter_mut() {
+            item.name = get_string_at(modn, item.name_offset);
+        }
+    }
+}
+
+impl ChunkMfog {
+    pub fn from_chunk(c: &Chunk) -> R<ChunkMfog> {
+        assert_eq!(c.get_id_as_string(), "MFOG");
+        let items = records(c, 48)?
+            .map(|data| Ok(ChunkMfogItem {
+                flags: data.get_u32(0)?,
+                position: get_vec3(&data, 4)?,
+                small_radius: data.get_f32(16)?,
+                large_radius: data.get_f32(20)?,
+                fog_end: data.get_f32(24)?,
+                fog_start_multiplier: data.get_f32(28)?,
+                fog_color: WmoColor::from_bgra(data.get_u32(32)?),
+                underwater_fog_end: data.get_f32(36)?,
+                underwater_fog_start_multiplier: data.get_f32(40)?,
+                underwater_fog_color: WmoColor::from_bgra(data.get_u32(44)?),
+            }))
+            .collect::<R<_>>()?;
+        Ok(ChunkMfog(items))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(self.0.len() * 48);
+        for item in &self.0 {
+            data.push_u32(item.flags);
+            item.position.iter().for_each(|v| data.push_f32(*v));
+            [item.small_radius, item.large_radius, item.fog_end, item.fog_start_multiplier]
+                .iter()
+                .for_each(|v| data.push_f32(*v));
+            data.push_u32(item.fog_color.to_bgra());
+            data.push_f32(item.underwater_fog_end);
+            data.push_f32(item.underwater_fog_start_multiplier);
+            data.push_u32(item.underwater_fog_color.to_bgra());
+        }
+        Chunk::new("MFOG", data)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn wmo_root_resolves_names() {
+    use crate::byte_utils::VecWriteUtils;
+    use crate::formats::chunk::ChunkMver;
+    use crate::formats::wmo::WmoRootFile;
+
+    let mut momt = vec![0u8; 64];
+    momt[12..16].copy_from_slice(&8u32.to_le_bytes());
+    momt[16..20].copy_from_slice(&0x80FF0000u32.to_le_bytes());
+    let mut modd = Vec::new();
+    modd.push_u32(0x0100_0000 | 6);
+    [1.0, 2.0, 3.0, 0.0, 0.0, 0.0, 1.0, 1.5].iter().for_each(|v| modd.push_f32(*v));
+    modd.push_u32(0xFF00FF00);
+    let mut mods = b"Set_$DefaultGlobal\0\0".to_vec();
+    [0u32, 1, 0].iter().for_each(|v| mods.push_u32(*v));
+    let mut mogi = Vec::new();
+    mogi.push_u32(0);
+    [0.0f32; 6].iter().for_each(|v| mogi.push_f32(*v));
+    mogi.push_i32(1);
+
+    let root = WmoRootFile::new(vec![
+        ChunkMver { map_version: 17 }.to_chunk(),
+        Chunk::new("MOHD", vec![0; 64]),
+        Chunk::new("MOTX", b"a.blp\0\0\0b\\c.blp\0\0\0\0".to_vec()),
+        Chunk::new("MOMT", momt),
+        Chunk::new("MOGN", b"\0Hall\0".to_vec()),
+        Chunk::new("MOGI", mogi),
+        Chunk::new("MOSB", b"\0".to_vec()),
+        Chunk::new("MODS", mods),
+        Chunk::new("MODN", b"\0\0\0\0\0\0x.m2\0".to_vec()),
+        Chunk::new("MODD", modd),
+    ]).unwrap();
+
+    assert_eq!(root.momt.0[0].texture_1_name.as_deref(), Some("b\\c.blp"));
+    assert_eq!(root.momt.0[0].texture_2_name.as_deref(), Some("a.blp"));
+    assert_eq!(root.momt.0[0].sidn_color, WmoColor { r: 0xFF, g: 0, b: 0, a: 0x80 });
+    assert_eq!(root.mogi.0[0].name.as_deref(), Some("Hall"));
+    assert_eq!(root.mods.0[0].name, "Set_$DefaultGlobal");
+    let doodad = &root.modd.0[0];
+    assert_eq!((doodad.name.as_deref(), doodad.flags, doodad.scale), (Some("x.m2"), 1, 1.5));
+    assert_eq!(doodad.color.to_bgra(), 0xFF00FF00);
+    assert!(root.mfog.0.is_empty());
+}
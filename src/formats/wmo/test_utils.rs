@@ -0,0 +1,126 @@
+use crate::byte_utils::VecWriteUtils;
+use crate::test_utils::{push_reversed_chunk, TempDir};
+use std::path::PathBuf;
+
+/// Root of a one group, one material WMO with a doodad set holding one doodad.
+pub fn build_test_wmo_root() -> Vec<u8> {
+    let mut out = Vec::new();
+    push_reversed_chunk(&mut out, "MVER", &17u32.to_le_bytes());
+
+    let mut mohd = Vec::new();
+    // materials, groups, portals, lights, models, doodads, doodad sets, ambient color, wmo id
+    [1u32, 1, 0, 0, 1, 1, 1, 0xFF202020, 0].iter().for_each(|v| mohd.push_u32(*v));
+    [0.0f32, 0.0, 0.0, 10.0, 10.0, 5.0].iter().for_each(|v| mohd.push_f32(*v));
+    mohd.push_u16(0);
+    mohd.push_u16(0);
+    push_reversed_chunk(&mut out, "MOHD", &mohd);
+    push_reversed_chunk(&mut out, "MOTX", b"Dungeons\\Textures\\Wall.blp\0\0");
+
+    let mut momt = Vec::new();
+    [0u32, 0, 0, 0, 0xFF7F7F7F, 0, 0, 0xFFFFFFFF, 0, 0, 0, 0, 0, 0, 0, 0].iter().for_each(|v| momt.push_u32(*v));
+    push_reversed_chunk(&mut out, "MOMT", &momt);
+    push_reversed_chunk(&mut out, "MOGN", b"\0Hall\0\0\0");
+
+    let mut mogi = Vec::new();
+    // mirrors the MOGP flags
+    mogi.push_u32(0x1 | 0x4 | 0x1000);
+    [0.0f32, 0.0, 0.0, 10.0, 10.0, 5.0].iter().for_each(|v| mogi.push_f32(*v));
+    mogi.push_i32(1);
+    push_reversed_chunk(&mut out, "MOGI", &mogi);
+    push_reversed_chunk(&mut out, "MOSB", &[0, 0, 0, 0]);
+    for name in &["MOPV", "MOPT", "MOPR", "MOVV", "MOVB", "MOLT"] {
+        push_reversed_chunk(&mut out, name, &[]);
+    }
+
+    let mut mods = b"Set_$DefaultGlobal\0\0".to_vec();
+    [0u32, 1, 0].iter().for_each(|v| mods.push_u32(*v));
+    push_reversed_chunk(&mut out, "MODS", &mods);
+    push_reversed_chunk(&mut out, "MODN", b"World\\Generic\\Barrel.m2\0");
+    let mut modd = Vec::new();
+    modd.push_u32(0);
+    [5.0f32, 5.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0].iter().for_each(|v| modd.push_f32(*v));
+    modd.push_u32(0xFFFFFFFF);
+    push_reversed_chunk(&mut out, "MODD", &modd);
+
+    let mut mfog = Vec::new();
+    mfog.push_u32(0);
+    [0.0f32, 0.0, 0.0, 0.0, 0.0, 444.4445, 0.25].iter().for_each(|v| mfog.push_f32(*v));
+    mfog.push_u32(0xFF000000);
+    [222.2222f32, -0.5].iter().for_each(|v| mfog.push_f32(*v));
+    mfog.push_u32(0xFF000000);
+    push_reversed_chunk(&mut out, "MFOG", &mfog);
+    out
+}
+
+/// Group of a 10x10 quad on two rendered triangles plus one collision only triangle,
+/// a single BSP leaf, vertex colors and a 1x1 tile of water.
+pub fn build_test_wmo_group() -> Vec<u8> {
+    let mut sub_chunks = Vec::new();
+    push_reversed_chunk(&mut sub_chunks, "MOPY", &[0x20, 0, 0x20, 0, 0x08, 0xFF]);
+    let mut movi = Vec::new();
+    [0u16, 1, 2, 2, 1, 3, 0, 2, 4].iter().for_each(|v| movi.push_u16(*v));
+    push_reversed_chunk(&mut sub_chunks, "MOVI", &movi);
+    let mut movt = Vec::new();
+    [
+        [0.0f32, 0.0, 0.0], [10.0, 0.0, 0.0], [0.0, 10.0, 0.0], [10.0, 10.0, 0.0], [0.0, 0.0, 5.0],
+    ].iter().flatten().for_each(|v| movt.push_f32(*v));
+    push_reversed_chunk(&mut sub_chunks, "MOVT", &movt);
+    let mut monr = Vec::new();
+    (0..5).for_each(|_| [0.0f32, 0.0, 1.0].iter().for_each(|v| monr.push_f32(*v)));
+    push_reversed_chunk(&mut sub_chunks, "MONR", &monr);
+    let mut motv = Vec::new();
+    [0.0f32, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 0.0, 0.0].iter().for_each(|v| motv.push_f32(*v));
+    push_reversed_chunk(&mut sub_chunks, "MOTV", &motv);
+
+    let mut moba = Vec::new();
+    [0i16, 0, 0, 10, 10, 0].iter().for_each(|v| moba.push_u16(*v as u16));
+    moba.push_u32(0);
+    [6u16, 0, 3].iter().for_each(|v| moba.push_u16(*v));
+    moba.extend_from_slice(&[0, 0]);
+    push_reversed_chunk(&mut sub_chunks, "MOBA", &moba);
+
+    let mut mobn = Vec::new();
+    [4u16, 0xFFFF, 0xFFFF, 3].iter().for_each(|v| mobn.push_u16(*v));
+    mobn.push_u32(0);
+    mobn.push_f32(0.0);
+    push_reversed_chunk(&mut sub_chunks, "MOBN", &mobn);
+    let mut mobr = Vec::new();
+    [0u16, 1, 2].iter().for_each(|v| mobr.push_u16(*v));
+    push_reversed_chunk(&mut sub_chunks, "MOBR", &mobr);
+    let mut mocv = Vec::new();
+    (0..5).for_each(|_| mocv.push_u32(0xFF804020));
+    push_reversed_chunk(&mut sub_chunks, "MOCV", &mocv);
+
+    let mut mliq = Vec::new();
+    [2u32, 2, 1, 1].iter().for_each(|v| mliq.push_u32(*v));
+    [0.0f32, 0.0, 1.0].iter().for_each(|v| mliq.push_f32(*v));
+    mliq.push_u16(0);
+    (0..4).for_each(|_| {
+        mliq.extend_from_slice(&[0, 0, 0, 0]);
+        mliq.push_f32(1.0);
+    });
+    mliq.push(0);
+    push_reversed_chunk(&mut sub_chunks, "MLIQ", &mliq);
+
+    let mut mogp = Vec::new();
+    // group name, descriptive group name, flags
+    [1u32, 0, 0x1 | 0x4 | 0x1000].iter().for_each(|v| mogp.push_u32(*v));
+    [0.0f32, 0.0, 0.0, 10.0, 10.0, 5.0].iter().for_each(|v| mogp.push_f32(*v));
+    // portals, batches
+    [0u16, 0, 0, 0, 1, 0].iter().for_each(|v| mogp.push_u16(*v));
+    mogp.extend_from_slice(&[0, 0, 0, 0]);
+    [15u32, 0, 0, 0].iter().for_each(|v| mogp.push_u32(*v));
+    mogp.extend_from_slice(&sub_chunks);
+
+    let mut out = Vec::new();
+    push_reversed_chunk(&mut out, "MVER", &17u32.to_le_bytes());
+    push_reversed_chunk(&mut out, "MOGP", &mogp);
+    out
+}
+
+/// Writes the test root and its group to `dir`, returns the root path.
+pub fn write_test_wmo(dir: &TempDir) -> PathBuf {
+    std::fs::write(dir.join("test.wmo"), build_test_wmo_root()).unwrap();
+    std::fs::write(dir.join("test_000.wmo"), build_test_wmo_group()).unwrap();
+    dir.join("test.wmo")
+}
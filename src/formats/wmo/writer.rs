@@ -0,0 +1,347 @@
+use crate::common::{R, err};
+use crate::formats::chunk::Chunk;
+use crate::formats::wmo::group::*;
+use crate::formats::wmo::{WmoFile, WmoGroupFile, WmoRootFile};
+use anyhow::Context;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// String block (MOTX, MOGN, MODN) built while writing, identical strings share one offset.
+struct StringBlock {
+    data: Vec<u8>,
+    offsets: HashMap<String, u32>,
+    // MOTX keeps every string 4 byte aligned
+    align_strings: bool,
+}
+
+impl StringBlock {
+    fn new(align_strings: bool) -> StringBlock {
+        StringBlock {
+            data: Vec::new(),
+            offsets: HashMap::new(),
+            align_strings,
+        }
+    }
+
+    fn add(&mut self, string: &str) -> u32 {
+        if let Some(offset) = self.offsets.get(string) {
+            return *offset;
+        }
+        let offset = self.data.len() as u32;
+        self.data.extend_from_slice(string.as_bytes());
+        self.data.push(0);
+        if self.align_strings {
+            self.data.resize(self.data.len() + (4 - self.data.len() % 4) % 4, 0);
+        }
+        self.offsets.insert(string.to_string(), offset);
+        offset
+    }
+
+    /// Adds the `previous` strings still in use first, so unchanged files keep their offsets.
+    fn add_in_order(&mut self, previous: &[String], used: &[&str]) {
+        let used_lookup: HashSet<&str> = used.iter().cloned().collect();
+        for string in previous.iter().filter(|it| used_lookup.contains(it.as_str())) {
+            self.add(string);
+        }
+        for string in used {
+            self.add(string);
+        }
+    }
+
+    fn strings(&self) -> Vec<String> {
+        let mut strings: Vec<(&String, &u32)> = self.offsets.iter()
+            .filter(|(string, _)| !string.is_empty())
+            .collect();
+        strings.sort_by_key(|(_, offset)| **offset);
+        strings.into_iter().map(|(string, _)| string.clone()).collect()
+    }
+
+    fn to_chunk(&self, chunk_type: &str) -> Chunk {
+        let mut data = self.data.clone();
+        data.resize(data.len() + (4 - data.len() % 4) % 4, 0);
+        Chunk::new(chunk_type, data)
+    }
+}
+
+impl WmoFile {
+    /// Writes the root to `path` and the groups next to it, named after the root.
+    pub fn write_to_path<P: AsRef<Path>>(&mut self, path: P) -> R<Vec<PathBuf>> {
+        let path = path.as_ref();
+        let file_name = path.file_name()
+            .and_then(|it| it.to_str())
+            .with_context(|| format!("Invalid WMO path {}", path.display()))?;
+        let (root, groups) = self.to_bytes()?;
+
+        let mut written = vec![path.to_path_buf()];
+        std::fs::write(path, root)
+            .with_context(|| format!("Failed to write wmo file {}", path.display()))?;
+        let group_names = WmoRootFile::get_group_names(groups.len() as u32, file_name);
+        for (group_name, bytes) in group_names.iter().zip(groups) {
+            let group_path = path.with_file_name(group_name);
+            std::fs::write(&group_path, bytes)
+                .with_context(|| format!("Failed to write wmo file {}", group_path.display()))?;
+            written.push(group_path);
+        }
+        Ok(written)
+    }
+
+    /// Serializes the root and every group. The string blocks are rebuilt from the resolved
+    /// names, offsets, counts, bounding boxes and group flags are updated to match.
+    pub fn to_bytes(&mut self) -> R<(Vec<u8>, Vec<Vec<u8>>)> {
+        if let Some(error) = self.group_errors.first() {
+            return err(format!(
+                "WmoFile#to_bytes: group {} failed to load, the WMO can only be written with all of its groups",
+                error.path().display()
+            ));
+        }
+        if self.root.mogi.0.len() != self.groups.len() {
+            return err(format!(
+                "WmoFile#to_bytes: MOGI lists {} groups but {} are loaded",
+                self.root.mogi.0.len(),
+                self.groups.len()
+            ));
+        }
+        for group in self.groups.iter_mut() {
+            group.update_derived_fields();
+        }
+        let (motx, mogn, modn) = self.update_string_offsets()?;
+        self.update_root_header();
+
+        let root = self.root.to_bytes(&motx, &mogn, &modn)?;
+        let groups = self.groups.iter().map(|it| it.to_bytes()).collect();
+        Ok((root, groups))
+    }
+
+    fn update_string_offsets(&mut self) -> R<(StringBlock, StringBlock, StringBlock)> {
+        let root = &mut self.root;
+
+        let mut motx = StringBlock::new(true);
+        {
+            let used: Vec<&str> = root.momt.0.iter()
+                .flat_map(|it| vec![&it.texture_1_name, &it.texture_2_name, &it.texture_3_name])
+                .filter_map(|it| it.as_deref())
+                .collect();
+            motx.add_in_order(&root.motx.0, &used);
+        }
+        for material in root.momt.0.iter_mut() {
+            material.texture_1 = motx.add(material.texture_1_name.as_deref().unwrap_or(""));
+            material.texture_2 = motx.add(material.texture_2_name.as_deref().unwrap_or(""));
+            material.texture_3 = motx.add(material.texture_3_name.as_deref().unwrap_or(""));
+        }
+        root.motx.0 = motx.strings();
+
+        // offset 0 is the empty name
+        let mut mogn = StringBlock::new(false);
+        mogn.add("");
+        {
+            let used: Vec<&str> = root.mogi.0.iter()
+                .filter_map(|it| it.name.as_deref())
+                .chain(self.groups.iter().flat_map(|it| vec![&it.mogp.name, &it.mogp.descriptive_name]).filter_map(|it| it.as_deref()))
+                .collect();
+            mogn.add_in_order(&root.mogn.0, &used);
+        }
+        for (info, group) in root.mogi.0.iter_mut().zip(self.groups.iter_mut()) {
+            info.name_offset = match &info.name {
+                Some(name) => mogn.add(name) as i32,
+                None => -1,
+            };
+            // the group header falls back to the name of its MOGI entry
+            let name = group.mogp.name.clone().or_else(|| info.name.clone());
+            group.mogp.group_name = mogn.add(name.as_deref().unwrap_or(""));
+            group.mogp.descriptive_group_name = mogn.add(group.mogp.descriptive_name.as_deref().unwrap_or(""));
+        }
+        root.mogn.0 = mogn.strings();
+
+        let mut modn = StringBlock::new(false);
+        {
+            let used = root.modd.0.iter()
+                .enumerate()
+                .map(|(i, it)| it.name.as_deref().with_context(|| format!("MODD entry {} has no model name", i)))
+                .collect::<R<Vec<&str>>>()?;
+            modn.add_in_order(&root.modn.0, &used);
+        }
+        for doodad in root.modd.0.iter_mut() {
+            doodad.name_offset = modn.add(doodad.name.as_deref().unwrap_or(""));
+        }
+        if modn.data.len() > 0xFFFFFF {
+            return err(format!("MODN is {} bytes long, MODD can only address 16MB", modn.data.len()));
+        }
+        root.modn.0 = modn.strings();
+
+        Ok((motx, mogn, modn))
+    }
+
+    fn update_root_header(&mut self) {
+        let root = &mut self.root;
+        for (info, group) in root.mogi.0.iter_mut().zip(self.groups.iter()) {
+            info.flags = group.mogp.flags;
+            info.bounding_box = group.mogp.bounding_box;
+        }
+
+        let mohd = &mut root.mohd;
+        mohd.n_materials = root.momt.0.len() as u32;
+        mohd.n_groups = root.mogi.0.len() as u32;
+        mohd.n_ports = root.mopt.0.len() as u32;
+        mohd.n_lights = root.molt.0.len() as u32;
+        mohd.n_models = root.modn.0.len() as u32;
+        mohd.n_doodads = root.modd.0.len() as u32;
+        mohd.n_doodad_sets = root.mods.0.len() as u32;
+        if let Some(bounding_box) = merge_bounding_boxes(root.mogi.0.iter().map(|it| &it.bounding_box)) {
+            mohd.bounding_box_corner_1 = [bounding_box[0], bounding_box[1], bounding_box[2]];
+            mohd.bounding_box_corner_2 = [bounding_box[3], bounding_box[4], bounding_box[5]];
+        }
+    }
+}
+
+fn merge_bounding_boxes<'a, I: Iterator<Item = &'a [f32; 6]>>(boxes: I) -> Option<[f32; 6]> {
+    boxes.fold(None, |merged, b| match merged {
+        None => Some(*b),
+        Some(m) => Some([
+            m[0].min(b[0]), m[1].min(b[1]), m[2].min(b[2]),
+            m[3].max(b[3]), m[4].max(b[4]), m[5].max(b[5]),
+        ]),
+    })
+}
+
+impl WmoRootFile {
+    fn to_bytes(&self, motx: &StringBlock, mogn: &StringBlock, modn: &StringBlock) -> R<Vec<u8>> {
+        let mut chunks = vec![
+            self.mver.to_chunk(),
+            self.mohd.to_chunk(),
+            motx.to_chunk("MOTX"),
+            self.momt.to_chunk(),
+            mogn.to_chunk("MOGN"),
+            self.mogi.to_chunk(),
+            self.mosb.to_chunk(),
+            self.mopv.to_chunk(),
+            self.mopt.to_chunk(),
+            self.mopr.to_chunk(),
+            self.movv.to_chunk(),
+            self.movb.to_chunk(),
+            self.molt.to_chunk(),
+            self.mods.to_chunk()?,
+            modn.to_chunk("MODN"),
+            self.modd.to_chunk(),
+            self.mfog.to_chunk(),
+        ];
+        chunks.extend(self.other_chunks.iter().map(|it| Chunk { id: it.id, size: it.size, data: it.data.clone() }));
+
+        let mut bytes = Vec::new();
+        for chunk in &chunks {
+            chunk.write_to(&mut bytes);
+        }
+        Ok(bytes)
+    }
+}
+
+impl WmoGroupFile {
+    /// The MOGP flags of the optional sub chunks follow which of them are present,
+    /// the bounding box is recomputed from the vertices.
+    pub fn update_derived_fields(&mut self) {
+        let flags = [
+            (MOGP_FLAG_HAS_BSP, self.mobn.is_some()),
+            (MOGP_FLAG_HAS_VERTEX_COLORS, self.mocv.is_some()),
+            (MOGP_FLAG_HAS_LIGHTS, self.molr.is_some()),
+            (MOGP_FLAG_HAS_DOODADS, self.modr.is_some()),
+            (MOGP_FLAG_HAS_WATER, self.mliq.is_some()),
+            (MOGP_FLAG_HAS_TWO_MOTV, self.motv.len() > 1),
+        ];
+        for (flag, present) in &flags {
+            if *present {
+                self.mogp.flags |= flag;
+            } else {
+                self.mogp.flags &= !flag;
+            }
+        }
+
+        let boxes: Vec<[f32; 6]> = self.movt.0.iter()
+            .map(|v| [v[0], v[1], v[2], v[0], v[1], v[2]])
+            .collect();
+        if let Some(bounding_box) = merge_bounding_boxes(boxes.iter()) {
+            self.mogp.bounding_box = bounding_box;
+        }
+    }
+
+    /// Serializes the group as it is, see [WmoFile::to_bytes] to keep it in sync with its root.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut sub_chunks = vec![
+            self.mopy.to_chunk(),
+            self.movi.to_chunk(),
+            self.movt.to_chunk(),
+            self.monr.to_chunk(),
+        ];
+        sub_chunks.extend(self.motv.iter().take(1).map(|it| it.to_chunk()));
+        sub_chunks.push(self.moba.to_chunk());
+        sub_chunks.extend(self.molr.iter().map(|it| it.to_chunk()));
+        sub_chunks.extend(self.modr.iter().map(|it| it.to_chunk()));
+        sub_chunks.extend(self.mobn.iter().map(|it| it.to_chunk()));
+        sub_chunks.extend(self.mobr.iter().map(|it| it.to_chunk()));
+        sub_chunks.extend(self.mocv.iter().map(|it| it.to_chunk()));
+        sub_chunks.extend(self.mliq.iter().map(|it| it.to_chunk()));
+        sub_chunks.extend(self.motv.iter().skip(1).map(|it| it.to_chunk()));
+        sub_chunks.extend(self.other_chunks.iter().map(|it| Chunk { id: it.id, size: it.size, data: it.data.clone() }));
+
+        let mut bytes = Vec::new();
+        self.mver.to_chunk().write_to(&mut bytes);
+        self.mogp.to_chunk(&sub_chunks).write_to(&mut bytes);
+        bytes
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn wmo_round_trip_keeps_parsed_fields() {
+    use crate::formats::wmo::test_utils;
+
+    let dir = crate::test_utils::TempDir::new("wow-file-tools-wmo-round-trip");
+    let mut wmo = WmoFile::from_path(test_utils::write_test_wmo(&dir)).unwrap();
+    let before = serde_json::to_value(&wmo).unwrap();
+    let (root, groups) = wmo.to_bytes().unwrap();
+    assert_eq!(root, test_utils::build_test_wmo_root());
+    assert_eq!(groups, vec![test_utils::build_test_wmo_group()]);
+
+    std::fs::create_dir_all(dir.join("out")).unwrap();
+    let written = wmo.write_to_path(dir.join("out/test.wmo")).unwrap();
+    assert_eq!(written.len(), 2);
+    let mut reloaded = WmoFile::from_path(dir.join("out/test.wmo")).unwrap();
+    reloaded.loaded_group_files = wmo.loaded_group_files.clone();
+    assert_eq!(serde_json::to_value(&reloaded).unwrap(), before);
+}
+
+#[cfg(test)]
+#[test]
+fn wmo_writer_updates_offsets_counts_and_boxes() {
+    use crate::byte_utils::VecUtils;
+    use crate::formats::wmo::test_utils;
+
+    let dir = crate::test_utils::TempDir::new("wow-file-tools-wmo-offsets");
+    let mut wmo = WmoFile::from_path(test_utils::write_test_wmo(&dir)).unwrap();
+    let mut material = wmo.root.momt.0[0].clone();
+    material.texture_1_name = Some("Dungeons\\Textures\\Floor.blp".to_string());
+    wmo.root.momt.0.push(material);
+    wmo.groups[0].movt.0[4] = [0.0, 0.0, 8.0];
+
+    let (root, _) = wmo.to_bytes().unwrap();
+    let chunks = Chunk::from_bytes(&root).unwrap();
+    let chunk = |id: &str| chunks.iter().find(|it| it.get_id_as_string() == id).unwrap().data.clone();
+
+    // Wall.blp takes 26 + 1 bytes padded to 28, Floor.blp 27 + 1
+    let motx = chunk("MOTX");
+    assert_eq!(motx.len(), 56);
+    assert_eq!(motx.get_string_null_terminated(28).unwrap(), "Dungeons\\Textures\\Floor.blp");
+    // texture_1 and texture_2 of the second 64 byte material
+    let momt = chunk("MOMT");
+    assert_eq!(momt.len(), 128);
+    assert_eq!((momt.get_u32(64 + 12).unwrap(), momt.get_u32(64 + 24).unwrap()), (28, 0));
+    // materials, groups, portals, lights, models, doodads, doodad sets
+    let mohd = chunk("MOHD");
+    let counts: Vec<u32> = (0..7).map(|i| mohd.get_u32(i * 4).unwrap()).collect();
+    assert_eq!(counts, vec![2, 1, 0, 0, 1, 1, 1]);
+    let mohd_box: Vec<f32> = (0..6).map(|i| mohd.get_f32(36 + i * 4).unwrap()).collect();
+    assert_eq!(mohd_box, vec![0.0, 0.0, 0.0, 10.0, 10.0, 8.0]);
+    let mogi = chunk("MOGI");
+    assert_eq!(mogi.len(), 32);
+    assert_eq!(mogi.get_u32(0).unwrap(), 0x1 | 0x4 | 0x1000);
+    let mogi_box: Vec<f32> = (0..6).map(|i| mogi.get_f32(4 + i * 4).unwrap()).collect();
+    assert_eq!(mogi_box, vec![0.0, 0.0, 0.0, 10.0, 10.0, 8.0]);
+}
@@ -5,13 +5,23 @@ pub mod common;
 pub mod formats;
 pub mod mpq;
 pub mod proxy;
+#[cfg(test)]
+pub(crate) mod test_utils;
 
+use crate::command_handler::adt::handle_adt_command;
 use crate::command_handler::dbc_join::handle_dbc_join;
+use crate::command_handler::m2::handle_m2_command;
 use crate::command_handler::mpq::handle_mpq_command;
 use crate::command_handler::resolve_map_assets::handle_resolve_map_assets;
 use crate::command_handler::view::handle_view_command;
+use crate::command_handler::wdl::handle_wdl_command;
+use crate::command_handler::wdt::handle_wdt_command;
+use crate::command_handler::wmo::handle_wmo_command;
 
 use crate::common::R;
+use crate::formats::adt::mcnk::McnkRect;
+use crate::formats::mesh::MeshFormat;
+use crate::formats::wmo::collision::CollisionFormat;
 
 use clap::Clap;
 use command_handler::proxy::handle_proxy_command;
@@ -38,6 +48,11 @@ fn handle_cmd(root_cmd: RootCmd) -> R<()> {
         Cmd::ResolveMapAssets(cmd) => handle_resolve_map_assets(cmd)?,
         Cmd::DbcJoin(cmd) => handle_dbc_join(cmd)?,
         Cmd::Mpq { cmd } => handle_mpq_command(cmd)?,
+        Cmd::Adt { cmd } => handle_adt_command(cmd)?,
+        Cmd::Wdt { cmd } => handle_wdt_command(cmd)?,
+        Cmd::Wdl { cmd } => handle_wdl_command(cmd)?,
+        Cmd::Wmo { cmd } => handle_wmo_command(cmd)?,
+        Cmd::M2 { cmd } => handle_m2_command(cmd)?,
         Cmd::Proxy(cmd) => handle_proxy_command(&cmd.host, &cmd.username, &cmd.password)?,
     };
 
@@ -83,6 +98,26 @@ pub enum Cmd {
         #[clap(subcommand)]
         cmd: MpqToolCmd,
     },
+    Adt {
+        #[clap(subcommand)]
+        cmd: AdtToolCmd,
+    },
+    Wdt {
+        #[clap(subcommand)]
+        cmd: WdtToolCmd,
+    },
+    Wdl {
+        #[clap(subcommand)]
+        cmd: WdlToolCmd,
+    },
+    Wmo {
+        #[clap(subcommand)]
+        cmd: WmoToolCmd,
+    },
+    M2 {
+        #[clap(subcommand)]
+        cmd: M2ToolCmd,
+    },
     Proxy(ProxyCmd),
 }
 
@@ -95,6 +130,388 @@ pub enum MpqToolCmd {
     Pack(MpqToolCmdPack),
 }
 
+#[derive(Clap)]
+#[clap(about = "A set of ADT related tools")]
+pub enum AdtToolCmd {
+    Heightmap {
+        #[clap(subcommand)]
+        cmd: AdtHeightmapCmd,
+    },
+    Move(AdtMoveCmd),
+    CopyTiles(AdtCopyTilesCmd),
+    ReplacePaths(AdtReplacePathsCmd),
+    SetArea(AdtSetAreaCmd),
+    Placements(AdtPlacementsCmd),
+}
+
+#[derive(Clap)]
+#[clap(about = "Exchange terrain heights with image editors")]
+pub enum AdtHeightmapCmd {
+    Export(AdtHeightmapExportCmd),
+    Import(AdtHeightmapImportCmd),
+}
+
+#[derive(Clap)]
+#[clap(
+    about = "Export the heights of an ADT, or of a whole map folder stitched along the WDT grid, as a 16 bit grayscale image"
+)]
+pub struct AdtHeightmapExportCmd {
+    #[clap(short = 'i', long = "input", about = "An ADT file or a map folder")]
+    input: String,
+
+    #[clap(
+        short = 'o',
+        long = "output",
+        about = "Image to create, .png or .r16/.raw. Height range and tiles are written next to it as <output>.json"
+    )]
+    output: String,
+}
+
+#[derive(Clap)]
+#[clap(about = "Write an edited heightmap back into the ADTs it was exported from")]
+pub struct AdtHeightmapImportCmd {
+    #[clap(short = 'i', long = "input", about = "The ADT file or map folder that was exported")]
+    input: String,
+
+    #[clap(short = 'm', long = "image", about = "The edited image, <image>.json must sit next to it")]
+    image: String,
+}
+
+#[derive(Clap)]
+#[clap(about = "Move a tile to other grid coordinates within its map folder, the file is renamed")]
+pub struct AdtMoveCmd {
+    #[clap(short = 'm', long = "map-folder")]
+    map_folder: String,
+
+    #[clap(long = "from", about = "Tile to move, as <x>_<y>")]
+    from: TileArg,
+
+    #[clap(long = "to", about = "New tile coordinates, as <x>_<y>")]
+    to: TileArg,
+}
+
+#[derive(Clap)]
+#[clap(about = "Copy tiles from one map folder to another, moving them to new grid coordinates")]
+pub struct AdtCopyTilesCmd {
+    #[clap(short = 's', long = "source", about = "Map folder to copy from")]
+    source: String,
+
+    #[clap(short = 'd', long = "dest", about = "Map folder to copy to, tiles are named after it")]
+    dest: String,
+
+    #[clap(
+        short = 't',
+        long = "tiles",
+        about = "Tiles to copy as <x>_<y>, all tiles of the source when omitted"
+    )]
+    tiles: Vec<TileArg>,
+
+    #[clap(
+        long = "to",
+        about = "Where the top left most tile lands, the others keep their relative position"
+    )]
+    to: Option<TileArg>,
+}
+
+#[derive(Clap)]
+#[clap(about = "Rewrite texture, model and WMO paths of ADTs from a mapping file")]
+pub struct AdtReplacePathsCmd {
+    #[clap(short = 'i', long = "input", about = "An ADT file or a folder searched recursively for ADTs")]
+    input: String,
+
+    #[clap(
+        short = 'm',
+        long = "mapping-file",
+        about = "One `old path -> new path` per line, `*` and `?` wildcards allowed"
+    )]
+    mapping_file: String,
+
+    #[clap(long = "dry-run", about = "Only report what would change")]
+    dry_run: bool,
+}
+
+#[derive(Clap)]
+#[clap(about = "Set or remap the area ID of MCNKs")]
+pub struct AdtSetAreaCmd {
+    #[clap(short = 'i', long = "input", about = "An ADT file or a map folder")]
+    input: String,
+
+    #[clap(short = 'a', long = "area-id", about = "Area ID to give to every chunk, or the --chunks")]
+    area_id: Option<u32>,
+
+    #[clap(
+        long = "chunks",
+        about = "Only set the chunks in this inclusive rectangle of MCNK indices, as <x>_<y>:<x>_<y>"
+    )]
+    chunks: Option<McnkRectArg>,
+
+    #[clap(
+        short = 'r',
+        long = "remap",
+        about = "Replace an area ID by another, as <old>:<new>, can be repeated"
+    )]
+    remap: Vec<AreaRemapArg>,
+}
+
+#[derive(Clap)]
+#[clap(about = "Add, remove, move, rotate and scale doodads and WMOs of a map from a JSON placement file")]
+pub struct AdtPlacementsCmd {
+    #[clap(short = 'm', long = "map-folder")]
+    map_folder: String,
+
+    #[clap(short = 'p', long = "placement-file")]
+    placement_file: String,
+
+    #[clap(
+        short = 'w',
+        long = "workspace",
+        about = "Client files root, used to compute the extents of added or rotated WMOs"
+    )]
+    workspace: Option<String>,
+}
+
+#[derive(Clap)]
+#[clap(about = "A set of WDT related tools")]
+pub enum WdtToolCmd {
+    Generate(WdtGenerateCmd),
+}
+
+#[derive(Clap)]
+#[clap(about = "Build the WDT of a map folder from the ADTs it contains")]
+pub struct WdtGenerateCmd {
+    #[clap(short = 'm', long = "map-folder")]
+    map_folder: String,
+
+    #[clap(short = 'o', long = "output", about = "WDT to write, <map folder>/<map>.wdt when omitted")]
+    output: Option<String>,
+
+    #[clap(long = "big-alpha", about = "true when the ADTs use 4096 byte alpha maps, taken from the existing WDT or guessed when omitted")]
+    big_alpha: Option<bool>,
+}
+
+#[derive(Clap)]
+#[clap(about = "A set of WDL related tools")]
+pub enum WdlToolCmd {
+    Generate(WdlGenerateCmd),
+}
+
+#[derive(Clap)]
+#[clap(about = "Rebuild the low resolution heights of a map folder from its ADTs")]
+pub struct WdlGenerateCmd {
+    #[clap(short = 'm', long = "map-folder")]
+    map_folder: String,
+
+    #[clap(short = 'o', long = "output", about = "WDL to write, <map folder>/<map>.wdl when omitted")]
+    output: Option<String>,
+}
+
+#[derive(Clap)]
+#[clap(about = "A set of WMO related tools")]
+pub enum WmoToolCmd {
+    Export(WmoExportCmd),
+    Collision(WmoCollisionCmd),
+    Edit(WmoEditCmd),
+}
+
+#[derive(Clap)]
+#[clap(about = "Export the groups of a root WMO as Wavefront OBJ or glTF")]
+pub struct WmoExportCmd {
+    #[clap(short = 'i', long = "input", about = "Root WMO, its groups are loaded from the same folder")]
+    input: String,
+
+    #[clap(short = 'o', long = "output", about = "File to create, the .mtl or .bin is written next to it")]
+    output: String,
+
+    #[clap(short = 'f', long = "format", about = "obj or gltf, guessed from the output extension when omitted")]
+    format: Option<MeshFormatArg>,
+
+    #[clap(
+        short = 'w',
+        long = "workspace",
+        about = "Client files root, textures found in it are converted to PNG next to the output"
+    )]
+    workspace: Option<String>,
+
+    #[clap(long = "doodads", about = "Also export the default doodad set with the M2s of the workspace, empty nodes in glTF without them")]
+    doodads: bool,
+}
+
+#[derive(Clap)]
+#[clap(about = "Export the collision triangles, BSP trees, liquids and portals of a root WMO for server side tools")]
+pub struct WmoCollisionCmd {
+    #[clap(short = 'i', long = "input", about = "Root WMO, its groups are loaded from the same folder")]
+    input: String,
+
+    #[clap(short = 'o', long = "output")]
+    output: String,
+
+    #[clap(short = 'f', long = "format", about = "obj or wcol (also bin), guessed from the .obj / .wcol / .bin extension when omitted")]
+    format: Option<CollisionFormatArg>,
+}
+
+#[derive(Clap)]
+#[clap(about = "Edit the texture paths, group names and doodad sets of a root WMO and write it with its groups")]
+pub struct WmoEditCmd {
+    #[clap(short = 'i', long = "input", about = "Root WMO, its groups are loaded from the same folder")]
+    input: String,
+
+    #[clap(short = 'e', long = "edit-file")]
+    edit_file: String,
+
+    #[clap(short = 'o', long = "output", about = "Root WMO to write, the input is overwritten when omitted")]
+    output: Option<String>,
+}
+
+#[derive(Clap)]
+#[clap(about = "A set of M2 related tools")]
+pub enum M2ToolCmd {
+    Export(M2ExportCmd),
+    Convert(M2ConvertCmd),
+    SetTexture(M2SetTextureCmd),
+}
+
+#[derive(Clap)]
+#[clap(about = "Export a Wrath M2 with one of its skin profiles, its bones and animations as glTF (or a static OBJ)")]
+pub struct M2ExportCmd {
+    #[clap(short = 'i', long = "input", about = "M2, its .skin and .anim files are loaded from the same folder")]
+    input: String,
+
+    #[clap(short = 'o', long = "output", about = "File to create, the .mtl or .bin is written next to it")]
+    output: String,
+
+    #[clap(short = 'f', long = "format", about = "obj or gltf, guessed from the output extension when omitted")]
+    format: Option<MeshFormatArg>,
+
+    #[clap(
+        short = 'w',
+        long = "workspace",
+        about = "Client files root, textures found in it are converted to PNG next to the output"
+    )]
+    workspace: Option<String>,
+
+    #[clap(short = 's', long = "skin", about = "Skin profile to export, 0 (the most detailed) when omitted")]
+    skin: Option<usize>,
+}
+
+#[derive(Clap)]
+#[clap(about = "Convert a Cataclysm to Legion M2 (plain or MD21 chunked) with its skins and .anim files to an older client")]
+pub struct M2ConvertCmd {
+    #[clap(short = 'i', long = "input", about = "M2, its .skin and .anim files are loaded from the same folder")]
+    input: String,
+
+    #[clap(short = 'o', long = "output", about = "M2 to write, the skins and .anim files are written next to it")]
+    output: String,
+
+    #[clap(short = 't', long = "target", about = "Version of the written model, only 264 (Wrath) is supported")]
+    target: u32,
+}
+
+#[derive(Clap)]
+#[clap(about = "Point a texture of a Wrath M2 at another file, replaceable textures become plain ones")]
+pub struct M2SetTextureCmd {
+    #[clap(short = 'i', long = "input")]
+    input: String,
+
+    #[clap(long = "index", about = "Index in the texture list, see `view`")]
+    index: usize,
+
+    #[clap(long = "path", about = "Texture path as the client resolves it, e.g. Creature\\Wolf\\WolfSkinBlue.blp")]
+    path: String,
+
+    #[clap(
+        short = 'o',
+        long = "output",
+        about = "M2 to write, its skins and .anim files are copied next to it. The input is overwritten when omitted"
+    )]
+    output: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+struct MeshFormatArg(MeshFormat);
+
+impl std::str::FromStr for MeshFormatArg {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, &'static str> {
+        match s.to_lowercase().as_str() {
+            "obj" => Ok(MeshFormatArg(MeshFormat::Obj)),
+            "gltf" => Ok(MeshFormatArg(MeshFormat::Gltf)),
+            _ => Err("Must be one of ( obj, gltf )"),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct CollisionFormatArg(CollisionFormat);
+
+impl std::str::FromStr for CollisionFormatArg {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, &'static str> {
+        match s.to_lowercase().as_str() {
+            "obj" => Ok(CollisionFormatArg(CollisionFormat::Obj)),
+            "wcol" | "bin" => Ok(CollisionFormatArg(CollisionFormat::Binary)),
+            _ => Err("Must be one of ( obj, wcol )"),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct McnkRectArg(McnkRect);
+
+impl std::str::FromStr for McnkRectArg {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, &'static str> {
+        let corners: Vec<Option<(u32, u32)>> = s.split(':')
+            .map(|corner| {
+                let mut parts = corner.split('_');
+                match (parts.next(), parts.next(), parts.next()) {
+                    (Some(x), Some(y), None) => x.parse().ok().zip(y.parse().ok()),
+                    _ => None,
+                }
+            })
+            .collect();
+        match corners.as_slice() {
+            [Some((x0, y0)), Some((x1, y1))] if *x0.max(x1) < 16 && *y0.max(y1) < 16 => Ok(McnkRectArg(McnkRect {
+                min_x: *x0.min(x1),
+                min_y: *y0.min(y1),
+                max_x: *x0.max(x1),
+                max_y: *y0.max(y1),
+            })),
+            _ => Err("Must be two MCNK indices between 0 and 15 as <x>_<y>:<x>_<y>, e.g. 0_0:7_7\n"),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct AreaRemapArg(u32, u32);
+
+impl std::str::FromStr for AreaRemapArg {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, &'static str> {
+        let mut parts = s.split(':');
+        match (parts.next().map(str::parse), parts.next().map(str::parse), parts.next()) {
+            (Some(Ok(old)), Some(Ok(new)), None) => Ok(AreaRemapArg(old, new)),
+            _ => Err("Must be two area IDs as <old>:<new>, e.g. 1519:5000\n"),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct TileArg(u32, u32);
+
+impl std::str::FromStr for TileArg {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, &'static str> {
+        let mut parts = s.split('_');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(x), Some(y), None) => match (x.parse::<u32>(), y.parse::<u32>()) {
+                (Ok(x), Ok(y)) if x < 64 && y < 64 => Ok(TileArg(x, y)),
+                _ => Err("Tile coordinates must be between 0 and 63\n"),
+            },
+            _ => Err("Must be tile coordinates as <x>_<y>, e.g. 32_48\n"),
+        }
+    }
+}
+
 #[derive(Clap)]
 #[clap(about = "Create a proxy server and inspect traffic")]
 pub struct ProxyCmd {
@@ -169,6 +586,20 @@ pub struct MpqToolCmdExtractTree {
 pub struct ViewCmd {
     #[clap(short = 'f', long = "file")]
     file: String,
+
+    #[clap(
+        short = 'l',
+        long = "liquid-type-dbc",
+        about = "LiquidType.dbc used to resolve liquid names of ADTs"
+    )]
+    liquid_type_dbc: Option<String>,
+
+    #[clap(
+        short = 's',
+        long = "skins",
+        about = "Also load the <model>0N.skin profiles next to an M2"
+    )]
+    skins: bool,
 }
 
 #[derive(Clap)]
@@ -0,0 +1,37 @@
+use crate::byte_utils::VecWriteUtils;
+use std::path::{Path, PathBuf};
+
+/// Folder below the system temp dir, emptied when created and removed again when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Appends a chunk with its id as given, M2 and MDX chunk ids are not reversed.
+pub fn push_chunk(out: &mut Vec<u8>, id: &[u8], data: &[u8]) {
+    out.extend_from_slice(id);
+    out.push_u32(data.len() as u32);
+    out.extend_from_slice(data);
+}
+
+/// Appends a chunk by its readable name, the id is stored reversed like in ADT and WMO files.
+pub fn push_reversed_chunk(out: &mut Vec<u8>, name: &str, data: &[u8]) {
+    let id: Vec<u8> = name.bytes().rev().collect();
+    push_chunk(out, &id, data);
+}